@@ -0,0 +1,635 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{
+    decode, encode, split_params, AbiError, Event, EventParam, LogParam, ParamType, RawLog, Revert,
+    Token,
+};
+use crate::hash::keccak256;
+use horror::{Error, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// A named parameter of a function, error or constructor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub kind: ParamType,
+    pub internal_type: Option<String>,
+}
+
+/// State mutability of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateMutability {
+    Pure,
+    View,
+    #[default]
+    NonPayable,
+    Payable,
+}
+
+/// A contract function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<Param>,
+    pub outputs: Vec<Param>,
+    pub state_mutability: StateMutability,
+}
+
+impl Function {
+    /// Parse a function from its signature, e.g. `transfer(address,uint256)`.
+    /// Parameter names are optional: `transfer(address to, uint256 amount)`.
+    pub fn parse(signature: &str) -> Result<Self> {
+        let (name, inputs) = parse_signature(signature)?;
+        Ok(Self {
+            name,
+            inputs,
+            outputs: Vec::new(),
+            state_mutability: StateMutability::default(),
+        })
+    }
+
+    /// Return the canonical signature, e.g. `transfer(address,uint256)`.
+    pub fn signature(&self) -> String {
+        format_signature(&self.name, &self.inputs)
+    }
+
+    /// Return the 4 bytes selector.
+    pub fn selector(&self) -> [u8; 4] {
+        selector(&self.signature())
+    }
+
+    /// Encode a call to the function with the given arguments, suitable for
+    /// the `data` field of a transaction.
+    pub fn encode_input(&self, tokens: &[Token]) -> Result<Vec<u8>> {
+        let mut data = self.selector().to_vec();
+        data.extend_from_slice(&encode_params(&self.inputs, tokens)?);
+        Ok(data)
+    }
+
+    /// Decode the arguments of a call to the function, including the selector.
+    pub fn decode_input(&self, data: &[u8]) -> Result<Vec<Token>> {
+        decode_params(&self.inputs, strip_selector(data, self.selector())?)
+    }
+
+    /// Decode the data returned by the function.
+    pub fn decode_output(&self, data: &[u8]) -> Result<Vec<Token>> {
+        decode_params(&self.outputs, data)
+    }
+}
+
+/// A custom error, as introduced in Solidity 0.8.4.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomError {
+    pub name: String,
+    pub inputs: Vec<Param>,
+}
+
+impl CustomError {
+    /// Parse an error from its signature, e.g. `InsufficientBalance(uint256,uint256)`.
+    pub fn parse(signature: &str) -> Result<Self> {
+        let (name, inputs) = parse_signature(signature)?;
+        Ok(Self { name, inputs })
+    }
+
+    /// Return the canonical signature.
+    pub fn signature(&self) -> String {
+        format_signature(&self.name, &self.inputs)
+    }
+
+    /// Return the 4 bytes selector.
+    pub fn selector(&self) -> [u8; 4] {
+        selector(&self.signature())
+    }
+
+    /// Encode the error with the given arguments, including the selector.
+    pub fn encode(&self, tokens: &[Token]) -> Result<Vec<u8>> {
+        let mut data = self.selector().to_vec();
+        data.extend_from_slice(&encode_params(&self.inputs, tokens)?);
+        Ok(data)
+    }
+
+    /// Decode the arguments of the error, including the selector.
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<Token>> {
+        decode_params(&self.inputs, strip_selector(data, self.selector())?)
+    }
+}
+
+/// A contract constructor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constructor {
+    pub inputs: Vec<Param>,
+}
+
+impl Constructor {
+    /// Encode the deployment data: the given bytecode followed by the
+    /// encoded constructor arguments.
+    pub fn encode_input(&self, bytecode: &[u8], tokens: &[Token]) -> Result<Vec<u8>> {
+        let mut data = bytecode.to_vec();
+        data.extend_from_slice(&encode_params(&self.inputs, tokens)?);
+        Ok(data)
+    }
+}
+
+/// A contract ABI, as produced by the Solidity compiler.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Abi {
+    pub constructor: Option<Constructor>,
+    pub functions: BTreeMap<String, Vec<Function>>,
+    pub events: BTreeMap<String, Vec<Event>>,
+    pub errors: BTreeMap<String, Vec<CustomError>>,
+}
+
+impl Abi {
+    /// Parse a JSON ABI. Both a bare ABI array and a compiler artifact
+    /// holding the ABI under an `abi` key are accepted.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let value = match value.get("abi") {
+            Some(abi) => abi.clone(),
+            None => value,
+        };
+        let items: Vec<RawItem> = serde_json::from_value(value)?;
+
+        let mut abi = Abi::default();
+        for item in items {
+            let inputs = item
+                .inputs
+                .iter()
+                .map(RawParam::to_param)
+                .collect::<Result<Vec<_>>>()?;
+
+            match item.ty.as_str() {
+                "function" => {
+                    let function = Function {
+                        name: item.name.clone(),
+                        inputs,
+                        outputs: item
+                            .outputs
+                            .iter()
+                            .map(RawParam::to_param)
+                            .collect::<Result<_>>()?,
+                        state_mutability: item.state_mutability(),
+                    };
+                    abi.functions.entry(item.name).or_default().push(function);
+                }
+                "event" => {
+                    let event = Event {
+                        name: item.name.clone(),
+                        inputs: item
+                            .inputs
+                            .iter()
+                            .map(RawParam::to_event_param)
+                            .collect::<Result<_>>()?,
+                        anonymous: item.anonymous,
+                    };
+                    abi.events.entry(item.name).or_default().push(event);
+                }
+                "error" => {
+                    let error = CustomError {
+                        name: item.name.clone(),
+                        inputs,
+                    };
+                    abi.errors.entry(item.name).or_default().push(error);
+                }
+                "constructor" => abi.constructor = Some(Constructor { inputs }),
+                _ => {}
+            }
+        }
+
+        Ok(abi)
+    }
+
+    /// Return the function with the given name. If the function is
+    /// overloaded, the first definition is returned.
+    pub fn function(&self, name: &str) -> Result<&Function> {
+        self.functions
+            .get(name)
+            .and_then(|functions| functions.first())
+            .ok_or_else(|| AbiError::NotFound(name.to_string()).into())
+    }
+
+    /// Return the function matching the given selector.
+    pub fn function_by_selector(&self, selector: [u8; 4]) -> Option<&Function> {
+        self.functions
+            .values()
+            .flatten()
+            .find(|function| function.selector() == selector)
+    }
+
+    /// Return the event with the given name. If the event is overloaded,
+    /// the first definition is returned.
+    pub fn event(&self, name: &str) -> Result<&Event> {
+        self.events
+            .get(name)
+            .and_then(|events| events.first())
+            .ok_or_else(|| AbiError::NotFound(name.to_string()).into())
+    }
+
+    /// Return the non-anonymous event matching the given topic.
+    pub fn event_by_topic(&self, topic: &[u8; 32]) -> Option<&Event> {
+        self.events
+            .values()
+            .flatten()
+            .find(|event| !event.anonymous && event.topic() == *topic)
+    }
+
+    /// Decode the given log, matching its first topic against the events of
+    /// this ABI. Anonymous events have no such topic, so they are tried in
+    /// turn when no event matches.
+    pub fn decode_log(&self, log: &RawLog) -> Result<(&Event, Vec<LogParam>)> {
+        if let Some(event) = log.topics.first().and_then(|t| self.event_by_topic(t)) {
+            return Ok((event, event.decode_log(log)?));
+        }
+
+        self.events
+            .values()
+            .flatten()
+            .filter(|event| event.anonymous)
+            .find_map(|event| Some((event, event.decode_log(log).ok()?)))
+            .ok_or_else(|| AbiError::InvalidSelector.into())
+    }
+
+    /// Return the error with the given name.
+    pub fn error(&self, name: &str) -> Result<&CustomError> {
+        self.errors
+            .get(name)
+            .and_then(|errors| errors.first())
+            .ok_or_else(|| AbiError::NotFound(name.to_string()).into())
+    }
+
+    /// Decode revert data, looking up custom errors in this ABI.
+    pub fn decode_revert(&self, data: &[u8]) -> Result<Revert> {
+        let revert = super::decode_revert(data)?;
+        if let Revert::Unknown(data) = &revert {
+            if data.len() >= 4 {
+                if let Some(error) = self
+                    .errors
+                    .values()
+                    .flatten()
+                    .find(|error| error.selector() == data[..4])
+                {
+                    return Ok(Revert::Custom {
+                        name: error.name.clone(),
+                        tokens: error.decode(data)?,
+                    });
+                }
+            }
+        }
+        Ok(revert)
+    }
+}
+
+impl std::str::FromStr for Abi {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_json(s)
+    }
+}
+
+/// Return the 4 bytes selector of the given signature.
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn format_signature(name: &str, params: &[Param]) -> String {
+    let types = params
+        .iter()
+        .map(|param| param.kind.to_string())
+        .collect::<Vec<_>>();
+    format!("{}({})", name, types.join(","))
+}
+
+fn parse_signature(signature: &str) -> Result<(String, Vec<Param>)> {
+    let invalid = || AbiError::InvalidSignature(signature.to_string());
+    let signature = signature.trim();
+    let open = signature.find('(').ok_or_else(invalid)?;
+    let params = signature[open + 1..]
+        .strip_suffix(')')
+        .ok_or_else(invalid)?;
+
+    let inputs = split_params(params)?
+        .into_iter()
+        .map(|param| {
+            let param = param.trim();
+            let (ty, name) = match param.rsplit_once(' ') {
+                Some((ty, name)) if !name.ends_with(')') && !name.ends_with(']') => (ty, name),
+                _ => (param, ""),
+            };
+            Ok(Param {
+                name: name.to_string(),
+                kind: ParamType::parse(ty)?,
+                internal_type: None,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok((signature[..open].to_string(), inputs))
+}
+
+fn encode_params(params: &[Param], tokens: &[Token]) -> Result<Vec<u8>> {
+    if params.len() != tokens.len()
+        || !params
+            .iter()
+            .zip(tokens)
+            .all(|(param, token)| token.type_check(&param.kind))
+    {
+        return Err(AbiError::InvalidArguments.into());
+    }
+    Ok(encode(tokens))
+}
+
+fn decode_params(params: &[Param], data: &[u8]) -> Result<Vec<Token>> {
+    let types = params
+        .iter()
+        .map(|param| param.kind.clone())
+        .collect::<Vec<_>>();
+    decode(&types, data)
+}
+
+fn strip_selector(data: &[u8], selector: [u8; 4]) -> Result<&[u8]> {
+    match data.strip_prefix(&selector) {
+        Some(data) => Ok(data),
+        None => Err(AbiError::InvalidSelector.into()),
+    }
+}
+
+#[derive(Deserialize)]
+struct RawParam {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default)]
+    components: Vec<RawParam>,
+    #[serde(rename = "internalType")]
+    internal_type: Option<String>,
+    #[serde(default)]
+    indexed: bool,
+}
+
+impl RawParam {
+    fn to_param(&self) -> Result<Param> {
+        Ok(Param {
+            name: self.name.clone(),
+            kind: self.kind()?,
+            internal_type: self.internal_type.clone(),
+        })
+    }
+
+    fn to_event_param(&self) -> Result<EventParam> {
+        Ok(EventParam {
+            name: self.name.clone(),
+            kind: self.kind()?,
+            indexed: self.indexed,
+        })
+    }
+
+    fn kind(&self) -> Result<ParamType> {
+        match self.ty.strip_prefix("tuple") {
+            Some(suffix) => {
+                let components = self
+                    .components
+                    .iter()
+                    .map(|param| Ok(param.kind()?.to_string()))
+                    .collect::<Result<Vec<_>>>()?;
+                ParamType::parse(&format!("({}){}", components.join(","), suffix))
+            }
+            None => ParamType::parse(&self.ty),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawItem {
+    #[serde(rename = "type", default = "default_item_type")]
+    ty: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    inputs: Vec<RawParam>,
+    #[serde(default)]
+    outputs: Vec<RawParam>,
+    #[serde(rename = "stateMutability")]
+    state_mutability: Option<StateMutability>,
+    #[serde(default)]
+    constant: bool,
+    #[serde(default)]
+    payable: bool,
+    #[serde(default)]
+    anonymous: bool,
+}
+
+impl RawItem {
+    fn state_mutability(&self) -> StateMutability {
+        // Fall back on the `constant` and `payable` fields used before
+        // Solidity 0.4.16.
+        match self.state_mutability {
+            Some(state_mutability) => state_mutability,
+            None if self.constant => StateMutability::View,
+            None if self.payable => StateMutability::Payable,
+            None => StateMutability::NonPayable,
+        }
+    }
+}
+
+fn default_item_type() -> String {
+    "function".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+    use primitive_types::U256;
+
+    const ERC20: &str = r#"[
+        {
+            "type": "function",
+            "name": "transfer",
+            "inputs": [
+                { "name": "to", "type": "address", "internalType": "address" },
+                { "name": "amount", "type": "uint256", "internalType": "uint256" }
+            ],
+            "outputs": [{ "name": "", "type": "bool", "internalType": "bool" }],
+            "stateMutability": "nonpayable"
+        },
+        {
+            "type": "function",
+            "name": "balanceOf",
+            "inputs": [{ "name": "account", "type": "address" }],
+            "outputs": [{ "name": "", "type": "uint256" }],
+            "constant": true
+        },
+        {
+            "type": "function",
+            "name": "submit",
+            "inputs": [{
+                "name": "order",
+                "type": "tuple[]",
+                "components": [
+                    { "name": "maker", "type": "address" },
+                    { "name": "amounts", "type": "uint256[2]" }
+                ]
+            }],
+            "outputs": []
+        },
+        {
+            "type": "error",
+            "name": "InsufficientBalance",
+            "inputs": [
+                { "name": "available", "type": "uint256" },
+                { "name": "required", "type": "uint256" }
+            ]
+        },
+        {
+            "type": "event",
+            "name": "Transfer",
+            "inputs": [
+                { "name": "from", "type": "address", "indexed": true },
+                { "name": "to", "type": "address", "indexed": true },
+                { "name": "value", "type": "uint256", "indexed": false }
+            ],
+            "anonymous": false
+        },
+        {
+            "type": "event",
+            "name": "Deposit",
+            "inputs": [
+                { "name": "owner", "type": "address", "indexed": true },
+                { "name": "amount", "type": "uint256", "indexed": false }
+            ],
+            "anonymous": true
+        }
+    ]"#;
+
+    #[test]
+    fn test_json() {
+        let abi = Abi::from_json(ERC20).unwrap();
+        let transfer = abi.function("transfer").unwrap();
+        assert_eq!(transfer.signature(), "transfer(address,uint256)");
+        assert_eq!(hex::encode(transfer.selector()), "a9059cbb");
+        assert_eq!(
+            abi.function("balanceOf").unwrap().state_mutability,
+            StateMutability::View
+        );
+        assert_eq!(
+            abi.function("submit").unwrap().signature(),
+            "submit((address,uint256[2])[])"
+        );
+        assert!(abi.function("approve").is_err());
+        assert_eq!(
+            abi.function_by_selector([0xa9, 0x05, 0x9c, 0xbb]),
+            Some(transfer)
+        );
+
+        let artifact = format!(r#"{{ "contractName": "ERC20", "abi": {} }}"#, ERC20);
+        assert_eq!(Abi::from_json(&artifact).unwrap(), abi);
+    }
+
+    #[test]
+    fn test_function() {
+        let function = Function::parse("transfer(address to, uint256 amount)").unwrap();
+        assert_eq!(function.signature(), "transfer(address,uint256)");
+        assert_eq!(function.inputs[1].name, "amount");
+
+        let to: Address = "0x1111111111111111111111111111111111111111"
+            .parse()
+            .unwrap();
+        let tokens = vec![Token::Address(to), Token::Uint(U256::from(1000))];
+        let data = function.encode_input(&tokens).unwrap();
+        assert_eq!(
+            hex::encode(&data),
+            "a9059cbb\
+             0000000000000000000000001111111111111111111111111111111111111111\
+             00000000000000000000000000000000000000000000000000000000000003e8"
+        );
+        assert_eq!(function.decode_input(&data).unwrap(), tokens);
+
+        assert!(function.encode_input(&[Token::Address(to)]).is_err());
+        assert!(function
+            .encode_input(&[Token::Bool(true), Token::Uint(U256::from(1000))])
+            .is_err());
+        assert!(function.decode_input(&data[1..]).is_err());
+
+        let function = Function::parse("call(int8 value, function callback)").unwrap();
+        assert_eq!(function.signature(), "call(int8,function)");
+        let callback = Token::FixedBytes([0x22; 24].to_vec());
+        for value in [-128, 127] {
+            let tokens = vec![Token::int(value), callback.clone()];
+            let data = function.encode_input(&tokens).unwrap();
+            assert_eq!(function.decode_input(&data).unwrap(), tokens);
+        }
+        for value in [-129, 128] {
+            assert!(function
+                .encode_input(&[Token::int(value), callback.clone()])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_log() {
+        let abi = Abi::from_json(ERC20).unwrap();
+        let owner = Event::encode_topic(&Token::Address(Address::new([0x11; 20]))).unwrap();
+
+        let transfer = abi.event("Transfer").unwrap();
+        let log = RawLog {
+            topics: vec![transfer.topic(), owner, owner],
+            data: encode(&[Token::Uint(U256::from(7))]),
+        };
+        let (event, params) = abi.decode_log(&log).unwrap();
+        assert_eq!(event.name, "Transfer");
+        assert_eq!(params[2].value, Token::Uint(U256::from(7)));
+
+        let log = RawLog {
+            topics: vec![owner],
+            data: encode(&[Token::Uint(U256::from(7))]),
+        };
+        let (event, params) = abi.decode_log(&log).unwrap();
+        assert_eq!(event.name, "Deposit");
+        assert_eq!(params[0].value, Token::Address(Address::new([0x11; 20])));
+
+        let log = RawLog {
+            topics: vec![[0xff; 32], owner],
+            data: vec![],
+        };
+        assert!(abi.decode_log(&log).is_err());
+    }
+
+    #[test]
+    fn test_custom_error() {
+        let abi = Abi::from_json(ERC20).unwrap();
+        let error = abi.error("InsufficientBalance").unwrap();
+        let data = error
+            .encode(&[Token::Uint(U256::from(1)), Token::Uint(U256::from(2))])
+            .unwrap();
+
+        assert_eq!(
+            abi.decode_revert(&data).unwrap(),
+            Revert::Custom {
+                name: "InsufficientBalance".to_string(),
+                tokens: vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))],
+            }
+        );
+    }
+}
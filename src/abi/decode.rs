@@ -0,0 +1,185 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{token::int_fits, AbiError, ParamType, Token};
+use crate::address::Address;
+use horror::Result;
+use primitive_types::U256;
+
+/// Decode the given data as a tuple of the given types, as done for
+/// function arguments and return values.
+pub fn decode(types: &[ParamType], data: &[u8]) -> Result<Vec<Token>> {
+    let mut tokens = Vec::with_capacity(types.len());
+    let mut cursor = 0;
+
+    for ty in types {
+        if ty.is_dynamic() {
+            let offset = read_usize(data, cursor)?;
+            tokens.push(decode_token(ty, tail(data, offset)?)?);
+            cursor += 32;
+        } else {
+            tokens.push(decode_token(ty, tail(data, cursor)?)?);
+            cursor += ty.head_size();
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Decode a single token, located at the start of the given data.
+fn decode_token(ty: &ParamType, data: &[u8]) -> Result<Token> {
+    let token = match ty {
+        ParamType::Address => Token::Address(Address::from_slice(&read_word(data, 0)?[12..])?),
+        ParamType::Bool => match read_u256(data, 0)? {
+            value if value.is_zero() => Token::Bool(false),
+            value if value == U256::one() => Token::Bool(true),
+            _ => return Err(AbiError::InvalidData.into()),
+        },
+        ParamType::Int(bits) => {
+            let value = read_u256(data, 0)?;
+            if !int_fits(value, *bits) {
+                return Err(AbiError::InvalidData.into());
+            }
+            Token::Int(value)
+        }
+        ParamType::Uint(bits) => {
+            let value = read_u256(data, 0)?;
+            if value.bits() > *bits {
+                return Err(AbiError::InvalidData.into());
+            }
+            Token::Uint(value)
+        }
+        ParamType::FixedBytes(size) => Token::FixedBytes(read_word(data, 0)?[..*size].to_vec()),
+        ParamType::Function => Token::FixedBytes(read_word(data, 0)?[..24].to_vec()),
+        ParamType::Bytes => Token::Bytes(read_bytes(data)?.to_vec()),
+        ParamType::String => Token::String(
+            String::from_utf8(read_bytes(data)?.to_vec()).map_err(|_| AbiError::InvalidData)?,
+        ),
+        ParamType::Array(ty) => {
+            let len = read_usize(data, 0)?;
+            let data = tail(data, 32)?;
+            // Every element takes at least one word, reject lengths that
+            // cannot fit before allocating.
+            if len > data.len() / 32 {
+                return Err(AbiError::InvalidData.into());
+            }
+            Token::Array(decode(&vec![ty.as_ref().clone(); len], data)?)
+        }
+        ParamType::FixedArray(ty, len) => {
+            if *len > data.len() / 32 {
+                return Err(AbiError::InvalidData.into());
+            }
+            Token::FixedArray(decode(&vec![ty.as_ref().clone(); *len], data)?)
+        }
+        ParamType::Tuple(types) => Token::Tuple(decode(types, data)?),
+    };
+
+    Ok(token)
+}
+
+fn tail(data: &[u8], offset: usize) -> Result<&[u8]> {
+    data.get(offset..)
+        .ok_or_else(|| AbiError::InvalidData.into())
+}
+
+fn read_word(data: &[u8], offset: usize) -> Result<&[u8]> {
+    offset
+        .checked_add(32)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| AbiError::InvalidData.into())
+}
+
+fn read_u256(data: &[u8], offset: usize) -> Result<U256> {
+    Ok(U256::from_big_endian(read_word(data, offset)?))
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Result<usize> {
+    let value = read_u256(data, offset)?;
+    if value > U256::from(u32::MAX) {
+        return Err(AbiError::InvalidData.into());
+    }
+    Ok(value.as_usize())
+}
+
+fn read_bytes(data: &[u8]) -> Result<&[u8]> {
+    let len = read_usize(data, 0)?;
+    data.get(32..)
+        .and_then(|data| data.get(..len))
+        .ok_or_else(|| AbiError::InvalidData.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::encode;
+
+    #[test]
+    fn test_roundtrip() {
+        let types = [
+            ParamType::parse("uint256").unwrap(),
+            ParamType::parse("uint32[]").unwrap(),
+            ParamType::parse("bytes10").unwrap(),
+            ParamType::parse("bytes").unwrap(),
+            ParamType::parse("(int8,string[2],bool)").unwrap(),
+        ];
+        let tokens = vec![
+            Token::Uint(U256::from(0x123)),
+            Token::Array(vec![Token::Uint(0x456.into()), Token::Uint(0x789.into())]),
+            Token::FixedBytes(b"1234567890".to_vec()),
+            Token::Bytes(b"Hello, world!".to_vec()),
+            Token::Tuple(vec![
+                Token::int(-2),
+                Token::FixedArray(vec!["one".into(), "two".into()]),
+                Token::Bool(true),
+            ]),
+        ];
+
+        let encoded = encode(&tokens);
+        assert_eq!(decode(&types, &encoded).unwrap(), tokens);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let types = [ParamType::Bytes];
+        assert!(decode(&types, &[]).is_err());
+
+        // offset pointing outside of the data
+        let mut data = [0u8; 32];
+        data[31] = 0x40;
+        assert!(decode(&types, &data).is_err());
+
+        // array length larger than the data
+        let types = [ParamType::parse("uint256[]").unwrap()];
+        let mut data = [0u8; 64];
+        data[31] = 0x20;
+        data[32..].copy_from_slice(&[0xff; 32]);
+        assert!(decode(&types, &data).is_err());
+
+        // int8 words must be sign extended
+        let types = [ParamType::Int(8)];
+        let mut data = [0xff; 32];
+        data[31] = 0x80;
+        assert_eq!(decode(&types, &data).unwrap(), [Token::int(-128)]);
+        data[0] = 0;
+        assert!(decode(&types, &data).is_err());
+        let mut data = [0u8; 32];
+        data[31] = 0x80;
+        assert!(decode(&types, &data).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Token;
+use primitive_types::U256;
+
+/// Encode the given tokens as a tuple, as done for function arguments
+/// and return values.
+pub fn encode(tokens: &[Token]) -> Vec<u8> {
+    let head_size = tokens
+        .iter()
+        .map(|token| match token {
+            _ if token.is_dynamic() => 32,
+            Token::FixedArray(tokens) | Token::Tuple(tokens) => encode(tokens).len(),
+            _ => 32,
+        })
+        .sum::<usize>();
+
+    let mut head = Vec::with_capacity(head_size);
+    let mut tail = Vec::new();
+
+    for token in tokens {
+        if token.is_dynamic() {
+            head.extend_from_slice(&usize_word(head_size + tail.len()));
+            tail.extend_from_slice(&encode_token(token));
+        } else {
+            head.extend_from_slice(&encode_token(token));
+        }
+    }
+
+    head.extend_from_slice(&tail);
+    head
+}
+
+/// Encode a single token as it appears in a tuple.
+fn encode_token(token: &Token) -> Vec<u8> {
+    match token {
+        Token::Address(address) => {
+            let mut word = [0u8; 32];
+            word[12..].copy_from_slice(address.as_ref());
+            word.to_vec()
+        }
+        Token::Bool(value) => u256_word(U256::from(*value as u8)).to_vec(),
+        Token::Int(value) | Token::Uint(value) => u256_word(*value).to_vec(),
+        Token::FixedBytes(bytes) => pad_right(bytes),
+        Token::Bytes(bytes) => encode_bytes(bytes),
+        Token::String(value) => encode_bytes(value.as_bytes()),
+        Token::Array(tokens) => {
+            let mut result = usize_word(tokens.len()).to_vec();
+            result.extend_from_slice(&encode(tokens));
+            result
+        }
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => encode(tokens),
+    }
+}
+
+fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut result = usize_word(bytes.len()).to_vec();
+    result.extend_from_slice(&pad_right(bytes));
+    result
+}
+
+fn pad_right(bytes: &[u8]) -> Vec<u8> {
+    let mut result = bytes.to_vec();
+    result.resize(bytes.len().div_ceil(32) * 32, 0);
+    result
+}
+
+pub(crate) fn u256_word(value: U256) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word);
+    word
+}
+
+fn usize_word(value: usize) -> [u8; 32] {
+    u256_word(U256::from(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+
+    #[test]
+    fn test_encode_static() {
+        let address: Address = "0x1111111111111111111111111111111111111111"
+            .parse()
+            .unwrap();
+        let encoded = encode(&[Token::Address(address), Token::Uint(U256::from(69))]);
+        assert_eq!(
+            hex::encode(encoded),
+            "0000000000000000000000001111111111111111111111111111111111111111\
+             0000000000000000000000000000000000000000000000000000000000000045"
+        );
+
+        let encoded = encode(&[Token::int(-1), Token::FixedBytes(b"abc".to_vec())]);
+        assert_eq!(
+            hex::encode(encoded),
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+             6162630000000000000000000000000000000000000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_encode_dynamic() {
+        // https://docs.soliditylang.org/en/latest/abi-spec.html#examples
+        // f(uint256,uint32[],bytes10,bytes) with (0x123, [0x456, 0x789], "1234567890", "Hello, world!")
+        let encoded = encode(&[
+            Token::Uint(U256::from(0x123)),
+            Token::Array(vec![
+                Token::Uint(U256::from(0x456)),
+                Token::Uint(U256::from(0x789)),
+            ]),
+            Token::FixedBytes(b"1234567890".to_vec()),
+            Token::Bytes(b"Hello, world!".to_vec()),
+        ]);
+        assert_eq!(
+            hex::encode(encoded),
+            "0000000000000000000000000000000000000000000000000000000000000123\
+             0000000000000000000000000000000000000000000000000000000000000080\
+             3132333435363738393000000000000000000000000000000000000000000000\
+             00000000000000000000000000000000000000000000000000000000000000e0\
+             0000000000000000000000000000000000000000000000000000000000000002\
+             0000000000000000000000000000000000000000000000000000000000000456\
+             0000000000000000000000000000000000000000000000000000000000000789\
+             000000000000000000000000000000000000000000000000000000000000000d\
+             48656c6c6f2c20776f726c642100000000000000000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_encode_nested() {
+        // g(uint256[][],string[]) with ([[1, 2], [3]], ["one", "two", "three"])
+        let encoded = encode(&[
+            Token::Array(vec![
+                Token::Array(vec![Token::Uint(1.into()), Token::Uint(2.into())]),
+                Token::Array(vec![Token::Uint(3.into())]),
+            ]),
+            Token::Array(vec!["one".into(), "two".into(), "three".into()]),
+        ]);
+        assert_eq!(
+            hex::encode(encoded),
+            "0000000000000000000000000000000000000000000000000000000000000040\
+             0000000000000000000000000000000000000000000000000000000000000140\
+             0000000000000000000000000000000000000000000000000000000000000002\
+             0000000000000000000000000000000000000000000000000000000000000040\
+             00000000000000000000000000000000000000000000000000000000000000a0\
+             0000000000000000000000000000000000000000000000000000000000000002\
+             0000000000000000000000000000000000000000000000000000000000000001\
+             0000000000000000000000000000000000000000000000000000000000000002\
+             0000000000000000000000000000000000000000000000000000000000000001\
+             0000000000000000000000000000000000000000000000000000000000000003\
+             0000000000000000000000000000000000000000000000000000000000000003\
+             0000000000000000000000000000000000000000000000000000000000000060\
+             00000000000000000000000000000000000000000000000000000000000000a0\
+             00000000000000000000000000000000000000000000000000000000000000e0\
+             0000000000000000000000000000000000000000000000000000000000000003\
+             6f6e650000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000003\
+             74776f0000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000005\
+             7468726565000000000000000000000000000000000000000000000000000000"
+        );
+    }
+}
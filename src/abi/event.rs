@@ -0,0 +1,251 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{decode, encode, split_params, AbiError, ParamType, Token};
+use crate::hash::keccak256;
+use horror::Result;
+
+/// A parameter of an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventParam {
+    pub name: String,
+    pub kind: ParamType,
+    pub indexed: bool,
+}
+
+/// A decoded event parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogParam {
+    pub name: String,
+    /// The decoded value. Indexed parameters of dynamic types cannot be
+    /// recovered from the log; their topic, the hash of the value, is
+    /// returned as a `bytes32` instead.
+    pub value: Token,
+}
+
+/// A log entry, as emitted by the `LOG` opcodes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawLog {
+    pub topics: Vec<[u8; 32]>,
+    pub data: Vec<u8>,
+}
+
+/// A contract event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub name: String,
+    pub inputs: Vec<EventParam>,
+    pub anonymous: bool,
+}
+
+impl Event {
+    /// Parse an event from its signature, e.g.
+    /// `Transfer(address indexed from, address indexed to, uint256 value)`.
+    pub fn parse(signature: &str) -> Result<Self> {
+        let invalid = || AbiError::InvalidSignature(signature.to_string());
+        let signature = signature.trim();
+        let open = signature.find('(').ok_or_else(invalid)?;
+        let params = signature[open + 1..]
+            .strip_suffix(')')
+            .ok_or_else(invalid)?;
+
+        let inputs = split_params(params)?
+            .into_iter()
+            .map(|param| {
+                let mut words = param.split_whitespace();
+                let kind = ParamType::parse(words.next().ok_or_else(invalid)?)?;
+                let mut indexed = false;
+                let mut name = "";
+                for word in words {
+                    match word {
+                        "indexed" => indexed = true,
+                        _ => name = word,
+                    }
+                }
+                Ok(EventParam {
+                    name: name.to_string(),
+                    kind,
+                    indexed,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            name: signature[..open].to_string(),
+            inputs,
+            anonymous: false,
+        })
+    }
+
+    /// Return the canonical signature, e.g. `Transfer(address,address,uint256)`.
+    pub fn signature(&self) -> String {
+        let types = self
+            .inputs
+            .iter()
+            .map(|param| param.kind.to_string())
+            .collect::<Vec<_>>();
+        format!("{}({})", self.name, types.join(","))
+    }
+
+    /// Return the event topic, the hash of the signature. This is the
+    /// first topic of every log emitted by a non-anonymous event.
+    pub fn topic(&self) -> [u8; 32] {
+        keccak256(self.signature())
+    }
+
+    /// Decode the given log. The parameters are returned in the order of
+    /// the event definition.
+    pub fn decode_log(&self, log: &RawLog) -> Result<Vec<LogParam>> {
+        let mut topics = log.topics.iter();
+        if !self.anonymous && topics.next() != Some(&self.topic()) {
+            return Err(AbiError::InvalidSelector.into());
+        }
+
+        let indexed = self.inputs.iter().filter(|param| param.indexed).count();
+        if topics.len() != indexed {
+            return Err(AbiError::InvalidData.into());
+        }
+
+        let types = self
+            .inputs
+            .iter()
+            .filter(|param| !param.indexed)
+            .map(|param| param.kind.clone())
+            .collect::<Vec<_>>();
+        let mut values = decode(&types, &log.data)?.into_iter();
+
+        self.inputs
+            .iter()
+            .map(|param| {
+                let value = if param.indexed {
+                    let topic = topics.next().ok_or(AbiError::InvalidData)?;
+                    decode_topic(&param.kind, topic)?
+                } else {
+                    values.next().ok_or(AbiError::InvalidData)?
+                };
+                Ok(LogParam {
+                    name: param.name.clone(),
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// Encode the given value as a topic, to filter logs on an indexed
+    /// parameter. Only value types, `string` and `bytes` are supported.
+    pub fn encode_topic(token: &Token) -> Result<[u8; 32]> {
+        match token {
+            Token::String(value) => Ok(keccak256(value)),
+            Token::Bytes(bytes) => Ok(keccak256(bytes)),
+            Token::Array(_) | Token::FixedArray(_) | Token::Tuple(_) => {
+                Err(AbiError::InvalidArguments.into())
+            }
+            _ => Ok(encode(std::slice::from_ref(token))[..].try_into()?),
+        }
+    }
+}
+
+fn decode_topic(ty: &ParamType, topic: &[u8; 32]) -> Result<Token> {
+    if ty.is_dynamic() || matches!(ty, ParamType::FixedArray(..) | ParamType::Tuple(_)) {
+        return Ok(Token::FixedBytes(topic.to_vec()));
+    }
+    Ok(decode(std::slice::from_ref(ty), topic)?.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+    use primitive_types::U256;
+
+    fn topic(hex: &str) -> [u8; 32] {
+        hex::decode(hex).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_transfer() {
+        let event =
+            Event::parse("Transfer(address indexed from, address indexed to, uint256 value)")
+                .unwrap();
+        assert_eq!(
+            hex::encode(event.topic()),
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+
+        let from: Address = "0x1111111111111111111111111111111111111111"
+            .parse()
+            .unwrap();
+        let to: Address = "0x2222222222222222222222222222222222222222"
+            .parse()
+            .unwrap();
+        let log = RawLog {
+            topics: vec![
+                event.topic(),
+                Event::encode_topic(&Token::Address(from)).unwrap(),
+                Event::encode_topic(&Token::Address(to)).unwrap(),
+            ],
+            data: encode(&[Token::Uint(U256::from(1000))]),
+        };
+
+        let params = event.decode_log(&log).unwrap();
+        assert_eq!(params[0].name, "from");
+        assert_eq!(params[0].value, Token::Address(from));
+        assert_eq!(params[1].value, Token::Address(to));
+        assert_eq!(params[2].value, Token::Uint(U256::from(1000)));
+
+        let mut log = log;
+        log.topics[0] = [0u8; 32];
+        assert!(event.decode_log(&log).is_err());
+    }
+
+    #[test]
+    fn test_indexed_dynamic() {
+        let event = Event::parse("Named(string indexed name, string value)").unwrap();
+        let log = RawLog {
+            topics: vec![
+                event.topic(),
+                Event::encode_topic(&Token::from("laron")).unwrap(),
+            ],
+            data: encode(&[Token::from("wallet")]),
+        };
+
+        let params = event.decode_log(&log).unwrap();
+        assert_eq!(
+            params[0].value,
+            Token::FixedBytes(keccak256("laron").to_vec())
+        );
+        assert_eq!(params[1].value, Token::from("wallet"));
+    }
+
+    #[test]
+    fn test_anonymous() {
+        let mut event = Event::parse("Deposit(address indexed owner, uint256 amount)").unwrap();
+        event.anonymous = true;
+
+        let owner = topic("0000000000000000000000001111111111111111111111111111111111111111");
+        let log = RawLog {
+            topics: vec![owner],
+            data: encode(&[Token::Uint(U256::from(5))]),
+        };
+
+        let params = event.decode_log(&log).unwrap();
+        assert_eq!(params[0].value, Token::Address(Address::new([0x11; 20])));
+        assert_eq!(params[1].value, Token::Uint(U256::from(5)));
+    }
+}
@@ -0,0 +1,214 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Solidity ABI
+//!
+//! This module implements the
+//! [contract ABI specification](https://docs.soliditylang.org/en/latest/abi-spec.html):
+//! encoding of function calls, decoding of return data, revert reasons and
+//! event logs, and parsing of JSON ABI files. The encoded call can be used
+//! as the `data` field of a transaction.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::abi::{Abi, Token};
+//! use laron_wallet::address::Address;
+//! use laron_wallet::U256;
+//!
+//! let abi = Abi::from_json(r#"[{
+//!     "type": "function",
+//!     "name": "transfer",
+//!     "inputs": [
+//!         { "name": "to", "type": "address" },
+//!         { "name": "amount", "type": "uint256" }
+//!     ],
+//!     "outputs": [{ "name": "", "type": "bool" }],
+//!     "stateMutability": "nonpayable"
+//! }]"#).unwrap();
+//!
+//! let to: Address = "0x1111111111111111111111111111111111111111".parse().unwrap();
+//! let data = abi
+//!     .function("transfer")
+//!     .unwrap()
+//!     .encode_input(&[Token::Address(to), Token::Uint(U256::from(1000))])
+//!     .unwrap();
+//! ```
+
+mod contract;
+mod decode;
+mod encode;
+mod event;
+mod param;
+mod token;
+
+pub use contract::*;
+pub use decode::*;
+pub use encode::*;
+pub use event::*;
+pub use param::*;
+pub use token::*;
+
+use horror::Result;
+use primitive_types::U256;
+
+/// Selector of the `Error(string)` revert reason.
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Selector of the `Panic(uint256)` revert reason.
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Error returned when encoding or decoding ABI data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiError {
+    /// The type string cannot be parsed.
+    InvalidType(String),
+    /// The function or error signature cannot be parsed.
+    InvalidSignature(String),
+    /// The data is truncated or does not match the expected types.
+    InvalidData,
+    /// The arguments do not match the parameter types.
+    InvalidArguments,
+    /// The data does not start with the expected selector.
+    InvalidSelector,
+    /// No item with the given name exists in the ABI.
+    NotFound(String),
+}
+
+impl std::fmt::Display for AbiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AbiError::InvalidType(ty) => write!(f, "Invalid type: {}", ty),
+            AbiError::InvalidSignature(sig) => write!(f, "Invalid signature: {}", sig),
+            AbiError::InvalidData => write!(f, "Invalid data"),
+            AbiError::InvalidArguments => write!(f, "Invalid arguments"),
+            AbiError::InvalidSelector => write!(f, "Invalid selector"),
+            AbiError::NotFound(name) => write!(f, "Not found: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for AbiError {}
+
+/// The reason of a reverted call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Revert {
+    /// `revert("reason")` or `require(false, "reason")`.
+    Error(String),
+    /// A `Panic(uint256)` raised by the compiler, e.g. on overflow.
+    Panic(U256),
+    /// A custom error defined in the contract ABI.
+    Custom { name: String, tokens: Vec<Token> },
+    /// Data that could not be decoded, including an empty revert.
+    Unknown(Vec<u8>),
+}
+
+impl Revert {
+    /// Return the description of a panic code, as listed in the Solidity
+    /// documentation.
+    pub fn panic_reason(code: U256) -> Option<&'static str> {
+        if code > U256::from(u8::MAX) {
+            return None;
+        }
+
+        let reason = match code.as_u32() {
+            0x00 => "generic compiler inserted panic",
+            0x01 => "assertion failed",
+            0x11 => "arithmetic underflow or overflow",
+            0x12 => "division or modulo by zero",
+            0x21 => "invalid enum value",
+            0x22 => "invalid storage byte array encoding",
+            0x31 => "pop on empty array",
+            0x32 => "array index out of bounds",
+            0x41 => "out of memory",
+            0x51 => "call to zero-initialized internal function",
+            _ => return None,
+        };
+
+        Some(reason)
+    }
+}
+
+impl std::fmt::Display for Revert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Revert::Error(reason) => write!(f, "execution reverted: {}", reason),
+            Revert::Panic(code) => match Revert::panic_reason(*code) {
+                Some(reason) => write!(f, "panic: {} (0x{:02x})", reason, code),
+                None => write!(f, "panic: 0x{:02x}", code),
+            },
+            Revert::Custom { name, .. } => write!(f, "execution reverted: {}", name),
+            Revert::Unknown(data) => write!(f, "execution reverted: 0x{}", hex::encode(data)),
+        }
+    }
+}
+
+/// Decode the built-in `Error(string)` and `Panic(uint256)` revert reasons.
+/// Other data is returned as [`Revert::Unknown`]; use
+/// [`Abi::decode_revert`] to decode custom errors.
+pub fn decode_revert(data: &[u8]) -> Result<Revert> {
+    if let Some(data) = data.strip_prefix(&ERROR_SELECTOR) {
+        if let Token::String(reason) = &decode(&[ParamType::String], data)?[0] {
+            return Ok(Revert::Error(reason.clone()));
+        }
+    }
+
+    if let Some(data) = data.strip_prefix(&PANIC_SELECTOR) {
+        if let Token::Uint(code) = decode(&[ParamType::Uint(256)], data)?[0] {
+            return Ok(Revert::Panic(code));
+        }
+    }
+
+    Ok(Revert::Unknown(data.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_revert() {
+        let data = hex::decode(
+            "08c379a0\
+             0000000000000000000000000000000000000000000000000000000000000020\
+             000000000000000000000000000000000000000000000000000000000000001a\
+             4e6f7420656e6f7567682045746865722070726f76696465642e000000000000",
+        )
+        .unwrap();
+        let revert = decode_revert(&data).unwrap();
+        assert_eq!(
+            revert,
+            Revert::Error("Not enough Ether provided.".to_string())
+        );
+
+        let data = hex::decode(
+            "4e487b71\
+             0000000000000000000000000000000000000000000000000000000000000011",
+        )
+        .unwrap();
+        let revert = decode_revert(&data).unwrap();
+        assert_eq!(revert, Revert::Panic(U256::from(0x11)));
+        assert_eq!(
+            revert.to_string(),
+            "panic: arithmetic underflow or overflow (0x11)"
+        );
+
+        assert_eq!(decode_revert(&[]).unwrap(), Revert::Unknown(vec![]));
+        assert!(decode_revert(&ERROR_SELECTOR).is_err());
+    }
+}
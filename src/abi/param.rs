@@ -0,0 +1,243 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::AbiError;
+use horror::Result;
+
+/// The type of a Solidity ABI parameter.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ParamType {
+    /// `address`
+    Address,
+    /// `bool`
+    Bool,
+    /// `intN`, where N is the number of bits.
+    Int(usize),
+    /// `uintN`, where N is the number of bits.
+    Uint(usize),
+    /// `bytesN`, where N is the number of bytes.
+    FixedBytes(usize),
+    /// `bytes`
+    Bytes,
+    /// `string`
+    String,
+    /// `function`, an address followed by a selector, encoded as `bytes24`.
+    Function,
+    /// `T[]`
+    Array(Box<ParamType>),
+    /// `T[N]`
+    FixedArray(Box<ParamType>, usize),
+    /// `(T1,T2,...)`
+    Tuple(Vec<ParamType>),
+}
+
+impl ParamType {
+    /// Parse a parameter type from its canonical string representation,
+    /// e.g. `uint256`, `bytes32[]` or `(address,uint256)[2]`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        if let Some(inner) = s.strip_suffix(']') {
+            let open = inner
+                .rfind('[')
+                .ok_or_else(|| AbiError::InvalidType(s.to_string()))?;
+            let ty = Box::new(Self::parse(&inner[..open])?);
+            let len = &inner[open + 1..];
+            if len.is_empty() {
+                return Ok(ParamType::Array(ty));
+            }
+            let len = len
+                .parse()
+                .map_err(|_| AbiError::InvalidType(s.to_string()))?;
+            return Ok(ParamType::FixedArray(ty, len));
+        }
+
+        if let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            return Ok(ParamType::Tuple(
+                split_params(inner)?
+                    .into_iter()
+                    .map(Self::parse)
+                    .collect::<Result<_>>()?,
+            ));
+        }
+
+        let invalid = || AbiError::InvalidType(s.to_string());
+        let ty = match s {
+            "address" => ParamType::Address,
+            "bool" => ParamType::Bool,
+            "string" => ParamType::String,
+            "bytes" => ParamType::Bytes,
+            "int" => ParamType::Int(256),
+            "uint" => ParamType::Uint(256),
+            "function" => ParamType::Function,
+            _ if s.starts_with("bytes") => {
+                let size = s[5..].parse().map_err(|_| invalid())?;
+                if size == 0 || size > 32 {
+                    return Err(invalid().into());
+                }
+                ParamType::FixedBytes(size)
+            }
+            _ if s.starts_with("uint") || s.starts_with("int") => {
+                let bits = s.trim_start_matches('u')[3..]
+                    .parse()
+                    .map_err(|_| invalid())?;
+                if bits == 0 || bits > 256 || bits % 8 != 0 {
+                    return Err(invalid().into());
+                }
+                if s.starts_with('u') {
+                    ParamType::Uint(bits)
+                } else {
+                    ParamType::Int(bits)
+                }
+            }
+            _ => return Err(invalid().into()),
+        };
+
+        Ok(ty)
+    }
+
+    /// Return true if the type is dynamically sized.
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(ty, _) => ty.is_dynamic(),
+            ParamType::Tuple(types) => types.iter().any(ParamType::is_dynamic),
+            _ => false,
+        }
+    }
+
+    /// Return the size in bytes of the head of a value of this type.
+    pub(crate) fn head_size(&self) -> usize {
+        if self.is_dynamic() {
+            return 32;
+        }
+
+        match self {
+            ParamType::FixedArray(ty, len) => ty.head_size() * len,
+            ParamType::Tuple(types) => types.iter().map(ParamType::head_size).sum(),
+            _ => 32,
+        }
+    }
+}
+
+impl std::fmt::Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamType::Address => write!(f, "address"),
+            ParamType::Bool => write!(f, "bool"),
+            ParamType::Int(bits) => write!(f, "int{}", bits),
+            ParamType::Uint(bits) => write!(f, "uint{}", bits),
+            ParamType::FixedBytes(size) => write!(f, "bytes{}", size),
+            ParamType::Bytes => write!(f, "bytes"),
+            ParamType::String => write!(f, "string"),
+            ParamType::Function => write!(f, "function"),
+            ParamType::Array(ty) => write!(f, "{}[]", ty),
+            ParamType::FixedArray(ty, len) => write!(f, "{}[{}]", ty, len),
+            ParamType::Tuple(types) => {
+                let types = types.iter().map(|ty| ty.to_string()).collect::<Vec<_>>();
+                write!(f, "({})", types.join(","))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for ParamType {
+    type Err = horror::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/// Split a comma separated parameter list, ignoring the commas nested
+/// inside tuples.
+pub(crate) fn split_params(s: &str) -> Result<Vec<&str>> {
+    let mut result = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| AbiError::InvalidType(s.to_string()))?
+            }
+            ',' if depth == 0 => {
+                result.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        return Err(AbiError::InvalidType(s.to_string()).into());
+    }
+    if !s.trim().is_empty() {
+        result.push(&s[start..]);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for ty in [
+            "address",
+            "bool",
+            "int8",
+            "uint256",
+            "bytes4",
+            "bytes",
+            "string",
+            "function",
+            "uint256[]",
+            "bytes32[3]",
+            "(address,(uint256,string)[])[2]",
+        ] {
+            assert_eq!(ParamType::parse(ty).unwrap().to_string(), ty);
+        }
+
+        assert_eq!(ParamType::parse("uint").unwrap(), ParamType::Uint(256));
+        assert!(ParamType::parse("uint7").is_err());
+        assert!(ParamType::parse("bytes33").is_err());
+        assert!(ParamType::parse("(uint256").is_err());
+    }
+
+    #[test]
+    fn test_dynamic() {
+        assert!(!ParamType::parse("(uint256,bytes32[2])")
+            .unwrap()
+            .is_dynamic());
+        assert!(ParamType::parse("(uint256,bytes)").unwrap().is_dynamic());
+        assert!(ParamType::parse("string[2]").unwrap().is_dynamic());
+        assert_eq!(
+            ParamType::parse("(uint256,bytes32[2])")
+                .unwrap()
+                .head_size(),
+            96
+        );
+    }
+}
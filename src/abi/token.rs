@@ -0,0 +1,186 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::ParamType;
+use crate::address::Address;
+use primitive_types::U256;
+
+/// A Solidity ABI value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Token {
+    /// `address`
+    Address(Address),
+    /// `bool`
+    Bool(bool),
+    /// `intN`, stored as the two's complement of the value on 256 bits.
+    Int(U256),
+    /// `uintN`
+    Uint(U256),
+    /// `bytesN`
+    FixedBytes(Vec<u8>),
+    /// `bytes`
+    Bytes(Vec<u8>),
+    /// `string`
+    String(String),
+    /// `T[]`
+    Array(Vec<Token>),
+    /// `T[N]`
+    FixedArray(Vec<Token>),
+    /// `(T1,T2,...)`
+    Tuple(Vec<Token>),
+}
+
+impl Token {
+    /// Create an `int` token from the given signed value.
+    pub fn int(value: i128) -> Self {
+        let abs = U256::from(value.unsigned_abs());
+        if value < 0 {
+            Token::Int((!abs).overflowing_add(U256::one()).0)
+        } else {
+            Token::Int(abs)
+        }
+    }
+
+    /// Return true if the token is dynamically sized.
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            Token::Bytes(_) | Token::String(_) | Token::Array(_) => true,
+            Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+                tokens.iter().any(Token::is_dynamic)
+            }
+            _ => false,
+        }
+    }
+
+    /// Return true if the token can be encoded as the given type.
+    pub fn type_check(&self, ty: &ParamType) -> bool {
+        match (self, ty) {
+            (Token::Address(_), ParamType::Address)
+            | (Token::Bool(_), ParamType::Bool)
+            | (Token::Bytes(_), ParamType::Bytes)
+            | (Token::String(_), ParamType::String) => true,
+            (Token::Int(value), ParamType::Int(bits)) => int_fits(*value, *bits),
+            (Token::Uint(value), ParamType::Uint(bits)) => value.bits() <= *bits,
+            (Token::FixedBytes(bytes), ParamType::FixedBytes(size)) => bytes.len() <= *size,
+            (Token::FixedBytes(bytes), ParamType::Function) => bytes.len() <= 24,
+            (Token::Array(tokens), ParamType::Array(ty)) => {
+                tokens.iter().all(|token| token.type_check(ty))
+            }
+            (Token::FixedArray(tokens), ParamType::FixedArray(ty, len)) => {
+                tokens.len() == *len && tokens.iter().all(|token| token.type_check(ty))
+            }
+            (Token::Tuple(tokens), ParamType::Tuple(types)) => {
+                tokens.len() == types.len()
+                    && tokens
+                        .iter()
+                        .zip(types)
+                        .all(|(token, ty)| token.type_check(ty))
+            }
+            _ => false,
+        }
+    }
+
+    /// Return the address, if the token is an address.
+    pub fn as_address(&self) -> Option<Address> {
+        match self {
+            Token::Address(address) => Some(*address),
+            _ => None,
+        }
+    }
+
+    /// Return the boolean, if the token is a bool.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Token::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Return the unsigned integer, if the token is an uint.
+    pub fn as_uint(&self) -> Option<U256> {
+        match self {
+            Token::Uint(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Return the raw bytes, if the token is a bytes or bytesN.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Token::Bytes(bytes) | Token::FixedBytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Return the string, if the token is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Token::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Return the inner tokens, if the token is an array or a tuple.
+    pub fn as_tokens(&self) -> Option<&[Token]> {
+        match self {
+            Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => Some(tokens),
+            _ => None,
+        }
+    }
+}
+
+/// Return true if the two's complement value on 256 bits is the sign
+/// extension of a value on the given number of bits.
+pub(crate) fn int_fits(value: U256, bits: usize) -> bool {
+    if bits >= 256 {
+        return true;
+    }
+    let high = value >> (bits - 1);
+    high.is_zero() || high == U256::MAX >> (bits - 1)
+}
+
+impl From<Address> for Token {
+    fn from(address: Address) -> Self {
+        Token::Address(address)
+    }
+}
+
+impl From<bool> for Token {
+    fn from(value: bool) -> Self {
+        Token::Bool(value)
+    }
+}
+
+impl From<U256> for Token {
+    fn from(value: U256) -> Self {
+        Token::Uint(value)
+    }
+}
+
+impl From<&str> for Token {
+    fn from(value: &str) -> Self {
+        Token::String(value.to_string())
+    }
+}
+
+impl From<String> for Token {
+    fn from(value: String) -> Self {
+        Token::String(value)
+    }
+}
@@ -0,0 +1,177 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Ethereum account address.
+
+use crate::hash::keccak256;
+use horror::{Error, Result};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use laron_crypto::PublicKey;
+
+/// Error returned when parsing an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressError {
+    /// The address is not 20 bytes long.
+    InvalidLength(usize),
+}
+
+impl std::fmt::Display for AddressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AddressError::InvalidLength(len) => write!(f, "Invalid address length: {}", len),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// A 20 bytes Ethereum address, derived from the last 20 bytes of the
+/// Keccak-256 hash of the uncompressed public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Address([u8; 20]);
+
+impl Address {
+    /// Create a new address from the given bytes.
+    pub fn new(bytes: [u8; 20]) -> Self {
+        Self(bytes)
+    }
+
+    /// Create a new address from the given slice.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let bytes: [u8; 20] = bytes
+            .try_into()
+            .map_err(|_| AddressError::InvalidLength(bytes.len()))?;
+        Ok(Self(bytes))
+    }
+
+    /// Compute the address of the given public key.
+    pub fn from_public_key(public_key: &PublicKey) -> Result<Self> {
+        let key = k256::PublicKey::from_sec1_bytes(&public_key.to_bytes()[..])?;
+        Ok(Self::from_point(&key))
+    }
+
+    pub(crate) fn from_point(key: &k256::PublicKey) -> Self {
+        let point = key.to_encoded_point(false);
+        let hash = keccak256(&point.as_bytes()[1..]);
+        let mut bytes = [0u8; 20];
+        bytes.copy_from_slice(&hash[12..]);
+        Self(bytes)
+    }
+
+    /// Return the underlying bytes.
+    pub fn to_bytes(&self) -> [u8; 20] {
+        self.0
+    }
+
+    /// Return the EIP-55 mixed-case checksum encoding of the address.
+    pub fn to_checksum(&self) -> String {
+        let lower = hex::encode(self.0);
+        let hash = keccak256(lower.as_bytes());
+
+        let checksum = lower
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                let nibble = (hash[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+                if nibble >= 8 {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                }
+            })
+            .collect::<String>();
+
+        format!("0x{}", checksum)
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_checksum())
+    }
+}
+
+impl std::str::FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        Self::from_slice(&hex::decode(s)?)
+    }
+}
+
+impl From<[u8; 20]> for Address {
+    fn from(bytes: [u8; 20]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl AsRef<[u8]> for Address {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl serde::Serialize for Address {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_checksum())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Address {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        let address: Address = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            address.to_string(),
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        );
+    }
+
+    #[test]
+    fn test_from_public_key() {
+        let key = laron_crypto::PrivateKey::from_bytes(
+            &hex::decode("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318")
+                .unwrap(),
+        )
+        .unwrap();
+        let address = Address::from_public_key(&key.public_key()).unwrap();
+        assert_eq!(
+            address.to_string(),
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+        );
+    }
+}
@@ -0,0 +1,677 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # laron-wallet
+//!
+//! Command-line interface of the wallet, enabled by the `cli` feature flag.
+//!
+//! Secrets, such as mnemonics, passphrases, private keys and passwords, are
+//! never taken as arguments. They are prompted for without echo, or read
+//! line by line from the standard input when it is not a terminal, so that
+//! scripts can pipe them in. Every command prints its result as `key: value`
+//! lines, or as a JSON object with `--json`.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use horror::Result;
+use laron_wallet::{
+    bips::{
+        bip32::ExtendedKey,
+        bip39::{Mnemonic, MnemonicType},
+        wordlists::Language,
+        ChildNumber, DerivationPath,
+    },
+    bitcoin::{AddressType, ExtendedPublicKey, KeyVersion, Network},
+    keyring::Keyring,
+    signer::{HdAccount, Signer, Wallet},
+    transaction::{SignedTransaction, Transaction},
+    vault::{Kdf, Vault},
+};
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, IsTerminal},
+    path::PathBuf,
+};
+use zeroize::Zeroizing;
+
+const DEFAULT_PATH: &str = "m/44'/60'/0'/0/0";
+const ACCOUNTS_PATH: &str = "m/44'/60'/0'/0";
+
+#[derive(Debug, Clone)]
+struct CliError(String);
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[derive(Parser)]
+#[command(name = "laron-wallet", version, about = "Ethereum and Bitcoin wallet")]
+struct Cli {
+    /// Print the result as JSON.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate, validate or convert BIP39 mnemonics.
+    #[command(subcommand)]
+    Mnemonic(MnemonicCommand),
+    /// Derive the key at the given path from a mnemonic.
+    Derive {
+        #[arg(long, default_value = DEFAULT_PATH)]
+        path: String,
+        /// Also print the private key.
+        #[arg(long)]
+        private: bool,
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+    },
+    /// Print the Ethereum address of a mnemonic account or a private key.
+    Address {
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Print the SLIP-132 extended public key of a Bitcoin account.
+    Xpub {
+        #[arg(long = "type", value_enum, default_value_t = ScriptType::P2wpkh)]
+        script_type: ScriptType,
+        #[arg(long, value_enum, default_value_t = Chain::Mainnet)]
+        network: Chain,
+        #[arg(long, default_value_t = 0)]
+        account: u32,
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+    },
+    /// Sign a message with the EIP-191 prefix.
+    SignMessage {
+        message: String,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Sign a hex encoded unsigned transaction.
+    SignTx {
+        raw: String,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Encrypt keys into a vault file, or decrypt one.
+    #[command(subcommand)]
+    Keystore(KeystoreCommand),
+    /// Decode a hex encoded transaction, signed or not.
+    Inspect { raw: String },
+}
+
+#[derive(Subcommand)]
+enum MnemonicCommand {
+    /// Generate a new mnemonic.
+    New {
+        #[arg(long, default_value_t = 12)]
+        words: usize,
+        #[arg(long, default_value = "english", value_parser = parse_language)]
+        language: Language,
+    },
+    /// Check the words and the checksum of a mnemonic.
+    Validate {
+        #[arg(long, default_value = "english", value_parser = parse_language)]
+        language: Language,
+    },
+    /// Print the BIP39 seed of a mnemonic.
+    ToSeed {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeystoreCommand {
+    /// Encrypt a mnemonic and its first accounts, or a private key, into a
+    /// vault file.
+    Encrypt {
+        file: PathBuf,
+        /// Encrypt a private key, hex or WIF, instead of a mnemonic.
+        #[arg(long)]
+        key: bool,
+        /// The number of accounts to derive from the mnemonic.
+        #[arg(long, default_value_t = 1)]
+        accounts: u32,
+        #[arg(long, value_enum, default_value_t = KdfType::Argon2id)]
+        kdf: KdfType,
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+    },
+    /// Print the accounts of a vault file.
+    Decrypt {
+        file: PathBuf,
+        /// Also print the mnemonics and the private keys.
+        #[arg(long)]
+        secrets: bool,
+    },
+}
+
+#[derive(Args)]
+struct MnemonicArgs {
+    #[arg(long, default_value = "english", value_parser = parse_language)]
+    language: Language,
+    /// Prompt for a BIP39 passphrase.
+    #[arg(long)]
+    passphrase: bool,
+}
+
+impl MnemonicArgs {
+    fn read(&self, input: &mut Input) -> Result<(Mnemonic, Zeroizing<String>)> {
+        let phrase = input.prompt("Mnemonic: ")?;
+        let mnemonic = Mnemonic::from_phrase(phrase.trim(), self.language)?;
+        let passphrase = match self.passphrase {
+            true => input.prompt("Passphrase: ")?,
+            false => Zeroizing::new(String::new()),
+        };
+        Ok((mnemonic, passphrase))
+    }
+}
+
+#[derive(Args)]
+struct KeyArgs {
+    /// Use a private key, hex or WIF, instead of a mnemonic.
+    #[arg(long)]
+    key: bool,
+    #[arg(long, default_value = DEFAULT_PATH, conflicts_with = "key")]
+    path: String,
+    #[command(flatten)]
+    mnemonic: MnemonicArgs,
+}
+
+impl KeyArgs {
+    fn signer(&self, input: &mut Input) -> Result<Box<dyn Signer>> {
+        if self.key {
+            return Ok(Box::new(parse_key(&input.prompt("Private key: ")?)?));
+        }
+        let (mnemonic, passphrase) = self.mnemonic.read(input)?;
+        let path = DerivationPath::parse(&self.path)?;
+        Ok(Box::new(HdAccount::from_mnemonic(
+            &mnemonic,
+            &passphrase,
+            &path,
+        )?))
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ScriptType {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+    P2tr,
+}
+
+impl From<ScriptType> for AddressType {
+    fn from(ty: ScriptType) -> Self {
+        match ty {
+            ScriptType::P2pkh => AddressType::P2pkh,
+            ScriptType::P2shP2wpkh => AddressType::P2shP2wpkh,
+            ScriptType::P2wpkh => AddressType::P2wpkh,
+            ScriptType::P2tr => AddressType::P2tr,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Chain {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl From<Chain> for Network {
+    fn from(chain: Chain) -> Self {
+        match chain {
+            Chain::Mainnet => Network::Mainnet,
+            Chain::Testnet => Network::Testnet,
+            Chain::Regtest => Network::Regtest,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum KdfType {
+    Scrypt,
+    Argon2id,
+}
+
+impl From<KdfType> for Kdf {
+    fn from(kdf: KdfType) -> Self {
+        match kdf {
+            KdfType::Scrypt => Kdf::Scrypt {
+                log_n: 17,
+                r: 8,
+                p: 1,
+            },
+            KdfType::Argon2id => Kdf::default(),
+        }
+    }
+}
+
+fn parse_language(s: &str) -> std::result::Result<Language, String> {
+    s.parse().map_err(|e: horror::Error| e.to_string())
+}
+
+/// Source of secrets: the terminal, or lines of the standard input when it
+/// is not a terminal.
+struct Input {
+    terminal: bool,
+    reader: Box<dyn BufRead>,
+}
+
+impl Input {
+    fn stdin() -> Self {
+        Self {
+            terminal: std::io::stdin().is_terminal(),
+            reader: Box::new(std::io::stdin().lock()),
+        }
+    }
+
+    /// Read a secret from the terminal without echoing it, or the next line
+    /// of the input, failing at the end of the input.
+    fn prompt(&mut self, message: &str) -> Result<Zeroizing<String>> {
+        if self.terminal {
+            return Ok(Zeroizing::new(rpassword::prompt_password(message)?));
+        }
+        let mut line = Zeroizing::new(String::new());
+        if self.reader.read_line(&mut line)? == 0 {
+            let prompt = message.trim_end_matches([':', ' ']);
+            return Err(CliError(format!("{} expected on stdin", prompt)).into());
+        }
+        Ok(Zeroizing::new(
+            line.trim_end_matches(['\r', '\n']).to_string(),
+        ))
+    }
+
+    /// Read a new, non-empty password, typed twice on a terminal.
+    fn new_password(&mut self) -> Result<Zeroizing<String>> {
+        let password = self.prompt("Password: ")?;
+        if password.is_empty() {
+            return Err(CliError("Password must not be empty".to_string()).into());
+        }
+        if self.terminal && *self.prompt("Confirm password: ")? != *password {
+            return Err(CliError("Passwords do not match".to_string()).into());
+        }
+        Ok(password)
+    }
+}
+
+/// Parse a private key, hex encoded or in Wallet Import Format.
+fn parse_key(key: &str) -> Result<Wallet> {
+    let key = key.trim();
+    match hex::decode(key.strip_prefix("0x").unwrap_or(key)) {
+        Ok(bytes) => Wallet::from_bytes(&Zeroizing::new(bytes)),
+        Err(_) => Wallet::from_wif(key),
+    }
+}
+
+fn parse_hex(raw: &str) -> Result<Vec<u8>> {
+    let raw = raw.trim();
+    Ok(hex::decode(raw.strip_prefix("0x").unwrap_or(raw))?)
+}
+
+fn run(command: Command, input: &mut Input) -> Result<Value> {
+    match command {
+        Command::Mnemonic(MnemonicCommand::New { words, language }) => {
+            let mnemonic = Mnemonic::new(MnemonicType::from_word_count(words)?, language);
+            Ok(json!({ "mnemonic": mnemonic.phrase() }))
+        }
+        Command::Mnemonic(MnemonicCommand::Validate { language }) => {
+            let phrase = input.prompt("Mnemonic: ")?;
+            Mnemonic::validate_phrase(phrase.trim(), language)?;
+            Ok(json!({ "valid": true }))
+        }
+        Command::Mnemonic(MnemonicCommand::ToSeed { mnemonic }) => {
+            let (mnemonic, passphrase) = mnemonic.read(input)?;
+            let seed = mnemonic.to_seed(&passphrase);
+            Ok(json!({ "seed": hex::encode(seed.to_bytes()) }))
+        }
+        Command::Derive {
+            path,
+            private,
+            mnemonic,
+        } => {
+            let (mnemonic, passphrase) = mnemonic.read(input)?;
+            let path = DerivationPath::parse(&path)?;
+            let account = HdAccount::from_mnemonic(&mnemonic, &passphrase, &path)?;
+            let key = account.extended_key();
+            let mut output = json!({
+                "path": path.to_string(),
+                "address": account.address().to_string(),
+                "publicKey": key.public_key().to_string(),
+            });
+            if private {
+                output["privateKey"] = json!(key.private_key().to_string());
+            }
+            Ok(output)
+        }
+        Command::Address { key } => {
+            Ok(json!({ "address": key.signer(input)?.address().to_string() }))
+        }
+        Command::Xpub {
+            script_type,
+            network,
+            account,
+            mnemonic,
+        } => {
+            let (address_type, network) = (AddressType::from(script_type), Network::from(network));
+            let (mnemonic, passphrase) = mnemonic.read(input)?;
+            let root = ExtendedKey::new_master(&mnemonic.to_seed(&passphrase))?;
+            let path = address_type.account_path(network, account);
+            let xpub = ExtendedPublicKey::from_extended_key(&root.derive_path(&path)?)?;
+            Ok(json!({
+                "path": path.to_string(),
+                "fingerprint": hex::encode(root.fingerprint()),
+                "xpub": xpub.encode(KeyVersion::from_address_type(address_type), network),
+            }))
+        }
+        Command::SignMessage { message, key } => {
+            let signer = key.signer(input)?;
+            let signature = signer.sign_message(message.as_bytes())?;
+            Ok(json!({
+                "address": signer.address().to_string(),
+                "signature": signature.to_string(),
+            }))
+        }
+        Command::SignTx { raw, key } => {
+            let (transaction, signature) = Transaction::decode(&parse_hex(&raw)?)?;
+            if signature.is_some() {
+                return Err(CliError("Transaction is already signed".to_string()).into());
+            }
+            let signed = key.signer(input)?.sign_transaction(&transaction)?;
+            Ok(json!({
+                "from": signed.sender()?.to_string(),
+                "hash": format!("{:?}", signed.hash()),
+                "raw": format!("0x{}", hex::encode(signed.raw())),
+            }))
+        }
+        Command::Keystore(KeystoreCommand::Encrypt {
+            file,
+            key,
+            accounts,
+            kdf,
+            mnemonic,
+        }) => {
+            let mut keyring = Keyring::new();
+            if key {
+                let wallet = parse_key(&input.prompt("Private key: ")?)?;
+                keyring.import_key("Imported Account 1", wallet.private_key().clone())?;
+            } else {
+                let (mnemonic, passphrase) = mnemonic.read(input)?;
+                let root =
+                    keyring.add_mnemonic("Secret Recovery Phrase", &mnemonic, &passphrase)?;
+                let path = DerivationPath::parse(ACCOUNTS_PATH)?;
+                for index in 0..accounts {
+                    let label = format!("Account {}", index + 1);
+                    keyring.derive_account(
+                        root,
+                        &path.child(ChildNumber::normal(index)),
+                        &label,
+                    )?;
+                }
+            }
+            let addresses = keyring.addresses();
+            Vault::new(keyring)
+                .kdf(kdf.into())
+                .save(&file, &input.new_password()?)?;
+            Ok(json!({
+                "file": file.display().to_string(),
+                "addresses": addresses.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            }))
+        }
+        Command::Keystore(KeystoreCommand::Decrypt { file, secrets }) => {
+            let vault = Vault::load(&file, &input.prompt("Password: ")?)?;
+            let keyring = vault.keyring();
+            let accounts = keyring
+                .accounts()
+                .iter()
+                .map(|account| {
+                    let mut output = json!({
+                        "label": account.label(),
+                        "address": account.address().to_string(),
+                    });
+                    if let Some(path) = account.signer().path() {
+                        output["path"] = json!(path.to_string());
+                    }
+                    if secrets {
+                        output["privateKey"] = json!(account.signer().private_key().to_string());
+                    }
+                    output
+                })
+                .collect::<Vec<_>>();
+            let mut output = json!({ "accounts": accounts });
+            if secrets {
+                let roots = keyring
+                    .roots()
+                    .iter()
+                    .map(|root| {
+                        json!({
+                            "label": root.label(),
+                            "mnemonic": root.mnemonic().phrase(),
+                            "passphrase": root.passphrase(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                output["roots"] = json!(roots);
+            }
+            Ok(output)
+        }
+        Command::Inspect { raw } => inspect(&parse_hex(&raw)?),
+    }
+}
+
+fn inspect(raw: &[u8]) -> Result<Value> {
+    let (transaction, signature) = Transaction::decode(raw)?;
+    let mut output = json!({
+        "chainId": transaction.chain_id(),
+        "nonce": transaction.nonce(),
+        "gasLimit": transaction.gas_limit().to_string(),
+        "to": transaction.to().map(|to| to.to_string()),
+        "value": transaction.value().to_string(),
+        "data": format!("0x{}", hex::encode(transaction.data())),
+    });
+    match &transaction {
+        Transaction::Legacy(tx) => {
+            output["type"] = json!("legacy");
+            output["gasPrice"] = json!(tx.gas_price.to_string());
+        }
+        Transaction::Eip1559(tx) => {
+            output["type"] = json!("eip1559");
+            output["maxPriorityFeePerGas"] = json!(tx.max_priority_fee_per_gas.to_string());
+            output["maxFeePerGas"] = json!(tx.max_fee_per_gas.to_string());
+            output["accessList"] = serde_json::to_value(&tx.access_list)?;
+        }
+    }
+    if let Some(signature) = signature {
+        let signed = SignedTransaction::new(transaction, signature)?;
+        output["from"] = json!(signed.sender()?.to_string());
+        output["hash"] = json!(format!("{:?}", signed.hash()));
+        output["signature"] = json!(signature.to_string());
+    }
+    Ok(output)
+}
+
+/// Print the result of a command, as `key: value` lines unless JSON is
+/// requested.
+fn print(output: &Value, json: bool) {
+    if json {
+        println!("{}", output);
+        return;
+    }
+    let text = |value: &Value| match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        value => value.to_string(),
+    };
+    match output {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::Array(items) => {
+                        println!("{}:", key);
+                        for item in items {
+                            println!("  {}", text(item));
+                        }
+                    }
+                    value => println!("{}: {}", key, text(value)),
+                }
+            }
+        }
+        output => println!("{}", text(output)),
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    match run(cli.command, &mut Input::stdin()) {
+        Ok(output) => print(&output, cli.json),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn input(lines: &str) -> Input {
+        Input {
+            terminal: false,
+            reader: Box::new(std::io::Cursor::new(lines.to_string())),
+        }
+    }
+
+    fn command(args: &[&str]) -> Command {
+        Cli::try_parse_from(std::iter::once("laron-wallet").chain(args.iter().copied()))
+            .unwrap()
+            .command
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let hex =
+            parse_key("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+        let prefixed =
+            parse_key(" 0x0000000000000000000000000000000000000000000000000000000000000001\n")
+                .unwrap();
+        let wif = parse_key("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn").unwrap();
+        assert_eq!(
+            hex.address().to_string(),
+            "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+        );
+        assert_eq!(prefixed.address(), hex.address());
+        assert_eq!(wif.address(), hex.address());
+
+        assert!(parse_key("0x00").is_err());
+        assert!(parse_key("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWm").is_err());
+    }
+
+    #[test]
+    fn test_sign_tx() {
+        let key = "0x4646464646464646464646464646464646464646464646464646464646464646\n";
+        let raw = "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080";
+        let output = run(command(&["sign-tx", raw, "--key"]), &mut input(key)).unwrap();
+        assert_eq!(output["from"], "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+
+        let signed = output["raw"].as_str().unwrap().to_string();
+        let err = run(command(&["sign-tx", &signed, "--key"]), &mut input(key)).unwrap_err();
+        assert_eq!(err.to_string(), "Transaction is already signed");
+    }
+
+    #[test]
+    fn test_prompt() {
+        let mut input = input("\nsecret\r\n");
+        assert_eq!(*input.prompt("Passphrase: ").unwrap(), "");
+        assert_eq!(*input.prompt("Passphrase: ").unwrap(), "secret");
+        let err = input.prompt("Passphrase: ").unwrap_err();
+        assert_eq!(err.to_string(), "Passphrase expected on stdin");
+    }
+
+    #[test]
+    fn test_keystore() {
+        let path = std::env::temp_dir().join(format!("laron-wallet-cli-{}", std::process::id()));
+        let file = path.to_str().unwrap();
+        let encrypt = || {
+            command(&[
+                "keystore",
+                "encrypt",
+                file,
+                "--accounts",
+                "2",
+                "--kdf",
+                "scrypt",
+            ])
+        };
+        let err = run(encrypt(), &mut input(PHRASE)).unwrap_err();
+        assert_eq!(err.to_string(), "Password expected on stdin");
+        let err = run(encrypt(), &mut input(&format!("{}\n\n", PHRASE))).unwrap_err();
+        assert_eq!(err.to_string(), "Password must not be empty");
+        assert!(!path.exists());
+
+        let output = run(encrypt(), &mut input(&format!("{}\npassword\n", PHRASE))).unwrap();
+        let addresses = [
+            "0x9858EfFD232B4033E47d90003D41EC34EcaEda94",
+            "0x6Fac4D18c912343BF86fa7049364Dd4E424Ab9C0",
+        ];
+        assert_eq!(output["addresses"], json!(addresses));
+
+        let decrypt = command(&["keystore", "decrypt", file, "--secrets"]);
+        let output = run(decrypt, &mut input("password\n")).unwrap();
+        assert_eq!(output["accounts"][0]["address"], addresses[0]);
+        assert_eq!(output["accounts"][1]["address"], addresses[1]);
+        assert_eq!(output["accounts"][1]["path"], "m/44'/60'/0'/0/1");
+        assert_eq!(output["roots"][0]["mnemonic"], PHRASE);
+
+        let decrypt = command(&["keystore", "decrypt", file]);
+        assert!(run(decrypt, &mut input("wrong\n")).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_inspect() {
+        let key = parse_key("0x4646464646464646464646464646464646464646464646464646464646464646")
+            .unwrap();
+        let raw = "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080";
+        let (transaction, _) = Transaction::decode(&parse_hex(raw).unwrap()).unwrap();
+        let signed = key.sign_transaction(&transaction).unwrap();
+
+        let output = inspect(&signed.raw()).unwrap();
+        assert_eq!(output["type"], "legacy");
+        assert_eq!(output["nonce"], 9);
+        assert_eq!(output["value"], "1000000000000000000");
+        assert_eq!(output["from"], "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+        assert_eq!(
+            output["hash"],
+            "0x33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788"
+        );
+    }
+}
@@ -25,10 +25,13 @@
 //! Ethereum wallets generation and derivation.
 
 use super::{bip39::Seed, ChildNumber, DerivationPath};
+use crate::{
+    bitcoin::{Network, Wif},
+    hash::hash160,
+};
 use hmac::{Hmac, Mac};
 use horror::Result;
 use laron_crypto::{PrivateKey, PublicKey};
-use ripemd::{Digest, Ripemd160};
 use sha2::Sha512;
 
 #[derive(Debug, Clone)]
@@ -127,8 +130,7 @@ impl ExtendedKey {
 
         let private_key = self.key.derive_child(child_key.try_into()?)?;
         let public_key = private_key.public_key();
-        let fp = Ripemd160::digest(&self.key.public_key().to_bytes());
-        let parent_fingerprint: [u8; 4] = fp[0..4].try_into()?;
+        let parent_fingerprint = self.fingerprint();
 
         Ok(Self::new(
             private_key,
@@ -156,6 +158,12 @@ impl ExtendedKey {
         &self.key
     }
 
+    /// Export the private key in Wallet Import Format, flagged as
+    /// compressed like every BIP32 public key.
+    pub fn to_wif(&self, network: Network) -> String {
+        Wif::new(self.key.clone(), network, true).to_wif()
+    }
+
     /// Get the public key.
     pub fn public_key(&self) -> &PublicKey {
         &self.public_key
@@ -166,6 +174,13 @@ impl ExtendedKey {
         &self.parent_fingerprint
     }
 
+    /// Get the fingerprint of this key, the first 4 bytes of the HASH160 of
+    /// its public key, which children refer to as their parent fingerprint.
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let hash = hash160(&self.public_key.to_bytes()[..]);
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
     /// Get the child number.
     pub fn child_number(&self) -> &ChildNumber {
         &self.child_number
@@ -222,4 +237,24 @@ mod tests {
             "0237b0bb7a8288d38ed49a524b5dc98cff3eb5ca824c9f9dc0dfdb3d9cd600f299"
         );
     }
+
+    #[test]
+    pub fn test_to_wif() {
+        use crate::bitcoin::AddressType;
+
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mnemonic = Mnemonic::from_phrase(phrase, Language::English).unwrap();
+        let seed = mnemonic.to_seed("");
+        let key = ExtendedKey::new_master(&seed).unwrap();
+        let path = DerivationPath::parse("m/44'/0'/0'/0/0").unwrap();
+        let child = key.derive_path(&path).unwrap();
+
+        let wif = Wif::from_wif(&child.to_wif(Network::Mainnet)).unwrap();
+        assert!(wif.is_compressed());
+        assert_eq!(wif.private_key(), child.private_key());
+        assert_eq!(
+            wif.address(AddressType::P2pkh).unwrap().to_string(),
+            "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"
+        );
+    }
 }
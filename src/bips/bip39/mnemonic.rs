@@ -18,7 +18,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use horror::{Error, Result};
-use rand::Rng;
+use rand::{CryptoRng, RngCore};
 use sha2::Digest;
 use unicode_normalization::UnicodeNormalization;
 
@@ -26,10 +26,21 @@ use crate::bips::wordlists::Language;
 
 use super::Seed;
 
+/// The number of cards in a deck, for [`Mnemonic::from_cards`].
+pub const DECK_SIZE: u8 = 52;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum MnemonicError {
     InvalidMnemonicLength(usize),
     InvalidChecksum,
+    InvalidSides(u8),
+    InvalidRoll(u8),
+    NotEnoughRolls(usize),
+    BiasedRolls,
+    InvalidCard(u8),
+    NotEnoughCards(usize),
+    BiasedCards,
+    DeckTooSmall,
 }
 
 impl std::fmt::Display for MnemonicError {
@@ -39,6 +50,26 @@ impl std::fmt::Display for MnemonicError {
                 write!(f, "Invalid mnemonic length: {}", len)
             }
             MnemonicError::InvalidChecksum => write!(f, "Invalid checksum"),
+            MnemonicError::InvalidSides(sides) => write!(f, "Invalid number of sides: {}", sides),
+            MnemonicError::InvalidRoll(roll) => write!(f, "Invalid roll: {}", roll),
+            MnemonicError::NotEnoughRolls(min) => write!(f, "At least {} rolls are needed", min),
+            MnemonicError::BiasedRolls => {
+                write!(
+                    f,
+                    "The rolls fall outside of the unbiased range, roll again"
+                )
+            }
+            MnemonicError::InvalidCard(card) => write!(f, "Invalid or repeated card: {}", card),
+            MnemonicError::NotEnoughCards(min) => write!(f, "At least {} cards are needed", min),
+            MnemonicError::BiasedCards => {
+                write!(
+                    f,
+                    "The cards fall outside of the unbiased range, shuffle again"
+                )
+            }
+            MnemonicError::DeckTooSmall => {
+                write!(f, "A deck of {} cards holds too little entropy", DECK_SIZE)
+            }
         }
     }
 }
@@ -108,6 +139,84 @@ impl MnemonicType {
     pub fn total_bits(&self) -> usize {
         self.entropy_bits() + self.checksum_bits()
     }
+
+    /// Return the minimum number of rolls of a die with the given number of
+    /// sides holding the entropy of the mnemonic.
+    pub fn min_dice_rolls(&self, sides: u8) -> Result<usize> {
+        if sides < 2 {
+            return Err(MnemonicError::InvalidSides(sides).into());
+        }
+        let (mut range, mut rolls) = (vec![1], 0);
+        while shr(&range, self.entropy_bits()).is_empty() {
+            mul_add(&mut range, sides.into(), 0);
+            rolls += 1;
+        }
+        Ok(rolls)
+    }
+
+    /// Return the minimum number of cards drawn from a shuffled deck of
+    /// [`DECK_SIZE`] cards holding the entropy of the mnemonic. A deck holds
+    /// about 225 bits, too little for 24 words.
+    pub fn min_cards(&self) -> Result<usize> {
+        let (mut range, mut cards) = (vec![1], 0);
+        while shr(&range, self.entropy_bits()).is_empty() {
+            if cards == DECK_SIZE {
+                return Err(MnemonicError::DeckTooSmall.into());
+            }
+            mul_add(&mut range, (DECK_SIZE - cards).into(), 0);
+            cards += 1;
+        }
+        Ok(cards.into())
+    }
+}
+
+/// Multiply the little-endian number by `base` and add `digit`.
+fn mul_add(limbs: &mut Vec<u32>, base: u32, digit: u32) {
+    let mut carry = u64::from(digit);
+    for limb in limbs.iter_mut() {
+        let value = u64::from(*limb) * u64::from(base) + carry;
+        *limb = value as u32;
+        carry = value >> 32;
+    }
+    if carry > 0 {
+        limbs.push(carry as u32);
+    }
+}
+
+/// Shift the little-endian number right by the given number of bits,
+/// without leading zero limbs.
+fn shr(limbs: &[u32], bits: usize) -> Vec<u32> {
+    let (words, shift) = (bits / 32, bits % 32);
+    let mut out = limbs.get(words..).unwrap_or_default().to_vec();
+    if shift > 0 {
+        for i in 0..out.len() {
+            let high = out.get(i + 1).map_or(0, |limb| limb << (32 - shift));
+            out[i] = (out[i] >> shift) | high;
+        }
+    }
+    while out.last() == Some(&0) {
+        out.pop();
+    }
+    out
+}
+
+/// Return the lowest `bits` bits of the little-endian number drawn below
+/// `range`, as big-endian bytes, unless the number is at or above the last
+/// multiple of `2^bits` below `range`, where they would be biased.
+fn unbiased_bits(value: &[u32], range: &[u32], bits: usize) -> Option<Vec<u8>> {
+    let (value_high, range_high) = (shr(value, bits), shr(range, bits));
+    let below = value_high.len() < range_high.len()
+        || (value_high.len() == range_high.len()
+            && value_high.iter().rev().lt(range_high.iter().rev()));
+    if !below {
+        return None;
+    }
+
+    let mut bytes = vec![0u8; bits / 8];
+    for (i, byte) in bytes.iter_mut().rev().enumerate() {
+        *byte = (value.get(i / 4).copied().unwrap_or_default() >> (8 * (i % 4))) as u8;
+    }
+    Some(bytes)
 }
 
 /// A BIP39 mnemonic.
@@ -123,8 +232,19 @@ pub struct Mnemonic {
 impl Mnemonic {
     /// Create a new `Mnemonic` by the given type and by the given language.
     pub fn new(ty: MnemonicType, language: Language) -> Self {
+        Self::new_with_rng(ty, language, &mut rand::thread_rng())
+    }
+
+    /// Create a new `Mnemonic` by the given type and by the given language,
+    /// with entropy drawn from the given RNG, such as a hardware TRNG or a
+    /// seeded one in tests.
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        ty: MnemonicType,
+        language: Language,
+        rng: &mut R,
+    ) -> Self {
         let mut bytes = vec![0u8; ty.entropy_bits() / 8];
-        rand::thread_rng().fill(&mut bytes[..]);
+        rng.fill_bytes(&mut bytes);
         Self::from_entropy_unchecked(&bytes, language)
     }
 
@@ -165,6 +285,103 @@ impl Mnemonic {
         }
     }
 
+    /// Create a new `Mnemonic` of the given type from the given rolls of a
+    /// die with the given number of sides, each between 1 and `sides`.
+    ///
+    /// The rolls are read as the digits of a number, whose lowest bits are
+    /// the entropy. These bits are only unbiased when the number is below
+    /// the largest multiple of their range, so the rolls are rejected
+    /// otherwise. Rolling more than [`MnemonicType::min_dice_rolls`] makes
+    /// this unlikely, each extra roll dividing its probability by `sides`.
+    pub fn from_dice(
+        rolls: &[u8],
+        sides: u8,
+        ty: MnemonicType,
+        language: Language,
+    ) -> Result<Self> {
+        let min = ty.min_dice_rolls(sides)?;
+        if rolls.len() < min {
+            return Err(MnemonicError::NotEnoughRolls(min).into());
+        }
+
+        let (mut value, mut range) = (Vec::new(), vec![1]);
+        for roll in rolls {
+            if *roll == 0 || *roll > sides {
+                return Err(MnemonicError::InvalidRoll(*roll).into());
+            }
+            mul_add(&mut value, sides.into(), u32::from(roll - 1));
+            mul_add(&mut range, sides.into(), 0);
+        }
+
+        let entropy =
+            unbiased_bits(&value, &range, ty.entropy_bits()).ok_or(MnemonicError::BiasedRolls)?;
+        Ok(Self::from_entropy_unchecked(&entropy, language))
+    }
+
+    /// Create a new `Mnemonic` of the given type from the first cards of a
+    /// shuffled deck, each numbered below [`DECK_SIZE`], such as
+    /// `13 * suit + rank`.
+    ///
+    /// The order of the cards is read as a number, each card being a digit
+    /// in base of the number of cards left in the deck, then converted to
+    /// entropy with the same rejection as [`Mnemonic::from_dice`]. Drawing
+    /// more than [`MnemonicType::min_cards`] cards makes the rejection
+    /// unlikely.
+    pub fn from_cards(cards: &[u8], ty: MnemonicType, language: Language) -> Result<Self> {
+        let min = ty.min_cards()?;
+        if cards.len() < min {
+            return Err(MnemonicError::NotEnoughCards(min).into());
+        }
+
+        let mut deck = (0..DECK_SIZE).collect::<Vec<_>>();
+        let (mut value, mut range) = (Vec::new(), vec![1]);
+        for card in cards {
+            let index = deck
+                .iter()
+                .position(|c| c == card)
+                .ok_or(MnemonicError::InvalidCard(*card))?;
+            let left = deck.len() as u32;
+            deck.remove(index);
+            mul_add(&mut value, left, index as u32);
+            mul_add(&mut range, left, 0);
+        }
+
+        let entropy =
+            unbiased_bits(&value, &range, ty.entropy_bits()).ok_or(MnemonicError::BiasedCards)?;
+        Ok(Self::from_entropy_unchecked(&entropy, language))
+    }
+
+    /// Create a new `Mnemonic` of the given type from the given coin flips,
+    /// `true` for heads, one per bit of entropy.
+    pub fn from_coin_flips(flips: &[bool], ty: MnemonicType, language: Language) -> Result<Self> {
+        let rolls = flips
+            .iter()
+            .map(|heads| 1 + *heads as u8)
+            .collect::<Vec<_>>();
+        Self::from_dice(&rolls, 2, ty, language)
+    }
+
+    /// Return the mnemonic of this entropy XORed with the output of the
+    /// system RNG. It is as strong as the better of the two sources, so
+    /// that neither a biased die nor a broken RNG is enough to weaken it.
+    pub fn xor_system_entropy(&self) -> Self {
+        self.xor_entropy_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Return the mnemonic of this entropy XORed with the output of the
+    /// given RNG.
+    pub fn xor_entropy_with_rng<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Self {
+        let mut random = vec![0u8; self.entropy.len()];
+        rng.fill_bytes(&mut random);
+        let entropy = self
+            .entropy
+            .iter()
+            .zip(random)
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+        Self::from_entropy_unchecked(&entropy, self.language)
+    }
+
     /// Create a new `Mnemonic` from the given phrase and by the given language.
     pub fn from_phrase(phrase: &str, language: Language) -> Result<Self> {
         let phrase = phrase.nfkd().collect::<String>();
@@ -291,6 +508,95 @@ mod tests {
         assert_eq!(mnemonic.language(), Language::English);
     }
 
+    #[test]
+    fn test_new_with_rng() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let ty = MnemonicType::Words24;
+        let mnemonic = Mnemonic::new_with_rng(ty, Language::English, &mut StdRng::seed_from_u64(7));
+        assert_eq!(
+            mnemonic,
+            Mnemonic::new_with_rng(ty, Language::English, &mut StdRng::seed_from_u64(7))
+        );
+        assert_ne!(
+            mnemonic,
+            Mnemonic::new_with_rng(ty, Language::English, &mut StdRng::seed_from_u64(8))
+        );
+
+        let mixed = mnemonic.xor_entropy_with_rng(&mut StdRng::seed_from_u64(7));
+        assert_eq!(mixed.entropy(), &[0; 32]);
+    }
+
+    #[test]
+    fn test_dice() {
+        let ty = MnemonicType::Words12;
+        assert_eq!(ty.min_dice_rolls(6).unwrap(), 50);
+        assert_eq!(ty.min_dice_rolls(2).unwrap(), 128);
+        assert_eq!(ty.min_dice_rolls(20).unwrap(), 30);
+        assert_eq!(MnemonicType::Words24.min_dice_rolls(6).unwrap(), 100);
+        assert!(ty.min_dice_rolls(1).is_err());
+
+        let mnemonic = Mnemonic::from_dice(&[1; 50], 6, ty, Language::English).unwrap();
+        assert_eq!(
+            mnemonic.phrase(),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        // the largest value is above the last multiple of 2^128
+        assert!(Mnemonic::from_dice(&[6; 50], 6, ty, Language::English).is_err());
+        assert!(Mnemonic::from_dice(&[6; 49], 6, ty, Language::English).is_err());
+        assert!(Mnemonic::from_dice(&[7; 50], 6, ty, Language::English).is_err());
+
+        let rolls = [1, 16].repeat(16);
+        let mnemonic = Mnemonic::from_dice(&rolls, 16, ty, Language::English).unwrap();
+        assert_eq!(mnemonic.entropy(), &[0x0f; 16]);
+
+        let flips = [false, true].repeat(128);
+        let mnemonic =
+            Mnemonic::from_coin_flips(&flips, MnemonicType::Words24, Language::English).unwrap();
+        assert_eq!(mnemonic.entropy(), &[0x55; 32]);
+        assert_ne!(mnemonic.xor_system_entropy(), mnemonic);
+        assert_eq!(mnemonic.xor_system_entropy().entropy().len(), 32);
+    }
+
+    #[test]
+    fn test_cards() {
+        assert_eq!(MnemonicType::Words12.min_cards().unwrap(), 25);
+        assert_eq!(MnemonicType::Words21.min_cards().unwrap(), 50);
+        assert!(MnemonicType::Words24.min_cards().is_err());
+
+        let ty = MnemonicType::Words12;
+        let sorted = (0..DECK_SIZE).collect::<Vec<_>>();
+        let mnemonic = Mnemonic::from_cards(&sorted, ty, Language::English).unwrap();
+        assert_eq!(mnemonic.entropy(), &[0; 16]);
+
+        let deck = (0..52u16)
+            .map(|i| ((7 * i + 3) % 52) as u8)
+            .collect::<Vec<_>>();
+        let mnemonic = Mnemonic::from_cards(&deck, ty, Language::English).unwrap();
+        assert_eq!(
+            hex::encode(mnemonic.entropy()),
+            "d6a5772c536b8d396338b99ce832e150"
+        );
+        let mnemonic = Mnemonic::from_cards(&deck[..26], ty, Language::English).unwrap();
+        assert_eq!(
+            hex::encode(mnemonic.entropy()),
+            "f8b3e3f10edb3733250550206d6a0867"
+        );
+        let mnemonic =
+            Mnemonic::from_cards(&deck, MnemonicType::Words21, Language::English).unwrap();
+        assert_eq!(
+            hex::encode(mnemonic.entropy()),
+            "2edfe80d2463da2d4bd054dad6a5772c536b8d396338b99ce832e150"
+        );
+
+        // the largest value is above the last multiple of 2^224
+        let reversed = sorted.iter().rev().copied().collect::<Vec<_>>();
+        assert!(Mnemonic::from_cards(&reversed, MnemonicType::Words21, Language::English).is_err());
+        assert!(Mnemonic::from_cards(&deck[..24], ty, Language::English).is_err());
+        assert!(Mnemonic::from_cards(&[0; 25], ty, Language::English).is_err());
+        assert!(Mnemonic::from_cards(&[52; 25], ty, Language::English).is_err());
+    }
+
     #[test]
     fn test_entropy() {
         let entropy = vec![0u8; 16];
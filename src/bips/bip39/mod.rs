@@ -34,7 +34,9 @@
 //! ```
 
 mod mnemonic;
+mod report;
 mod seed;
 
 pub use mnemonic::*;
+pub use report::*;
 pub use seed::*;
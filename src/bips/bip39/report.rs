@@ -0,0 +1,253 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Checks of a mnemonic against well-known phrases and low entropy patterns,
+//! run before funds are sent to it.
+
+use super::Mnemonic;
+use crate::bips::wordlists::Language;
+use std::collections::BTreeMap;
+
+/// The phrases of the BIP39 test vectors, which anyone can sweep.
+const TEST_VECTORS: &[&str] = &[
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+    "legal winner thank year wave sausage worth useful legal winner thank yellow",
+    "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+    "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+    "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+    "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter always",
+    "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo when",
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+    "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title",
+    "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+    "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+    "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+    "gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog",
+    "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+    "scheme spot photo card baby mountain device kick cradle pact join borrow",
+    "horn tenant knee talent sponsor spell gate clip pulse soap slush warm silver nephew swap uncle crack brave",
+    "panda eyebrow bullet gorilla call smoke muffin taste mesh discover soft ostrich alcohol speed nation flash devote level hobby quick inner drive ghost inside",
+    "cat swing flag economy stadium alone churn speed unique patch report train",
+    "light rule cinnamon wrap drastic word pride squirrel upgrade then income fatal apart sustain crack supply proud access",
+    "all hour make first leader extend hole alien behind guard gospel lava path output census museum junior mass reopen famous sing advance salt reform",
+    "vessel ladder alter error federal sibling chat ability sun glass valve picture",
+    "scissors invite lock maple supreme raw rapid void congress muscle digital elegant little brisk hair mango congress clump",
+    "void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+];
+
+/// Phrases published as the defaults of development tools and in
+/// documentation, whose accounts are swept as soon as they are funded.
+const PUBLISHED_PHRASES: &[&str] = &[
+    // Hardhat and Foundry
+    "test test test test test test test test test test test junk",
+    // Truffle
+    "candy maple cake sugar pudding cream honey rich smooth crumble sweet treat",
+    "myth like bonus scare over problem client lizard pioneer submit female collect",
+    // Trezor
+    "all all all all all all all all all all all all",
+];
+
+/// A reason for a mnemonic to be unsafe to hold funds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntropyWarning {
+    /// The entropy is one of the BIP39 test vectors.
+    TestVector,
+    /// The phrase is the published default of a development tool.
+    PublishedPhrase,
+    /// Fewer than a quarter, or more than three quarters, of the bits of the
+    /// entropy are set.
+    HammingWeight { ones: usize, bits: usize },
+    /// The entropy repeats itself every `period` bytes.
+    RepeatingPattern { period: usize },
+    /// Every byte of the entropy is the previous one plus `step`.
+    Sequence { step: u8 },
+    /// These words appear more than once in the phrase.
+    DuplicateWords(Vec<String>),
+}
+
+impl std::fmt::Display for EntropyWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EntropyWarning::TestVector => write!(f, "The mnemonic is a BIP39 test vector"),
+            EntropyWarning::PublishedPhrase => write!(f, "The mnemonic is publicly known"),
+            EntropyWarning::HammingWeight { ones, bits } => {
+                write!(f, "Only {} of {} entropy bits are set", ones, bits)
+            }
+            EntropyWarning::RepeatingPattern { period } => {
+                write!(f, "The entropy repeats every {} bytes", period)
+            }
+            EntropyWarning::Sequence { step } => {
+                write!(f, "The entropy is a sequence of step {}", step)
+            }
+            EntropyWarning::DuplicateWords(words) => {
+                write!(f, "Repeated words: {}", words.join(", "))
+            }
+        }
+    }
+}
+
+/// The result of [`Mnemonic::entropy_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntropyReport {
+    warnings: Vec<EntropyWarning>,
+}
+
+impl EntropyReport {
+    /// Return the reasons for the mnemonic to be weak.
+    pub fn warnings(&self) -> &[EntropyWarning] {
+        &self.warnings
+    }
+
+    /// Return true if the mnemonic should not hold funds.
+    pub fn is_weak(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+}
+
+impl Mnemonic {
+    /// Check the mnemonic for signs that it was not randomly generated,
+    /// such as a test vector, a published phrase or a pattern in its
+    /// entropy, so that a wallet can warn before funds are sent to it.
+    ///
+    /// An empty report does not prove that the entropy is random. Random
+    /// phrases repeat a word by chance, about one in thirty of 12 words, so
+    /// repeated words are only flagged above one repeat per 12 words.
+    pub fn entropy_report(&self) -> EntropyReport {
+        let entropy = self.entropy();
+        let mut warnings = Vec::new();
+
+        let known = |phrases: &[&str]| {
+            phrases.iter().any(|phrase| {
+                Mnemonic::from_phrase(phrase, Language::English)
+                    .is_ok_and(|known| known.entropy() == entropy)
+            })
+        };
+        if known(TEST_VECTORS) {
+            warnings.push(EntropyWarning::TestVector);
+        }
+        if known(PUBLISHED_PHRASES) {
+            warnings.push(EntropyWarning::PublishedPhrase);
+        }
+
+        let bits = entropy.len() * 8;
+        let ones = entropy.iter().map(|b| b.count_ones() as usize).sum();
+        if ones < bits / 4 || ones > bits * 3 / 4 {
+            warnings.push(EntropyWarning::HammingWeight { ones, bits });
+        }
+
+        let period = (1..=entropy.len() / 2)
+            .find(|period| entropy.iter().zip(&entropy[*period..]).all(|(a, b)| a == b));
+        if let Some(period) = period {
+            warnings.push(EntropyWarning::RepeatingPattern { period });
+        }
+
+        let step = entropy[1].wrapping_sub(entropy[0]);
+        if step != 0
+            && entropy
+                .windows(2)
+                .all(|pair| pair[1].wrapping_sub(pair[0]) == step)
+        {
+            warnings.push(EntropyWarning::Sequence { step });
+        }
+
+        let words = self.phrase().split_whitespace().collect::<Vec<_>>();
+        let mut counts = BTreeMap::new();
+        for word in &words {
+            *counts.entry(*word).or_insert(0) += 1;
+        }
+        if words.len() - counts.len() > words.len() / 12 {
+            let duplicates = counts
+                .into_iter()
+                .filter(|(_, count)| *count > 1)
+                .map(|(word, _)| word.to_string())
+                .collect();
+            warnings.push(EntropyWarning::DuplicateWords(duplicates));
+        }
+
+        EntropyReport { warnings }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(phrase: &str) -> Vec<EntropyWarning> {
+        Mnemonic::from_phrase(phrase, Language::English)
+            .unwrap()
+            .entropy_report()
+            .warnings()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_entropy_report() {
+        for phrase in TEST_VECTORS.iter().chain(PUBLISHED_PHRASES) {
+            assert!(Mnemonic::validate_phrase(phrase, Language::English).is_ok());
+        }
+
+        assert_eq!(
+            report(TEST_VECTORS[0]),
+            [
+                EntropyWarning::TestVector,
+                EntropyWarning::HammingWeight { ones: 0, bits: 128 },
+                EntropyWarning::RepeatingPattern { period: 1 },
+                EntropyWarning::DuplicateWords(vec!["abandon".to_string()]),
+            ]
+        );
+        assert_eq!(report(TEST_VECTORS[12]), [EntropyWarning::TestVector]);
+        assert_eq!(
+            report(PUBLISHED_PHRASES[1]),
+            [EntropyWarning::PublishedPhrase]
+        );
+
+        let counting = Mnemonic::from_entropy(&(0..16).collect::<Vec<_>>(), Language::English)
+            .unwrap()
+            .entropy_report();
+        assert!(counting
+            .warnings()
+            .contains(&EntropyWarning::Sequence { step: 1 }));
+
+        let halves =
+            Mnemonic::from_entropy(&[[0x5a, 0xc3, 0x96, 0x3c]; 4].concat(), Language::English)
+                .unwrap()
+                .entropy_report();
+        assert_eq!(
+            halves.warnings(),
+            [EntropyWarning::RepeatingPattern { period: 4 }]
+        );
+
+        // the report is independent of the language
+        #[cfg(feature = "japanese")]
+        let japanese = Mnemonic::from_entropy(&[0; 32], Language::Japanese).unwrap();
+        #[cfg(feature = "japanese")]
+        assert!(japanese
+            .entropy_report()
+            .warnings()
+            .contains(&EntropyWarning::TestVector));
+
+        let random = Mnemonic::from_entropy(
+            &hex::decode("f585c11aec520db57dd353c69554b21a").unwrap(),
+            Language::English,
+        )
+        .unwrap();
+        assert!(!random.entropy_report().is_weak());
+    }
+}
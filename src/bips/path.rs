@@ -159,12 +159,25 @@ impl DerivationPath {
         result
     }
 
+    /// Returns the derivation path extended with the given child.
+    pub fn child(&self, child: ChildNumber) -> Self {
+        let mut path = self.0.clone();
+        path.push(child);
+        Self(path)
+    }
+
     /// Returns the iterator over the components of the derivation path.
-    pub fn iter(&self) -> std::slice::Iter<ChildNumber> {
+    pub fn iter(&self) -> std::slice::Iter<'_, ChildNumber> {
         self.0.iter()
     }
 }
 
+impl FromIterator<ChildNumber> for DerivationPath {
+    fn from_iter<I: IntoIterator<Item = ChildNumber>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
 impl Default for DerivationPath {
     fn default() -> Self {
         Self::parse("m/44'/60'/0'/0").unwrap()
@@ -177,6 +190,92 @@ impl std::fmt::Display for DerivationPath {
     }
 }
 
+/// The derivation paths used by popular Ethereum wallets for their accounts.
+///
+/// The same seed gives different addresses in different wallets, since each
+/// wallet increments a different component of the path:
+///
+/// | Scheme           | Path of account `i`  |
+/// |------------------|----------------------|
+/// | `MetaMask`       | `m/44'/60'/0'/0/i`   |
+/// | `LedgerLive`     | `m/44'/60'/i'/0/0`   |
+/// | `LedgerLegacy`   | `m/44'/60'/0'/i`     |
+///
+/// `MetaMask` is also used by Trezor and most software wallets, and
+/// `LedgerLegacy` by MyEtherWallet and the Ledger Chrome app.
+///
+/// ## Example
+/// ```rust
+/// use laron_wallet::bips::DerivationScheme;
+///
+/// for scheme in DerivationScheme::ALL {
+///     for path in scheme.paths(0, 3) {
+///         println!("{}: {}", scheme, path);
+///     }
+/// }
+/// assert_eq!(DerivationScheme::LedgerLive.path(2).string(), "m/44'/60'/2'/0/0");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DerivationScheme {
+    #[default]
+    MetaMask,
+    LedgerLive,
+    LedgerLegacy,
+}
+
+impl DerivationScheme {
+    /// Every known scheme.
+    pub const ALL: [DerivationScheme; 3] = [
+        DerivationScheme::MetaMask,
+        DerivationScheme::LedgerLive,
+        DerivationScheme::LedgerLegacy,
+    ];
+
+    /// Returns the name of the scheme.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DerivationScheme::MetaMask => "MetaMask",
+            DerivationScheme::LedgerLive => "Ledger Live",
+            DerivationScheme::LedgerLegacy => "Ledger Legacy",
+        }
+    }
+
+    /// Returns the derivation path of the account at the given index.
+    pub fn path(&self, index: u32) -> DerivationPath {
+        let base = DerivationPath::default();
+        match self {
+            DerivationScheme::MetaMask => base.child(ChildNumber::normal(index)),
+            DerivationScheme::LedgerLive => DerivationPath(vec![
+                ChildNumber::hardened(44),
+                ChildNumber::hardened(60),
+                ChildNumber::hardened(index),
+                ChildNumber::normal(0),
+                ChildNumber::normal(0),
+            ]),
+            DerivationScheme::LedgerLegacy => DerivationPath(vec![
+                ChildNumber::hardened(44),
+                ChildNumber::hardened(60),
+                ChildNumber::hardened(0),
+                ChildNumber::normal(index),
+            ]),
+        }
+    }
+
+    /// Returns the derivation paths of `count` accounts, starting at the
+    /// given index.
+    pub fn paths(&self, start: u32, count: u32) -> Vec<DerivationPath> {
+        (start..start.saturating_add(count))
+            .map(|index| self.path(index))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for DerivationScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,4 +285,32 @@ mod tests {
         let path = DerivationPath::parse("m/44'/60'/0'/0").unwrap();
         assert_eq!(path.string(), "m/44'/60'/0'/0");
     }
+
+    #[test]
+    fn test_schemes() {
+        let paths = |scheme: DerivationScheme| {
+            scheme
+                .paths(0, 2)
+                .iter()
+                .map(DerivationPath::string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            paths(DerivationScheme::MetaMask),
+            vec!["m/44'/60'/0'/0/0", "m/44'/60'/0'/0/1"]
+        );
+        assert_eq!(
+            paths(DerivationScheme::LedgerLive),
+            vec!["m/44'/60'/0'/0/0", "m/44'/60'/1'/0/0"]
+        );
+        assert_eq!(
+            paths(DerivationScheme::LedgerLegacy),
+            vec!["m/44'/60'/0'/0", "m/44'/60'/0'/1"]
+        );
+        assert_eq!(
+            DerivationScheme::LedgerLive.path(7),
+            DerivationPath::parse("m/44'/60'/7'/0/0").unwrap()
+        );
+    }
 }
@@ -56,12 +56,14 @@ use std::collections::HashMap;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WordListError {
     InvalidWord,
+    UnknownLanguage(String),
 }
 
 impl std::fmt::Display for WordListError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             WordListError::InvalidWord => write!(f, "Invalid word"),
+            WordListError::UnknownLanguage(name) => write!(f, "Unknown language: {}", name),
         }
     }
 }
@@ -198,6 +200,31 @@ impl Language {
         }
     }
 
+    /// Get the name of the language, as used by its feature flag.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "english",
+            #[cfg(feature = "chinese_simplified")]
+            Language::ChineseSimplified => "chinese_simplified",
+            #[cfg(feature = "chinese_traditional")]
+            Language::ChineseTraditional => "chinese_traditional",
+            #[cfg(feature = "czech")]
+            Language::Czech => "czech",
+            #[cfg(feature = "french")]
+            Language::French => "french",
+            #[cfg(feature = "italian")]
+            Language::Italian => "italian",
+            #[cfg(feature = "japanese")]
+            Language::Japanese => "japanese",
+            #[cfg(feature = "korean")]
+            Language::Korean => "korean",
+            #[cfg(feature = "portuguese")]
+            Language::Portuguese => "portuguese",
+            #[cfg(feature = "spanish")]
+            Language::Spanish => "spanish",
+        }
+    }
+
     /// Get the wordmap for the given language.
     pub fn wordmap(self) -> WordMap {
         let mut map = HashMap::new();
@@ -208,6 +235,42 @@ impl Language {
     }
 }
 
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::str::FromStr for Language {
+    type Err = horror::Error;
+
+    /// Parse the name of a language enabled by its feature flag.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "english" => Ok(Language::English),
+            #[cfg(feature = "chinese_simplified")]
+            "chinese_simplified" => Ok(Language::ChineseSimplified),
+            #[cfg(feature = "chinese_traditional")]
+            "chinese_traditional" => Ok(Language::ChineseTraditional),
+            #[cfg(feature = "czech")]
+            "czech" => Ok(Language::Czech),
+            #[cfg(feature = "french")]
+            "french" => Ok(Language::French),
+            #[cfg(feature = "italian")]
+            "italian" => Ok(Language::Italian),
+            #[cfg(feature = "japanese")]
+            "japanese" => Ok(Language::Japanese),
+            #[cfg(feature = "korean")]
+            "korean" => Ok(Language::Korean),
+            #[cfg(feature = "portuguese")]
+            "portuguese" => Ok(Language::Portuguese),
+            #[cfg(feature = "spanish")]
+            "spanish" => Ok(Language::Spanish),
+            _ => Err(WordListError::UnknownLanguage(s.to_string()).into()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -227,4 +290,11 @@ mod tests {
         assert_eq!(wordmap.get_index("zoo").unwrap(), 2047);
         assert!(wordmap.get_index("zonee").is_err());
     }
+
+    #[test]
+    fn test_language_name() {
+        assert_eq!(Language::English.to_string(), "english");
+        assert_eq!("english".parse::<Language>().unwrap(), Language::English);
+        assert!("klingon".parse::<Language>().is_err());
+    }
 }
@@ -0,0 +1,252 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{taproot_output_key, Network};
+use crate::{
+    bips::{ChildNumber, DerivationPath},
+    hash::hash160,
+};
+use bech32::{ToBase32, Variant};
+use horror::Result;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use laron_crypto::PublicKey;
+
+/// The script types of single key Bitcoin addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressType {
+    /// Legacy pay-to-public-key-hash.
+    P2pkh,
+    /// Segwit v0 pay-to-witness-public-key-hash nested in pay-to-script-hash.
+    P2shP2wpkh,
+    /// Native segwit v0 pay-to-witness-public-key-hash.
+    P2wpkh,
+    /// Segwit v1 pay-to-taproot, key path only.
+    P2tr,
+}
+
+impl AddressType {
+    /// Every address type.
+    pub const ALL: [AddressType; 4] = [
+        AddressType::P2pkh,
+        AddressType::P2shP2wpkh,
+        AddressType::P2wpkh,
+        AddressType::P2tr,
+    ];
+
+    /// Return the BIP-43 purpose of the derivation paths of this type.
+    pub fn purpose(&self) -> u32 {
+        match self {
+            AddressType::P2pkh => 44,
+            AddressType::P2shP2wpkh => 49,
+            AddressType::P2wpkh => 84,
+            AddressType::P2tr => 86,
+        }
+    }
+
+    /// Return the path of the given account, `m/purpose'/coin_type'/account'`.
+    pub fn account_path(&self, network: Network, account: u32) -> DerivationPath {
+        DerivationPath::parse(&format!(
+            "m/{}'/{}'/{}'",
+            self.purpose(),
+            network.coin_type(),
+            account
+        ))
+        .unwrap()
+    }
+
+    /// Return the path of the address at the given index of the given
+    /// account, on the change chain if `change` is set.
+    pub fn path(&self, network: Network, account: u32, change: bool, index: u32) -> DerivationPath {
+        self.account_path(network, account)
+            .child(ChildNumber::normal(change as u32))
+            .child(ChildNumber::normal(index))
+    }
+}
+
+/// A single key Bitcoin address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitcoinAddress {
+    network: Network,
+    address_type: AddressType,
+    /// The key or script hash, or the x-only output key for taproot.
+    program: Vec<u8>,
+}
+
+impl BitcoinAddress {
+    /// Compute the address of the given type for the given public key.
+    pub fn from_public_key(
+        public_key: &PublicKey,
+        address_type: AddressType,
+        network: Network,
+    ) -> Result<Self> {
+        let key = k256::PublicKey::from_sec1_bytes(&public_key.to_bytes()[..])?;
+        Self::from_point(&key, address_type, network)
+    }
+
+    pub(crate) fn from_point(
+        key: &k256::PublicKey,
+        address_type: AddressType,
+        network: Network,
+    ) -> Result<Self> {
+        let compressed = key.to_encoded_point(true);
+
+        let program = match address_type {
+            AddressType::P2pkh | AddressType::P2wpkh => hash160(compressed.as_bytes()).to_vec(),
+            AddressType::P2shP2wpkh => {
+                hash160(p2wpkh_script(&hash160(compressed.as_bytes()))).to_vec()
+            }
+            AddressType::P2tr => taproot_output_key(key)?.to_vec(),
+        };
+
+        Ok(Self {
+            network,
+            address_type,
+            program,
+        })
+    }
+
+    pub(crate) fn uncompressed_p2pkh(key: &k256::PublicKey, network: Network) -> Self {
+        let uncompressed = key.to_encoded_point(false);
+        Self {
+            network,
+            address_type: AddressType::P2pkh,
+            program: hash160(uncompressed.as_bytes()).to_vec(),
+        }
+    }
+
+    /// Return the network of the address.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Return the type of the address.
+    pub fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    /// Return the output script paying to this address.
+    pub fn script_pubkey(&self) -> Vec<u8> {
+        match self.address_type {
+            // OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG
+            AddressType::P2pkh => p2pkh_script(&self.program),
+            // OP_HASH160 <hash> OP_EQUAL
+            AddressType::P2shP2wpkh => [&[0xa9, 0x14][..], &self.program, &[0x87]].concat(),
+            // OP_0 <hash>
+            AddressType::P2wpkh => p2wpkh_script(&self.program),
+            // OP_1 <key>
+            AddressType::P2tr => [&[0x51, 0x20][..], &self.program].concat(),
+        }
+    }
+
+    fn base58(&self, prefix: u8) -> String {
+        let mut data = vec![prefix];
+        data.extend_from_slice(&self.program);
+        bs58::encode(data).with_check().into_string()
+    }
+
+    fn segwit(&self, version: u8, variant: Variant) -> String {
+        let mut data = vec![bech32::u5::try_from_u8(version).unwrap()];
+        data.extend(self.program.to_base32());
+        bech32::encode(self.network.hrp(), data, variant).unwrap()
+    }
+}
+
+impl std::fmt::Display for BitcoinAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let address = match self.address_type {
+            AddressType::P2pkh => self.base58(self.network.p2pkh_prefix()),
+            AddressType::P2shP2wpkh => self.base58(self.network.p2sh_prefix()),
+            AddressType::P2wpkh => self.segwit(0, Variant::Bech32),
+            AddressType::P2tr => self.segwit(1, Variant::Bech32m),
+        };
+        write!(f, "{}", address)
+    }
+}
+
+/// Return the legacy output script of the given public key hash.
+pub(crate) fn p2pkh_script(hash: &[u8]) -> Vec<u8> {
+    [&[0x76, 0xa9, 0x14][..], hash, &[0x88, 0xac]].concat()
+}
+
+/// Return the segwit v0 output script of the given public key hash.
+pub(crate) fn p2wpkh_script(hash: &[u8]) -> Vec<u8> {
+    [&[0x00, 0x14][..], hash].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bips::{bip32::ExtendedKey, bip39::Mnemonic, wordlists::Language};
+
+    fn address(address_type: AddressType, network: Network, index: u32) -> String {
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            Language::English,
+        )
+        .unwrap();
+        let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+        let key = root
+            .derive_path(&address_type.path(network, 0, false, index))
+            .unwrap();
+        BitcoinAddress::from_public_key(key.public_key(), address_type, network)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_bip44() {
+        assert_eq!(
+            address(AddressType::P2pkh, Network::Mainnet, 0),
+            "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"
+        );
+    }
+
+    #[test]
+    fn test_bip49() {
+        assert_eq!(
+            address(AddressType::P2shP2wpkh, Network::Testnet, 0),
+            "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2"
+        );
+    }
+
+    #[test]
+    fn test_bip84() {
+        assert_eq!(
+            address(AddressType::P2wpkh, Network::Mainnet, 0),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            address(AddressType::P2wpkh, Network::Mainnet, 1),
+            "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g"
+        );
+        assert!(address(AddressType::P2wpkh, Network::Regtest, 0).starts_with("bcrt1q"));
+    }
+
+    #[test]
+    fn test_bip86() {
+        assert_eq!(
+            address(AddressType::P2tr, Network::Mainnet, 0),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_eq!(
+            address(AddressType::P2tr, Network::Mainnet, 1),
+            "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh"
+        );
+    }
+}
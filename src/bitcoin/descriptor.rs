@@ -0,0 +1,398 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{AddressType, BitcoinAddress, BitcoinError, ExtendedPublicKey, KeyVersion, Network};
+use crate::bips::{bip32::ExtendedKey, ChildNumber, DerivationPath};
+use horror::Result;
+
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Return the BIP-380 checksum of the given descriptor.
+pub fn descriptor_checksum(descriptor: &str) -> Result<String> {
+    const GENERATOR: [u64; 5] = [
+        0xf5dee51989,
+        0xa9fdca3312,
+        0x1bab10e32d,
+        0x3706b1677a,
+        0x644d626ffd,
+    ];
+
+    fn polymod(checksum: u64, value: u64) -> u64 {
+        let top = checksum >> 35;
+        let mut checksum = ((checksum & 0x7ffffffff) << 5) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+        checksum
+    }
+
+    let mut checksum = 1;
+    let mut groups = Vec::with_capacity(3);
+    for c in descriptor.chars() {
+        let position = INPUT_CHARSET
+            .find(c)
+            .ok_or_else(|| BitcoinError::InvalidDescriptor(descriptor.to_string()))?
+            as u64;
+        checksum = polymod(checksum, position & 31);
+        groups.push(position >> 5);
+        if groups.len() == 3 {
+            checksum = polymod(checksum, groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups[..] {
+        [a] => checksum = polymod(checksum, a),
+        [a, b] => checksum = polymod(checksum, a * 3 + b),
+        _ => {}
+    }
+    for _ in 0..8 {
+        checksum = polymod(checksum, 0);
+    }
+    checksum ^= 1;
+
+    Ok((0..8)
+        .map(|i| CHECKSUM_CHARSET[((checksum >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect())
+}
+
+/// The fingerprint of the root key and the path a key was derived at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyOrigin {
+    pub fingerprint: [u8; 4],
+    pub path: DerivationPath,
+}
+
+/// An extended public key of a descriptor, with its origin and the path of
+/// the derived keys, ending with `*` for a range of keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorKey {
+    pub origin: Option<KeyOrigin>,
+    pub key: ExtendedPublicKey,
+    /// The network of the key, given by its `xpub` or `tpub` version.
+    pub network: Network,
+    pub path: DerivationPath,
+    pub wildcard: bool,
+}
+
+impl DescriptorKey {
+    /// Derive the public key at the given index of the range, which is
+    /// ignored if the key is not a range.
+    pub fn derive(&self, index: u32) -> Result<ExtendedPublicKey> {
+        let mut path = self.path.clone();
+        if self.wildcard {
+            path = path.child(ChildNumber::normal(index));
+        }
+        self.key.derive_path(&path)
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        let invalid = || BitcoinError::InvalidDescriptor(s.to_string());
+
+        let (origin, rest) = match s.strip_prefix('[') {
+            Some(s) => {
+                let (origin, rest) = s.split_once(']').ok_or_else(invalid)?;
+                let (fingerprint, path) = origin.split_once('/').unwrap_or((origin, ""));
+                let fingerprint = hex::decode(fingerprint)?;
+                let origin = KeyOrigin {
+                    fingerprint: fingerprint.as_slice().try_into().map_err(|_| invalid())?,
+                    path: parse_path(path)?,
+                };
+                (Some(origin), rest)
+            }
+            None => (None, s),
+        };
+
+        let (key, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let (key, version, network) = ExtendedPublicKey::decode(key)?;
+        if version != KeyVersion::Xpub {
+            return Err(invalid().into());
+        }
+        let (path, wildcard) = match path.strip_suffix('*') {
+            Some(path) => (path.trim_end_matches('/'), true),
+            None => (path, false),
+        };
+        let path = parse_path(path)?;
+        if path.iter().any(ChildNumber::is_hardened) {
+            return Err(BitcoinError::HardenedDerivation.into());
+        }
+
+        Ok(Self {
+            origin,
+            key,
+            network,
+            path,
+            wildcard,
+        })
+    }
+}
+
+impl std::fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(origin) = &self.origin {
+            write!(f, "[{}", hex::encode(origin.fingerprint))?;
+            for child in origin.path.iter() {
+                write!(f, "/{}", format_child(child))?;
+            }
+            write!(f, "]")?;
+        }
+        write!(f, "{}", self.key.encode(KeyVersion::Xpub, self.network))?;
+        for child in self.path.iter() {
+            write!(f, "/{}", format_child(child))?;
+        }
+        if self.wildcard {
+            write!(f, "/*")?;
+        }
+        Ok(())
+    }
+}
+
+/// A BIP-380 output script descriptor of single key addresses:
+/// `pkh(KEY)`, `sh(wpkh(KEY))`, `wpkh(KEY)` or `tr(KEY)`.
+///
+/// ## Example
+/// ```rust
+/// use laron_wallet::bitcoin::Descriptor;
+///
+/// let descriptor: Descriptor = "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(
+///     descriptor.address(0).unwrap().to_string(),
+///     "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Descriptor {
+    address_type: AddressType,
+    key: DescriptorKey,
+}
+
+impl Descriptor {
+    /// Create a descriptor of the given address type for the given key.
+    pub fn new(address_type: AddressType, key: DescriptorKey) -> Self {
+        Self { address_type, key }
+    }
+
+    /// Create the descriptor of the receive or change addresses of the given
+    /// account, with the origin of the account key.
+    pub fn from_account(
+        root: &ExtendedKey,
+        address_type: AddressType,
+        network: Network,
+        account: u32,
+        change: bool,
+    ) -> Result<Self> {
+        let path = address_type.account_path(network, account);
+        let key = ExtendedPublicKey::from_extended_key(&root.derive_path(&path)?)?;
+        Ok(Self::new(
+            address_type,
+            DescriptorKey {
+                origin: Some(KeyOrigin {
+                    fingerprint: root.fingerprint(),
+                    path,
+                }),
+                key,
+                network,
+                path: DerivationPath::parse("m")?.child(ChildNumber::normal(change as u32)),
+                wildcard: true,
+            },
+        ))
+    }
+
+    /// Return the address type of the descriptor.
+    pub fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    /// Return the key of the descriptor.
+    pub fn key(&self) -> &DescriptorKey {
+        &self.key
+    }
+
+    /// Return the address at the given index of the range.
+    pub fn address(&self, index: u32) -> Result<BitcoinAddress> {
+        self.key
+            .derive(index)?
+            .address(self.address_type, self.key.network)
+    }
+
+    fn body(&self) -> String {
+        match self.address_type {
+            AddressType::P2pkh => format!("pkh({})", self.key),
+            AddressType::P2shP2wpkh => format!("sh(wpkh({}))", self.key),
+            AddressType::P2wpkh => format!("wpkh({})", self.key),
+            AddressType::P2tr => format!("tr({})", self.key),
+        }
+    }
+}
+
+/// Displays the descriptor with its checksum.
+impl std::fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let body = self.body();
+        let checksum = descriptor_checksum(&body).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}#{}", body, checksum)
+    }
+}
+
+/// Parses a descriptor, verifying its checksum if present.
+impl std::str::FromStr for Descriptor {
+    type Err = horror::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (body, checksum) = match s.split_once('#') {
+            Some((body, checksum)) => (body, Some(checksum)),
+            None => (s, None),
+        };
+        if let Some(checksum) = checksum {
+            if descriptor_checksum(body)? != checksum {
+                return Err(BitcoinError::InvalidChecksum.into());
+            }
+        }
+
+        let invalid = || BitcoinError::InvalidDescriptor(s.to_string());
+        let (address_type, key) = if let Some(key) = unwrap(body, "sh(wpkh(", "))") {
+            (AddressType::P2shP2wpkh, key)
+        } else if let Some(key) = unwrap(body, "pkh(", ")") {
+            (AddressType::P2pkh, key)
+        } else if let Some(key) = unwrap(body, "wpkh(", ")") {
+            (AddressType::P2wpkh, key)
+        } else if let Some(key) = unwrap(body, "tr(", ")") {
+            (AddressType::P2tr, key)
+        } else {
+            return Err(invalid().into());
+        };
+
+        Ok(Self::new(address_type, DescriptorKey::parse(key)?))
+    }
+}
+
+fn unwrap<'a>(s: &'a str, prefix: &str, suffix: &str) -> Option<&'a str> {
+    s.strip_prefix(prefix)?.strip_suffix(suffix)
+}
+
+/// Parse a relative path such as `84'/0'/0'` or `84h/0h/0h`.
+fn parse_path(path: &str) -> Result<DerivationPath> {
+    if path.is_empty() {
+        return DerivationPath::parse("m");
+    }
+    DerivationPath::parse(&format!("m/{}", path.replace('h', "'")))
+}
+
+fn format_child(child: &ChildNumber) -> String {
+    if child.is_hardened() {
+        format!("{}'", child.index())
+    } else {
+        child.index().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bips::{bip39::Mnemonic, wordlists::Language};
+
+    fn root() -> ExtendedKey {
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            Language::English,
+        )
+        .unwrap();
+        ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap()
+    }
+
+    #[test]
+    fn test_checksum() {
+        // https://github.com/bitcoin/bips/blob/master/bip-0380.mediawiki#test-vectors
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+    }
+
+    #[test]
+    fn test_descriptor() {
+        let root = root();
+        let descriptor =
+            Descriptor::from_account(&root, AddressType::P2wpkh, Network::Mainnet, 0, false)
+                .unwrap();
+        let encoded = descriptor.to_string();
+        assert!(encoded.starts_with("wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)#"));
+        assert_eq!(
+            descriptor.address(0).unwrap().to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+
+        let parsed: Descriptor = encoded.parse().unwrap();
+        assert_eq!(parsed, descriptor);
+
+        // hardened steps can be written with 'h'
+        let parsed: Descriptor = encoded
+            .split('#')
+            .next()
+            .unwrap()
+            .replace('\'', "h")
+            .parse()
+            .unwrap();
+        assert_eq!(parsed, descriptor);
+
+        let mut corrupted = encoded.clone();
+        corrupted.pop();
+        corrupted.push('x');
+        assert!(corrupted.parse::<Descriptor>().is_err());
+    }
+
+    #[test]
+    fn test_descriptor_types() {
+        let root = root();
+        let expected = [
+            (
+                AddressType::P2pkh,
+                Network::Mainnet,
+                "pkh(",
+                "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA",
+            ),
+            (
+                AddressType::P2shP2wpkh,
+                Network::Testnet,
+                "sh(wpkh(",
+                "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2",
+            ),
+            (
+                AddressType::P2tr,
+                Network::Mainnet,
+                "tr(",
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            ),
+        ];
+        for (address_type, network, prefix, address) in expected {
+            let descriptor =
+                Descriptor::from_account(&root, address_type, network, 0, false).unwrap();
+            let encoded = descriptor.to_string();
+            assert!(encoded.starts_with(prefix));
+
+            let parsed: Descriptor = encoded.parse().unwrap();
+            assert_eq!(parsed.address_type(), address_type);
+            assert_eq!(parsed.address(0).unwrap().to_string(), address);
+        }
+
+        let hardened = "wpkh(xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8/0'/*)";
+        assert!(hardened.parse::<Descriptor>().is_err());
+    }
+}
@@ -0,0 +1,237 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Bitcoin
+//!
+//! [`ExtendedKey`](crate::bips::bip32::ExtendedKey) follows BIP-32, so the
+//! keys derived from a [`Seed`](crate::bips::bip39::Seed) can hold bitcoins
+//! as well as ethers. This module encodes them as Bitcoin addresses:
+//!
+//! | Type                          | Purpose | Encoding     | Mainnet prefix |
+//! |-------------------------------|---------|--------------|----------------|
+//! | [`AddressType::P2pkh`]        | BIP-44  | Base58Check  | `1`            |
+//! | [`AddressType::P2shP2wpkh`]   | BIP-49  | Base58Check  | `3`            |
+//! | [`AddressType::P2wpkh`]       | BIP-84  | Bech32       | `bc1q`         |
+//! | [`AddressType::P2tr`]         | BIP-86  | Bech32m      | `bc1p`         |
+//!
+//! Taproot addresses commit to the key alone, tweaked as described by
+//! [BIP-341](https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki)
+//! with no script path.
+//!
+//! Account keys are exported for watch-only wallets as an
+//! [`ExtendedPublicKey`], serialized with the SLIP-132 `ypub`/`zpub` versions
+//! if needed, or as a BIP-380 [`Descriptor`] such as
+//! `wpkh([73c5da0a/84'/0'/0']xpub.../0/*)`.
+//!
+//! Spending is done with a [`Psbt`]: a watch-only wallet builds the
+//! transaction and records the origin of its keys, then the holder of the
+//! seed signs it with [`Psbt::sign`] before it is finalized and extracted.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::bips::bip39::Mnemonic;
+//! use laron_wallet::bips::bip32::ExtendedKey;
+//! use laron_wallet::bips::wordlists::Language;
+//! use laron_wallet::bitcoin::{AddressType, BitcoinAddress, Network};
+//!
+//! let mnemonic = Mnemonic::from_phrase(
+//!     "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+//!     Language::English,
+//! ).unwrap();
+//! let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+//!
+//! let path = AddressType::P2wpkh.path(Network::Mainnet, 0, false, 0);
+//! let key = root.derive_path(&path).unwrap();
+//! let address = BitcoinAddress::from_public_key(key.public_key(), AddressType::P2wpkh, Network::Mainnet).unwrap();
+//! assert_eq!(address.to_string(), "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+//! ```
+
+mod address;
+mod descriptor;
+mod psbt;
+mod sighash;
+mod transaction;
+mod wif;
+mod xpub;
+
+pub use address::*;
+pub use descriptor::*;
+pub use psbt::*;
+pub use sighash::*;
+pub use transaction::*;
+pub use wif::*;
+pub use xpub::*;
+
+use crate::hash::sha256;
+use horror::Result;
+use k256::{
+    elliptic_curve::{sec1::ToEncodedPoint, PrimeField},
+    ProjectivePoint, Scalar,
+};
+
+/// Error returned by the Bitcoin specific modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BitcoinError {
+    /// The BIP-341 tweak is not a valid scalar.
+    InvalidTweak,
+    /// The extended key is malformed or has an unknown version.
+    InvalidExtendedKey,
+    /// A hardened child cannot be derived from a public key.
+    HardenedDerivation,
+    /// The descriptor is malformed or not supported.
+    InvalidDescriptor(String),
+    /// The descriptor checksum does not match.
+    InvalidChecksum,
+    /// The serialized data ended unexpectedly.
+    Truncated,
+    /// The transaction is malformed.
+    InvalidTransaction,
+    /// The transaction has no input at the given index.
+    InvalidInputIndex(usize),
+    /// The output spent by the input at the given index is unknown.
+    MissingUtxo(usize),
+    /// The sighash type is not valid for the input.
+    InvalidSighashType(u32),
+    /// The PSBT is malformed or not supported.
+    InvalidPsbt(String),
+    /// The input at the given index cannot be finalized.
+    NotFinalized(usize),
+    /// The WIF string is malformed or has an unknown prefix.
+    InvalidWif,
+    /// Segwit outputs require a compressed public key.
+    UncompressedKey,
+}
+
+impl std::fmt::Display for BitcoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BitcoinError::InvalidTweak => write!(f, "Invalid taproot tweak"),
+            BitcoinError::InvalidExtendedKey => write!(f, "Invalid extended key"),
+            BitcoinError::HardenedDerivation => {
+                write!(f, "Cannot derive a hardened child from a public key")
+            }
+            BitcoinError::InvalidDescriptor(descriptor) => {
+                write!(f, "Invalid descriptor: {}", descriptor)
+            }
+            BitcoinError::InvalidChecksum => write!(f, "Invalid descriptor checksum"),
+            BitcoinError::Truncated => write!(f, "Unexpected end of data"),
+            BitcoinError::InvalidTransaction => write!(f, "Invalid transaction"),
+            BitcoinError::InvalidInputIndex(index) => write!(f, "Invalid input index: {}", index),
+            BitcoinError::MissingUtxo(index) => write!(f, "Missing UTXO of input {}", index),
+            BitcoinError::InvalidSighashType(ty) => write!(f, "Invalid sighash type: {:#x}", ty),
+            BitcoinError::InvalidPsbt(reason) => write!(f, "Invalid PSBT: {}", reason),
+            BitcoinError::NotFinalized(index) => write!(f, "Input {} cannot be finalized", index),
+            BitcoinError::InvalidWif => write!(f, "Invalid WIF private key"),
+            BitcoinError::UncompressedKey => write!(f, "Segwit requires a compressed public key"),
+        }
+    }
+}
+
+impl std::error::Error for BitcoinError {}
+
+/// A Bitcoin network. Signet uses the same encodings as testnet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl Network {
+    /// Return the SLIP-44 coin type used in derivation paths.
+    pub fn coin_type(&self) -> u32 {
+        match self {
+            Network::Mainnet => 0,
+            Network::Testnet | Network::Regtest => 1,
+        }
+    }
+
+    /// Return the version byte of P2PKH addresses.
+    pub fn p2pkh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet | Network::Regtest => 0x6f,
+        }
+    }
+
+    /// Return the version byte of P2SH addresses.
+    pub fn p2sh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            Network::Testnet | Network::Regtest => 0xc4,
+        }
+    }
+
+    /// Return the version byte of WIF private keys.
+    pub fn wif_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x80,
+            Network::Testnet | Network::Regtest => 0xef,
+        }
+    }
+
+    /// Return the human readable part of segwit addresses.
+    pub fn hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+}
+
+/// Compute the BIP-340 tagged hash of the given data.
+pub(crate) fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag = sha256(tag);
+    let mut preimage = Vec::with_capacity(64 + data.len());
+    preimage.extend_from_slice(&tag);
+    preimage.extend_from_slice(&tag);
+    preimage.extend_from_slice(data);
+    sha256(preimage)
+}
+
+/// Return the x-only form of the given key, and whether its y coordinate
+/// is odd.
+pub(crate) fn x_only(key: &k256::PublicKey) -> ([u8; 32], bool) {
+    let point = key.to_encoded_point(true);
+    let mut x = [0u8; 32];
+    x.copy_from_slice(&point.as_bytes()[1..]);
+    (x, point.as_bytes()[0] == 0x03)
+}
+
+/// Return the BIP-341 tweak of the given internal key, without script tree.
+pub(crate) fn taproot_tweak(internal_key: &[u8; 32]) -> Result<Scalar> {
+    let hash = tagged_hash("TapTweak", internal_key);
+    Option::<Scalar>::from(Scalar::from_repr(hash.into()))
+        .ok_or_else(|| BitcoinError::InvalidTweak.into())
+}
+
+/// Return the x-only taproot output key committing to the given key.
+pub(crate) fn taproot_output_key(key: &k256::PublicKey) -> Result<[u8; 32]> {
+    let (internal_key, odd) = x_only(key);
+    let mut point = key.to_projective();
+    if odd {
+        point = -point;
+    }
+
+    let output = point + ProjectivePoint::GENERATOR * taproot_tweak(&internal_key)?;
+    let output = k256::PublicKey::from_affine(output.to_affine())?;
+    Ok(x_only(&output).0)
+}
@@ -0,0 +1,1630 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{
+    address::{p2pkh_script, p2wpkh_script},
+    sighash::*,
+    taproot_tweak,
+    transaction::{
+        write_compact_size, write_var_bytes, OutPoint, Reader, Transaction, TxIn, TxOut,
+    },
+    x_only, BitcoinError, Wif,
+};
+use crate::{
+    bips::{bip32::ExtendedKey, ChildNumber, DerivationPath},
+    hash::{hash160, sha256},
+    signature::signing_key,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use horror::{Error, Result};
+use k256::{elliptic_curve::PrimeField, schnorr};
+use laron_crypto::PrivateKey;
+use rand::{CryptoRng, RngCore};
+use std::collections::BTreeMap;
+
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+/// The unsigned transaction of a version 0 PSBT.
+pub const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+/// The version of the transaction of a version 2 PSBT.
+pub const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+/// The lock time used when no input requires one.
+pub const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+/// The number of inputs of a version 2 PSBT.
+pub const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+/// The number of outputs of a version 2 PSBT.
+pub const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+/// The version of the PSBT, 0 if absent.
+pub const PSBT_GLOBAL_VERSION: u8 = 0xfb;
+
+/// The full transaction spent by a legacy input.
+pub const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+/// The output spent by a segwit input.
+pub const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+/// An ECDSA signature, keyed by its public key.
+pub const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+/// The sighash type signers must use.
+pub const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+/// The redeem script of a P2SH input.
+pub const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+/// The witness script of a P2WSH input.
+pub const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+/// The origin of a public key: master fingerprint and path.
+pub const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+/// The final script sig of the input.
+pub const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+/// The final witness of the input.
+pub const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+/// The id of the spent transaction, in a version 2 PSBT.
+pub const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+/// The index of the spent output, in a version 2 PSBT.
+pub const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+/// The sequence number of the input, in a version 2 PSBT.
+pub const PSBT_IN_SEQUENCE: u8 = 0x10;
+/// The minimum time based lock time required by the input.
+pub const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+/// The minimum height based lock time required by the input.
+pub const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+/// The Schnorr signature of a taproot key path spend.
+pub const PSBT_IN_TAP_KEY_SIG: u8 = 0x13;
+/// The leaf hashes and origin of an x-only public key.
+pub const PSBT_IN_TAP_BIP32_DERIVATION: u8 = 0x16;
+/// The internal key of a taproot output.
+pub const PSBT_IN_TAP_INTERNAL_KEY: u8 = 0x17;
+
+/// The redeem script of a P2SH output.
+pub const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
+/// The witness script of a P2WSH output.
+pub const PSBT_OUT_WITNESS_SCRIPT: u8 = 0x01;
+/// The origin of a public key of the output.
+pub const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+/// The amount of the output, in a version 2 PSBT.
+pub const PSBT_OUT_AMOUNT: u8 = 0x03;
+/// The script of the output, in a version 2 PSBT.
+pub const PSBT_OUT_SCRIPT: u8 = 0x04;
+
+/// The sighash types signed by default: `SIGHASH_ALL`, and `SIGHASH_DEFAULT`
+/// for taproot inputs. The others let the transaction be changed once
+/// signed, so a PSBT asking for them is only signed when they are allowed
+/// explicitly.
+pub const SAFE_SIGHASH_TYPES: [u32; 2] = [SIGHASH_DEFAULT, SIGHASH_ALL];
+
+/// The key-value pairs of a PSBT map, keyed by the full key including its
+/// type byte. Unknown and proprietary pairs are kept as they are.
+pub type PsbtMap = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// A partially signed Bitcoin transaction, as specified by
+/// [BIP-174](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki)
+/// (version 0) and
+/// [BIP-370](https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki)
+/// (version 2).
+///
+/// [`Psbt::sign`] looks for the keys whose origin matches the fingerprint of
+/// the given master key, and signs the legacy, segwit v0 and taproot key
+/// path inputs spending them. Once every input is signed, [`Psbt::finalize`]
+/// builds their script sigs and witnesses, and [`Psbt::extract`] returns the
+/// transaction ready to be broadcast.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Psbt {
+    pub global: PsbtMap,
+    pub inputs: Vec<PsbtMap>,
+    pub outputs: Vec<PsbtMap>,
+}
+
+impl Psbt {
+    /// Create a version 0 PSBT spending the given unsigned transaction.
+    pub fn from_unsigned_tx(tx: &Transaction) -> Result<Self> {
+        if tx
+            .inputs
+            .iter()
+            .any(|input| !input.script_sig.is_empty() || !input.witness.is_empty())
+        {
+            return Err(invalid("the transaction is already signed"));
+        }
+
+        let mut global = PsbtMap::new();
+        global.insert(vec![PSBT_GLOBAL_UNSIGNED_TX], tx.encode_legacy());
+        Ok(Self {
+            global,
+            inputs: vec![PsbtMap::new(); tx.inputs.len()],
+            outputs: vec![PsbtMap::new(); tx.outputs.len()],
+        })
+    }
+
+    /// Parse a serialized PSBT.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let data = data
+            .strip_prefix(PSBT_MAGIC)
+            .ok_or_else(|| invalid("missing magic bytes"))?;
+        let mut reader = Reader::new(data);
+
+        let mut psbt = Self {
+            global: read_map(&mut reader)?,
+            ..Default::default()
+        };
+        let (inputs, outputs) = match psbt.version()? {
+            0 => {
+                if V2_GLOBAL_FIELDS
+                    .iter()
+                    .any(|ty| get(&psbt.global, *ty).is_some())
+                {
+                    return Err(invalid("version 2 field in a version 0 PSBT"));
+                }
+                let tx = psbt.unsigned_tx()?;
+                if tx
+                    .inputs
+                    .iter()
+                    .any(|input| !input.script_sig.is_empty() || !input.witness.is_empty())
+                {
+                    return Err(invalid("the unsigned transaction has script sigs"));
+                }
+                if get(&psbt.global, PSBT_GLOBAL_UNSIGNED_TX) != Some(&tx.encode_legacy()) {
+                    return Err(invalid(
+                        "the unsigned transaction has a witness serialization",
+                    ));
+                }
+                (tx.inputs.len() as u64, tx.outputs.len() as u64)
+            }
+            _ => {
+                if get(&psbt.global, PSBT_GLOBAL_UNSIGNED_TX).is_some() {
+                    return Err(invalid("unsigned transaction in a version 2 PSBT"));
+                }
+                (
+                    read_count(&psbt.global, PSBT_GLOBAL_INPUT_COUNT)?,
+                    read_count(&psbt.global, PSBT_GLOBAL_OUTPUT_COUNT)?,
+                )
+            }
+        };
+
+        for _ in 0..inputs {
+            psbt.inputs.push(read_map(&mut reader)?);
+        }
+        for _ in 0..outputs {
+            psbt.outputs.push(read_map(&mut reader)?);
+        }
+        if !reader.is_empty() {
+            return Err(invalid("trailing bytes"));
+        }
+
+        check_keys(&psbt.global, &GLOBAL_KEYLESS, &[])?;
+        for input in &psbt.inputs {
+            check_keys(input, &INPUT_KEYLESS, &INPUT_KEYED)?;
+        }
+        for output in &psbt.outputs {
+            check_keys(output, &OUTPUT_KEYLESS, &OUTPUT_KEYED)?;
+        }
+
+        // validates the version 2 fields
+        psbt.unsigned_tx()?;
+        Ok(psbt)
+    }
+
+    /// Serialize the PSBT.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = PSBT_MAGIC.to_vec();
+        for map in std::iter::once(&self.global)
+            .chain(&self.inputs)
+            .chain(&self.outputs)
+        {
+            for (key, value) in map {
+                write_var_bytes(&mut out, key);
+                write_var_bytes(&mut out, value);
+            }
+            out.push(0x00);
+        }
+        out
+    }
+
+    /// Parse a base64 encoded PSBT.
+    pub fn from_base64(s: &str) -> Result<Self> {
+        Self::from_bytes(&STANDARD.decode(s.trim())?)
+    }
+
+    /// Encode the PSBT in base64, the format exchanged between wallets.
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.to_bytes())
+    }
+
+    /// Return the PSBT version, 0 or 2.
+    pub fn version(&self) -> Result<u32> {
+        match read_u32(&self.global, PSBT_GLOBAL_VERSION)? {
+            None | Some(0) => Ok(0),
+            Some(2) => Ok(2),
+            Some(version) => Err(invalid(&format!("unsupported version {}", version))),
+        }
+    }
+
+    /// Return the transaction being signed, without signatures.
+    pub fn unsigned_tx(&self) -> Result<Transaction> {
+        if self.version()? == 0 {
+            let raw = get(&self.global, PSBT_GLOBAL_UNSIGNED_TX)
+                .ok_or_else(|| invalid("missing unsigned transaction"))?;
+            return Transaction::decode(raw);
+        }
+
+        let version = read_u32(&self.global, PSBT_GLOBAL_TX_VERSION)?
+            .ok_or_else(|| invalid("missing transaction version"))?;
+
+        let mut inputs = Vec::new();
+        for input in &self.inputs {
+            let txid = get(input, PSBT_IN_PREVIOUS_TXID)
+                .and_then(|txid| <[u8; 32]>::try_from(txid.as_slice()).ok())
+                .ok_or_else(|| invalid("missing previous txid"))?;
+            let vout = read_u32(input, PSBT_IN_OUTPUT_INDEX)?
+                .ok_or_else(|| invalid("missing output index"))?;
+            inputs.push(TxIn {
+                previous_output: OutPoint { txid, vout },
+                sequence: read_u32(input, PSBT_IN_SEQUENCE)?.unwrap_or(0xffff_ffff),
+                ..Default::default()
+            });
+        }
+
+        let mut outputs = Vec::new();
+        for output in &self.outputs {
+            let value = get(output, PSBT_OUT_AMOUNT)
+                .and_then(|amount| <[u8; 8]>::try_from(amount.as_slice()).ok())
+                .ok_or_else(|| invalid("missing output amount"))?;
+            let script_pubkey =
+                get(output, PSBT_OUT_SCRIPT).ok_or_else(|| invalid("missing output script"))?;
+            outputs.push(TxOut {
+                value: u64::from_le_bytes(value),
+                script_pubkey: script_pubkey.clone(),
+            });
+        }
+
+        Ok(Transaction {
+            version: version as i32,
+            inputs,
+            outputs,
+            lock_time: self.lock_time()?,
+        })
+    }
+
+    /// Compute the lock time of a version 2 PSBT, as specified by BIP-370.
+    fn lock_time(&self) -> Result<u32> {
+        let mut heights = Vec::new();
+        let mut times = Vec::new();
+        let mut height_only = false;
+        let mut time_only = false;
+        for input in &self.inputs {
+            let height = read_u32(input, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME)?;
+            let time = read_u32(input, PSBT_IN_REQUIRED_TIME_LOCKTIME)?;
+            height_only |= height.is_some() && time.is_none();
+            time_only |= time.is_some() && height.is_none();
+            heights.extend(height);
+            times.extend(time);
+        }
+
+        match (height_only, time_only) {
+            (true, true) => Err(invalid("incompatible input lock times")),
+            (_, false) if !heights.is_empty() => Ok(heights.into_iter().max().unwrap_or_default()),
+            _ if !times.is_empty() => Ok(times.into_iter().max().unwrap_or_default()),
+            _ => Ok(read_u32(&self.global, PSBT_GLOBAL_FALLBACK_LOCKTIME)?.unwrap_or_default()),
+        }
+    }
+
+    /// Return the output spent by the input at the given index, from its
+    /// witness or non-witness UTXO.
+    pub fn spent_output(&self, index: usize) -> Result<TxOut> {
+        self.utxo(&self.unsigned_tx()?, index)?
+            .ok_or_else(|| BitcoinError::MissingUtxo(index).into())
+    }
+
+    fn utxo(&self, tx: &Transaction, index: usize) -> Result<Option<TxOut>> {
+        let input = self
+            .inputs
+            .get(index)
+            .ok_or(BitcoinError::InvalidInputIndex(index))?;
+
+        if let Some(utxo) = get(input, PSBT_IN_WITNESS_UTXO) {
+            let mut reader = Reader::new(utxo);
+            let utxo = TxOut::decode(&mut reader)?;
+            if !reader.is_empty() {
+                return Err(invalid("malformed witness UTXO"));
+            }
+            return Ok(Some(utxo));
+        }
+
+        if let Some(raw) = get(input, PSBT_IN_NON_WITNESS_UTXO) {
+            let previous = Transaction::decode(raw)?;
+            let outpoint = tx.inputs[index].previous_output;
+            if previous.txid() != outpoint.txid {
+                return Err(invalid("the non-witness UTXO does not match the input"));
+            }
+            return previous
+                .outputs
+                .get(outpoint.vout as usize)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| invalid("the non-witness UTXO does not match the input"));
+        }
+
+        Ok(None)
+    }
+
+    /// Set the output spent by a segwit input.
+    pub fn set_witness_utxo(&mut self, index: usize, utxo: &TxOut) -> Result<()> {
+        let mut value = Vec::new();
+        utxo.encode(&mut value);
+        self.input_mut(index)?
+            .insert(vec![PSBT_IN_WITNESS_UTXO], value);
+        Ok(())
+    }
+
+    /// Set the transaction whose output is spent by a legacy input.
+    pub fn set_non_witness_utxo(&mut self, index: usize, previous: &Transaction) -> Result<()> {
+        self.input_mut(index)?
+            .insert(vec![PSBT_IN_NON_WITNESS_UTXO], previous.encode());
+        Ok(())
+    }
+
+    /// Record that the input at the given index spends the key derived from
+    /// `root` along `path`, so that [`Psbt::sign`] can find it.
+    ///
+    /// The UTXO of the input must be set first: taproot inputs get a
+    /// BIP-371 derivation and internal key, and P2SH-P2WPKH inputs their
+    /// redeem script.
+    pub fn add_bip32_derivation(
+        &mut self,
+        index: usize,
+        root: &ExtendedKey,
+        path: &DerivationPath,
+    ) -> Result<()> {
+        let utxo = self.spent_output(index)?;
+        let key = root.derive_path(path)?;
+        let public_key = key.public_key().to_bytes();
+        let mut origin = root.fingerprint().to_vec();
+        for child in path.iter() {
+            origin.extend_from_slice(&u32::from(*child).to_le_bytes());
+        }
+
+        let input = self.input_mut(index)?;
+        if is_p2tr(&utxo.script_pubkey) {
+            let (internal_key, _) = x_only(&k256::PublicKey::from_sec1_bytes(&public_key[..])?);
+            // no leaf hash, the key is only used for key path spends
+            let mut value = vec![0x00];
+            value.extend_from_slice(&origin);
+            input.insert(
+                [&[PSBT_IN_TAP_BIP32_DERIVATION][..], &internal_key].concat(),
+                value,
+            );
+            input.insert(vec![PSBT_IN_TAP_INTERNAL_KEY], internal_key.to_vec());
+        } else {
+            let redeem_script = p2wpkh_script(&hash160(&public_key[..]));
+            if is_p2sh(&utxo.script_pubkey) && utxo.script_pubkey[2..22] == hash160(&redeem_script)
+            {
+                input.insert(vec![PSBT_IN_REDEEM_SCRIPT], redeem_script);
+            }
+            input.insert(
+                [&[PSBT_IN_BIP32_DERIVATION][..], &public_key[..]].concat(),
+                origin,
+            );
+        }
+        Ok(())
+    }
+
+    /// Sign every input spending a key derived from the given master key,
+    /// and return the number of signatures added.
+    ///
+    /// Keys are matched by the fingerprint and path of their BIP-32
+    /// derivation entries. ECDSA signatures of P2PKH, P2WPKH, P2WSH and
+    /// their P2SH wrapped forms are added as partial signatures, while
+    /// taproot inputs get a key path Schnorr signature with the BIP-86
+    /// tweak. Inputs which are already finalized are skipped, and inputs
+    /// asking for a sighash type other than [`SAFE_SIGHASH_TYPES`] are
+    /// rejected.
+    pub fn sign(&mut self, root: &ExtendedKey) -> Result<usize> {
+        self.sign_with_rng(root, &mut rand::thread_rng())
+    }
+
+    /// Sign like [`sign`](Psbt::sign), drawing the auxiliary randomness of
+    /// the BIP-340 Schnorr signatures from the given RNG. ECDSA signatures
+    /// are deterministic and do not use it.
+    pub fn sign_with_rng<R: RngCore + CryptoRng>(
+        &mut self,
+        root: &ExtendedKey,
+        rng: &mut R,
+    ) -> Result<usize> {
+        self.sign_with_sighash_types(root, &SAFE_SIGHASH_TYPES, rng)
+    }
+
+    /// Sign like [`sign_with_rng`](Psbt::sign_with_rng), accepting the given
+    /// sighash types, including their `SIGHASH_ANYONECANPAY` flag, instead
+    /// of [`SAFE_SIGHASH_TYPES`].
+    pub fn sign_with_sighash_types<R: RngCore + CryptoRng>(
+        &mut self,
+        root: &ExtendedKey,
+        allowed: &[u32],
+        rng: &mut R,
+    ) -> Result<usize> {
+        let (tx, prevouts) = self.prevouts()?;
+
+        let mut signed = 0;
+        for (index, utxo) in prevouts.iter().enumerate() {
+            let Some(utxo) = utxo else {
+                continue;
+            };
+            let input = &self.inputs[index];
+            if is_finalized(input) {
+                continue;
+            }
+
+            if is_p2tr(&utxo.script_pubkey) {
+                for key in taproot_keys(root, input)? {
+                    if self.sign_taproot(&tx, index, &prevouts, &key, allowed, rng)? {
+                        signed += 1;
+                        break;
+                    }
+                }
+            } else {
+                let keys = ecdsa_keys(root, input)?;
+                signed += self.sign_ecdsa(&tx, index, utxo, &keys, allowed)?;
+            }
+        }
+        Ok(signed)
+    }
+
+    /// Sign every input spending the given imported key, and return the
+    /// number of signatures added.
+    ///
+    /// Imported keys have no recorded origin, so inputs are matched by
+    /// their script: P2PKH, P2WPKH and P2SH-P2WPKH inputs paying to the
+    /// hash of the key, and taproot inputs paying to its BIP-86 output key.
+    /// Uncompressed keys only sign P2PKH inputs. Inputs asking for a sighash
+    /// type other than [`SAFE_SIGHASH_TYPES`] are rejected.
+    pub fn sign_with_wif(&mut self, wif: &Wif) -> Result<usize> {
+        self.sign_with_wif_and_rng(wif, &mut rand::thread_rng())
+    }
+
+    /// Sign like [`sign_with_wif`](Psbt::sign_with_wif), drawing the
+    /// auxiliary randomness of the BIP-340 Schnorr signatures from the
+    /// given RNG.
+    pub fn sign_with_wif_and_rng<R: RngCore + CryptoRng>(
+        &mut self,
+        wif: &Wif,
+        rng: &mut R,
+    ) -> Result<usize> {
+        self.sign_with_wif_and_sighash_types(wif, &SAFE_SIGHASH_TYPES, rng)
+    }
+
+    /// Sign like [`sign_with_wif_and_rng`](Psbt::sign_with_wif_and_rng),
+    /// accepting the given sighash types instead of [`SAFE_SIGHASH_TYPES`].
+    pub fn sign_with_wif_and_sighash_types<R: RngCore + CryptoRng>(
+        &mut self,
+        wif: &Wif,
+        allowed: &[u32],
+        rng: &mut R,
+    ) -> Result<usize> {
+        let (tx, prevouts) = self.prevouts()?;
+        let public_key = wif.public_key_bytes()?;
+        let key_script = p2pkh_script(&hash160(&public_key));
+
+        let mut signed = 0;
+        for (index, utxo) in prevouts.iter().enumerate() {
+            let Some(utxo) = utxo else {
+                continue;
+            };
+            let input = &self.inputs[index];
+            if is_finalized(input) {
+                continue;
+            }
+
+            if is_p2tr(&utxo.script_pubkey) {
+                if wif.is_compressed()
+                    && self.sign_taproot(&tx, index, &prevouts, wif.private_key(), allowed, rng)?
+                {
+                    signed += 1;
+                }
+            } else if let Some((script_code, segwit)) = script_code(input, utxo)? {
+                // every single key script is signed with its P2PKH script code
+                if script_code == key_script && (wif.is_compressed() || !segwit) {
+                    let keys = [(public_key.clone(), wif.private_key().clone())];
+                    signed += self.sign_ecdsa(&tx, index, utxo, &keys, allowed)?;
+                }
+            }
+        }
+        Ok(signed)
+    }
+
+    /// Return the unsigned transaction and the outputs spent by its inputs,
+    /// if known.
+    fn prevouts(&self) -> Result<(Transaction, Vec<Option<TxOut>>)> {
+        let tx = self.unsigned_tx()?;
+        let prevouts = (0..tx.inputs.len())
+            .map(|index| self.utxo(&tx, index))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((tx, prevouts))
+    }
+
+    /// Return the sighash type asked for by the input at the given index,
+    /// unless it is invalid, not allowed, or `SIGHASH_SINGLE` without an
+    /// output at the same index, which signs a constant hash in legacy
+    /// inputs that could be replayed to spend any other output of the key.
+    fn sighash_type(
+        &self,
+        tx: &Transaction,
+        index: usize,
+        taproot: bool,
+        allowed: &[u32],
+    ) -> Result<u32> {
+        let default = if taproot {
+            SIGHASH_DEFAULT
+        } else {
+            SIGHASH_ALL
+        };
+        let sighash_type = read_u32(&self.inputs[index], PSBT_IN_SIGHASH_TYPE)?.unwrap_or(default);
+        let valid = match sighash_type {
+            SIGHASH_DEFAULT => taproot,
+            0x01..=0x03 | 0x81..=0x83 => true,
+            _ => false,
+        };
+        let base_type = sighash_type & !SIGHASH_ANYONECANPAY;
+        if !valid
+            || !allowed.contains(&sighash_type)
+            || (base_type == SIGHASH_SINGLE && index >= tx.outputs.len())
+        {
+            return Err(BitcoinError::InvalidSighashType(sighash_type).into());
+        }
+        Ok(sighash_type)
+    }
+
+    /// Add the ECDSA signatures of the given keys, along with their
+    /// serialized public keys, to the input at the given index.
+    fn sign_ecdsa(
+        &mut self,
+        tx: &Transaction,
+        index: usize,
+        utxo: &TxOut,
+        keys: &[(Vec<u8>, PrivateKey)],
+        allowed: &[u32],
+    ) -> Result<usize> {
+        let input = &self.inputs[index];
+        let Some((script_code, segwit)) = script_code(input, utxo)? else {
+            return Ok(0);
+        };
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        let sighash_type = self.sighash_type(tx, index, false, allowed)?;
+        let sighash = if segwit {
+            segwit_v0_sighash(tx, index, &script_code, utxo.value, sighash_type)?
+        } else {
+            legacy_sighash(tx, index, &script_code, sighash_type)?
+        };
+
+        for (public_key, key) in keys {
+            let (signature, _) = signing_key(key)?.sign_prehash_recoverable(&sighash)?;
+            let mut signature = signature.to_der().as_bytes().to_vec();
+            signature.push(sighash_type as u8);
+            self.inputs[index].insert([&[PSBT_IN_PARTIAL_SIG][..], public_key].concat(), signature);
+        }
+        Ok(keys.len())
+    }
+
+    /// Add the key path signature of the given internal key to the taproot
+    /// input at the given index, if its output commits to that key.
+    fn sign_taproot<R: RngCore + CryptoRng>(
+        &mut self,
+        tx: &Transaction,
+        index: usize,
+        prevouts: &[Option<TxOut>],
+        key: &PrivateKey,
+        allowed: &[u32],
+        rng: &mut R,
+    ) -> Result<bool> {
+        let output_key = prevouts[index]
+            .as_ref()
+            .map(|utxo| &utxo.script_pubkey[2..]);
+
+        let secret = signing_key(key)?;
+        let (x, odd) = x_only(&k256::PublicKey::from(secret.verifying_key()));
+        let mut scalar = *secret.as_nonzero_scalar().as_ref();
+        if odd {
+            scalar = -scalar;
+        }
+        let tweaked = schnorr::SigningKey::from_bytes(&(scalar + taproot_tweak(&x)?).to_repr())?;
+        if output_key != Some(&tweaked.verifying_key().to_bytes()[..]) {
+            return Ok(false);
+        }
+
+        let sighash_type = self.sighash_type(tx, index, true, allowed)?;
+        let prevouts = prevouts
+            .iter()
+            .enumerate()
+            .map(|(i, utxo)| utxo.clone().ok_or(BitcoinError::MissingUtxo(i)))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let sighash = taproot_key_spend_sighash(tx, index, &prevouts, sighash_type)?;
+        let mut aux_rand = [0u8; 32];
+        rng.fill_bytes(&mut aux_rand);
+        let signature = tweaked.sign_raw(&sighash, &aux_rand)?;
+        let mut signature = signature.to_bytes().to_vec();
+        if sighash_type != SIGHASH_DEFAULT {
+            signature.push(sighash_type as u8);
+        }
+        self.inputs[index].insert(vec![PSBT_IN_TAP_KEY_SIG], signature);
+        Ok(true)
+    }
+
+    /// Build the final script sig and witness of every input from their
+    /// signatures, and clear the fields which are no longer needed.
+    ///
+    /// Single key inputs are supported: P2PKH, P2WPKH, P2SH-P2WPKH and
+    /// taproot key path spends, along with `OP_CHECKMULTISIG` scripts in
+    /// P2SH, P2WSH and P2SH-P2WSH.
+    pub fn finalize(&mut self) -> Result<()> {
+        let tx = self.unsigned_tx()?;
+        for index in 0..self.inputs.len() {
+            if is_finalized(&self.inputs[index]) {
+                continue;
+            }
+            let utxo = self
+                .utxo(&tx, index)?
+                .ok_or(BitcoinError::MissingUtxo(index))?;
+            let (script_sig, witness) = finalize_input(&self.inputs[index], &utxo)
+                .ok_or(BitcoinError::NotFinalized(index))?;
+
+            let input = &mut self.inputs[index];
+            input.retain(|key, _| !matches!(key.first(), Some(0x02..=0x0d | 0x13..=0x18)));
+            if let Some(script_sig) = script_sig {
+                input.insert(vec![PSBT_IN_FINAL_SCRIPTSIG], script_sig);
+            }
+            if let Some(witness) = witness {
+                let mut value = Vec::new();
+                write_compact_size(&mut value, witness.len() as u64);
+                for item in &witness {
+                    write_var_bytes(&mut value, item);
+                }
+                input.insert(vec![PSBT_IN_FINAL_SCRIPTWITNESS], value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the signed transaction of a finalized PSBT.
+    pub fn extract(&self) -> Result<Transaction> {
+        let mut tx = self.unsigned_tx()?;
+        for (index, (input, map)) in tx.inputs.iter_mut().zip(&self.inputs).enumerate() {
+            if !is_finalized(map) {
+                return Err(BitcoinError::NotFinalized(index).into());
+            }
+            if let Some(script_sig) = get(map, PSBT_IN_FINAL_SCRIPTSIG) {
+                input.script_sig = script_sig.clone();
+            }
+            if let Some(witness) = get(map, PSBT_IN_FINAL_SCRIPTWITNESS) {
+                let mut reader = Reader::new(witness);
+                for _ in 0..reader.read_compact_size()? {
+                    input.witness.push(reader.read_var_bytes()?.to_vec());
+                }
+            }
+        }
+        Ok(tx)
+    }
+
+    fn input_mut(&mut self, index: usize) -> Result<&mut PsbtMap> {
+        self.inputs
+            .get_mut(index)
+            .ok_or_else(|| BitcoinError::InvalidInputIndex(index).into())
+    }
+}
+
+impl std::fmt::Display for Psbt {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_base64())
+    }
+}
+
+impl std::str::FromStr for Psbt {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_base64(s)
+    }
+}
+
+const V2_GLOBAL_FIELDS: [u8; 4] = [
+    PSBT_GLOBAL_TX_VERSION,
+    PSBT_GLOBAL_FALLBACK_LOCKTIME,
+    PSBT_GLOBAL_INPUT_COUNT,
+    PSBT_GLOBAL_OUTPUT_COUNT,
+];
+
+/// The global fields keyed by their type alone.
+const GLOBAL_KEYLESS: [u8; 6] = [
+    PSBT_GLOBAL_UNSIGNED_TX,
+    PSBT_GLOBAL_TX_VERSION,
+    PSBT_GLOBAL_FALLBACK_LOCKTIME,
+    PSBT_GLOBAL_INPUT_COUNT,
+    PSBT_GLOBAL_OUTPUT_COUNT,
+    PSBT_GLOBAL_VERSION,
+];
+
+/// The input fields keyed by their type alone.
+const INPUT_KEYLESS: [u8; 14] = [
+    PSBT_IN_NON_WITNESS_UTXO,
+    PSBT_IN_WITNESS_UTXO,
+    PSBT_IN_SIGHASH_TYPE,
+    PSBT_IN_REDEEM_SCRIPT,
+    PSBT_IN_WITNESS_SCRIPT,
+    PSBT_IN_FINAL_SCRIPTSIG,
+    PSBT_IN_FINAL_SCRIPTWITNESS,
+    PSBT_IN_PREVIOUS_TXID,
+    PSBT_IN_OUTPUT_INDEX,
+    PSBT_IN_SEQUENCE,
+    PSBT_IN_REQUIRED_TIME_LOCKTIME,
+    PSBT_IN_REQUIRED_HEIGHT_LOCKTIME,
+    PSBT_IN_TAP_KEY_SIG,
+    PSBT_IN_TAP_INTERNAL_KEY,
+];
+
+/// The output fields keyed by their type alone.
+const OUTPUT_KEYLESS: [u8; 4] = [
+    PSBT_OUT_REDEEM_SCRIPT,
+    PSBT_OUT_WITNESS_SCRIPT,
+    PSBT_OUT_AMOUNT,
+    PSBT_OUT_SCRIPT,
+];
+
+/// The lengths of a serialized public key, compressed or not.
+const PUBLIC_KEY_LENGTHS: &[usize] = &[33, 65];
+
+/// The input fields keyed by a public key, with the lengths of that key.
+const INPUT_KEYED: [(u8, &[usize]); 3] = [
+    (PSBT_IN_PARTIAL_SIG, PUBLIC_KEY_LENGTHS),
+    (PSBT_IN_BIP32_DERIVATION, PUBLIC_KEY_LENGTHS),
+    (PSBT_IN_TAP_BIP32_DERIVATION, &[32]),
+];
+
+/// The output fields keyed by a public key, with the lengths of that key.
+const OUTPUT_KEYED: [(u8, &[usize]); 1] = [(PSBT_OUT_BIP32_DERIVATION, PUBLIC_KEY_LENGTHS)];
+
+fn invalid(reason: &str) -> Error {
+    BitcoinError::InvalidPsbt(reason.to_string()).into()
+}
+
+fn read_map(reader: &mut Reader) -> Result<PsbtMap> {
+    let mut map = PsbtMap::new();
+    loop {
+        let key = reader.read_var_bytes()?;
+        if key.is_empty() {
+            return Ok(map);
+        }
+        let value = reader.read_var_bytes()?;
+        if map.insert(key.to_vec(), value.to_vec()).is_some() {
+            return Err(invalid("duplicate key"));
+        }
+    }
+}
+
+/// Check that the known fields of a map carry the key data of their type:
+/// none, or a public key of one of the given lengths.
+fn check_keys(map: &PsbtMap, keyless: &[u8], keyed: &[(u8, &[usize])]) -> Result<()> {
+    for key in map.keys() {
+        let data = key.len() - 1;
+        let valid = match keyed.iter().find(|(ty, _)| *ty == key[0]) {
+            Some((_, lengths)) => lengths.contains(&data),
+            None => data == 0 || !keyless.contains(&key[0]),
+        };
+        if !valid {
+            return Err(invalid(&format!("malformed key of type {:#04x}", key[0])));
+        }
+    }
+    Ok(())
+}
+
+fn get(map: &PsbtMap, ty: u8) -> Option<&Vec<u8>> {
+    map.get(&[ty][..])
+}
+
+fn get_keyed<'a>(map: &'a PsbtMap, ty: u8, key: &[u8]) -> Option<&'a Vec<u8>> {
+    map.get(&[&[ty][..], key].concat())
+}
+
+/// Return the pairs of the given type whose key carries data.
+fn entries(map: &PsbtMap, ty: u8) -> impl Iterator<Item = (&Vec<u8>, &Vec<u8>)> {
+    map.iter()
+        .filter(move |(key, _)| key.len() > 1 && key[0] == ty)
+}
+
+fn read_u32(map: &PsbtMap, ty: u8) -> Result<Option<u32>> {
+    get(map, ty)
+        .map(|value| {
+            <[u8; 4]>::try_from(value.as_slice())
+                .map(u32::from_le_bytes)
+                .map_err(|_| invalid("malformed 32-bit field"))
+        })
+        .transpose()
+}
+
+fn read_count(map: &PsbtMap, ty: u8) -> Result<u64> {
+    let value = get(map, ty).ok_or_else(|| invalid("missing input or output count"))?;
+    let mut reader = Reader::new(value);
+    let count = reader.read_compact_size()?;
+    if !reader.is_empty() {
+        return Err(invalid("malformed count"));
+    }
+    Ok(count)
+}
+
+fn is_finalized(input: &PsbtMap) -> bool {
+    get(input, PSBT_IN_FINAL_SCRIPTSIG).is_some()
+        || get(input, PSBT_IN_FINAL_SCRIPTWITNESS).is_some()
+}
+
+/// Derive the key of the given `fingerprint || path` origin, if it belongs
+/// to the given master key.
+fn derive(root: &ExtendedKey, origin: &[u8]) -> Result<Option<ExtendedKey>> {
+    if origin.len() < 4 || !origin.len().is_multiple_of(4) {
+        return Err(invalid("malformed key origin"));
+    }
+    if origin[..4] != root.fingerprint() {
+        return Ok(None);
+    }
+
+    let mut path = DerivationPath::parse("m")?;
+    for child in origin[4..].chunks(4) {
+        // the raw index, with the hardened bit
+        let child = u32::from_le_bytes(child.try_into()?);
+        path = path.child(ChildNumber::from(child));
+    }
+    Ok(Some(root.derive_path(&path)?))
+}
+
+/// Return the keys of the given master key listed by the BIP-32 derivations
+/// of an input, along with their serialized public keys.
+fn ecdsa_keys(root: &ExtendedKey, input: &PsbtMap) -> Result<Vec<(Vec<u8>, PrivateKey)>> {
+    let mut keys = Vec::new();
+    for (key, value) in entries(input, PSBT_IN_BIP32_DERIVATION) {
+        let Some(child) = derive(root, value)? else {
+            continue;
+        };
+        if child.public_key().to_bytes()[..] == key[1..] {
+            keys.push((key[1..].to_vec(), child.private_key().clone()));
+        }
+    }
+    Ok(keys)
+}
+
+/// Return the keys of the given master key listed by the taproot BIP-32
+/// derivations of an input, which may be its internal key.
+fn taproot_keys(root: &ExtendedKey, input: &PsbtMap) -> Result<Vec<PrivateKey>> {
+    let internal_key = get(input, PSBT_IN_TAP_INTERNAL_KEY);
+
+    let mut keys = Vec::new();
+    for (key, value) in entries(input, PSBT_IN_TAP_BIP32_DERIVATION) {
+        if internal_key.is_some_and(|internal_key| internal_key[..] != key[1..]) {
+            continue;
+        }
+        let mut reader = Reader::new(value);
+        let leaves = reader.read_compact_size()?;
+        let leaf_hashes = usize::try_from(leaves)?
+            .checked_mul(32)
+            .ok_or_else(|| invalid("malformed taproot derivation"))?;
+        reader.read_bytes(leaf_hashes)?;
+        // keys used in script leaves are not signed for the key path
+        if leaves != 0 {
+            continue;
+        }
+        let Some(child) = derive(root, reader.read_remaining())? else {
+            continue;
+        };
+
+        let public_key = k256::PublicKey::from_sec1_bytes(&child.public_key().to_bytes()[..])?;
+        if x_only(&public_key).0[..] == key[1..] {
+            keys.push(child.private_key().clone());
+        }
+    }
+    Ok(keys)
+}
+
+fn is_p2pkh(script: &[u8]) -> bool {
+    script.len() == 25 && script[..3] == [0x76, 0xa9, 0x14] && script[23..] == [0x88, 0xac]
+}
+
+fn is_p2sh(script: &[u8]) -> bool {
+    script.len() == 23 && script[..2] == [0xa9, 0x14] && script[22] == 0x87
+}
+
+fn is_p2wpkh(script: &[u8]) -> bool {
+    script.len() == 22 && script[..2] == [0x00, 0x14]
+}
+
+fn is_p2wsh(script: &[u8]) -> bool {
+    script.len() == 34 && script[..2] == [0x00, 0x20]
+}
+
+fn is_p2tr(script: &[u8]) -> bool {
+    script.len() == 34 && script[..2] == [0x51, 0x20]
+}
+
+/// Return the script code signed by an ECDSA input, and whether it is
+/// signed with the segwit v0 algorithm.
+fn script_code(input: &PsbtMap, utxo: &TxOut) -> Result<Option<(Vec<u8>, bool)>> {
+    let mut script = &utxo.script_pubkey;
+    if is_p2sh(script) {
+        let Some(redeem_script) = get(input, PSBT_IN_REDEEM_SCRIPT) else {
+            return Ok(None);
+        };
+        if hash160(redeem_script) != script[2..22] {
+            return Err(invalid("the redeem script does not match the UTXO"));
+        }
+        script = redeem_script;
+    }
+
+    if is_p2wpkh(script) {
+        return Ok(Some((p2pkh_script(&script[2..]), true)));
+    }
+    if is_p2wsh(script) {
+        let Some(witness_script) = get(input, PSBT_IN_WITNESS_SCRIPT) else {
+            return Ok(None);
+        };
+        if sha256(witness_script) != script[2..] {
+            return Err(invalid("the witness script does not match the UTXO"));
+        }
+        return Ok(Some((witness_script.clone(), true)));
+    }
+
+    // any other witness program is not signed with ECDSA
+    let witness_program = script.len() >= 4
+        && script.len() <= 42
+        && matches!(script[0], 0x00 | 0x51..=0x60)
+        && script[1] as usize == script.len() - 2;
+    if witness_program {
+        return Ok(None);
+    }
+    Ok(Some((script.clone(), false)))
+}
+
+/// The final script sig and witness of an input.
+type FinalScripts = (Option<Vec<u8>>, Option<Vec<Vec<u8>>>);
+
+/// Return the final script sig and witness of a single key or multisig
+/// input.
+fn finalize_input(input: &PsbtMap, utxo: &TxOut) -> Option<FinalScripts> {
+    let script = &utxo.script_pubkey;
+    if is_p2tr(script) {
+        let signature = get(input, PSBT_IN_TAP_KEY_SIG)?;
+        return Some((None, Some(vec![signature.clone()])));
+    }
+
+    let (program, script_sig) = if is_p2sh(script) {
+        let redeem_script = get(input, PSBT_IN_REDEEM_SCRIPT)?;
+        (redeem_script, Some(push(redeem_script)))
+    } else {
+        (script, None)
+    };
+
+    if is_p2wsh(program) {
+        let witness_script = get(input, PSBT_IN_WITNESS_SCRIPT)?;
+        let mut witness = multisig_signatures(input, witness_script)?;
+        witness.push(witness_script.clone());
+        return Some((script_sig, Some(witness)));
+    }
+    if script_sig.is_some() && !is_p2wpkh(program) {
+        let mut items = multisig_signatures(input, program)?;
+        items.push(program.clone());
+        return Some((
+            Some(items.iter().flat_map(|item| push(item)).collect()),
+            None,
+        ));
+    }
+
+    let hash = if is_p2wpkh(program) {
+        &program[2..]
+    } else if is_p2pkh(program) && script_sig.is_none() {
+        &program[3..23]
+    } else {
+        return None;
+    };
+    let (key, signature) =
+        entries(input, PSBT_IN_PARTIAL_SIG).find(|(key, _)| hash160(&key[1..]) == hash)?;
+
+    if is_p2wpkh(program) {
+        Some((script_sig, Some(vec![signature.clone(), key[1..].to_vec()])))
+    } else {
+        Some((Some([push(signature), push(&key[1..])].concat()), None))
+    }
+}
+
+/// Return the stack items satisfying the given `OP_CHECKMULTISIG` script:
+/// the dummy element, then the signatures of the first required keys which
+/// signed the input, in the order of the script.
+fn multisig_signatures(input: &PsbtMap, script: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let (&op_m, rest) = script.split_first()?;
+    let (&op_n, keys) = rest.strip_suffix(&[0xae])?.split_last()?;
+    if !(0x51..=0x60).contains(&op_m) || !(op_m..=0x60).contains(&op_n) {
+        return None;
+    }
+
+    let mut public_keys = Vec::new();
+    let mut keys = keys;
+    while let Some((&len, rest)) = keys.split_first() {
+        if !PUBLIC_KEY_LENGTHS.contains(&(len as usize)) || rest.len() < len as usize {
+            return None;
+        }
+        let (key, rest) = rest.split_at(len as usize);
+        public_keys.push(key);
+        keys = rest;
+    }
+    if public_keys.len() != (op_n - 0x50) as usize {
+        return None;
+    }
+
+    let required = (op_m - 0x50) as usize;
+    let mut items = vec![vec![]];
+    items.extend(
+        public_keys
+            .iter()
+            .filter_map(|key| get_keyed(input, PSBT_IN_PARTIAL_SIG, key).cloned())
+            .take(required),
+    );
+    (items.len() == required + 1).then_some(items)
+}
+
+/// Return the script pushing the given data onto the stack.
+fn push(data: &[u8]) -> Vec<u8> {
+    let mut script = Vec::with_capacity(data.len() + 5);
+    match data.len() {
+        len @ 0..=0x4b => script.push(len as u8),
+        len @ 0x4c..=0xff => script.extend_from_slice(&[0x4c, len as u8]),
+        len @ 0x100..=0xffff => {
+            script.push(0x4d);
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        len => {
+            script.push(0x4e);
+            script.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bips::{bip39::Mnemonic, wordlists::Language};
+    use crate::bitcoin::{AddressType, BitcoinAddress, Network};
+    use k256::ecdsa::{signature::hazmat::PrehashVerifier, VerifyingKey};
+
+    fn root() -> ExtendedKey {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mnemonic = Mnemonic::from_phrase(phrase, Language::English).unwrap();
+        ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap()
+    }
+
+    #[test]
+    fn test_sign_finalize_extract() {
+        let root = root();
+        let paths: Vec<_> = AddressType::ALL
+            .iter()
+            .map(|ty| ty.path(Network::Mainnet, 0, false, 0))
+            .collect();
+        let utxos: Vec<_> = AddressType::ALL
+            .iter()
+            .zip(&paths)
+            .enumerate()
+            .map(|(i, (ty, path))| {
+                let key = root.derive_path(path).unwrap();
+                let address =
+                    BitcoinAddress::from_public_key(key.public_key(), *ty, Network::Mainnet);
+                TxOut {
+                    value: 100_000 * (i as u64 + 1),
+                    script_pubkey: address.unwrap().script_pubkey(),
+                }
+            })
+            .collect();
+
+        // the P2PKH input spends the output of a full previous transaction
+        let previous = Transaction {
+            version: 2,
+            inputs: vec![TxIn::default()],
+            outputs: vec![utxos[0].clone()],
+            lock_time: 0,
+        };
+        let tx = Transaction {
+            version: 2,
+            inputs: (0..4u8)
+                .map(|i| TxIn {
+                    previous_output: OutPoint {
+                        txid: if i == 0 { previous.txid() } else { [i; 32] },
+                        vout: 0,
+                    },
+                    sequence: 0xffff_fffd,
+                    ..Default::default()
+                })
+                .collect(),
+            outputs: vec![TxOut {
+                value: 900_000,
+                script_pubkey: utxos[2].script_pubkey.clone(),
+            }],
+            lock_time: 0,
+        };
+
+        let mut psbt = Psbt::from_unsigned_tx(&tx).unwrap();
+        psbt.set_non_witness_utxo(0, &previous).unwrap();
+        for (i, utxo) in utxos.iter().enumerate().skip(1) {
+            psbt.set_witness_utxo(i, utxo).unwrap();
+        }
+        for (i, path) in paths.iter().enumerate() {
+            psbt.add_bip32_derivation(i, &root, path).unwrap();
+        }
+
+        let mut psbt = Psbt::from_base64(&psbt.to_base64()).unwrap();
+        assert!(psbt.to_string().starts_with("cHNidP8"));
+        assert_eq!(psbt.spent_output(0).unwrap(), utxos[0]);
+
+        let other = root.derive_child(ChildNumber::hardened(0)).unwrap();
+        assert_eq!(psbt.sign(&other).unwrap(), 0);
+        assert_eq!(psbt.sign(&root).unwrap(), 4);
+
+        for (i, utxo) in utxos.iter().enumerate().take(3) {
+            let public_key = root.derive_path(&paths[i]).unwrap().public_key().to_bytes();
+            let signature =
+                &psbt.inputs[i][&[&[PSBT_IN_PARTIAL_SIG][..], &public_key[..]].concat()];
+            assert_eq!(signature.last(), Some(&(SIGHASH_ALL as u8)));
+
+            let sighash = if i == 0 {
+                legacy_sighash(&tx, i, &utxo.script_pubkey, SIGHASH_ALL)
+            } else {
+                let script_code = p2pkh_script(&hash160(&public_key[..]));
+                segwit_v0_sighash(&tx, i, &script_code, utxo.value, SIGHASH_ALL)
+            }
+            .unwrap();
+            let signature =
+                k256::ecdsa::Signature::from_der(&signature[..signature.len() - 1]).unwrap();
+            VerifyingKey::from_sec1_bytes(&public_key[..])
+                .unwrap()
+                .verify_prehash(&sighash, &signature)
+                .unwrap();
+        }
+
+        let tap_signature = psbt.inputs[3][&vec![PSBT_IN_TAP_KEY_SIG]].clone();
+        assert_eq!(tap_signature.len(), 64);
+        let sighash = taproot_key_spend_sighash(&tx, 3, &utxos, SIGHASH_DEFAULT).unwrap();
+        schnorr::VerifyingKey::from_bytes(&utxos[3].script_pubkey[2..])
+            .unwrap()
+            .verify_raw(&sighash, &tap_signature[..].try_into().unwrap())
+            .unwrap();
+
+        assert!(psbt.extract().is_err());
+        psbt.finalize().unwrap();
+        let keys: Vec<_> = psbt.inputs[2].keys().cloned().collect();
+        assert_eq!(
+            keys,
+            vec![
+                vec![PSBT_IN_WITNESS_UTXO],
+                vec![PSBT_IN_FINAL_SCRIPTWITNESS]
+            ]
+        );
+
+        let signed = psbt.extract().unwrap();
+        assert_eq!(signed.outputs, tx.outputs);
+        assert!(signed.inputs[0].witness.is_empty());
+        assert_eq!(
+            signed.inputs[0].script_sig.len(),
+            signed.inputs[0].script_sig[0] as usize + 35
+        );
+        assert_eq!(&signed.inputs[1].script_sig[..3], &[0x16, 0x00, 0x14]);
+        assert_eq!(signed.inputs[1].witness.len(), 2);
+        assert!(signed.inputs[2].script_sig.is_empty());
+        assert_eq!(signed.inputs[2].witness.len(), 2);
+        assert_eq!(signed.inputs[3].witness, vec![tap_signature]);
+    }
+
+    #[test]
+    fn test_sign_with_wif() {
+        let key = Wif::new(
+            PrivateKey::from_bytes(&[0x11; 32]).unwrap(),
+            Network::Mainnet,
+            true,
+        );
+        let uncompressed = Wif::new(
+            PrivateKey::from_bytes(&[0x22; 32]).unwrap(),
+            Network::Mainnet,
+            false,
+        );
+        let utxos = [
+            (&key, AddressType::P2wpkh),
+            (&key, AddressType::P2tr),
+            (&uncompressed, AddressType::P2pkh),
+        ]
+        .iter()
+        .map(|(wif, ty)| TxOut {
+            value: 50_000,
+            script_pubkey: wif.address(*ty).unwrap().script_pubkey(),
+        })
+        .collect::<Vec<_>>();
+        let tx = Transaction {
+            version: 2,
+            inputs: (1..=3u8)
+                .map(|i| TxIn {
+                    previous_output: OutPoint {
+                        txid: [i; 32],
+                        vout: 0,
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            outputs: vec![utxos[0].clone()],
+            lock_time: 0,
+        };
+
+        let mut psbt = Psbt::from_unsigned_tx(&tx).unwrap();
+        for (i, utxo) in utxos.iter().enumerate() {
+            psbt.set_witness_utxo(i, utxo).unwrap();
+        }
+        assert_eq!(psbt.sign(&root()).unwrap(), 0);
+        assert_eq!(psbt.sign_with_wif(&uncompressed).unwrap(), 1);
+        assert_eq!(psbt.sign_with_wif(&key).unwrap(), 2);
+        psbt.finalize().unwrap();
+
+        let signed = psbt.extract().unwrap();
+        assert_eq!(signed.inputs[0].witness[1], key.public_key_bytes().unwrap());
+        assert_eq!(signed.inputs[1].witness[0].len(), 64);
+        assert!(signed.inputs[2]
+            .script_sig
+            .ends_with(&uncompressed.public_key_bytes().unwrap()));
+
+        // the Schnorr signature only depends on the RNG
+        let signatures = (0..2)
+            .map(|_| {
+                let mut psbt = Psbt::from_unsigned_tx(&tx).unwrap();
+                for (i, utxo) in utxos.iter().enumerate() {
+                    psbt.set_witness_utxo(i, utxo).unwrap();
+                }
+                let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(7);
+                assert_eq!(psbt.sign_with_wif_and_rng(&key, &mut rng).unwrap(), 2);
+                psbt.inputs[1][&[PSBT_IN_TAP_KEY_SIG][..]].clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(signatures[0], signatures[1]);
+    }
+
+    #[test]
+    fn test_sighash_types() {
+        let key = Wif::new(
+            PrivateKey::from_bytes(&[0x11; 32]).unwrap(),
+            Network::Mainnet,
+            true,
+        );
+        let utxo = TxOut {
+            value: 50_000,
+            script_pubkey: key.address(AddressType::P2pkh).unwrap().script_pubkey(),
+        };
+        let tx = Transaction {
+            version: 2,
+            inputs: (1..=2u8)
+                .map(|i| TxIn {
+                    previous_output: OutPoint {
+                        txid: [i; 32],
+                        vout: 0,
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            outputs: vec![utxo.clone()],
+            lock_time: 0,
+        };
+        let psbt = |index: usize, sighash_type: u32| {
+            let mut psbt = Psbt::from_unsigned_tx(&tx).unwrap();
+            psbt.set_witness_utxo(index, &utxo).unwrap();
+            psbt.inputs[index].insert(
+                vec![PSBT_IN_SIGHASH_TYPE],
+                sighash_type.to_le_bytes().to_vec(),
+            );
+            psbt
+        };
+        let rng = &mut rand::thread_rng();
+
+        assert_eq!(psbt(0, SIGHASH_ALL).sign_with_wif(&key).unwrap(), 1);
+        assert!(psbt(0, SIGHASH_NONE).sign_with_wif(&key).is_err());
+        assert!(psbt(0, SIGHASH_ALL | SIGHASH_ANYONECANPAY)
+            .sign_with_wif(&key)
+            .is_err());
+        assert!(psbt(0, SIGHASH_DEFAULT).sign_with_wif(&key).is_err());
+
+        let mut none = psbt(0, SIGHASH_NONE);
+        let allowed = [SIGHASH_ALL, SIGHASH_NONE];
+        assert_eq!(
+            none.sign_with_wif_and_sighash_types(&key, &allowed, rng)
+                .unwrap(),
+            1
+        );
+        let signature = entries(&none.inputs[0], PSBT_IN_PARTIAL_SIG)
+            .next()
+            .unwrap()
+            .1;
+        assert_eq!(signature.last(), Some(&(SIGHASH_NONE as u8)));
+
+        // the second input has no output, so SIGHASH_SINGLE would sign the
+        // constant hash of the historical bug
+        let allowed = [SIGHASH_SINGLE, SIGHASH_SINGLE | SIGHASH_ANYONECANPAY];
+        assert_eq!(
+            psbt(0, SIGHASH_SINGLE)
+                .sign_with_wif_and_sighash_types(&key, &allowed, rng)
+                .unwrap(),
+            1
+        );
+        assert!(psbt(1, SIGHASH_SINGLE)
+            .sign_with_wif_and_sighash_types(&key, &allowed, rng)
+            .is_err());
+
+        // only the low byte would be appended to the signature
+        let allowed = [SIGHASH_ALL | 0x100];
+        assert!(psbt(0, SIGHASH_ALL | 0x100)
+            .sign_with_wif_and_sighash_types(&key, &allowed, rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_taproot_leaf_count() {
+        let root = root();
+        let path = AddressType::P2tr.path(Network::Mainnet, 0, false, 0);
+        let key = root.derive_path(&path).unwrap();
+        let utxo = TxOut {
+            value: 50_000,
+            script_pubkey: BitcoinAddress::from_public_key(
+                key.public_key(),
+                AddressType::P2tr,
+                Network::Mainnet,
+            )
+            .unwrap()
+            .script_pubkey(),
+        };
+        let tx = Transaction {
+            version: 2,
+            inputs: vec![TxIn::default()],
+            outputs: vec![utxo.clone()],
+            lock_time: 0,
+        };
+        let mut psbt = Psbt::from_unsigned_tx(&tx).unwrap();
+        psbt.set_witness_utxo(0, &utxo).unwrap();
+        psbt.add_bip32_derivation(0, &root, &path).unwrap();
+
+        // a leaf count whose hashes would overflow the length to read
+        for (key, value) in psbt.inputs[0].iter_mut() {
+            if key[0] == PSBT_IN_TAP_BIP32_DERIVATION {
+                value.splice(..1, [0xff].into_iter().chain(u64::MAX.to_le_bytes()));
+            }
+        }
+        assert!(psbt.sign(&root).is_err());
+    }
+
+    #[test]
+    fn test_version_2() {
+        let root = root();
+        let path = AddressType::P2wpkh.path(Network::Mainnet, 0, false, 0);
+        let key = root.derive_path(&path).unwrap();
+        let script_pubkey = p2wpkh_script(&hash160(&key.public_key().to_bytes()[..]));
+
+        let mut psbt = Psbt::default();
+        psbt.global
+            .insert(vec![PSBT_GLOBAL_VERSION], 2u32.to_le_bytes().to_vec());
+        psbt.global
+            .insert(vec![PSBT_GLOBAL_TX_VERSION], 2u32.to_le_bytes().to_vec());
+        psbt.global.insert(
+            vec![PSBT_GLOBAL_FALLBACK_LOCKTIME],
+            0u32.to_le_bytes().to_vec(),
+        );
+        psbt.global.insert(vec![PSBT_GLOBAL_INPUT_COUNT], vec![1]);
+        psbt.global.insert(vec![PSBT_GLOBAL_OUTPUT_COUNT], vec![1]);
+        // a proprietary field, which must be kept as it is
+        psbt.global.insert(b"\xfc\x05laron".to_vec(), vec![1, 2, 3]);
+
+        let mut input = PsbtMap::new();
+        input.insert(vec![PSBT_IN_PREVIOUS_TXID], vec![7; 32]);
+        input.insert(vec![PSBT_IN_OUTPUT_INDEX], 1u32.to_le_bytes().to_vec());
+        input.insert(
+            vec![PSBT_IN_REQUIRED_HEIGHT_LOCKTIME],
+            800_000u32.to_le_bytes().to_vec(),
+        );
+        psbt.inputs.push(input);
+
+        let mut output = PsbtMap::new();
+        output.insert(vec![PSBT_OUT_AMOUNT], 90_000u64.to_le_bytes().to_vec());
+        output.insert(vec![PSBT_OUT_SCRIPT], script_pubkey.clone());
+        psbt.outputs.push(output);
+
+        let mut psbt = Psbt::from_bytes(&psbt.to_bytes()).unwrap();
+        assert_eq!(psbt.version().unwrap(), 2);
+        let tx = psbt.unsigned_tx().unwrap();
+        assert_eq!(tx.lock_time, 800_000);
+        assert_eq!(tx.inputs[0].previous_output.vout, 1);
+        assert_eq!(tx.outputs[0].value, 90_000);
+
+        psbt.set_witness_utxo(
+            0,
+            &TxOut {
+                value: 100_000,
+                script_pubkey,
+            },
+        )
+        .unwrap();
+        psbt.add_bip32_derivation(0, &root, &path).unwrap();
+        assert_eq!(psbt.sign(&root).unwrap(), 1);
+        psbt.finalize().unwrap();
+        assert!(get(&psbt.inputs[0], PSBT_IN_REQUIRED_HEIGHT_LOCKTIME).is_some());
+
+        let signed = psbt.extract().unwrap();
+        assert_eq!(signed.lock_time, 800_000);
+        assert_eq!(
+            signed.inputs[0].witness[1],
+            key.public_key().to_bytes()[..].to_vec()
+        );
+        assert_eq!(Psbt::from_bytes(&psbt.to_bytes()).unwrap(), psbt);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Psbt::from_base64("cHNidP8=").is_err());
+        assert!(Psbt::from_bytes(b"psbt\x00\x00").is_err());
+
+        let tx = Transaction {
+            version: 2,
+            inputs: vec![TxIn::default()],
+            outputs: vec![TxOut::default()],
+            lock_time: 0,
+        };
+        let bytes = Psbt::from_unsigned_tx(&tx).unwrap().to_bytes();
+        assert!(Psbt::from_bytes(&bytes).is_ok());
+        assert!(Psbt::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut duplicate = PSBT_MAGIC.to_vec();
+        write_var_bytes(&mut duplicate, &[PSBT_GLOBAL_UNSIGNED_TX]);
+        write_var_bytes(&mut duplicate, &tx.encode());
+        duplicate.extend_from_slice(&bytes[PSBT_MAGIC.len()..]);
+        assert!(Psbt::from_bytes(&duplicate).is_err());
+    }
+
+    // https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki#test-vectors
+    // The PSBT given to the signers: a P2SH 2-of-2 multisig input and a
+    // P2SH-P2WSH 2-of-2 multisig input, whose keys are derived from
+    // `BIP174_TPRV`, asking for SIGHASH_ALL.
+    const BIP174_UNSIGNED: &str = "cHNidP8BAJoCAAAAAljoeiG1ba8MI76OcHBFbDNvfLqlyHV5JPVFiHuyq911AAAAAAD/////g40EJ9DsZQpoqka7CwmK6kQiwHGyyng1Kgd5WdB86h0BAAAAAP////8CcKrwCAAAAAAWABTYXCtx0AYLCcmIauuBXlCZHdoSTQDh9QUAAAAAFgAUAK6pouXw+HaliN9VRuh0LR2HAI8AAAAAAAEAuwIAAAABqtc5MQGL0l+ErkALaISL4J23BurCrBgpi6vucatlb4sAAAAASEcwRAIgWPb8fGoz4bMVSNSByCbAFb0wE1qtQs1neQ2rZtKtJDsCIEoc7SYExnNbY5PltBaR3XiwDwxZQvufdRhW+qk4FX26Af7///8CgPD6AgAAAAAXqRQPuUY0IWlrgsgzryQceMF9295JNIfQ8gonAQAAABepFCnKdPigj4GZlCgYXJe12FLkBj9hh2UAAAABAwQBAAAAAQRHUiEClYO/Oa4KYJdHrRma3dY0+mEIVZ1sXNObTCGD8auW4H8hAtq2H/SaFNtqfQKwzR+7ePxLGDErW05U2uTbovv+9TbXUq4iBgKVg785rgpgl0etGZrd1jT6YQhVnWxc05tMIYPxq5bgfxDZDGpPAAAAgAAAAIAAAACAIgYC2rYf9JoU22p9ArDNH7t4/EsYMStbTlTa5Nui+/71NtcQ2QxqTwAAAIAAAACAAQAAgAABASAAwusLAAAAABepFLf1+vQOPUClpFmx2zU18rcvqSHohwEDBAEAAAABBCIAIIwjUxc3Q7WV37Sge3K6jkLjeX2nTof+fZ10l+OyAokDAQVHUiEDCJ3BDHrG21T5EymvYXMz2ziM6tDCMfcjN50bmQMLAtwhAjrdkE89bc9Z3bkGsN7iNSm3/7ntUOXoYVGSaGAiHw5zUq4iBgI63ZBPPW3PWd25BrDe4jUpt/+57VDl6GFRkmhgIh8OcxDZDGpPAAAAgAAAAIADAACAIgYDCJ3BDHrG21T5EymvYXMz2ziM6tDCMfcjN50bmQMLAtwQ2QxqTwAAAIAAAACAAgAAgAAiAgOppMN/WZbTqiXbrGtXCvBlA5RJKUJGCzVHU+2e7KWHcRDZDGpPAAAAgAAAAIAEAACAACICAn9jmXV9Lv9VoTatAsaEsYOLZVbl8bazQoKpS2tQBRCWENkMak8AAACAAAAAgAUAAIAA";
+    const BIP174_TPRV: &str = "tprv8ZgxMBicQKsPd9TeAdPADNnSyH9SSUUbTVeFszDE23Ki6TBB5nCefAdHkK8Fm3qMQR6sHwA56zqRmKmxnHk37JkiFzvncDqoKmPWubu7hDF";
+    // The partial signatures of both signers, by input and public key.
+    const BIP174_SIGNATURES: [(usize, &str, &str); 4] = [
+        (0, "029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f", "3044022074018ad4180097b873323c0015720b3684cc8123891048e7dbcd9b55ad679c99022073d369b740e3eb53dcefa33823c8070514ca55a7dd9544f157c167913261118c01"),
+        (0, "02dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d7", "3045022100f61038b308dc1da865a34852746f015772934208c6d24454393cd99bdf2217770220056e675a675a6d0a02b85b14e5e29074d8a25a9b5760bea2816f661910a006ea01"),
+        (1, "03089dc10c7ac6db54f91329af617333db388cead0c231f723379d1b99030b02dc", "3044022062eb7a556107a7c73f45ac4ab5a1dddf6f7075fb1275969a7f383efff784bcb202200c05dbb7470dbf2f08557dd356c7325c1ed30913e996cd3840945db12228da5f01"),
+        (1, "023add904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e86151926860221f0e73", "3044022065f45ba5998b59a27ffe1a7bed016af1f1f90d54b3aa8f7450aa5f56a25103bd02207f724703ad1edb96680b284b56d4ffcb88f7fb759eabbe08aa30f29b851383d201"),
+    ];
+    // The transaction extracted from the finalized PSBT.
+    const BIP174_EXTRACTED: &str = "0200000000010258e87a21b56daf0c23be8e7070456c336f7cbaa5c8757924f545887bb2abdd7500000000da00473044022074018ad4180097b873323c0015720b3684cc8123891048e7dbcd9b55ad679c99022073d369b740e3eb53dcefa33823c8070514ca55a7dd9544f157c167913261118c01483045022100f61038b308dc1da865a34852746f015772934208c6d24454393cd99bdf2217770220056e675a675a6d0a02b85b14e5e29074d8a25a9b5760bea2816f661910a006ea01475221029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f2102dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d752aeffffffff838d0427d0ec650a68aa46bb0b098aea4422c071b2ca78352a077959d07cea1d01000000232200208c2353173743b595dfb4a07b72ba8e42e3797da74e87fe7d9d7497e3b2028903ffffffff0270aaf00800000000160014d85c2b71d0060b09c9886aeb815e50991dda124d00e1f5050000000016001400aea9a2e5f0f876a588df5546e8742d1d87008f000400473044022062eb7a556107a7c73f45ac4ab5a1dddf6f7075fb1275969a7f383efff784bcb202200c05dbb7470dbf2f08557dd356c7325c1ed30913e996cd3840945db12228da5f01473044022065f45ba5998b59a27ffe1a7bed016af1f1f90d54b3aa8f7450aa5f56a25103bd02207f724703ad1edb96680b284b56d4ffcb88f7fb759eabbe08aa30f29b851383d20147522103089dc10c7ac6db54f91329af617333db388cead0c231f723379d1b99030b02dc21023add904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e86151926860221f0e7352ae00000000";
+
+    fn bip174_root() -> ExtendedKey {
+        let data = bs58::decode(BIP174_TPRV)
+            .with_check(None)
+            .into_vec()
+            .unwrap();
+        let key = PrivateKey::from_bytes(&data[46..78]).unwrap();
+        let public_key = key.public_key();
+        let chain_code = data[13..45].try_into().unwrap();
+        ExtendedKey::new(key, public_key, [0; 4], ChildNumber::from(0), 0, chain_code)
+    }
+
+    #[test]
+    fn test_bip174_signer_finalizer() {
+        let root = bip174_root();
+        assert_eq!(root.fingerprint(), [0xd9, 0x0c, 0x6a, 0x4f]);
+
+        let mut psbt = Psbt::from_base64(BIP174_UNSIGNED).unwrap();
+        assert_eq!(psbt.to_base64(), BIP174_UNSIGNED);
+        assert!(psbt.extract().is_err());
+        assert!(psbt.finalize().is_err());
+
+        assert_eq!(psbt.sign(&root).unwrap(), 4);
+        for (index, public_key, signature) in BIP174_SIGNATURES {
+            let key = [vec![PSBT_IN_PARTIAL_SIG], hex::decode(public_key).unwrap()].concat();
+            assert_eq!(hex::encode(&psbt.inputs[index][&key]), signature);
+        }
+
+        psbt.finalize().unwrap();
+        for input in &psbt.inputs {
+            assert!(input.keys().all(|key| matches!(
+                key[0],
+                PSBT_IN_NON_WITNESS_UTXO
+                    | PSBT_IN_WITNESS_UTXO
+                    | PSBT_IN_FINAL_SCRIPTSIG
+                    | PSBT_IN_FINAL_SCRIPTWITNESS
+            )));
+        }
+        let psbt = Psbt::from_bytes(&psbt.to_bytes()).unwrap();
+        assert_eq!(
+            hex::encode(psbt.extract().unwrap().encode()),
+            BIP174_EXTRACTED
+        );
+    }
+
+    #[test]
+    fn test_bip174_parse() {
+        let psbt = Psbt::from_base64(BIP174_UNSIGNED).unwrap();
+        let parse = |psbt: &Psbt| Psbt::from_bytes(&psbt.to_bytes());
+
+        // unknown types are kept
+        let mut unknown = psbt.clone();
+        unknown.inputs[0].insert(vec![0x1f, 0x01], vec![0x02]);
+        unknown.outputs[1].insert(vec![0x1f], vec![]);
+        assert_eq!(parse(&unknown).unwrap(), unknown);
+
+        // a network transaction, not a PSBT
+        assert!(Psbt::from_bytes(&hex::decode(BIP174_EXTRACTED).unwrap()).is_err());
+
+        // missing outputs
+        let mut missing = psbt.clone();
+        missing.outputs.pop();
+        assert!(parse(&missing).is_err());
+
+        // inputs and outputs without an unsigned transaction
+        let mut missing = psbt.clone();
+        missing.global.clear();
+        assert!(parse(&missing).is_err());
+
+        // an unsigned transaction with script sigs, or in the witness format
+        let signed = Transaction::decode(&hex::decode(BIP174_EXTRACTED).unwrap()).unwrap();
+        let mut invalid = psbt.clone();
+        invalid
+            .global
+            .insert(vec![PSBT_GLOBAL_UNSIGNED_TX], signed.encode_legacy());
+        assert!(parse(&invalid).is_err());
+        let mut witness = psbt.unsigned_tx().unwrap().encode_legacy();
+        let lock_time = witness.split_off(witness.len() - 4);
+        witness.splice(4..4, [0x00, 0x01]);
+        witness.extend_from_slice(&[0x00, 0x00]);
+        witness.extend_from_slice(&lock_time);
+        invalid
+            .global
+            .insert(vec![PSBT_GLOBAL_UNSIGNED_TX], witness);
+        assert!(parse(&invalid).is_err());
+
+        // key data on a field keyed by its type alone, or a malformed
+        // public key
+        let keys = [
+            (None, PSBT_GLOBAL_UNSIGNED_TX, vec![0x00]),
+            (Some(0), PSBT_IN_NON_WITNESS_UTXO, vec![0x00]),
+            (Some(1), PSBT_IN_WITNESS_UTXO, vec![0x00]),
+            (Some(0), PSBT_IN_SIGHASH_TYPE, vec![0x00]),
+            (Some(0), PSBT_IN_REDEEM_SCRIPT, vec![0x00]),
+            (Some(1), PSBT_IN_WITNESS_SCRIPT, vec![0x00]),
+            (Some(0), PSBT_IN_FINAL_SCRIPTSIG, vec![0x00]),
+            (Some(1), PSBT_IN_FINAL_SCRIPTWITNESS, vec![0x00]),
+            (Some(0), PSBT_IN_PARTIAL_SIG, vec![0x02; 34]),
+            (Some(0), PSBT_IN_BIP32_DERIVATION, vec![0x02; 32]),
+        ];
+        for (input, ty, data) in keys {
+            let mut invalid = psbt.clone();
+            let map = match input {
+                None => &mut invalid.global,
+                Some(index) => &mut invalid.inputs[index],
+            };
+            let value = map.get(&vec![ty]).cloned().unwrap_or_default();
+            map.insert([vec![ty], data].concat(), value);
+            assert!(parse(&invalid).is_err(), "{:#04x}", ty);
+        }
+        for ty in [PSBT_OUT_REDEEM_SCRIPT, PSBT_OUT_WITNESS_SCRIPT] {
+            let mut invalid = psbt.clone();
+            invalid.outputs[0].insert(vec![ty, 0x00], vec![0x00]);
+            assert!(parse(&invalid).is_err());
+        }
+        let mut invalid = psbt.clone();
+        let (key, origin) = invalid.outputs[0].pop_first().unwrap();
+        invalid.outputs[0].insert(key[..33].to_vec(), origin);
+        assert!(parse(&invalid).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{
+    tagged_hash,
+    transaction::{sha256d, write_var_bytes, Transaction, TxOut},
+    BitcoinError,
+};
+use crate::hash::sha256;
+use horror::Result;
+
+/// Sign every input and output, the taproot default which is not appended
+/// to the signature.
+pub const SIGHASH_DEFAULT: u32 = 0x00;
+/// Sign every input and output.
+pub const SIGHASH_ALL: u32 = 0x01;
+/// Sign every input and no output.
+pub const SIGHASH_NONE: u32 = 0x02;
+/// Sign every input and the output with the same index.
+pub const SIGHASH_SINGLE: u32 = 0x03;
+/// Flag to sign only the current input.
+pub const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+/// Compute the signature hash of a legacy, pre-segwit input.
+///
+/// `script_code` is the script pubkey of the spent output, or the redeem
+/// script for P2SH outputs.
+pub fn legacy_sighash(
+    tx: &Transaction,
+    index: usize,
+    script_code: &[u8],
+    sighash_type: u32,
+) -> Result<[u8; 32]> {
+    if index >= tx.inputs.len() {
+        return Err(BitcoinError::InvalidInputIndex(index).into());
+    }
+
+    let base_type = sighash_type & 0x1f;
+    if base_type == SIGHASH_SINGLE && index >= tx.outputs.len() {
+        // the historical "SIGHASH_SINGLE bug", which signs the number one
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        return Ok(one);
+    }
+
+    let mut tx = tx.clone();
+    for (i, input) in tx.inputs.iter_mut().enumerate() {
+        input.witness.clear();
+        input.script_sig = if i == index {
+            script_code.to_vec()
+        } else {
+            Vec::new()
+        };
+        if i != index && (base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE) {
+            input.sequence = 0;
+        }
+    }
+
+    match base_type {
+        SIGHASH_NONE => tx.outputs.clear(),
+        SIGHASH_SINGLE => {
+            tx.outputs.truncate(index + 1);
+            for output in tx.outputs.iter_mut().take(index) {
+                *output = TxOut {
+                    value: u64::MAX,
+                    script_pubkey: Vec::new(),
+                };
+            }
+        }
+        _ => {}
+    }
+
+    if sighash_type & SIGHASH_ANYONECANPAY != 0 {
+        tx.inputs = vec![tx.inputs.swap_remove(index)];
+    }
+
+    let mut preimage = tx.encode_legacy();
+    preimage.extend_from_slice(&sighash_type.to_le_bytes());
+    Ok(sha256d(preimage))
+}
+
+/// Compute the BIP-143 signature hash of a segwit v0 input.
+///
+/// `script_code` is `76a914{hash}88ac` for P2WPKH outputs, or the witness
+/// script for P2WSH outputs, and `value` the amount of the spent output.
+pub fn segwit_v0_sighash(
+    tx: &Transaction,
+    index: usize,
+    script_code: &[u8],
+    value: u64,
+    sighash_type: u32,
+) -> Result<[u8; 32]> {
+    let input = tx
+        .inputs
+        .get(index)
+        .ok_or(BitcoinError::InvalidInputIndex(index))?;
+    let base_type = sighash_type & 0x1f;
+    let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+
+    let mut hash_prevouts = [0u8; 32];
+    if !anyone_can_pay {
+        let mut prevouts = Vec::new();
+        for input in &tx.inputs {
+            input.previous_output.encode(&mut prevouts);
+        }
+        hash_prevouts = sha256d(prevouts);
+    }
+
+    let mut hash_sequence = [0u8; 32];
+    if !anyone_can_pay && base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+        let sequences: Vec<u8> = tx
+            .inputs
+            .iter()
+            .flat_map(|input| input.sequence.to_le_bytes())
+            .collect();
+        hash_sequence = sha256d(sequences);
+    }
+
+    let mut hash_outputs = [0u8; 32];
+    if base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+        let mut outputs = Vec::new();
+        for output in &tx.outputs {
+            output.encode(&mut outputs);
+        }
+        hash_outputs = sha256d(outputs);
+    } else if base_type == SIGHASH_SINGLE && index < tx.outputs.len() {
+        let mut output = Vec::new();
+        tx.outputs[index].encode(&mut output);
+        hash_outputs = sha256d(output);
+    }
+
+    let mut preimage = Vec::new();
+    preimage.extend_from_slice(&tx.version.to_le_bytes());
+    preimage.extend_from_slice(&hash_prevouts);
+    preimage.extend_from_slice(&hash_sequence);
+    input.previous_output.encode(&mut preimage);
+    write_var_bytes(&mut preimage, script_code);
+    preimage.extend_from_slice(&value.to_le_bytes());
+    preimage.extend_from_slice(&input.sequence.to_le_bytes());
+    preimage.extend_from_slice(&hash_outputs);
+    preimage.extend_from_slice(&tx.lock_time.to_le_bytes());
+    preimage.extend_from_slice(&sighash_type.to_le_bytes());
+    Ok(sha256d(preimage))
+}
+
+/// Compute the BIP-341 signature hash of a taproot key path spend.
+///
+/// `prevouts` are the outputs spent by every input of the transaction, in
+/// order, since the signature commits to all their amounts and scripts.
+pub fn taproot_key_spend_sighash(
+    tx: &Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+    sighash_type: u32,
+) -> Result<[u8; 32]> {
+    let input = tx
+        .inputs
+        .get(index)
+        .ok_or(BitcoinError::InvalidInputIndex(index))?;
+    if prevouts.len() != tx.inputs.len() {
+        return Err(BitcoinError::MissingUtxo(index).into());
+    }
+    let output_type = match sighash_type & 0x03 {
+        SIGHASH_DEFAULT => SIGHASH_ALL,
+        output_type => output_type,
+    };
+    let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+    if !matches!(sighash_type, 0x00..=0x03 | 0x81..=0x83) {
+        return Err(BitcoinError::InvalidSighashType(sighash_type).into());
+    }
+    if output_type == SIGHASH_SINGLE && index >= tx.outputs.len() {
+        return Err(BitcoinError::InvalidSighashType(sighash_type).into());
+    }
+
+    // epoch 0
+    let mut message = vec![0x00, sighash_type as u8];
+    message.extend_from_slice(&tx.version.to_le_bytes());
+    message.extend_from_slice(&tx.lock_time.to_le_bytes());
+
+    if !anyone_can_pay {
+        let mut outpoints = Vec::new();
+        let mut amounts = Vec::new();
+        let mut scripts = Vec::new();
+        let mut sequences = Vec::new();
+        for (input, prevout) in tx.inputs.iter().zip(prevouts) {
+            input.previous_output.encode(&mut outpoints);
+            amounts.extend_from_slice(&prevout.value.to_le_bytes());
+            write_var_bytes(&mut scripts, &prevout.script_pubkey);
+            sequences.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        message.extend_from_slice(&sha256(outpoints));
+        message.extend_from_slice(&sha256(amounts));
+        message.extend_from_slice(&sha256(scripts));
+        message.extend_from_slice(&sha256(sequences));
+    }
+
+    if output_type == SIGHASH_ALL {
+        let mut outputs = Vec::new();
+        for output in &tx.outputs {
+            output.encode(&mut outputs);
+        }
+        message.extend_from_slice(&sha256(outputs));
+    }
+
+    // key path spend without annex
+    message.push(0x00);
+
+    if anyone_can_pay {
+        let prevout = &prevouts[index];
+        input.previous_output.encode(&mut message);
+        message.extend_from_slice(&prevout.value.to_le_bytes());
+        write_var_bytes(&mut message, &prevout.script_pubkey);
+        message.extend_from_slice(&input.sequence.to_le_bytes());
+    } else {
+        message.extend_from_slice(&(index as u32).to_le_bytes());
+    }
+
+    if output_type == SIGHASH_SINGLE {
+        let mut output = Vec::new();
+        tx.outputs[index].encode(&mut output);
+        message.extend_from_slice(&sha256(output));
+    }
+
+    Ok(tagged_hash("TapSighash", &message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segwit_v0_sighash() {
+        // https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki#native-p2wpkh
+        let raw = hex::decode("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        let tx = Transaction::decode(&raw).unwrap();
+        let script_code =
+            hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap();
+        let sighash = segwit_v0_sighash(&tx, 1, &script_code, 600_000_000, SIGHASH_ALL).unwrap();
+        assert_eq!(
+            hex::encode(sighash),
+            "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::BitcoinError;
+use crate::hash::sha256;
+use horror::Result;
+
+/// Compute the double SHA-256 hash of the given data.
+pub(crate) fn sha256d<T: AsRef<[u8]>>(data: T) -> [u8; 32] {
+    sha256(sha256(data))
+}
+
+/// Append a Bitcoin variable length integer.
+pub(crate) fn write_compact_size(out: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+/// Append the given bytes prefixed with their length.
+pub(crate) fn write_var_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_compact_size(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// A cursor over serialized Bitcoin data.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(BitcoinError::Truncated)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_remaining(&mut self) -> &'a [u8] {
+        let bytes = &self.data[self.position..];
+        self.position = self.data.len();
+        bytes
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into()?))
+    }
+
+    pub(crate) fn read_compact_size(&mut self) -> Result<u64> {
+        Ok(match self.read_u8()? {
+            0xfd => u16::from_le_bytes(self.read_bytes(2)?.try_into()?) as u64,
+            0xfe => self.read_u32()? as u64,
+            0xff => self.read_u64()?,
+            n => n as u64,
+        })
+    }
+
+    pub(crate) fn read_var_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_compact_size()?;
+        self.read_bytes(usize::try_from(len)?)
+    }
+}
+
+/// A reference to an output of a previous transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct OutPoint {
+    /// The id of the transaction, in internal byte order.
+    pub txid: [u8; 32],
+    pub vout: u32,
+}
+
+impl OutPoint {
+    pub(crate) fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.txid);
+        out.extend_from_slice(&self.vout.to_le_bytes());
+    }
+}
+
+/// A transaction input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxIn {
+    pub previous_output: OutPoint,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    pub witness: Vec<Vec<u8>>,
+}
+
+impl Default for TxIn {
+    fn default() -> Self {
+        Self {
+            previous_output: OutPoint::default(),
+            script_sig: Vec::new(),
+            sequence: 0xffff_ffff,
+            witness: Vec::new(),
+        }
+    }
+}
+
+/// A transaction output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxOut {
+    /// The value of the output, in satoshis.
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+impl TxOut {
+    pub(crate) fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.value.to_le_bytes());
+        write_var_bytes(out, &self.script_pubkey);
+    }
+
+    pub(crate) fn decode(reader: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            value: reader.read_u64()?,
+            script_pubkey: reader.read_var_bytes()?.to_vec(),
+        })
+    }
+}
+
+/// A Bitcoin transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub version: i32,
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+    pub lock_time: u32,
+}
+
+impl Transaction {
+    /// Serialize the transaction, with the BIP-144 witness data if any
+    /// input has a witness.
+    pub fn encode(&self) -> Vec<u8> {
+        let segwit = self.inputs.iter().any(|input| !input.witness.is_empty());
+        self.encode_with(segwit)
+    }
+
+    /// Serialize the transaction without witness data.
+    pub fn encode_legacy(&self) -> Vec<u8> {
+        self.encode_with(false)
+    }
+
+    fn encode_with(&self, segwit: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.version.to_le_bytes());
+        if segwit {
+            out.extend_from_slice(&[0x00, 0x01]);
+        }
+        write_compact_size(&mut out, self.inputs.len() as u64);
+        for input in &self.inputs {
+            input.previous_output.encode(&mut out);
+            write_var_bytes(&mut out, &input.script_sig);
+            out.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        write_compact_size(&mut out, self.outputs.len() as u64);
+        for output in &self.outputs {
+            output.encode(&mut out);
+        }
+        if segwit {
+            for input in &self.inputs {
+                write_compact_size(&mut out, input.witness.len() as u64);
+                for item in &input.witness {
+                    write_var_bytes(&mut out, item);
+                }
+            }
+        }
+        out.extend_from_slice(&self.lock_time.to_le_bytes());
+        out
+    }
+
+    /// Parse a serialized transaction, with or without witness data.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        let version = reader.read_u32()? as i32;
+
+        let mut count = reader.read_compact_size()?;
+        let segwit = count == 0;
+        if segwit {
+            if reader.read_u8()? != 0x01 {
+                return Err(BitcoinError::InvalidTransaction.into());
+            }
+            count = reader.read_compact_size()?;
+        }
+
+        let mut inputs = Vec::new();
+        for _ in 0..count {
+            inputs.push(TxIn {
+                previous_output: OutPoint {
+                    txid: reader.read_bytes(32)?.try_into()?,
+                    vout: reader.read_u32()?,
+                },
+                script_sig: reader.read_var_bytes()?.to_vec(),
+                sequence: reader.read_u32()?,
+                witness: Vec::new(),
+            });
+        }
+
+        let mut outputs = Vec::new();
+        for _ in 0..reader.read_compact_size()? {
+            outputs.push(TxOut::decode(&mut reader)?);
+        }
+
+        if segwit {
+            for input in inputs.iter_mut() {
+                for _ in 0..reader.read_compact_size()? {
+                    input.witness.push(reader.read_var_bytes()?.to_vec());
+                }
+            }
+        }
+
+        let lock_time = reader.read_u32()?;
+        if !reader.is_empty() {
+            return Err(BitcoinError::InvalidTransaction.into());
+        }
+
+        Ok(Self {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+        })
+    }
+
+    /// Return the id of the transaction, in internal byte order. It is
+    /// displayed reversed, see [`Transaction::txid_hex`].
+    pub fn txid(&self) -> [u8; 32] {
+        sha256d(self.encode_legacy())
+    }
+
+    /// Return the id of the transaction as displayed by block explorers.
+    pub fn txid_hex(&self) -> String {
+        let mut txid = self.txid();
+        txid.reverse();
+        hex::encode(txid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction() {
+        // the unsigned transaction of the BIP-143 native P2WPKH example
+        let raw = hex::decode("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        let mut tx = Transaction::decode(&raw).unwrap();
+        assert_eq!(tx.version, 1);
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.inputs[0].sequence, 0xffffffee);
+        assert_eq!(tx.outputs[0].value, 112340000);
+        assert_eq!(tx.lock_time, 17);
+        assert_eq!(tx.encode(), raw);
+
+        tx.inputs[1].witness = vec![vec![0x30; 71], vec![0x02; 33]];
+        let segwit = tx.encode();
+        assert_eq!(&segwit[4..6], &[0x00, 0x01]);
+        assert_eq!(Transaction::decode(&segwit).unwrap(), tx);
+        assert_eq!(sha256d(&raw), tx.txid());
+    }
+}
@@ -0,0 +1,171 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{AddressType, BitcoinAddress, BitcoinError, Network};
+use horror::{Error, Result};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use laron_crypto::PrivateKey;
+
+/// A private key in Wallet Import Format, the Base58Check encoding of the
+/// key used to paste it between wallets.
+///
+/// The format records the network of the key and whether its public key is
+/// compressed, which changes the hash of P2PKH addresses. Regtest keys use
+/// the testnet prefix, so they are decoded as [`Network::Testnet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wif {
+    key: PrivateKey,
+    network: Network,
+    compressed: bool,
+}
+
+impl Wif {
+    /// Create a WIF key from the given private key.
+    pub fn new(key: PrivateKey, network: Network, compressed: bool) -> Self {
+        Self {
+            key,
+            network,
+            compressed,
+        }
+    }
+
+    /// Decode the given WIF string.
+    pub fn from_wif(s: &str) -> Result<Self> {
+        let data = bs58::decode(s.trim())
+            .with_check(None)
+            .into_vec()
+            .map_err(|_| BitcoinError::InvalidWif)?;
+
+        let network = match data.first() {
+            Some(0x80) => Network::Mainnet,
+            Some(0xef) => Network::Testnet,
+            _ => return Err(BitcoinError::InvalidWif.into()),
+        };
+        let compressed = match data.len() {
+            33 => false,
+            34 if data[33] == 0x01 => true,
+            _ => return Err(BitcoinError::InvalidWif.into()),
+        };
+
+        Ok(Self {
+            key: PrivateKey::from_bytes(&data[1..33])?,
+            network,
+            compressed,
+        })
+    }
+
+    /// Encode the key in Wallet Import Format.
+    pub fn to_wif(&self) -> String {
+        let mut data = vec![self.network.wif_prefix()];
+        data.extend_from_slice(&self.key.to_bytes()[..]);
+        if self.compressed {
+            data.push(0x01);
+        }
+        bs58::encode(data).with_check().into_string()
+    }
+
+    /// Return the private key.
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.key
+    }
+
+    /// Return the network of the key.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Return whether the public key is used in its compressed form.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Return the serialized public key, in the form given by the WIF flag.
+    pub fn public_key_bytes(&self) -> Result<Vec<u8>> {
+        let key = k256::PublicKey::from_sec1_bytes(&self.key.public_key().to_bytes()[..])?;
+        Ok(key.to_encoded_point(self.compressed).as_bytes().to_vec())
+    }
+
+    /// Compute the address of the given type for the key. Uncompressed
+    /// keys only have P2PKH addresses.
+    pub fn address(&self, address_type: AddressType) -> Result<BitcoinAddress> {
+        let key = k256::PublicKey::from_sec1_bytes(&self.key.public_key().to_bytes()[..])?;
+        match (self.compressed, address_type) {
+            (true, _) => BitcoinAddress::from_point(&key, address_type, self.network),
+            (false, AddressType::P2pkh) => {
+                Ok(BitcoinAddress::uncompressed_p2pkh(&key, self.network))
+            }
+            (false, _) => Err(BitcoinError::UncompressedKey.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Wif {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_wif())
+    }
+}
+
+impl std::str::FromStr for Wif {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_wif(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wif() {
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let key = PrivateKey::from_bytes(&secret).unwrap();
+
+        let wif = Wif::new(key.clone(), Network::Mainnet, true);
+        assert_eq!(
+            wif.to_string(),
+            "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn"
+        );
+        assert_eq!(
+            wif.address(AddressType::P2pkh).unwrap().to_string(),
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"
+        );
+
+        let wif: Wif = "5HpHagT65TZzG1PH3CSu63k8DbpvD8s5ip4nEB3kEsreAnchuDf"
+            .parse()
+            .unwrap();
+        assert_eq!(wif.private_key(), &key);
+        assert_eq!(wif.network(), Network::Mainnet);
+        assert!(!wif.is_compressed());
+        assert_eq!(wif.public_key_bytes().unwrap().len(), 65);
+        assert_eq!(
+            wif.address(AddressType::P2pkh).unwrap().to_string(),
+            "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm"
+        );
+        assert!(wif.address(AddressType::P2wpkh).is_err());
+
+        let wif = Wif::new(key, Network::Testnet, true);
+        assert!(wif.to_string().starts_with('c'));
+        assert_eq!(Wif::from_wif(&wif.to_string()).unwrap(), wif);
+
+        assert!(Wif::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWm").is_err());
+    }
+}
@@ -0,0 +1,322 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{AddressType, BitcoinAddress, BitcoinError, Network};
+use crate::{
+    bips::{bip32::ExtendedKey, ChildNumber, DerivationPath},
+    hash::hash160,
+};
+use hmac::{Hmac, Mac};
+use horror::Result;
+use k256::{
+    elliptic_curve::{sec1::ToEncodedPoint, PrimeField},
+    ProjectivePoint, Scalar,
+};
+use sha2::Sha512;
+
+/// The SLIP-132 version of a serialized extended public key, telling
+/// wallets which addresses to derive from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyVersion {
+    /// `xpub` or `tpub`, for P2PKH and P2TR addresses.
+    Xpub,
+    /// `ypub` or `upub`, for P2SH-P2WPKH addresses.
+    Ypub,
+    /// `zpub` or `vpub`, for P2WPKH addresses.
+    Zpub,
+}
+
+impl KeyVersion {
+    /// Return the version used for the given address type.
+    pub fn from_address_type(address_type: AddressType) -> Self {
+        match address_type {
+            AddressType::P2pkh | AddressType::P2tr => KeyVersion::Xpub,
+            AddressType::P2shP2wpkh => KeyVersion::Ypub,
+            AddressType::P2wpkh => KeyVersion::Zpub,
+        }
+    }
+
+    /// Return the version bytes on the given network.
+    pub fn to_bytes(&self, network: Network) -> [u8; 4] {
+        let version: u32 = match (self, network) {
+            (KeyVersion::Xpub, Network::Mainnet) => 0x0488_b21e,
+            (KeyVersion::Ypub, Network::Mainnet) => 0x049d_7cb2,
+            (KeyVersion::Zpub, Network::Mainnet) => 0x04b2_4746,
+            (KeyVersion::Xpub, _) => 0x0435_87cf,
+            (KeyVersion::Ypub, _) => 0x044a_5262,
+            (KeyVersion::Zpub, _) => 0x045f_1c27,
+        };
+        version.to_be_bytes()
+    }
+
+    /// Return the version and network of the given version bytes. Testnet
+    /// and regtest share their versions, which are read as testnet.
+    pub fn from_bytes(bytes: [u8; 4]) -> Option<(Self, Network)> {
+        [Network::Mainnet, Network::Testnet]
+            .into_iter()
+            .flat_map(|network| {
+                [KeyVersion::Xpub, KeyVersion::Ypub, KeyVersion::Zpub]
+                    .into_iter()
+                    .map(move |version| (version, network))
+            })
+            .find(|(version, network)| version.to_bytes(*network) == bytes)
+    }
+}
+
+/// A BIP-32 extended public key, which derives the public keys of the
+/// non-hardened children of an account without its private key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+    key: k256::PublicKey,
+    chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: ChildNumber,
+}
+
+impl ExtendedPublicKey {
+    /// Create an extended public key from its parts.
+    pub fn new(
+        public_key: &[u8],
+        chain_code: [u8; 32],
+        parent_fingerprint: [u8; 4],
+        child_number: ChildNumber,
+        depth: u8,
+    ) -> Result<Self> {
+        Ok(Self {
+            key: k256::PublicKey::from_sec1_bytes(public_key)
+                .map_err(|_| BitcoinError::InvalidExtendedKey)?,
+            chain_code,
+            depth,
+            parent_fingerprint,
+            child_number,
+        })
+    }
+
+    /// Return the public part of the given extended key.
+    pub fn from_extended_key(key: &ExtendedKey) -> Result<Self> {
+        Ok(Self {
+            key: k256::PublicKey::from_sec1_bytes(&key.public_key().to_bytes()[..])?,
+            chain_code: *key.chain_code(),
+            depth: key.depth(),
+            parent_fingerprint: key.parent_fingerprint().try_into()?,
+            child_number: *key.child_number(),
+        })
+    }
+
+    /// Derive the public key of the given non-hardened child.
+    pub fn derive_child(&self, child_number: ChildNumber) -> Result<Self> {
+        if child_number.is_hardened() {
+            return Err(BitcoinError::HardenedDerivation.into());
+        }
+        let depth = self
+            .depth
+            .checked_add(1)
+            .ok_or(BitcoinError::InvalidExtendedKey)?;
+
+        let mut hmac: Hmac<Sha512> = Hmac::new_from_slice(&self.chain_code)?;
+        hmac.update(&self.to_bytes());
+        hmac.update(&child_number.to_bytes());
+        let result = hmac.finalize().into_bytes();
+        let (tweak, chain_code) = result.split_at(32);
+
+        let tweak: [u8; 32] = tweak.try_into()?;
+        let tweak = Option::<Scalar>::from(Scalar::from_repr(tweak.into()))
+            .ok_or(BitcoinError::InvalidExtendedKey)?;
+        let point = self.key.to_projective() + ProjectivePoint::GENERATOR * tweak;
+
+        Ok(Self {
+            key: k256::PublicKey::from_affine(point.to_affine())?,
+            chain_code: chain_code.try_into()?,
+            depth,
+            parent_fingerprint: self.fingerprint(),
+            child_number,
+        })
+    }
+
+    /// Derive the public key at the given path, relative to this key.
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self> {
+        let mut key = self.clone();
+        for child_number in path.iter() {
+            key = key.derive_child(*child_number)?;
+        }
+        Ok(key)
+    }
+
+    /// Return the compressed public key.
+    pub fn to_bytes(&self) -> [u8; 33] {
+        let mut bytes = [0u8; 33];
+        bytes.copy_from_slice(self.key.to_encoded_point(true).as_bytes());
+        bytes
+    }
+
+    /// Return the fingerprint of this key.
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let hash = hash160(self.to_bytes());
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Return the fingerprint of the parent key.
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    /// Return the child number of this key.
+    pub fn child_number(&self) -> ChildNumber {
+        self.child_number
+    }
+
+    /// Return the depth of this key.
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Return the chain code of this key.
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    /// Return the address of the given type for this key.
+    pub fn address(&self, address_type: AddressType, network: Network) -> Result<BitcoinAddress> {
+        BitcoinAddress::from_point(&self.key, address_type, network)
+    }
+
+    /// Serialize this key with the given version.
+    pub fn encode(&self, version: KeyVersion, network: Network) -> String {
+        let mut data = Vec::with_capacity(78);
+        data.extend_from_slice(&version.to_bytes(network));
+        data.push(self.depth);
+        data.extend_from_slice(&self.parent_fingerprint);
+        data.extend_from_slice(&self.child_number.to_bytes());
+        data.extend_from_slice(&self.chain_code);
+        data.extend_from_slice(&self.to_bytes());
+        bs58::encode(data).with_check().into_string()
+    }
+
+    /// Parse a serialized key, returning it with its version and network.
+    pub fn decode(encoded: &str) -> Result<(Self, KeyVersion, Network)> {
+        let data = bs58::decode(encoded).with_check(None).into_vec()?;
+        if data.len() != 78 {
+            return Err(BitcoinError::InvalidExtendedKey.into());
+        }
+
+        let (version, network) = KeyVersion::from_bytes(data[0..4].try_into()?)
+            .ok_or(BitcoinError::InvalidExtendedKey)?;
+        let child_number = u32::from_be_bytes(data[9..13].try_into()?);
+        let key = Self {
+            key: k256::PublicKey::from_sec1_bytes(&data[45..78])
+                .map_err(|_| BitcoinError::InvalidExtendedKey)?,
+            chain_code: data[13..45].try_into()?,
+            depth: data[4],
+            parent_fingerprint: data[5..9].try_into()?,
+            child_number: ChildNumber::from(child_number),
+        };
+        Ok((key, version, network))
+    }
+}
+
+/// Displays the key as a mainnet `xpub`.
+impl std::fmt::Display for ExtendedPublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.encode(KeyVersion::Xpub, Network::Mainnet))
+    }
+}
+
+impl std::str::FromStr for ExtendedPublicKey {
+    type Err = horror::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self::decode(s)?.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bips::{
+        bip39::{Mnemonic, Seed},
+        wordlists::Language,
+    };
+
+    #[test]
+    fn test_bip32_vector() {
+        // https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki#test-vector-1
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let root = ExtendedKey::new_master(&Seed::from(seed)).unwrap();
+
+        let xpub = ExtendedPublicKey::from_extended_key(&root).unwrap();
+        assert_eq!(xpub.to_string(), "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8");
+
+        let child = root.derive_child(ChildNumber::hardened(0)).unwrap();
+        let xpub = ExtendedPublicKey::from_extended_key(&child).unwrap();
+        assert_eq!(xpub.to_string(), "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw");
+
+        // public derivation matches private derivation
+        let grandchild = child.derive_child(ChildNumber::normal(1)).unwrap();
+        assert_eq!(
+            xpub.derive_child(ChildNumber::normal(1)).unwrap(),
+            ExtendedPublicKey::from_extended_key(&grandchild).unwrap()
+        );
+        assert!(xpub.derive_child(ChildNumber::hardened(1)).is_err());
+
+        let encoded = xpub.to_string();
+        assert_eq!(encoded.parse::<ExtendedPublicKey>().unwrap(), xpub);
+    }
+
+    #[test]
+    fn test_slip132() {
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            Language::English,
+        )
+        .unwrap();
+        let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+
+        let account = root
+            .derive_path(&AddressType::P2wpkh.account_path(Network::Mainnet, 0))
+            .unwrap();
+        let xpub = ExtendedPublicKey::from_extended_key(&account).unwrap();
+        let zpub = xpub.encode(KeyVersion::Zpub, Network::Mainnet);
+        assert_eq!(zpub, "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs");
+
+        let (decoded, version, network) = ExtendedPublicKey::decode(&zpub).unwrap();
+        assert_eq!(decoded, xpub);
+        assert_eq!(version, KeyVersion::Zpub);
+        assert_eq!(network, Network::Mainnet);
+
+        let address = decoded
+            .derive_path(&DerivationPath::parse("m/0/0").unwrap())
+            .unwrap()
+            .address(AddressType::P2wpkh, network)
+            .unwrap();
+        assert_eq!(
+            address.to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+
+        let account = root
+            .derive_path(&AddressType::P2shP2wpkh.account_path(Network::Mainnet, 0))
+            .unwrap();
+        let xpub = ExtendedPublicKey::from_extended_key(&account).unwrap();
+        assert_eq!(
+            xpub.encode(KeyVersion::Ypub, Network::Mainnet),
+            "ypub6Ww3ibxVfGzLrAH1PNcjyAWenMTbbAosGNB6VvmSEgytSER9azLDWCxoJwW7Ke7icmizBMXrzBx9979FfaHxHcrArf3zbeJJJUZPf663zsP"
+        );
+    }
+}
@@ -0,0 +1,279 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Account Discovery
+//!
+//! When a wallet is restored from a mnemonic, the accounts and addresses it
+//! used are unknown. [`AccountDiscovery`] scans them as described by
+//! [BIP-44](https://github.com/bitcoin/bips/blob/master/bip-0044.mediawiki#account-discovery):
+//! the addresses `m/44'/60'/account'/0/index` of each account are derived in
+//! order until `gap_limit` consecutive addresses are unused, and accounts are
+//! scanned in order until one has no used address.
+//!
+//! Whether an address was used is answered by an [`ActivityOracle`]. A
+//! [`Provider`] looks at the nonce and balance of the address, and any
+//! closure returning a `Result<bool>` can be used as well.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::address::Address;
+//! use laron_wallet::bips::bip39::Mnemonic;
+//! use laron_wallet::bips::wordlists::Language;
+//! use laron_wallet::discovery::AccountDiscovery;
+//!
+//! let mnemonic = Mnemonic::from_phrase(
+//!     "test test test test test test test test test test test junk",
+//!     Language::English,
+//! ).unwrap();
+//! let used: Address = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266".parse().unwrap();
+//!
+//! let oracle = |address: &Address| Ok(*address == used);
+//! let accounts = AccountDiscovery::new().discover_mnemonic(&mnemonic, "", &oracle).unwrap();
+//! assert_eq!(accounts.len(), 1);
+//! assert_eq!(accounts[0].addresses[0].address, used);
+//! ```
+
+use crate::{
+    address::Address,
+    bips::{bip32::ExtendedKey, bip39::Mnemonic, ChildNumber, DerivationPath},
+    rpc::{BlockNumber, Provider, Transport},
+};
+use horror::Result;
+
+/// The default number of consecutive unused addresses ending a scan.
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// Tells whether an address was used.
+pub trait ActivityOracle {
+    /// Return whether the given address has any activity.
+    fn is_used(&self, address: &Address) -> Result<bool>;
+}
+
+impl<T: Transport> ActivityOracle for Provider<T> {
+    /// An address is used if it sent a transaction or holds a balance.
+    fn is_used(&self, address: &Address) -> Result<bool> {
+        if self.get_transaction_count(address, BlockNumber::Latest)? > 0 {
+            return Ok(true);
+        }
+        Ok(!self.get_balance(address, BlockNumber::Latest)?.is_zero())
+    }
+}
+
+impl<F: Fn(&Address) -> Result<bool>> ActivityOracle for F {
+    fn is_used(&self, address: &Address) -> Result<bool> {
+        self(address)
+    }
+}
+
+/// A used address found by the discovery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsedAddress {
+    pub index: u32,
+    pub path: DerivationPath,
+    pub address: Address,
+}
+
+/// A used account found by the discovery, with its used addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredAccount {
+    pub index: u32,
+    pub addresses: Vec<UsedAddress>,
+}
+
+/// Scans the BIP-44 accounts of a root key for used addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDiscovery {
+    coin_type: u32,
+    gap_limit: u32,
+}
+
+impl Default for AccountDiscovery {
+    fn default() -> Self {
+        Self {
+            coin_type: 60,
+            gap_limit: DEFAULT_GAP_LIMIT,
+        }
+    }
+}
+
+impl AccountDiscovery {
+    /// Create a discovery for Ethereum accounts with the default gap limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of consecutive unused addresses ending the scan of an
+    /// account.
+    pub fn gap_limit(mut self, gap_limit: u32) -> Self {
+        self.gap_limit = gap_limit.max(1);
+        self
+    }
+
+    /// Set the SLIP-44 coin type, 60 for Ethereum.
+    pub fn coin_type(mut self, coin_type: u32) -> Self {
+        self.coin_type = coin_type;
+        self
+    }
+
+    /// Return the used accounts of the given root key.
+    pub fn discover<O: ActivityOracle + ?Sized>(
+        &self,
+        root: &ExtendedKey,
+        oracle: &O,
+    ) -> Result<Vec<DiscoveredAccount>> {
+        let mut accounts = Vec::new();
+        for index in 0.. {
+            let account = self.scan_account(root, index, oracle)?;
+            if account.addresses.is_empty() {
+                break;
+            }
+            accounts.push(account);
+        }
+        Ok(accounts)
+    }
+
+    /// Return the used accounts of the given mnemonic.
+    pub fn discover_mnemonic<O: ActivityOracle + ?Sized>(
+        &self,
+        mnemonic: &Mnemonic,
+        passphrase: &str,
+        oracle: &O,
+    ) -> Result<Vec<DiscoveredAccount>> {
+        let root = ExtendedKey::new_master(&mnemonic.to_seed(passphrase))?;
+        self.discover(&root, oracle)
+    }
+
+    /// Return the used addresses of the given account.
+    pub fn scan_account<O: ActivityOracle + ?Sized>(
+        &self,
+        root: &ExtendedKey,
+        account: u32,
+        oracle: &O,
+    ) -> Result<DiscoveredAccount> {
+        let path = DerivationPath::parse(&format!("m/44'/{}'/{}'/0", self.coin_type, account))?;
+        let key = root.derive_path(&path)?;
+
+        let mut addresses = Vec::new();
+        let mut gap = 0;
+        let mut index = 0;
+        while gap < self.gap_limit {
+            let child = ChildNumber::normal(index);
+            let address = Address::from_public_key(key.derive_child(child)?.public_key())?;
+            if oracle.is_used(&address)? {
+                addresses.push(UsedAddress {
+                    index,
+                    path: path.child(child),
+                    address,
+                });
+                gap = 0;
+            } else {
+                gap += 1;
+            }
+            index += 1;
+        }
+
+        Ok(DiscoveredAccount {
+            index: account,
+            addresses,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bips::wordlists::Language, rpc::MockTransport};
+    use serde_json::json;
+    use std::cell::Cell;
+
+    fn root() -> ExtendedKey {
+        let mnemonic = Mnemonic::from_phrase(
+            "test test test test test test test test test test test junk",
+            Language::English,
+        )
+        .unwrap();
+        ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap()
+    }
+
+    fn address(root: &ExtendedKey, path: &str) -> Address {
+        let key = root
+            .derive_path(&DerivationPath::parse(path).unwrap())
+            .unwrap();
+        Address::from_public_key(key.public_key()).unwrap()
+    }
+
+    #[test]
+    fn test_discovery() {
+        let root = root();
+        let used = [
+            address(&root, "m/44'/60'/0'/0/0"),
+            address(&root, "m/44'/60'/0'/0/3"),
+            address(&root, "m/44'/60'/1'/0/2"),
+            // beyond the gap limit of account 1
+            address(&root, "m/44'/60'/1'/0/6"),
+            // account 3 is unreachable because account 2 is unused
+            address(&root, "m/44'/60'/3'/0/0"),
+        ];
+        let queries = Cell::new(0);
+        let oracle = |address: &Address| {
+            queries.set(queries.get() + 1);
+            Ok(used.contains(address))
+        };
+
+        let accounts = AccountDiscovery::new()
+            .gap_limit(3)
+            .discover(&root, &oracle)
+            .unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(
+            accounts[0]
+                .addresses
+                .iter()
+                .map(|used| used.index)
+                .collect::<Vec<_>>(),
+            vec![0, 3]
+        );
+        assert_eq!(accounts[1].index, 1);
+        assert_eq!(accounts[1].addresses.len(), 1);
+        assert_eq!(accounts[1].addresses[0].path.string(), "m/44'/60'/1'/0/2");
+        assert_eq!(accounts[1].addresses[0].address, used[2]);
+        // 7 addresses for account 0, 6 for account 1 and 3 for account 2
+        assert_eq!(queries.get(), 16);
+    }
+
+    #[test]
+    fn test_provider_oracle() {
+        let transport = MockTransport::new();
+        transport.push("eth_getTransactionCount", "0x1");
+        transport.push("eth_getTransactionCount", "0x0");
+        transport.push("eth_getBalance", "0x10");
+        transport.push("eth_getTransactionCount", "0x0");
+        transport.push("eth_getBalance", "0x0");
+        let provider = Provider::new(transport);
+        let address = Address::new([0x11; 20]);
+
+        assert!(provider.is_used(&address).unwrap());
+        assert!(provider.is_used(&address).unwrap());
+        assert!(!provider.is_used(&address).unwrap());
+        assert_eq!(
+            provider.transport().requests()[0]["params"],
+            json!([address, "latest"])
+        );
+    }
+}
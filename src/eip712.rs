@@ -0,0 +1,540 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # EIP-712 Typed Structured Data
+//!
+//! This module implements hashing and signing of typed structured data as
+//! defined in [EIP-712](https://eips.ethereum.org/EIPS/eip-712). The input is
+//! the JSON shape used by `eth_signTypedData_v4`:
+//! `{ types, primaryType, domain, message }`.
+//!
+//! Nested structs, fixed and dynamic arrays, and the dynamic `bytes` and
+//! `string` types are supported. Missing struct values are encoded as zero,
+//! matching MetaMask's `eth-sig-util` V4 behaviour.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::eip712::TypedData;
+//!
+//! let data: TypedData = r#"{
+//!     "types": {
+//!         "EIP712Domain": [{ "name": "name", "type": "string" }],
+//!         "Greeting": [{ "name": "text", "type": "string" }]
+//!     },
+//!     "primaryType": "Greeting",
+//!     "domain": { "name": "Example" },
+//!     "message": { "text": "Hello" }
+//! }"#.parse().unwrap();
+//!
+//! let hash = data.sign_hash().unwrap();
+//! ```
+
+use crate::{hash::keccak256, signature::Signature};
+use horror::{Error, Result};
+use laron_crypto::PrivateKey;
+use primitive_types::U256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Name of the domain type.
+pub const EIP712_DOMAIN: &str = "EIP712Domain";
+
+/// Error returned when encoding typed data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Eip712Error {
+    /// The type is neither a known atomic type nor defined in `types`.
+    UndefinedType(String),
+    /// A non-struct field has no value.
+    MissingValue(String),
+    /// The value cannot be encoded as the given type.
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for Eip712Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Eip712Error::UndefinedType(ty) => write!(f, "Undefined type: {}", ty),
+            Eip712Error::MissingValue(name) => write!(f, "Missing value for field: {}", name),
+            Eip712Error::InvalidValue(ty) => write!(f, "Invalid value for type: {}", ty),
+        }
+    }
+}
+
+impl std::error::Error for Eip712Error {}
+
+/// A single member of a struct type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypedDataField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+impl TypedDataField {
+    /// Create a new field with the given name and type.
+    pub fn new(name: &str, ty: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ty: ty.to_string(),
+        }
+    }
+}
+
+/// Typed structured data, as passed to `eth_signTypedData_v4`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    pub types: BTreeMap<String, Vec<TypedDataField>>,
+    pub primary_type: String,
+    pub domain: Value,
+    pub message: Value,
+}
+
+impl TypedData {
+    /// Parse typed data from its JSON representation.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Return the canonical type encoding of the given struct type, followed
+    /// by the encodings of all referenced struct types sorted by name.
+    pub fn encode_type(&self, primary_type: &str) -> Result<String> {
+        let mut deps = BTreeSet::new();
+        self.find_dependencies(primary_type, &mut deps)?;
+        deps.remove(primary_type);
+
+        let mut result = String::new();
+        for name in std::iter::once(primary_type).chain(deps.iter().map(String::as_str)) {
+            let fields = self.fields(name)?;
+            let members = fields
+                .iter()
+                .map(|field| format!("{} {}", field.ty, field.name))
+                .collect::<Vec<_>>()
+                .join(",");
+            result.push_str(&format!("{}({})", name, members));
+        }
+
+        Ok(result)
+    }
+
+    /// Return the hash of the type encoding of the given struct type.
+    pub fn type_hash(&self, primary_type: &str) -> Result<[u8; 32]> {
+        Ok(keccak256(self.encode_type(primary_type)?))
+    }
+
+    /// Return the encoding of the given struct value, starting with its type hash.
+    pub fn encode_data(&self, primary_type: &str, value: &Value) -> Result<Vec<u8>> {
+        let mut result = self.type_hash(primary_type)?.to_vec();
+
+        for field in self.fields(primary_type)? {
+            let value = value.get(&field.name).unwrap_or(&Value::Null);
+            if value.is_null() && !self.types.contains_key(base_type(&field.ty)) {
+                return Err(Eip712Error::MissingValue(field.name.clone()).into());
+            }
+            result.extend_from_slice(&self.encode_field(&field.ty, value)?);
+        }
+
+        Ok(result)
+    }
+
+    /// Return `hashStruct` of the given struct value.
+    pub fn hash_struct(&self, primary_type: &str, value: &Value) -> Result<[u8; 32]> {
+        Ok(keccak256(self.encode_data(primary_type, value)?))
+    }
+
+    /// Return the domain separator.
+    pub fn domain_separator(&self) -> Result<[u8; 32]> {
+        if self.types.contains_key(EIP712_DOMAIN) {
+            return self.hash_struct(EIP712_DOMAIN, &self.domain);
+        }
+
+        // Infer the domain type from the fields present, in the order given
+        // by the specification.
+        let mut data = self.clone();
+        let fields = [
+            ("name", "string"),
+            ("version", "string"),
+            ("chainId", "uint256"),
+            ("verifyingContract", "address"),
+            ("salt", "bytes32"),
+        ]
+        .iter()
+        .filter(|(name, _)| self.domain.get(name).is_some())
+        .map(|(name, ty)| TypedDataField::new(name, ty))
+        .collect();
+        data.types.insert(EIP712_DOMAIN.to_string(), fields);
+        data.hash_struct(EIP712_DOMAIN, &self.domain)
+    }
+
+    /// Return the hash to be signed:
+    /// `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(message))`.
+    pub fn sign_hash(&self) -> Result<[u8; 32]> {
+        let mut data = vec![0x19, 0x01];
+        data.extend_from_slice(&self.domain_separator()?);
+        if self.primary_type != EIP712_DOMAIN {
+            data.extend_from_slice(&self.hash_struct(&self.primary_type, &self.message)?);
+        }
+        Ok(keccak256(data))
+    }
+
+    /// Sign the typed data with the given private key.
+    pub fn sign(&self, key: &PrivateKey) -> Result<Signature> {
+        Signature::sign_hash(key, &self.sign_hash()?)
+    }
+
+    fn fields(&self, name: &str) -> Result<&Vec<TypedDataField>> {
+        self.types
+            .get(name)
+            .ok_or_else(|| Eip712Error::UndefinedType(name.to_string()).into())
+    }
+
+    fn find_dependencies(&self, ty: &str, deps: &mut BTreeSet<String>) -> Result<()> {
+        let ty = base_type(ty);
+        if deps.contains(ty) || !self.types.contains_key(ty) {
+            return Ok(());
+        }
+
+        deps.insert(ty.to_string());
+        for field in self.fields(ty)? {
+            self.find_dependencies(&field.ty, deps)?;
+        }
+
+        Ok(())
+    }
+
+    fn encode_field(&self, ty: &str, value: &Value) -> Result<[u8; 32]> {
+        if self.types.contains_key(ty) {
+            if value.is_null() {
+                return Ok([0u8; 32]);
+            }
+            return self.hash_struct(ty, value);
+        }
+
+        if let Some((inner, len)) = array_type(ty) {
+            let items = value
+                .as_array()
+                .ok_or_else(|| Eip712Error::InvalidValue(ty.to_string()))?;
+            if len.is_some_and(|len| len != items.len()) {
+                return Err(Eip712Error::InvalidValue(ty.to_string()).into());
+            }
+
+            let mut data = Vec::with_capacity(items.len() * 32);
+            for item in items {
+                data.extend_from_slice(&self.encode_field(inner, item)?);
+            }
+            return Ok(keccak256(data));
+        }
+
+        encode_atomic(ty, value)
+    }
+}
+
+impl std::str::FromStr for TypedData {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_json(s)
+    }
+}
+
+/// Sign the given typed data with the given private key.
+pub fn sign_typed_data(key: &PrivateKey, data: &TypedData) -> Result<Signature> {
+    data.sign(key)
+}
+
+/// Split an array type into its element type and its length, if fixed.
+fn array_type(ty: &str) -> Option<(&str, Option<usize>)> {
+    let inner = ty.strip_suffix(']')?;
+    let open = inner.rfind('[')?;
+    let len = &inner[open + 1..];
+    let len = if len.is_empty() {
+        None
+    } else {
+        Some(len.parse().ok()?)
+    };
+    Some((&inner[..open], len))
+}
+
+/// Strip every array suffix from the given type.
+fn base_type(ty: &str) -> &str {
+    ty.find('[').map_or(ty, |i| &ty[..i])
+}
+
+fn invalid(ty: &str) -> Error {
+    Eip712Error::InvalidValue(ty.to_string()).into()
+}
+
+fn encode_atomic(ty: &str, value: &Value) -> Result<[u8; 32]> {
+    let mut word = [0u8; 32];
+
+    match ty {
+        "bool" => {
+            let value = value.as_bool().ok_or_else(|| invalid(ty))?;
+            word[31] = value as u8;
+        }
+        "address" => {
+            let bytes = decode_hex(value).ok_or_else(|| invalid(ty))?;
+            if bytes.len() != 20 {
+                return Err(invalid(ty));
+            }
+            word[12..].copy_from_slice(&bytes);
+        }
+        "string" => {
+            let value = value.as_str().ok_or_else(|| invalid(ty))?;
+            word = keccak256(value);
+        }
+        "bytes" => {
+            let bytes = decode_hex(value)
+                .or_else(|| value.as_str().map(|s| s.as_bytes().to_vec()))
+                .ok_or_else(|| invalid(ty))?;
+            word = keccak256(bytes);
+        }
+        _ if ty.starts_with("bytes") => {
+            let size = ty[5..].parse::<usize>().map_err(|_| invalid(ty))?;
+            let bytes = decode_hex(value).ok_or_else(|| invalid(ty))?;
+            if size == 0 || size > 32 || bytes.len() > size {
+                return Err(invalid(ty));
+            }
+            word[..bytes.len()].copy_from_slice(&bytes);
+        }
+        _ if ty.starts_with("uint") || ty.starts_with("int") => {
+            let signed = ty.starts_with("int");
+            let bits = match &ty.trim_start_matches('u')[3..] {
+                "" => 256,
+                width => width
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|bits| (8..=256).contains(bits) && bits.is_multiple_of(8))
+                    .ok_or_else(|| Eip712Error::UndefinedType(ty.to_string()))?,
+            };
+            let (negative, abs) = parse_integer(value).ok_or_else(|| invalid(ty))?;
+            if negative && !signed {
+                return Err(invalid(ty));
+            }
+
+            let limit = if signed { bits - 1 } else { bits };
+            let max = if limit == 256 {
+                U256::MAX
+            } else {
+                (U256::one() << limit) - 1
+            };
+            if abs > max + U256::from(negative as u8) {
+                return Err(invalid(ty));
+            }
+
+            let value = if negative {
+                (!abs).overflowing_add(U256::one()).0
+            } else {
+                abs
+            };
+            value.to_big_endian(&mut word);
+        }
+        _ => return Err(Eip712Error::UndefinedType(ty.to_string()).into()),
+    }
+
+    Ok(word)
+}
+
+fn decode_hex(value: &Value) -> Option<Vec<u8>> {
+    hex::decode(value.as_str()?.strip_prefix("0x")?).ok()
+}
+
+/// Parse an integer given either as a JSON number, a decimal string or a
+/// `0x` prefixed hex string. Return its sign and absolute value.
+pub(crate) fn parse_integer(value: &Value) -> Option<(bool, U256)> {
+    match value {
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => Some((false, U256::from(n))),
+            (None, Some(n)) => Some((true, U256::from(n.unsigned_abs()))),
+            _ => None,
+        },
+        Value::String(s) => {
+            let (negative, s) = match s.strip_prefix('-') {
+                Some(s) => (true, s),
+                None => (false, s.as_str()),
+            };
+            let abs = match s.strip_prefix("0x") {
+                Some(hex) => U256::from_str_radix(hex, 16).ok()?,
+                None => U256::from_dec_str(s).ok()?,
+            };
+            Some((negative && !abs.is_zero(), abs))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cow() -> PrivateKey {
+        PrivateKey::from_bytes(&keccak256("cow")).unwrap()
+    }
+
+    const MAIL: &str = r#"{
+        "types": {
+            "EIP712Domain": [
+                { "name": "name", "type": "string" },
+                { "name": "version", "type": "string" },
+                { "name": "chainId", "type": "uint256" },
+                { "name": "verifyingContract", "type": "address" }
+            ],
+            "Person": [
+                { "name": "name", "type": "string" },
+                { "name": "wallet", "type": "address" }
+            ],
+            "Mail": [
+                { "name": "from", "type": "Person" },
+                { "name": "to", "type": "Person" },
+                { "name": "contents", "type": "string" }
+            ]
+        },
+        "primaryType": "Mail",
+        "domain": {
+            "name": "Ether Mail",
+            "version": "1",
+            "chainId": 1,
+            "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+        },
+        "message": {
+            "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+            "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+            "contents": "Hello, Bob!"
+        }
+    }"#;
+
+    #[test]
+    fn test_mail() {
+        let data: TypedData = MAIL.parse().unwrap();
+        assert_eq!(
+            data.encode_type("Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert_eq!(
+            hex::encode(data.type_hash("Mail").unwrap()),
+            "a0cedeb2dc280ba39b857546d74f5549c3a1d7bdc2dd96bf881f76108e23dac2"
+        );
+        assert_eq!(
+            hex::encode(data.hash_struct("Mail", &data.message).unwrap()),
+            "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+        );
+        assert_eq!(
+            hex::encode(data.domain_separator().unwrap()),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        assert_eq!(
+            hex::encode(data.sign_hash().unwrap()),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+
+        let signature = sign_typed_data(&cow(), &data).unwrap();
+        assert_eq!(
+            signature.to_string(),
+            "0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c"
+        );
+    }
+
+    #[test]
+    fn test_arrays() {
+        // eth-sig-util `signTypedData` V4 example with nested arrays.
+        let data: TypedData = r#"{
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" }
+                ],
+                "Person": [
+                    { "name": "name", "type": "string" },
+                    { "name": "wallets", "type": "address[]" }
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person[]" },
+                    { "name": "contents", "type": "string" }
+                ],
+                "Group": [
+                    { "name": "name", "type": "string" },
+                    { "name": "members", "type": "Person[]" }
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+            },
+            "message": {
+                "from": {
+                    "name": "Cow",
+                    "wallets": [
+                        "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826",
+                        "0xDeaDbeefdEAdbeefdEadbEEFdeadbeEFdEaDbeeF"
+                    ]
+                },
+                "to": [{
+                    "name": "Bob",
+                    "wallets": [
+                        "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB",
+                        "0xB0BdaBea57B0BDABeA57b0bdABEA57b0BDabEa57",
+                        "0xB0B0b0b0b0b0B000000000000000000000000000"
+                    ]
+                }],
+                "contents": "Hello, Bob!"
+            }
+        }"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            data.encode_type("Group").unwrap(),
+            "Group(string name,Person[] members)Person(string name,address[] wallets)"
+        );
+        assert_eq!(
+            hex::encode(data.sign_hash().unwrap()),
+            "a85c2e2b118698e88db68a8105b794a8cc7cec074e89ef991cb4f5f533819cc2"
+        );
+
+        let signature = sign_typed_data(&cow(), &data).unwrap();
+        assert_eq!(
+            signature.to_string(),
+            "0x65cbd956f2fae28a601bebc9b906cea0191744bd4c4247bcd27cd08f8eb6b71c78efdf7a31dc9abee78f492292721f362d296cf86b4538e07b51303b67f749061b"
+        );
+    }
+
+    #[test]
+    fn test_atomic() {
+        let word = encode_atomic("int8", &Value::from(-1)).unwrap();
+        assert_eq!(word, [0xff; 32]);
+        assert!(encode_atomic("int8", &Value::from(-129)).is_err());
+        assert!(encode_atomic("uint8", &Value::from(256)).is_err());
+        assert!(encode_atomic("uint256", &Value::from("-1")).is_err());
+        for ty in ["intfoo", "uint7", "int0", "uint264", "int-8"] {
+            let err = encode_atomic(ty, &Value::from(1)).unwrap_err();
+            assert_eq!(err.to_string(), format!("Undefined type: {}", ty));
+        }
+
+        let word = encode_atomic("bytes4", &Value::from("0xdeadbeef")).unwrap();
+        assert_eq!(&word[..4], &[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(&word[4..], &[0u8; 28]);
+    }
+}
@@ -0,0 +1,419 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Fee Estimation
+//!
+//! A [`FeeOracle`] suggests the fees of EIP-1559 transactions from the
+//! priority fees paid in recent blocks, as returned by `eth_feeHistory`.
+//! The [`FeeSpeed`] presets pick a lower or higher percentile of those fees.
+//! The oracle also estimates gas limits with a safety margin and can cap the
+//! total fee a transaction may pay.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::fee::{FeeOracle, FeeSpeed};
+//! use laron_wallet::rpc::{MockTransport, Provider};
+//! use laron_wallet::U256;
+//! use serde_json::json;
+//!
+//! let transport = MockTransport::new();
+//! transport.push("eth_feeHistory", json!({
+//!     "oldestBlock": "0x1",
+//!     "baseFeePerGas": ["0x3b9aca00", "0x3b9aca00"],
+//!     "gasUsedRatio": [0.5],
+//!     "reward": [["0x1", "0x2", "0x3"]]
+//! }));
+//! let provider = Provider::new(transport);
+//!
+//! let fees = FeeOracle::new().fees(&provider, FeeSpeed::Standard).unwrap();
+//! assert_eq!(fees.max_priority_fee_per_gas, U256::from(2));
+//! assert_eq!(fees.max_fee_per_gas, U256::from(2_000_000_002u64));
+//! ```
+
+use crate::{
+    address::Address,
+    rpc::{BlockNumber, Bytes, CallRequest, Provider, Transport},
+    transaction::Eip1559Transaction,
+};
+use horror::Result;
+use primitive_types::U256;
+
+/// The reward percentiles requested from `eth_feeHistory`, one per
+/// [`FeeSpeed`].
+const REWARD_PERCENTILES: [f64; 3] = [10.0, 50.0, 90.0];
+
+/// Error returned when suggesting fees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeeError {
+    /// The node returned no fee history.
+    EmptyHistory,
+    /// The fee cap does not cover the base fee of the next block.
+    CapExceeded { required: U256, cap: U256 },
+    /// The fees or gas returned by the node overflow 256 bits.
+    Overflow,
+}
+
+impl std::fmt::Display for FeeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FeeError::EmptyHistory => write!(f, "Empty fee history"),
+            FeeError::CapExceeded { required, cap } => {
+                write!(
+                    f,
+                    "Fee cap exceeded: requires {} wei, cap is {} wei",
+                    required, cap
+                )
+            }
+            FeeError::Overflow => write!(f, "Fee or gas overflow"),
+        }
+    }
+}
+
+impl std::error::Error for FeeError {}
+
+/// How quickly a transaction should be included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FeeSpeed {
+    /// Pay the 10th percentile of recent priority fees.
+    Slow,
+    /// Pay the median of recent priority fees.
+    #[default]
+    Standard,
+    /// Pay the 90th percentile of recent priority fees.
+    Fast,
+}
+
+impl FeeSpeed {
+    /// Return the percentile of recent priority fees paid at this speed.
+    pub fn percentile(&self) -> f64 {
+        REWARD_PERCENTILES[self.index()]
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            FeeSpeed::Slow => 0,
+            FeeSpeed::Standard => 1,
+            FeeSpeed::Fast => 2,
+        }
+    }
+}
+
+/// The suggested fees of an EIP-1559 transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fees {
+    /// The expected base fee of the next block.
+    pub base_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+}
+
+/// Suggests gas limits and fees from the recent history of the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeOracle {
+    block_count: u64,
+    base_fee_multiplier: u64,
+    gas_margin: u64,
+    max_total_fee: Option<U256>,
+}
+
+impl Default for FeeOracle {
+    fn default() -> Self {
+        Self {
+            block_count: 10,
+            base_fee_multiplier: 2,
+            gas_margin: 20,
+            max_total_fee: None,
+        }
+    }
+}
+
+impl FeeOracle {
+    /// Create an oracle looking at the last 10 blocks, allowing the base
+    /// fee to double and adding a 20% margin to gas estimates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of blocks the priority fees are taken from.
+    pub fn block_count(mut self, block_count: u64) -> Self {
+        self.block_count = block_count.max(1);
+        self
+    }
+
+    /// Set by how much the base fee may grow before the transaction is
+    /// priced out. The default of 2 covers six full blocks in a row.
+    pub fn base_fee_multiplier(mut self, multiplier: u64) -> Self {
+        self.base_fee_multiplier = multiplier.max(1);
+        self
+    }
+
+    /// Set the margin added to gas estimates, in percent.
+    pub fn gas_margin(mut self, percent: u64) -> Self {
+        self.gas_margin = percent;
+        self
+    }
+
+    /// Set the most a transaction may pay in total, in wei.
+    pub fn max_total_fee(mut self, wei: U256) -> Self {
+        self.max_total_fee = Some(wei);
+        self
+    }
+
+    /// Suggest fees for a transaction to be included at the given speed.
+    pub fn fees<T: Transport>(&self, provider: &Provider<T>, speed: FeeSpeed) -> Result<Fees> {
+        let history =
+            provider.fee_history(self.block_count, BlockNumber::Latest, &REWARD_PERCENTILES)?;
+        // The last base fee is the one of the next block.
+        let base_fee_per_gas = *history
+            .base_fee_per_gas
+            .last()
+            .ok_or(FeeError::EmptyHistory)?;
+
+        let mut rewards = history
+            .reward
+            .iter()
+            .filter_map(|reward| reward.get(speed.index()).copied())
+            .collect::<Vec<_>>();
+        rewards.sort_unstable();
+        let max_priority_fee_per_gas = rewards.get(rewards.len() / 2).copied().unwrap_or_default();
+
+        let max_fee_per_gas = base_fee_per_gas
+            .checked_mul(self.base_fee_multiplier.into())
+            .and_then(|fee| fee.checked_add(max_priority_fee_per_gas))
+            .ok_or(FeeError::Overflow)?;
+        Ok(Fees {
+            base_fee_per_gas,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        })
+    }
+
+    /// Estimate the gas used by the given call, with the safety margin.
+    pub fn gas_limit<T: Transport>(
+        &self,
+        provider: &Provider<T>,
+        call: &CallRequest,
+    ) -> Result<U256> {
+        let estimate = provider.estimate_gas(call)?;
+        estimate
+            .checked_mul(self.gas_margin.into())
+            .and_then(|margin| estimate.checked_add(margin / 100))
+            .ok_or_else(|| FeeError::Overflow.into())
+    }
+
+    /// Lower the given fees so that a transaction using the given gas limit
+    /// pays at most the total fee cap. Fail if the capped fees cannot cover
+    /// the base fee.
+    pub fn apply_cap(&self, gas_limit: U256, fees: Fees) -> Result<Fees> {
+        let cap = match self.max_total_fee {
+            Some(cap) if !gas_limit.is_zero() => cap,
+            _ => return Ok(fees),
+        };
+
+        let max_fee_per_gas = fees.max_fee_per_gas.min(cap / gas_limit);
+        if max_fee_per_gas < fees.base_fee_per_gas {
+            return Err(FeeError::CapExceeded {
+                required: fees.base_fee_per_gas.saturating_mul(gas_limit),
+                cap,
+            }
+            .into());
+        }
+
+        Ok(Fees {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: fees.max_priority_fee_per_gas.min(max_fee_per_gas),
+            ..fees
+        })
+    }
+
+    /// Fill in the gas limit and fees of the given transaction sent from
+    /// the given address.
+    pub fn fill_transaction<T: Transport>(
+        &self,
+        provider: &Provider<T>,
+        from: &Address,
+        transaction: &mut Eip1559Transaction,
+        speed: FeeSpeed,
+    ) -> Result<()> {
+        let call = CallRequest {
+            from: Some(*from),
+            to: transaction.to,
+            value: Some(transaction.value),
+            data: Some(Bytes::from(transaction.data.as_slice())),
+            ..Default::default()
+        };
+        let gas_limit = self.gas_limit(provider, &call)?;
+        let fees = self.apply_cap(gas_limit, self.fees(provider, speed)?)?;
+
+        transaction.gas_limit = gas_limit;
+        transaction.max_fee_per_gas = fees.max_fee_per_gas;
+        transaction.max_priority_fee_per_gas = fees.max_priority_fee_per_gas;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::MockTransport;
+    use serde_json::json;
+
+    const GWEI: u64 = 1_000_000_000;
+
+    fn provider() -> Provider<MockTransport> {
+        let transport = MockTransport::new();
+        transport.on("eth_feeHistory", |_| {
+            Ok(json!({
+                "oldestBlock": "0x100",
+                "baseFeePerGas": ["0x2540be400", "0x2540be400", "0x2540be400", "0x2e90edd00"],
+                "gasUsedRatio": [0.4, 0.6, 0.9],
+                "reward": [
+                    ["0x3b9aca00", "0x77359400", "0x12a05f200"],
+                    ["0x0", "0x3b9aca00", "0x2540be400"],
+                    ["0x5f5e100", "0xb2d05e00", "0x1dcd65000"]
+                ]
+            }))
+        });
+        transport.on("eth_estimateGas", |_| Ok(json!("0xc350")));
+        Provider::new(transport)
+    }
+
+    #[test]
+    fn test_fees() {
+        let provider = provider();
+        let oracle = FeeOracle::new();
+
+        let slow = oracle.fees(&provider, FeeSpeed::Slow).unwrap();
+        let standard = oracle.fees(&provider, FeeSpeed::Standard).unwrap();
+        let fast = oracle.fees(&provider, FeeSpeed::Fast).unwrap();
+
+        assert_eq!(standard.base_fee_per_gas, U256::from(12_500_000_000u64));
+        assert_eq!(slow.max_priority_fee_per_gas, U256::from(100_000_000u64));
+        assert_eq!(standard.max_priority_fee_per_gas, U256::from(2 * GWEI));
+        assert_eq!(fast.max_priority_fee_per_gas, U256::from(8 * GWEI));
+        assert_eq!(standard.max_fee_per_gas, U256::from(27 * GWEI));
+        assert!(slow.max_fee_per_gas < standard.max_fee_per_gas);
+        assert!(standard.max_fee_per_gas < fast.max_fee_per_gas);
+
+        let request = &provider.transport().requests_for("eth_feeHistory")[0];
+        assert_eq!(
+            request["params"],
+            json!(["0xa", "latest", [10.0, 50.0, 90.0]])
+        );
+    }
+
+    #[test]
+    fn test_gas_limit() {
+        let provider = provider();
+        let call = CallRequest::default();
+
+        let gas = FeeOracle::new().gas_limit(&provider, &call).unwrap();
+        assert_eq!(gas, U256::from(60000));
+
+        let gas = FeeOracle::new()
+            .gas_margin(0)
+            .gas_limit(&provider, &call)
+            .unwrap();
+        assert_eq!(gas, U256::from(50000));
+
+        let transport = MockTransport::new();
+        transport.push("eth_estimateGas", U256::MAX);
+        let provider = Provider::new(transport);
+        assert!(FeeOracle::new().gas_limit(&provider, &call).is_err());
+    }
+
+    #[test]
+    fn test_fee_overflow() {
+        let transport = MockTransport::new();
+        transport.push(
+            "eth_feeHistory",
+            json!({
+                "oldestBlock": "0x100",
+                "baseFeePerGas": [U256::MAX / 2, U256::MAX / 2],
+                "gasUsedRatio": [1.0],
+                "reward": [["0x2", "0x2", "0x2"]]
+            }),
+        );
+        let provider = Provider::new(transport);
+        let err = FeeOracle::new()
+            .fees(&provider, FeeSpeed::Standard)
+            .unwrap_err();
+        assert_eq!(err.to_string(), FeeError::Overflow.to_string());
+
+        let fees = Fees {
+            base_fee_per_gas: U256::MAX,
+            max_fee_per_gas: U256::MAX,
+            max_priority_fee_per_gas: U256::zero(),
+        };
+        let oracle = FeeOracle::new().max_total_fee(U256::from(GWEI));
+        assert!(oracle.apply_cap(U256::from(21000), fees).is_err());
+    }
+
+    #[test]
+    fn test_cap() {
+        let fees = Fees {
+            base_fee_per_gas: U256::from(10 * GWEI),
+            max_fee_per_gas: U256::from(25 * GWEI),
+            max_priority_fee_per_gas: U256::from(5 * GWEI),
+        };
+        let gas_limit = U256::from(21000);
+
+        let oracle = FeeOracle::new().max_total_fee(U256::from(21000 * 12 * GWEI));
+        let capped = oracle.apply_cap(gas_limit, fees).unwrap();
+        assert_eq!(capped.max_fee_per_gas, U256::from(12 * GWEI));
+        assert_eq!(capped.max_priority_fee_per_gas, U256::from(5 * GWEI));
+
+        let oracle = FeeOracle::new().max_total_fee(U256::from(21000 * 3 * GWEI));
+        let capped = oracle.apply_cap(gas_limit, fees);
+        assert!(capped.is_err());
+
+        let uncapped = FeeOracle::new().apply_cap(gas_limit, fees).unwrap();
+        assert_eq!(uncapped, fees);
+    }
+
+    #[test]
+    fn test_fill_transaction() {
+        let provider = provider();
+        let mut transaction = Eip1559Transaction {
+            chain_id: 1,
+            to: Some(Address::new([0x22; 20])),
+            value: U256::from(1),
+            ..Default::default()
+        };
+
+        FeeOracle::new()
+            .max_total_fee(U256::from(60000 * 20 * GWEI))
+            .fill_transaction(
+                &provider,
+                &Address::new([0x11; 20]),
+                &mut transaction,
+                FeeSpeed::Fast,
+            )
+            .unwrap();
+        assert_eq!(transaction.gas_limit, U256::from(60000));
+        assert_eq!(transaction.max_fee_per_gas, U256::from(20 * GWEI));
+        assert_eq!(transaction.max_priority_fee_per_gas, U256::from(8 * GWEI));
+
+        let request = &provider.transport().requests_for("eth_estimateGas")[0];
+        assert_eq!(
+            request["params"][0]["from"],
+            "0x1111111111111111111111111111111111111111"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Hash functions used across the Ethereum and Bitcoin specific modules.
+
+use ripemd::Ripemd160;
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+/// Compute the Keccak-256 hash of the given data.
+pub fn keccak256<T: AsRef<[u8]>>(data: T) -> [u8; 32] {
+    Keccak256::digest(data.as_ref()).into()
+}
+
+/// Compute the SHA-256 hash of the given data.
+pub fn sha256<T: AsRef<[u8]>>(data: T) -> [u8; 32] {
+    Sha256::digest(data.as_ref()).into()
+}
+
+/// Compute the RIPEMD-160 hash of the SHA-256 hash of the given data, as
+/// used by Bitcoin for public key hashes.
+pub fn hash160<T: AsRef<[u8]>>(data: T) -> [u8; 20] {
+    Ripemd160::digest(sha256(data)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keccak256() {
+        assert_eq!(
+            hex::encode(keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
+    #[test]
+    fn test_hash160() {
+        assert_eq!(
+            hex::encode(hash160(b"")),
+            "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb"
+        );
+    }
+}
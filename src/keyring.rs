@@ -0,0 +1,445 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Keyring
+//!
+//! A [`Keyring`] holds every account of a user in one place: accounts
+//! derived from HD roots, such as the seed phrase of MetaMask or a hardware
+//! wallet, next to raw private keys imported on their own. Every account
+//! has a label, is looked up by its address, and signs through an
+//! [`AccountSigner`] whatever its origin.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::bips::bip39::Mnemonic;
+//! use laron_wallet::bips::wordlists::Language;
+//! use laron_wallet::bips::DerivationScheme;
+//! use laron_wallet::keyring::Keyring;
+//! use laron_wallet::signer::Signer;
+//!
+//! let mnemonic = Mnemonic::from_phrase(
+//!     "test test test test test test test test test test test junk",
+//!     Language::English,
+//! ).unwrap();
+//!
+//! let mut keyring = Keyring::new();
+//! let root = keyring.add_mnemonic("Main", &mnemonic, "").unwrap();
+//! let hd = keyring.derive_account(root, &DerivationScheme::MetaMask.path(0), "Savings").unwrap();
+//! let imported = keyring
+//!     .import_hex("Hot", "0x4646464646464646464646464646464646464646464646464646464646464646")
+//!     .unwrap();
+//!
+//! assert_eq!(keyring.accounts().len(), 2);
+//! for address in [hd, imported] {
+//!     let signer = keyring.signer(&address).unwrap();
+//!     assert_eq!(signer.address(), address);
+//! }
+//! ```
+
+use crate::{
+    address::Address,
+    bips::{bip32::ExtendedKey, bip39::Mnemonic, DerivationPath},
+    signature::Signature,
+    signer::{HdAccount, Signer, Wallet},
+};
+use horror::Result;
+use laron_crypto::PrivateKey;
+
+/// Error returned by the keyring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyringError {
+    /// A root with the same fingerprint is already in the keyring.
+    DuplicateRoot([u8; 4]),
+    /// No root has the given fingerprint.
+    UnknownRoot([u8; 4]),
+    /// An account with the same address is already in the keyring.
+    DuplicateAccount(Address),
+    /// No account has the given address.
+    UnknownAccount(Address),
+}
+
+impl std::fmt::Display for KeyringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            KeyringError::DuplicateRoot(fingerprint) => {
+                write!(f, "Duplicate root: {}", hex::encode(fingerprint))
+            }
+            KeyringError::UnknownRoot(fingerprint) => {
+                write!(f, "Unknown root: {}", hex::encode(fingerprint))
+            }
+            KeyringError::DuplicateAccount(address) => write!(f, "Duplicate account: {}", address),
+            KeyringError::UnknownAccount(address) => write!(f, "Unknown account: {}", address),
+        }
+    }
+}
+
+impl std::error::Error for KeyringError {}
+
+/// An HD root of the keyring, identified by its BIP32 fingerprint.
+#[derive(Clone)]
+pub struct KeyringRoot {
+    label: String,
+    mnemonic: Mnemonic,
+    passphrase: String,
+    key: ExtendedKey,
+}
+
+impl KeyringRoot {
+    /// Return the label of the root.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Return the mnemonic of the root.
+    pub fn mnemonic(&self) -> &Mnemonic {
+        &self.mnemonic
+    }
+
+    /// Return the BIP39 passphrase of the root.
+    pub fn passphrase(&self) -> &str {
+        &self.passphrase
+    }
+
+    /// Return the fingerprint of the master key.
+    pub fn fingerprint(&self) -> [u8; 4] {
+        self.key.fingerprint()
+    }
+
+    /// Return the master key.
+    pub fn extended_key(&self) -> &ExtendedKey {
+        &self.key
+    }
+}
+
+impl std::fmt::Debug for KeyringRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyringRoot")
+            .field("label", &self.label)
+            .field("fingerprint", &hex::encode(self.fingerprint()))
+            .field("mnemonic", &"<redacted>")
+            .field("passphrase", &"<redacted>")
+            .finish_non_exhaustive()
+    }
+}
+
+/// The signer of a keyring account, either derived from an HD root or
+/// imported.
+#[derive(Debug, Clone)]
+pub enum AccountSigner {
+    Hd { root: [u8; 4], account: HdAccount },
+    Imported(Wallet),
+}
+
+impl AccountSigner {
+    /// Return the fingerprint of the root of an HD account.
+    pub fn root(&self) -> Option<[u8; 4]> {
+        match self {
+            AccountSigner::Hd { root, .. } => Some(*root),
+            AccountSigner::Imported(_) => None,
+        }
+    }
+
+    /// Return the derivation path of an HD account.
+    pub fn path(&self) -> Option<&DerivationPath> {
+        match self {
+            AccountSigner::Hd { account, .. } => Some(account.path()),
+            AccountSigner::Imported(_) => None,
+        }
+    }
+
+    /// Return the private key of the account.
+    pub fn private_key(&self) -> &PrivateKey {
+        match self {
+            AccountSigner::Hd { account, .. } => account.extended_key().private_key(),
+            AccountSigner::Imported(wallet) => wallet.private_key(),
+        }
+    }
+}
+
+impl Signer for AccountSigner {
+    fn address(&self) -> Address {
+        match self {
+            AccountSigner::Hd { account, .. } => account.address(),
+            AccountSigner::Imported(wallet) => wallet.address(),
+        }
+    }
+
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<Signature> {
+        match self {
+            AccountSigner::Hd { account, .. } => account.sign_hash(hash),
+            AccountSigner::Imported(wallet) => wallet.sign_hash(hash),
+        }
+    }
+}
+
+/// A labeled account of the keyring.
+#[derive(Debug, Clone)]
+pub struct KeyringAccount {
+    label: String,
+    signer: AccountSigner,
+}
+
+impl KeyringAccount {
+    /// Return the label of the account.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Return the address of the account.
+    pub fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    /// Return the signer of the account.
+    pub fn signer(&self) -> &AccountSigner {
+        &self.signer
+    }
+
+    /// Return whether the account was imported rather than derived.
+    pub fn is_imported(&self) -> bool {
+        matches!(self.signer, AccountSigner::Imported(_))
+    }
+}
+
+/// A collection of HD roots and accounts, derived or imported.
+///
+/// Accounts are kept in the order they were added, and an address can only
+/// be added once, whichever way its key was obtained.
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    roots: Vec<KeyringRoot>,
+    accounts: Vec<KeyringAccount>,
+}
+
+impl Keyring {
+    /// Create an empty keyring.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the HD root of the given mnemonic, and return its fingerprint.
+    /// No account is derived until [`Keyring::derive_account`] is called.
+    pub fn add_mnemonic(
+        &mut self,
+        label: &str,
+        mnemonic: &Mnemonic,
+        passphrase: &str,
+    ) -> Result<[u8; 4]> {
+        let key = ExtendedKey::new_master(&mnemonic.to_seed(passphrase))?;
+        let fingerprint = key.fingerprint();
+        if self.root(fingerprint).is_some() {
+            return Err(KeyringError::DuplicateRoot(fingerprint).into());
+        }
+
+        self.roots.push(KeyringRoot {
+            label: label.to_string(),
+            mnemonic: mnemonic.clone(),
+            passphrase: passphrase.to_string(),
+            key,
+        });
+        Ok(fingerprint)
+    }
+
+    /// Return the HD roots of the keyring.
+    pub fn roots(&self) -> &[KeyringRoot] {
+        &self.roots
+    }
+
+    /// Return the HD root with the given fingerprint.
+    pub fn root(&self, fingerprint: [u8; 4]) -> Option<&KeyringRoot> {
+        self.roots
+            .iter()
+            .find(|root| root.fingerprint() == fingerprint)
+    }
+
+    /// Derive the account at the given path of an HD root, and return its
+    /// address.
+    pub fn derive_account(
+        &mut self,
+        root: [u8; 4],
+        path: &DerivationPath,
+        label: &str,
+    ) -> Result<Address> {
+        let key = &self.root(root).ok_or(KeyringError::UnknownRoot(root))?.key;
+        let account = HdAccount::new(key, path)?;
+        self.insert(label, AccountSigner::Hd { root, account })
+    }
+
+    /// Import the given private key, and return its address.
+    pub fn import_key(&mut self, label: &str, key: PrivateKey) -> Result<Address> {
+        self.insert(label, AccountSigner::Imported(Wallet::new(key)?))
+    }
+
+    /// Import a 32-byte private key encoded in hex, with or without the
+    /// `0x` prefix, as exported by MetaMask.
+    pub fn import_hex(&mut self, label: &str, key: &str) -> Result<Address> {
+        let key = key.trim();
+        let key = key.strip_prefix("0x").unwrap_or(key);
+        self.import_key(label, PrivateKey::from_bytes(&hex::decode(key)?)?)
+    }
+
+    fn insert(&mut self, label: &str, signer: AccountSigner) -> Result<Address> {
+        let address = signer.address();
+        if self.get(&address).is_some() {
+            return Err(KeyringError::DuplicateAccount(address).into());
+        }
+
+        self.accounts.push(KeyringAccount {
+            label: label.to_string(),
+            signer,
+        });
+        Ok(address)
+    }
+
+    /// Return the account with the given address.
+    pub fn get(&self, address: &Address) -> Option<&KeyringAccount> {
+        self.accounts
+            .iter()
+            .find(|account| account.address() == *address)
+    }
+
+    /// Return the signer of the account with the given address.
+    pub fn signer(&self, address: &Address) -> Option<&AccountSigner> {
+        self.get(address).map(KeyringAccount::signer)
+    }
+
+    /// Return every account, in the order they were added.
+    pub fn accounts(&self) -> &[KeyringAccount] {
+        &self.accounts
+    }
+
+    /// Return the address of every account.
+    pub fn addresses(&self) -> Vec<Address> {
+        self.accounts.iter().map(KeyringAccount::address).collect()
+    }
+
+    /// Change the label of the account with the given address.
+    pub fn set_label(&mut self, address: &Address, label: &str) -> Result<()> {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|account| account.address() == *address)
+            .ok_or(KeyringError::UnknownAccount(*address))?;
+        account.label = label.to_string();
+        Ok(())
+    }
+
+    /// Remove the account with the given address.
+    pub fn remove(&mut self, address: &Address) -> Result<KeyringAccount> {
+        let index = self
+            .accounts
+            .iter()
+            .position(|account| account.address() == *address)
+            .ok_or(KeyringError::UnknownAccount(*address))?;
+        Ok(self.accounts.remove(index))
+    }
+
+    /// Remove the HD root with the given fingerprint, along with every
+    /// account derived from it.
+    pub fn remove_root(&mut self, fingerprint: [u8; 4]) -> Result<KeyringRoot> {
+        let index = self
+            .roots
+            .iter()
+            .position(|root| root.fingerprint() == fingerprint)
+            .ok_or(KeyringError::UnknownRoot(fingerprint))?;
+        self.accounts
+            .retain(|account| account.signer.root() != Some(fingerprint));
+        Ok(self.roots.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bips::{wordlists::Language, DerivationScheme};
+
+    #[test]
+    fn test_keyring() {
+        let mnemonic = Mnemonic::from_phrase(
+            "test test test test test test test test test test test junk",
+            Language::English,
+        )
+        .unwrap();
+
+        let mut keyring = Keyring::new();
+        let root = keyring.add_mnemonic("Main", &mnemonic, "").unwrap();
+        assert!(keyring.add_mnemonic("Again", &mnemonic, "").is_err());
+        assert_eq!(keyring.root(root).unwrap().label(), "Main");
+
+        // the secrets of a root are not logged
+        let other = keyring.add_mnemonic("Other", &mnemonic, "secret").unwrap();
+        let debug = format!("{:?}", keyring.root(other).unwrap());
+        assert!(debug.contains(&hex::encode(other)));
+        assert!(!debug.contains("junk") && !debug.contains("secret"));
+        assert!(!format!("{:?}", keyring).contains("junk"));
+        keyring.remove_root(other).unwrap();
+
+        let first = keyring
+            .derive_account(root, &DerivationScheme::MetaMask.path(0), "First")
+            .unwrap();
+        assert_eq!(
+            first.to_string(),
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+        );
+        let second = keyring
+            .derive_account(root, &DerivationScheme::MetaMask.path(1), "Second")
+            .unwrap();
+        let imported = keyring
+            .import_hex(
+                "Imported",
+                "0x4646464646464646464646464646464646464646464646464646464646464646",
+            )
+            .unwrap();
+        assert_eq!(
+            imported.to_string(),
+            "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"
+        );
+        assert_eq!(keyring.addresses(), vec![first, second, imported]);
+
+        // the key of a derived account cannot be imported twice
+        let key = keyring.signer(&first).unwrap().private_key().clone();
+        assert!(keyring.import_key("Duplicate", key).is_err());
+
+        let account = keyring.get(&imported).unwrap();
+        assert!(account.is_imported());
+        assert_eq!(account.signer().path(), None);
+        assert_eq!(
+            keyring.signer(&second).unwrap().path(),
+            Some(&DerivationScheme::MetaMask.path(1))
+        );
+
+        let hash = [0xab; 32];
+        for account in keyring.accounts() {
+            let signature = account.signer().sign_hash(&hash).unwrap();
+            assert_eq!(signature.recover(&hash).unwrap(), account.address());
+        }
+
+        keyring.set_label(&imported, "Hot").unwrap();
+        assert_eq!(keyring.get(&imported).unwrap().label(), "Hot");
+        assert_eq!(keyring.remove(&second).unwrap().label(), "Second");
+        assert!(keyring.remove(&second).is_err());
+
+        keyring.remove_root(root).unwrap();
+        assert!(keyring.roots().is_empty());
+        assert_eq!(keyring.addresses(), vec![imported]);
+        assert!(keyring
+            .derive_account(root, &DerivationScheme::MetaMask.path(0), "First")
+            .is_err());
+    }
+}
@@ -25,11 +25,32 @@
 //! This is a library for generating and managing wallets. This library contains
 //! the following features:
 //! - BIP39 Mnemonic and Seed Generation
+//! - Weak Mnemonic Detection for Test Vectors, Published Phrases and Patterns
 //! - BIP32 HD Wallet Generation
+//! - BIP44 Account Discovery
+//! - MetaMask, Ledger Live and Legacy Ledger Derivation Schemes
+//! - Bitcoin P2PKH, P2SH-P2WPKH, P2WPKH and P2TR Addresses
+//! - SLIP-132 Extended Public Keys and BIP-380 Output Descriptors
+//! - PSBT Signing for Legacy, SegWit and Taproot Inputs
+//! - WIF Private Key Import and Export
+//! - Keyring Mixing HD Roots and Imported Private Keys
+//! - Password Encrypted Vault with scrypt or Argon2id
+//! - MetaMask Vault Import
+//! - Air-Gapped Signing with UR Encoded QR Codes
+//! - EIP-191 Message Signing and Recovery
+//! - EIP-712 Typed Structured Data Hashing and Signing
+//! - Solidity ABI Encoding and Decoding
+//! - JSON-RPC Client over HTTP, IPC and WebSocket
+//! - Legacy and EIP-1559 Transaction Signing
+//! - Signer Trait for Local, HD and Remote Signing
+//! - Nonce Management for Concurrent Submission
+//! - Fee Estimation from Recent Blocks
+//! - Pending Transaction Tracking with Speed-up and Cancellation
+//! - `laron-wallet` Command-Line Interface, behind the `cli` feature flag
 //!
 //! # TODO
-//! - [ ] Add support RPC calls
-//! - [ ] Add support for Contracts
+//! - [x] Add support RPC calls
+//! - [x] Add support for Contracts
 //!
 //! ## Example
 //! ```rust
@@ -50,4 +71,25 @@
 //! let address = public_key.address();
 //! ```
 
+pub mod abi;
+pub mod address;
 pub mod bips;
+pub mod bitcoin;
+pub mod discovery;
+pub mod eip712;
+pub mod fee;
+pub mod hash;
+pub mod keyring;
+pub mod message;
+pub mod metamask;
+pub mod nonce;
+pub mod pending;
+pub mod rlp;
+pub mod rpc;
+pub mod signature;
+pub mod signer;
+pub mod transaction;
+pub mod ur;
+pub mod vault;
+
+pub use primitive_types::{H256, U256};
@@ -0,0 +1,121 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # EIP-191 Signed Messages
+//!
+//! This module implements the `personal_sign` flavour of
+//! [EIP-191](https://eips.ethereum.org/EIPS/eip-191), where the message is
+//! prefixed with `"\x19Ethereum Signed Message:\n"` and its length before
+//! being hashed and signed.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::bips::bip39::{Mnemonic, MnemonicType};
+//! use laron_wallet::bips::wordlists::Language;
+//! use laron_wallet::bips::bip32::ExtendedKey;
+//! use laron_wallet::bips::DerivationPath;
+//! use laron_wallet::address::Address;
+//! use laron_wallet::message;
+//!
+//! let mnemonic = Mnemonic::new(MnemonicType::Words12, Language::English);
+//! let master_key = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+//! let key = master_key.derive_path(&DerivationPath::parse("m/44'/60'/0'/0/0").unwrap()).unwrap();
+//! let address = Address::from_public_key(key.public_key()).unwrap();
+//!
+//! let signature = message::sign_message(key.private_key(), "Hello World").unwrap();
+//! assert!(message::verify_message("Hello World", &signature, &address).unwrap());
+//! ```
+
+use crate::{address::Address, hash::keccak256, signature::Signature};
+use horror::Result;
+use laron_crypto::PrivateKey;
+
+/// The prefix prepended to every message before hashing.
+pub const MESSAGE_PREFIX: &str = "\x19Ethereum Signed Message:\n";
+
+/// Hash the given message with the EIP-191 prefix.
+pub fn hash_message<T: AsRef<[u8]>>(message: T) -> [u8; 32] {
+    let message = message.as_ref();
+    let mut data = format!("{}{}", MESSAGE_PREFIX, message.len()).into_bytes();
+    data.extend_from_slice(message);
+    keccak256(data)
+}
+
+/// Sign the given message with the given private key.
+pub fn sign_message<T: AsRef<[u8]>>(key: &PrivateKey, message: T) -> Result<Signature> {
+    Signature::sign_hash(key, &hash_message(message))
+}
+
+/// Recover the address of the signer of the given message.
+pub fn recover_message<T: AsRef<[u8]>>(message: T, signature: &Signature) -> Result<Address> {
+    signature.recover(&hash_message(message))
+}
+
+/// Verify that the given message was signed by the given address.
+pub fn verify_message<T: AsRef<[u8]>>(
+    message: T,
+    signature: &Signature,
+    address: &Address,
+) -> Result<bool> {
+    Ok(recover_message(message, signature)? == *address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn private_key(hex: &str) -> PrivateKey {
+        PrivateKey::from_bytes(&hex::decode(hex).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sign_message() {
+        let key = private_key("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        assert_eq!(
+            hex::encode(hash_message("Some data")),
+            "1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655"
+        );
+
+        let signature = sign_message(&key, "Some data").unwrap();
+        assert_eq!(
+            signature.to_string(),
+            "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c"
+        );
+    }
+
+    #[test]
+    fn test_recover_message() {
+        let address: Address = "0x2e988A386a799F506693793c6A5AF6B54dfAaBfB"
+            .parse()
+            .unwrap();
+
+        // 65 bytes signature with v = 27
+        let signature: Signature = "0x68a020a209d3d56c46f38cc50a33f704f4a9a10a59377f8dd762ac66910e9b907e865ad05c4035ab5792787d4a0297a43617ae897930a6fe4d822b8faea520641b".parse().unwrap();
+        assert_eq!(recover_message("Hello World", &signature).unwrap(), address);
+
+        // 65 bytes signature with v = 0
+        let signature: Signature = "0x68a020a209d3d56c46f38cc50a33f704f4a9a10a59377f8dd762ac66910e9b907e865ad05c4035ab5792787d4a0297a43617ae897930a6fe4d822b8faea5206400".parse().unwrap();
+        assert!(verify_message("Hello World", &signature, &address).unwrap());
+
+        // 64 bytes compact signature
+        let signature: Signature = "0x9328da16089fcba9bececa81663203989f2df5fe1faa6291a45381c81bd17f76939c6d6b623b42da56557e5e734a43dc83345ddfadec52cbe24d0cc64f550793".parse().unwrap();
+        assert!(verify_message("It's a small(er) world", &signature, &address).unwrap());
+        assert!(!verify_message("Hello World", &signature, &address).unwrap());
+    }
+}
@@ -0,0 +1,378 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # MetaMask
+//!
+//! Import of the vault in which MetaMask keeps its keyrings, so that users
+//! migrating from the browser extension keep their accounts.
+//!
+//! The vault is a JSON object holding a ciphertext encrypted with
+//! AES-256-GCM, under a key derived from the password with
+//! PBKDF2-HMAC-SHA256. It can be passed on its own, as shown by the
+//! extension's vault decryptor, or inside a state backup or storage dump,
+//! in which it is found under `KeyringController.vault`.
+//!
+//! Secret recovery phrases become HD roots of the [`Keyring`], with as
+//! many accounts derived as MetaMask had, and imported private keys are
+//! imported again. Hardware wallet keyrings hold no secret and are skipped.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use laron_wallet::metamask;
+//!
+//! let backup = std::fs::read_to_string("metamask-vault.json").unwrap();
+//! let import = metamask::import_vault(&backup, "password").unwrap();
+//! for account in import.keyring.accounts() {
+//!     println!("{}: {}", account.label(), account.address());
+//! }
+//! ```
+
+use crate::{
+    bips::{bip39::Mnemonic, wordlists::Language, ChildNumber, DerivationPath},
+    keyring::Keyring,
+};
+use aes_gcm::{
+    aead::{consts::U16, Aead, KeyInit},
+    aes::Aes256,
+    AesGcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::Hmac;
+use horror::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// The PBKDF2 iterations of vaults without key metadata, written by
+/// MetaMask before version 11.
+const DEFAULT_ITERATIONS: u32 = 10_000;
+
+/// The PBKDF2 iterations accepted from the key metadata. MetaMask never
+/// writes fewer than the default, and a crafted count above the maximum would
+/// keep the import running for hours before the password is checked.
+const ITERATIONS: std::ops::RangeInclusive<u32> = DEFAULT_ITERATIONS..=10_000_000;
+
+/// The largest number of accounts derived from one recovery phrase.
+const MAX_ACCOUNTS: u32 = 1000;
+const DEFAULT_HD_PATH: &str = "m/44'/60'/0'/0";
+const HD_KEY_TREE: &str = "HD Key Tree";
+const SIMPLE_KEY_PAIR: &str = "Simple Key Pair";
+
+/// AES-256-GCM with the 16-byte IV used by MetaMask.
+type Aes256Gcm16 = AesGcm<Aes256, U16>;
+
+/// Error returned when importing a MetaMask vault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetaMaskError {
+    /// No vault was found, or it is malformed.
+    InvalidVault(String),
+    /// The password is wrong, or the vault is corrupted.
+    Decryption,
+}
+
+impl std::fmt::Display for MetaMaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MetaMaskError::InvalidVault(reason) => write!(f, "Invalid MetaMask vault: {}", reason),
+            MetaMaskError::Decryption => write!(f, "Wrong password or corrupted MetaMask vault"),
+        }
+    }
+}
+
+impl std::error::Error for MetaMaskError {}
+
+/// The accounts imported from a MetaMask vault.
+#[derive(Debug, Clone)]
+pub struct MetaMaskImport {
+    /// The secret recovery phrases, their accounts and the imported keys.
+    pub keyring: Keyring,
+    /// The types of the keyrings which were not imported, such as
+    /// `Ledger Hardware`.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncryptedVault {
+    data: String,
+    iv: String,
+    salt: String,
+    key_metadata: Option<KeyMetadata>,
+}
+
+#[derive(Deserialize)]
+struct KeyMetadata {
+    algorithm: String,
+    params: KeyParams,
+}
+
+#[derive(Deserialize)]
+struct KeyParams {
+    iterations: u32,
+}
+
+#[derive(Deserialize)]
+struct SerializedKeyring {
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default)]
+    data: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HdKeyTree {
+    mnemonic: MnemonicData,
+    number_of_accounts: Option<u32>,
+    hd_path: Option<String>,
+}
+
+/// The mnemonic of an HD keyring, serialized as a string by old versions
+/// and as the array of its UTF-8 bytes by newer ones.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MnemonicData {
+    Phrase(String),
+    Bytes(Vec<u8>),
+}
+
+/// Decrypt the MetaMask vault found in the given JSON with the given
+/// password, and rebuild its keyrings.
+pub fn import_vault(json: &str, password: &str) -> Result<MetaMaskImport> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| MetaMaskError::InvalidVault(e.to_string()))?;
+    let vault = find_vault(&value)
+        .ok_or_else(|| MetaMaskError::InvalidVault("no vault found".to_string()))?;
+    let plaintext = decrypt(&vault, password)?;
+
+    let keyrings: Vec<SerializedKeyring> = serde_json::from_slice(&plaintext)
+        .map_err(|e| MetaMaskError::InvalidVault(e.to_string()))?;
+
+    let mut import = MetaMaskImport {
+        keyring: Keyring::new(),
+        skipped: Vec::new(),
+    };
+    let (mut roots, mut accounts, mut imported) = (0, 0, 0);
+    for serialized in keyrings {
+        match serialized.ty.as_str() {
+            HD_KEY_TREE => {
+                let data: HdKeyTree = serde_json::from_value(serialized.data)
+                    .map_err(|e| MetaMaskError::InvalidVault(e.to_string()))?;
+                let phrase = Zeroizing::new(match data.mnemonic {
+                    MnemonicData::Phrase(phrase) => phrase,
+                    MnemonicData::Bytes(bytes) => String::from_utf8(bytes)?,
+                });
+                let mnemonic = Mnemonic::from_phrase(&phrase, Language::English)?;
+
+                roots += 1;
+                let label = format!("Secret Recovery Phrase {}", roots);
+                let root = import.keyring.add_mnemonic(&label, &mnemonic, "")?;
+                let path =
+                    DerivationPath::parse(data.hd_path.as_deref().unwrap_or(DEFAULT_HD_PATH))?;
+                let count = data.number_of_accounts.unwrap_or(1);
+                if count > MAX_ACCOUNTS {
+                    let reason = format!("too many accounts: {}", count);
+                    return Err(MetaMaskError::InvalidVault(reason).into());
+                }
+                for index in 0..count {
+                    accounts += 1;
+                    let label = format!("Account {}", accounts);
+                    import.keyring.derive_account(
+                        root,
+                        &path.child(ChildNumber::normal(index)),
+                        &label,
+                    )?;
+                }
+            }
+            SIMPLE_KEY_PAIR => {
+                let keys: Vec<String> = serde_json::from_value(serialized.data)
+                    .map_err(|e| MetaMaskError::InvalidVault(e.to_string()))?;
+                for key in keys {
+                    imported += 1;
+                    let label = format!("Imported Account {}", imported);
+                    import.keyring.import_hex(&label, &key)?;
+                }
+            }
+            _ => import.skipped.push(serialized.ty),
+        }
+    }
+    Ok(import)
+}
+
+/// Look for the encrypted vault in the given JSON, which is either the
+/// vault itself or a state holding it, possibly as a serialized string.
+fn find_vault(value: &Value) -> Option<EncryptedVault> {
+    if let Ok(vault) = EncryptedVault::deserialize(value) {
+        return Some(vault);
+    }
+    match value {
+        Value::String(s) if s.trim_start().starts_with('{') => {
+            find_vault(&serde_json::from_str(s).ok()?)
+        }
+        Value::Object(map) => map.values().find_map(find_vault),
+        Value::Array(items) => items.iter().find_map(find_vault),
+        _ => None,
+    }
+}
+
+fn decrypt(vault: &EncryptedVault, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let iterations = match &vault.key_metadata {
+        None => DEFAULT_ITERATIONS,
+        Some(metadata) if metadata.algorithm == "PBKDF2" => metadata.params.iterations,
+        Some(metadata) => {
+            let reason = format!("unsupported key derivation {}", metadata.algorithm);
+            return Err(MetaMaskError::InvalidVault(reason).into());
+        }
+    };
+    if !ITERATIONS.contains(&iterations) {
+        let reason = format!("unsupported PBKDF2 iterations {}", iterations);
+        return Err(MetaMaskError::InvalidVault(reason).into());
+    }
+    let salt = STANDARD.decode(&vault.salt)?;
+    let iv = STANDARD.decode(&vault.iv)?;
+    let data = STANDARD.decode(&vault.data)?;
+    if iv.len() != 16 {
+        return Err(MetaMaskError::InvalidVault("invalid IV length".to_string()).into());
+    }
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), &salt, iterations, &mut key[..]);
+    let plaintext = Aes256Gcm16::new(key.as_ref().into())
+        .decrypt(Nonce::from_slice(&iv), &data[..])
+        .map_err(|_| MetaMaskError::Decryption)?;
+    Ok(Zeroizing::new(plaintext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Encrypt the given keyrings like MetaMask does.
+    fn encrypt(keyrings: &Value, password: &str, iterations: Option<u32>) -> Value {
+        let salt = [0x5a; 32];
+        let iv = [0xa5; 16];
+        let mut key = [0u8; 32];
+        pbkdf2::pbkdf2::<Hmac<Sha256>>(
+            password.as_bytes(),
+            &salt,
+            iterations.unwrap_or(DEFAULT_ITERATIONS),
+            &mut key,
+        );
+        let data = Aes256Gcm16::new((&key).into())
+            .encrypt(Nonce::from_slice(&iv), keyrings.to_string().as_bytes())
+            .unwrap();
+
+        let mut vault = json!({
+            "data": STANDARD.encode(data),
+            "iv": STANDARD.encode(iv),
+            "salt": STANDARD.encode(salt),
+        });
+        if let Some(iterations) = iterations {
+            vault["keyMetadata"] =
+                json!({ "algorithm": "PBKDF2", "params": { "iterations": iterations } });
+        }
+        vault
+    }
+
+    #[test]
+    fn test_import_vault() {
+        let phrase = "test test test test test test test test test test test junk";
+        let keyrings = json!([
+            {
+                "type": "HD Key Tree",
+                "data": {
+                    "mnemonic": phrase.as_bytes(),
+                    "numberOfAccounts": 2,
+                    "hdPath": "m/44'/60'/0'/0",
+                },
+            },
+            { "type": "Simple Key Pair", "data": [hex::encode([0x46; 32])] },
+            { "type": "Ledger Hardware", "data": { "hdPath": "m/44'/60'/0'" } },
+        ]);
+
+        // a state backup, holding the vault as a string
+        let vault = encrypt(&keyrings, "password", Some(20_000));
+        let backup = json!({ "KeyringController": { "vault": vault.to_string() } });
+        let import = import_vault(&backup.to_string(), "password").unwrap();
+
+        let accounts = import
+            .keyring
+            .accounts()
+            .iter()
+            .map(|account| (account.label(), account.address().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            accounts,
+            vec![
+                (
+                    "Account 1",
+                    "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266".to_string()
+                ),
+                (
+                    "Account 2",
+                    "0x70997970C51812dc3A010C7d01b50e0d17dc79C8".to_string()
+                ),
+                (
+                    "Imported Account 1",
+                    "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F".to_string()
+                ),
+            ]
+        );
+        assert_eq!(import.keyring.roots()[0].mnemonic().phrase(), phrase);
+        assert_eq!(import.skipped, vec!["Ledger Hardware".to_string()]);
+        assert!(import_vault(&backup.to_string(), "wrong").is_err());
+
+        // an old vault, with the mnemonic as a string and default iterations
+        let keyrings = json!([{ "type": "HD Key Tree", "data": { "mnemonic": phrase, "numberOfAccounts": 1 } }]);
+        let vault = encrypt(&keyrings, "password", None);
+        let import = import_vault(&vault.to_string(), "password").unwrap();
+        assert_eq!(import.keyring.accounts().len(), 1);
+
+        assert!(import_vault("{}", "password").is_err());
+    }
+
+    #[test]
+    fn test_import_limits() {
+        let keyrings = json!([{ "type": "Simple Key Pair", "data": [] }]);
+        let mut vault = encrypt(&keyrings, "password", Some(DEFAULT_ITERATIONS));
+        assert!(import_vault(&vault.to_string(), "password").is_ok());
+        for iterations in [0, 9_999, 10_000_001, u32::MAX] {
+            vault["keyMetadata"]["params"]["iterations"] = json!(iterations);
+            let err = import_vault(&vault.to_string(), "password").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "Invalid MetaMask vault: unsupported PBKDF2 iterations {}",
+                    iterations
+                )
+            );
+        }
+
+        let phrase = "test test test test test test test test test test test junk";
+        let keyrings = json!([{ "type": "HD Key Tree", "data": { "mnemonic": phrase, "numberOfAccounts": u32::MAX } }]);
+        let vault = encrypt(&keyrings, "password", None);
+        let err = import_vault(&vault.to_string(), "password").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid MetaMask vault: too many accounts: 4294967295"
+        );
+    }
+}
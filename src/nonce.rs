@@ -0,0 +1,402 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Nonce Management
+//!
+//! Several workers sending transactions from the same account must not use
+//! the same nonce. A [`NonceManager`] fetches the pending nonce of an address
+//! once, then hands out increasing nonces atomically. When a node reports
+//! that a nonce is stale, the manager resyncs with the node.
+//!
+//! A nonce that was handed out but never broadcast leaves a gap which blocks
+//! every later transaction. Such nonces are given back with
+//! [`NonceManager::release`] and filled with zero-value self-transfers by
+//! [`NonceManager::fill_gaps`].
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::address::Address;
+//! use laron_wallet::nonce::NonceManager;
+//! use laron_wallet::rpc::{MockTransport, Provider};
+//!
+//! let transport = MockTransport::new();
+//! transport.push("eth_getTransactionCount", "0x5");
+//! let provider = Provider::new(transport);
+//!
+//! let nonces = NonceManager::new();
+//! let address = Address::new([0x11; 20]);
+//! assert_eq!(nonces.next(&provider, &address).unwrap(), 5);
+//! assert_eq!(nonces.next(&provider, &address).unwrap(), 6);
+//! ```
+
+use crate::{
+    address::Address,
+    rpc::{BlockNumber, Provider, Transport},
+    signer::Signer,
+    transaction::{Eip1559Transaction, Transaction},
+};
+use horror::{Error, Result};
+use primitive_types::{H256, U256};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Node error messages meaning that the local nonce is out of date.
+pub const RESYNC_ERRORS: [&str; 3] = [
+    "nonce too low",
+    "replacement transaction underpriced",
+    "replacement underpriced",
+];
+
+/// The gas used by a plain transfer, and so by a cancellation.
+const TRANSFER_GAS: u64 = 21000;
+
+#[derive(Debug, Default)]
+struct AccountNonces {
+    /// The next nonce to hand out.
+    next: u64,
+    /// Nonces handed out but released without being broadcast.
+    gaps: BTreeSet<u64>,
+}
+
+/// Hands out transaction nonces per address, shared between workers.
+#[derive(Debug, Default)]
+pub struct NonceManager {
+    accounts: Mutex<HashMap<Address, AccountNonces>>,
+    /// Held while the pending nonce of an address is fetched, so that it
+    /// is fetched once without holding up the other addresses.
+    fetching: Mutex<HashMap<Address, Arc<Mutex<()>>>>,
+}
+
+impl NonceManager {
+    /// Create a new nonce manager with no known address.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the next nonce of the given address. The pending nonce is
+    /// fetched from the provider the first time an address is seen.
+    pub fn next<T: Transport>(&self, provider: &Provider<T>, address: &Address) -> Result<u64> {
+        if let Some(nonce) = self.take_next(address) {
+            return Ok(nonce);
+        }
+
+        let fetching = self
+            .fetching
+            .lock()
+            .unwrap()
+            .entry(*address)
+            .or_default()
+            .clone();
+        let _guard = fetching.lock().unwrap();
+        // Another worker may have fetched it while we were waiting.
+        if let Some(nonce) = self.take_next(address) {
+            return Ok(nonce);
+        }
+
+        let pending = provider.get_transaction_count(address, BlockNumber::Pending)?;
+        let mut accounts = self.accounts.lock().unwrap();
+        // A resync may have added the address in the meantime.
+        let account = accounts.entry(*address).or_insert(AccountNonces {
+            next: pending,
+            ..Default::default()
+        });
+        let nonce = account.next;
+        account.next += 1;
+        self.fetching.lock().unwrap().remove(address);
+        Ok(nonce)
+    }
+
+    /// Hand out the next nonce of the given address, if it is known.
+    fn take_next(&self, address: &Address) -> Option<u64> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.get_mut(address)?;
+        let nonce = account.next;
+        account.next += 1;
+        Some(nonce)
+    }
+
+    /// Return the nonce that will be handed out next for the given address,
+    /// if it is known.
+    pub fn peek(&self, address: &Address) -> Option<u64> {
+        let accounts = self.accounts.lock().unwrap();
+        accounts.get(address).map(|account| account.next)
+    }
+
+    /// Fetch the pending nonce of the given address and skip ahead to it.
+    /// Nonces are never handed out twice, so a node behind the manager does
+    /// not move it back. Return the next nonce.
+    pub fn resync<T: Transport>(&self, provider: &Provider<T>, address: &Address) -> Result<u64> {
+        let pending = provider.get_transaction_count(address, BlockNumber::Pending)?;
+
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.entry(*address).or_default();
+        account.next = account.next.max(pending);
+        // Gaps below the pending nonce were filled by someone else.
+        account.gaps = account.gaps.split_off(&pending);
+        Ok(account.next)
+    }
+
+    /// Resync the given address if the given error means its nonce is out
+    /// of date. Return whether the transaction should be retried with a new
+    /// nonce.
+    pub fn handle_error<T: Transport>(
+        &self,
+        provider: &Provider<T>,
+        address: &Address,
+        error: &Error,
+    ) -> Result<bool> {
+        let message = error.to_string().to_lowercase();
+        if !RESYNC_ERRORS
+            .iter()
+            .any(|pattern| message.contains(pattern))
+        {
+            return Ok(false);
+        }
+        self.resync(provider, address)?;
+        Ok(true)
+    }
+
+    /// Give back a nonce which was handed out but not broadcast. The last
+    /// nonce is simply handed out again, any other one is recorded as a gap.
+    pub fn release(&self, address: &Address, nonce: u64) {
+        let mut accounts = self.accounts.lock().unwrap();
+        if let Some(account) = accounts.get_mut(address) {
+            if nonce + 1 == account.next {
+                account.next = nonce;
+            } else if nonce < account.next {
+                account.gaps.insert(nonce);
+            }
+        }
+    }
+
+    /// Return the released nonces of the given address, in order.
+    pub fn gaps(&self, address: &Address) -> Vec<u64> {
+        let accounts = self.accounts.lock().unwrap();
+        accounts
+            .get(address)
+            .map(|account| account.gaps.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Send a cancellation for every gap of the signer address, so that the
+    /// transactions queued behind them can be mined. Return the hashes of
+    /// the cancellations.
+    pub fn fill_gaps<T: Transport, S: Signer>(
+        &self,
+        provider: &Provider<T>,
+        signer: &S,
+        max_fee_per_gas: U256,
+        max_priority_fee_per_gas: U256,
+    ) -> Result<Vec<H256>> {
+        let address = signer.address();
+        let gaps = self.gaps(&address);
+        if gaps.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let chain_id = provider.chain_id()?;
+        let mut hashes = Vec::with_capacity(gaps.len());
+        for nonce in gaps {
+            let transaction = cancellation(
+                &address,
+                chain_id,
+                nonce,
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            );
+            let signed = signer.sign_transaction(&transaction)?;
+            hashes.push(provider.send_raw_transaction(&signed.raw())?);
+
+            let mut accounts = self.accounts.lock().unwrap();
+            if let Some(account) = accounts.get_mut(&address) {
+                account.gaps.remove(&nonce);
+            }
+        }
+
+        Ok(hashes)
+    }
+}
+
+/// Return a zero-value transfer from the given address to itself, which
+/// takes the place of the transaction with the same nonce.
+pub fn cancellation(
+    address: &Address,
+    chain_id: u64,
+    nonce: u64,
+    max_fee_per_gas: U256,
+    max_priority_fee_per_gas: U256,
+) -> Transaction {
+    Transaction::Eip1559(Eip1559Transaction {
+        chain_id,
+        nonce,
+        max_priority_fee_per_gas,
+        max_fee_per_gas,
+        gas_limit: U256::from(TRANSFER_GAS),
+        to: Some(*address),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rpc::{Bytes, MockTransport},
+        signer::Wallet,
+        transaction::SignedTransaction,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_concurrent_nonces() {
+        let transport = MockTransport::new();
+        transport.push("eth_getTransactionCount", "0x5");
+        let provider = Arc::new(Provider::new(transport));
+        let nonces = Arc::new(NonceManager::new());
+        let address = Address::new([0x11; 20]);
+
+        let handles = (0..8)
+            .map(|_| {
+                let provider = provider.clone();
+                let nonces = nonces.clone();
+                std::thread::spawn(move || {
+                    (0..10)
+                        .map(|_| nonces.next(&provider, &address).unwrap())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut all = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+        all.sort_unstable();
+        assert_eq!(all, (5..85).collect::<Vec<_>>());
+        assert_eq!(
+            provider
+                .transport()
+                .requests_for("eth_getTransactionCount")
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_slow_node() {
+        /// Answers the nonce of the slow address once released.
+        struct Slow {
+            started: Mutex<std::sync::mpsc::Sender<()>>,
+            release: Mutex<std::sync::mpsc::Receiver<()>>,
+        }
+
+        impl Transport for Slow {
+            fn send(&self, request: &serde_json::Value) -> Result<serde_json::Value> {
+                let mut result = json!("0x2");
+                if request["params"][0] == json!(Address::new([0x11; 20])) {
+                    self.started.lock().unwrap().send(()).unwrap();
+                    let timeout = std::time::Duration::from_secs(5);
+                    self.release.lock().unwrap().recv_timeout(timeout)?;
+                    result = json!("0x7");
+                }
+                Ok(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+            }
+        }
+
+        let (started, wait_started) = std::sync::mpsc::channel();
+        let (release, wait_release) = std::sync::mpsc::channel();
+        let provider = Arc::new(Provider::new(Slow {
+            started: Mutex::new(started),
+            release: Mutex::new(wait_release),
+        }));
+        let nonces = Arc::new(NonceManager::new());
+        let slow = Address::new([0x11; 20]);
+
+        let handle = {
+            let (provider, nonces) = (provider.clone(), nonces.clone());
+            std::thread::spawn(move || nonces.next(&provider, &slow))
+        };
+        wait_started.recv().unwrap();
+
+        // other addresses do not wait for the slow call
+        let address = Address::new([0x22; 20]);
+        assert_eq!(nonces.next(&provider, &address).unwrap(), 2);
+        release.send(()).unwrap();
+        assert_eq!(handle.join().unwrap().unwrap(), 7);
+        assert_eq!(nonces.next(&provider, &slow).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_resync_on_error() {
+        let transport = MockTransport::new();
+        transport.push("eth_getTransactionCount", "0x1");
+        transport.push_error("eth_sendRawTransaction", -32000, "nonce too low");
+        transport.push_error("eth_sendRawTransaction", -32000, "insufficient funds");
+        transport.push("eth_getTransactionCount", "0x4");
+        let provider = Provider::new(transport);
+        let nonces = NonceManager::new();
+        let address = Address::new([0x11; 20]);
+
+        assert_eq!(nonces.next(&provider, &address).unwrap(), 1);
+        let err = provider.send_raw_transaction(&[0x01]).unwrap_err();
+        assert!(nonces.handle_error(&provider, &address, &err).unwrap());
+        assert_eq!(nonces.next(&provider, &address).unwrap(), 4);
+
+        let err = provider.send_raw_transaction(&[0x01]).unwrap_err();
+        assert!(!nonces.handle_error(&provider, &address, &err).unwrap());
+        assert_eq!(nonces.peek(&address), Some(5));
+    }
+
+    #[test]
+    fn test_fill_gaps() {
+        let transport = MockTransport::new();
+        transport.push("eth_getTransactionCount", "0x0");
+        transport.push("eth_chainId", "0x1");
+        transport.on("eth_sendRawTransaction", |_| {
+            Ok(json!(H256::repeat_byte(0xaa)))
+        });
+        let provider = Provider::new(transport);
+        let nonces = NonceManager::new();
+        let wallet = Wallet::from_bytes(&[0x46; 32]).unwrap();
+        let address = wallet.address();
+
+        for _ in 0..4 {
+            nonces.next(&provider, &address).unwrap();
+        }
+        nonces.release(&address, 3);
+        nonces.release(&address, 1);
+        assert_eq!(nonces.peek(&address), Some(3));
+        assert_eq!(nonces.gaps(&address), vec![1]);
+
+        let hashes = nonces
+            .fill_gaps(&provider, &wallet, U256::from(30), U256::from(2))
+            .unwrap();
+        assert_eq!(hashes.len(), 1);
+        assert!(nonces.gaps(&address).is_empty());
+
+        let requests = provider.transport().requests_for("eth_sendRawTransaction");
+        let raw: Bytes = serde_json::from_value(requests[0]["params"][0].clone()).unwrap();
+        let signed = SignedTransaction::decode(&raw.0).unwrap();
+        assert_eq!(signed.transaction().nonce(), 1);
+        assert_eq!(signed.transaction().to(), Some(address));
+        assert!(signed.transaction().value().is_zero());
+        assert_eq!(signed.sender().unwrap(), address);
+    }
+}
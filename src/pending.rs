@@ -0,0 +1,557 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Pending Transactions
+//!
+//! A [`PendingTransaction`] follows a broadcast transaction until it has
+//! enough confirmations. Each [`poll`](PendingTransaction::poll) reports
+//! whether the transaction is still pending, mined, moved out of the chain by
+//! a reorganization, dropped by the node or replaced by another transaction
+//! with the same nonce.
+//!
+//! A stuck transaction can be replaced by the same transaction paying higher
+//! fees ([`speed_up`](PendingTransaction::speed_up)) or by a zero-value
+//! transfer to the sender ([`cancel`](PendingTransaction::cancel)). Nodes
+//! only accept a replacement paying at least 10% more than the original.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use laron_wallet::pending::PendingTransaction;
+//! use laron_wallet::rpc::{HttpTransport, Provider};
+//! use laron_wallet::signer::{Signer, Wallet};
+//! use laron_wallet::transaction::{Eip1559Transaction, Transaction};
+//! use std::time::Duration;
+//!
+//! let provider = Provider::new(HttpTransport::new("http://localhost:8545"));
+//! let wallet = Wallet::from_bytes(&[0x46; 32]).unwrap();
+//! let transaction = Transaction::Eip1559(Eip1559Transaction::default());
+//!
+//! let signed = wallet.sign_transaction(&transaction).unwrap();
+//! let mut pending = PendingTransaction::send(&provider, signed)
+//!     .unwrap()
+//!     .confirmations(3);
+//! let receipt = pending
+//!     .wait_with_bumps(&wallet, Duration::from_secs(60))
+//!     .unwrap();
+//! ```
+
+use crate::{
+    address::Address,
+    fee::FeeError,
+    nonce::cancellation,
+    rpc::{BlockNumber, Provider, TransactionReceipt, Transport},
+    signer::Signer,
+    transaction::{LegacyTransaction, SignedTransaction, Transaction},
+};
+use horror::Result;
+use primitive_types::{H256, U256};
+use std::time::{Duration, Instant};
+
+/// The minimum fee increase, in percent, for a node to accept a replacement.
+pub const MIN_FEE_BUMP: u64 = 10;
+
+/// The default delay between two polls.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Error returned while waiting for a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PendingError {
+    /// The node forgot the transaction before it was mined.
+    Dropped(H256),
+    /// Another transaction with the same nonce was mined.
+    Replaced(u64),
+    /// The signer is not the sender of the transaction.
+    WrongSigner(Address),
+    /// Bumping the fees would exceed the fee cap.
+    FeeCapReached(U256),
+}
+
+impl std::fmt::Display for PendingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PendingError::Dropped(hash) => write!(f, "Transaction dropped: {:?}", hash),
+            PendingError::Replaced(nonce) => {
+                write!(f, "Transaction replaced at nonce {}", nonce)
+            }
+            PendingError::WrongSigner(address) => {
+                write!(f, "Signer {} is not the sender", address)
+            }
+            PendingError::FeeCapReached(fee) => {
+                write!(f, "Fee bump to {} wei exceeds the fee cap", fee)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PendingError {}
+
+/// The state of a pending transaction after a poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxStatus {
+    /// The transaction is waiting in the mempool.
+    Pending,
+    /// The transaction is mined but does not have enough confirmations.
+    Mined {
+        receipt: TransactionReceipt,
+        confirmations: u64,
+    },
+    /// The transaction has the required number of confirmations.
+    Confirmed(TransactionReceipt),
+    /// The transaction was mined, then removed by a reorganization.
+    Reorged,
+    /// The node does not know the transaction anymore.
+    Dropped,
+    /// Another transaction with the same nonce was mined.
+    Replaced,
+}
+
+/// A broadcast transaction, together with the replacements sent for it.
+pub struct PendingTransaction<'a, T> {
+    provider: &'a Provider<T>,
+    from: Address,
+    /// The original transaction followed by its replacements.
+    transactions: Vec<SignedTransaction>,
+    receipt: Option<TransactionReceipt>,
+    confirmations: u64,
+    interval: Duration,
+    fee_bump: u64,
+    max_fee_per_gas: Option<U256>,
+}
+
+impl<'a, T: Transport> PendingTransaction<'a, T> {
+    /// Track a transaction which was already broadcast.
+    pub fn new(provider: &'a Provider<T>, transaction: SignedTransaction) -> Result<Self> {
+        Ok(Self {
+            provider,
+            from: transaction.sender()?,
+            transactions: vec![transaction],
+            receipt: None,
+            confirmations: 1,
+            interval: DEFAULT_INTERVAL,
+            fee_bump: MIN_FEE_BUMP,
+            max_fee_per_gas: None,
+        })
+    }
+
+    /// Broadcast the given transaction and track it.
+    pub fn send(provider: &'a Provider<T>, transaction: SignedTransaction) -> Result<Self> {
+        provider.send_raw_transaction(&transaction.raw())?;
+        Self::new(provider, transaction)
+    }
+
+    /// Set the number of confirmations to wait for, the default being 1.
+    pub fn confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations.max(1);
+        self
+    }
+
+    /// Set the delay between two polls.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the fee increase of replacements, in percent. Values below
+    /// [`MIN_FEE_BUMP`] are raised to it.
+    pub fn fee_bump(mut self, percent: u64) -> Self {
+        self.fee_bump = percent.max(MIN_FEE_BUMP);
+        self
+    }
+
+    /// Set the highest fee per gas a replacement may pay.
+    pub fn max_fee_per_gas(mut self, wei: U256) -> Self {
+        self.max_fee_per_gas = Some(wei);
+        self
+    }
+
+    /// Return the latest transaction sent.
+    pub fn transaction(&self) -> &SignedTransaction {
+        self.transactions.last().unwrap()
+    }
+
+    /// Return the hash of the latest transaction sent.
+    pub fn hash(&self) -> H256 {
+        self.transaction().hash()
+    }
+
+    /// Return the hashes of the original transaction and its replacements.
+    pub fn hashes(&self) -> Vec<H256> {
+        self.transactions
+            .iter()
+            .map(SignedTransaction::hash)
+            .collect()
+    }
+
+    /// Return the nonce shared by the transaction and its replacements.
+    pub fn nonce(&self) -> u64 {
+        self.transaction().transaction().nonce()
+    }
+
+    /// Query the node once and return the state of the transaction. Any of
+    /// the replacements being mined counts as the transaction being mined.
+    pub fn poll(&mut self) -> Result<TxStatus> {
+        if let Some(status) = self.mined()? {
+            return Ok(status);
+        }
+        if self.receipt.take().is_some() {
+            return Ok(TxStatus::Reorged);
+        }
+
+        for transaction in self.transactions.iter().rev() {
+            if self
+                .provider
+                .get_transaction(&transaction.hash())?
+                .is_some()
+            {
+                return Ok(TxStatus::Pending);
+            }
+        }
+
+        let nonce = self
+            .provider
+            .get_transaction_count(&self.from, BlockNumber::Latest)?;
+        if nonce <= self.nonce() {
+            return Ok(TxStatus::Dropped);
+        }
+        // The nonce may have been used by one of ours since the first check.
+        match self.mined()? {
+            Some(status) => Ok(status),
+            None => Ok(TxStatus::Replaced),
+        }
+    }
+
+    /// Poll until the transaction is confirmed and return its receipt.
+    pub fn wait(&mut self) -> Result<TransactionReceipt> {
+        loop {
+            match self.poll()? {
+                TxStatus::Confirmed(receipt) => return Ok(receipt),
+                TxStatus::Dropped => return Err(PendingError::Dropped(self.hash()).into()),
+                TxStatus::Replaced => return Err(PendingError::Replaced(self.nonce()).into()),
+                _ => std::thread::sleep(self.interval),
+            }
+        }
+    }
+
+    /// Poll until the transaction is confirmed and return its receipt,
+    /// sending a replacement with higher fees whenever the transaction is
+    /// pending for longer than `stuck_after` or dropped by the node.
+    pub fn wait_with_bumps<S: Signer>(
+        &mut self,
+        signer: &S,
+        stuck_after: Duration,
+    ) -> Result<TransactionReceipt> {
+        let mut since = Instant::now();
+        loop {
+            match self.poll()? {
+                TxStatus::Confirmed(receipt) => return Ok(receipt),
+                TxStatus::Replaced => return Err(PendingError::Replaced(self.nonce()).into()),
+                TxStatus::Dropped => {
+                    self.speed_up(signer)?;
+                    since = Instant::now();
+                }
+                TxStatus::Pending if since.elapsed() >= stuck_after => {
+                    self.speed_up(signer)?;
+                    since = Instant::now();
+                }
+                _ => std::thread::sleep(self.interval),
+            }
+        }
+    }
+
+    /// Send the latest transaction again with higher fees. Return the hash
+    /// of the replacement.
+    pub fn speed_up<S: Signer>(&mut self, signer: &S) -> Result<H256> {
+        let transaction = self.bump(self.transaction().transaction())?;
+        self.replace(signer, &transaction)
+    }
+
+    /// Replace the transaction by a zero-value transfer to the sender with
+    /// higher fees. Return the hash of the cancellation.
+    pub fn cancel<S: Signer>(&mut self, signer: &S) -> Result<H256> {
+        let transaction = match self.bump(self.transaction().transaction())? {
+            Transaction::Legacy(tx) => Transaction::Legacy(LegacyTransaction {
+                nonce: tx.nonce,
+                gas_price: tx.gas_price,
+                gas_limit: U256::from(21000),
+                to: Some(self.from),
+                chain_id: tx.chain_id,
+                ..Default::default()
+            }),
+            Transaction::Eip1559(tx) => cancellation(
+                &self.from,
+                tx.chain_id,
+                tx.nonce,
+                tx.max_fee_per_gas,
+                tx.max_priority_fee_per_gas,
+            ),
+        };
+        self.replace(signer, &transaction)
+    }
+
+    fn mined(&mut self) -> Result<Option<TxStatus>> {
+        for transaction in self.transactions.iter().rev() {
+            let receipt = match self.provider.get_transaction_receipt(&transaction.hash())? {
+                Some(receipt) => receipt,
+                None => continue,
+            };
+
+            let head = self.provider.block_number()?;
+            let block = receipt.block_number.low_u64();
+            let confirmations = (head + 1).saturating_sub(block);
+            self.receipt = Some(receipt.clone());
+            if confirmations >= self.confirmations {
+                return Ok(Some(TxStatus::Confirmed(receipt)));
+            }
+            return Ok(Some(TxStatus::Mined {
+                receipt,
+                confirmations,
+            }));
+        }
+        Ok(None)
+    }
+
+    fn bump(&self, transaction: &Transaction) -> Result<Transaction> {
+        let transaction = bump_fees(transaction, self.fee_bump)?;
+        let max_fee_per_gas = match &transaction {
+            Transaction::Legacy(tx) => tx.gas_price,
+            Transaction::Eip1559(tx) => tx.max_fee_per_gas,
+        };
+        if self
+            .max_fee_per_gas
+            .is_some_and(|cap| max_fee_per_gas > cap)
+        {
+            return Err(PendingError::FeeCapReached(max_fee_per_gas).into());
+        }
+        Ok(transaction)
+    }
+
+    fn replace<S: Signer>(&mut self, signer: &S, transaction: &Transaction) -> Result<H256> {
+        if signer.address() != self.from {
+            return Err(PendingError::WrongSigner(signer.address()).into());
+        }
+        let signed = signer.sign_transaction(transaction)?;
+        let hash = self.provider.send_raw_transaction(&signed.raw())?;
+        self.transactions.push(signed);
+        Ok(hash)
+    }
+}
+
+/// Return the given transaction with its fees raised by the given percent.
+///
+/// Fails with [`FeeError::Overflow`] when a raised fee does not fit in 256
+/// bits.
+pub fn bump_fees(transaction: &Transaction, percent: u64) -> Result<Transaction> {
+    let bump = |fee: U256| -> Result<U256> {
+        100u64
+            .checked_add(percent)
+            .and_then(|factor| fee.checked_mul(U256::from(factor)))
+            .and_then(|fee| fee.checked_add(U256::from(99)))
+            .map(|fee| fee / 100)
+            .ok_or_else(|| FeeError::Overflow.into())
+    };
+    let mut transaction = transaction.clone();
+    match &mut transaction {
+        Transaction::Legacy(tx) => tx.gas_price = bump(tx.gas_price)?,
+        Transaction::Eip1559(tx) => {
+            tx.max_fee_per_gas = bump(tx.max_fee_per_gas)?;
+            tx.max_priority_fee_per_gas = bump(tx.max_priority_fee_per_gas)?;
+        }
+    }
+    Ok(transaction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rpc::{Bytes, MockTransport},
+        signer::Wallet,
+        transaction::Eip1559Transaction,
+    };
+    use serde_json::{json, Value};
+
+    fn signed(wallet: &Wallet) -> SignedTransaction {
+        let transaction = Transaction::Eip1559(Eip1559Transaction {
+            chain_id: 1,
+            nonce: 3,
+            max_priority_fee_per_gas: U256::from(100),
+            max_fee_per_gas: U256::from(1000),
+            gas_limit: U256::from(21000),
+            to: Some(Address::new([0x35; 20])),
+            value: U256::from(1),
+            ..Default::default()
+        });
+        wallet.sign_transaction(&transaction).unwrap()
+    }
+
+    fn receipt(hash: H256, block: u64, block_hash: u8) -> Value {
+        json!({
+            "transactionHash": hash,
+            "blockHash": H256::repeat_byte(block_hash),
+            "blockNumber": U256::from(block),
+            "from": Address::new([0x11; 20]),
+            "to": Address::new([0x35; 20]),
+            "contractAddress": null,
+            "gasUsed": "0x5208",
+            "status": "0x1",
+            "logs": []
+        })
+    }
+
+    #[test]
+    fn test_confirmations() {
+        let wallet = Wallet::from_bytes(&[0x46; 32]).unwrap();
+        let signed = signed(&wallet);
+        let hash = signed.hash();
+
+        let transport = MockTransport::new();
+        transport.push("eth_getTransactionReceipt", Value::Null);
+        transport.push(
+            "eth_getTransactionByHash",
+            json!({
+                "hash": hash,
+                "from": wallet.address(),
+                "nonce": "0x3",
+                "blockHash": null,
+                "blockNumber": null
+            }),
+        );
+        transport.push("eth_getTransactionReceipt", receipt(hash, 10, 0xaa));
+        transport.push("eth_blockNumber", "0xa");
+        transport.push("eth_getTransactionReceipt", Value::Null);
+        transport.push("eth_getTransactionByHash", Value::Null);
+        transport.push("eth_getTransactionCount", "0x3");
+        transport.push("eth_getTransactionReceipt", receipt(hash, 11, 0xbb));
+        transport.push("eth_blockNumber", "0xd");
+        let provider = Provider::new(transport);
+
+        let mut pending = PendingTransaction::new(&provider, signed)
+            .unwrap()
+            .confirmations(3)
+            .interval(Duration::ZERO);
+        assert_eq!(pending.poll().unwrap(), TxStatus::Pending);
+        assert!(matches!(
+            pending.poll().unwrap(),
+            TxStatus::Mined {
+                confirmations: 1,
+                ..
+            }
+        ));
+        // the block was reorganized away, and the transaction not re-added yet
+        assert_eq!(pending.poll().unwrap(), TxStatus::Reorged);
+        let receipt = pending.wait().unwrap();
+        assert_eq!(receipt.block_number, U256::from(11));
+        assert!(receipt.is_success());
+    }
+
+    #[test]
+    fn test_dropped_and_replaced() {
+        let wallet = Wallet::from_bytes(&[0x46; 32]).unwrap();
+
+        let transport = MockTransport::new();
+        transport.on("eth_getTransactionReceipt", |_| Ok(Value::Null));
+        transport.on("eth_getTransactionByHash", |_| Ok(Value::Null));
+        transport.push("eth_getTransactionCount", "0x3");
+        transport.push("eth_getTransactionCount", "0x4");
+        let provider = Provider::new(transport);
+
+        let mut pending = PendingTransaction::new(&provider, signed(&wallet)).unwrap();
+        assert_eq!(pending.poll().unwrap(), TxStatus::Dropped);
+        let err = pending.wait().unwrap_err();
+        assert_eq!(err.to_string(), "Transaction replaced at nonce 3");
+    }
+
+    #[test]
+    fn test_speed_up() {
+        let wallet = Wallet::from_bytes(&[0x46; 32]).unwrap();
+        let signed = signed(&wallet);
+        let original = signed.hash();
+
+        let transport = MockTransport::new();
+        transport.on("eth_getTransactionReceipt", move |params| {
+            let hash: H256 = serde_json::from_value(params[0].clone()).unwrap();
+            if hash == original {
+                Ok(Value::Null)
+            } else {
+                Ok(receipt(hash, 5, 0xaa))
+            }
+        });
+        transport.on("eth_getTransactionByHash", move |_| {
+            Ok(json!({ "hash": original, "from": Address::default(), "nonce": "0x3" }))
+        });
+        transport.on("eth_sendRawTransaction", |params| {
+            let raw: Bytes = serde_json::from_value(params[0].clone()).unwrap();
+            Ok(json!(SignedTransaction::decode(&raw.0).unwrap().hash()))
+        });
+        transport.on("eth_blockNumber", |_| Ok(json!("0x5")));
+        let provider = Provider::new(transport);
+
+        let mut pending = PendingTransaction::new(&provider, signed)
+            .unwrap()
+            .interval(Duration::ZERO);
+        let other = Wallet::from_bytes(&[0x47; 32]).unwrap();
+        assert!(pending.speed_up(&other).is_err());
+
+        let receipt = pending.wait_with_bumps(&wallet, Duration::ZERO).unwrap();
+        assert_eq!(pending.hashes().len(), 2);
+        assert_eq!(receipt.transaction_hash, pending.hash());
+
+        match pending.transaction().transaction() {
+            Transaction::Eip1559(tx) => {
+                assert_eq!(tx.nonce, 3);
+                assert_eq!(tx.max_fee_per_gas, U256::from(1100));
+                assert_eq!(tx.max_priority_fee_per_gas, U256::from(110));
+            }
+            _ => unreachable!(),
+        }
+
+        let mut capped = pending.max_fee_per_gas(U256::from(1250));
+        assert!(capped.cancel(&wallet).is_ok());
+        assert!(capped.cancel(&wallet).is_err());
+        assert_eq!(
+            capped.transaction().transaction().to(),
+            Some(wallet.address())
+        );
+        assert!(capped.transaction().transaction().value().is_zero());
+    }
+
+    #[test]
+    fn test_bump_fees() {
+        let transaction = Transaction::Eip1559(Eip1559Transaction {
+            max_priority_fee_per_gas: U256::from(100),
+            max_fee_per_gas: U256::from(1001),
+            ..Default::default()
+        });
+        match bump_fees(&transaction, 10).unwrap() {
+            Transaction::Eip1559(tx) => {
+                assert_eq!(tx.max_fee_per_gas, U256::from(1102));
+                assert_eq!(tx.max_priority_fee_per_gas, U256::from(110));
+            }
+            _ => unreachable!(),
+        }
+        assert!(bump_fees(&transaction, u64::MAX).is_err());
+
+        let transaction = Transaction::Eip1559(Eip1559Transaction {
+            max_fee_per_gas: U256::MAX,
+            ..Default::default()
+        });
+        let err = bump_fees(&transaction, 10).unwrap_err();
+        assert_eq!(err.to_string(), FeeError::Overflow.to_string());
+    }
+}
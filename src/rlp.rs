@@ -0,0 +1,308 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Recursive Length Prefix encoding, the serialization format of Ethereum
+//! transactions, as described in the
+//! [Ethereum documentation](https://ethereum.org/en/developers/docs/data-structures-and-encoding/rlp/).
+
+use crate::address::Address;
+use horror::Result;
+use primitive_types::U256;
+
+/// The maximum nesting of decoded lists.
+const MAX_DEPTH: usize = 32;
+
+/// Error returned when decoding RLP data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RlpError {
+    /// The data ends before the announced length.
+    Truncated,
+    /// Data remains after the decoded item.
+    TrailingBytes,
+    /// The item is not of the expected kind or size.
+    UnexpectedItem,
+    /// The lists are nested deeper than `MAX_DEPTH`.
+    TooDeep,
+}
+
+impl std::fmt::Display for RlpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RlpError::Truncated => write!(f, "Truncated RLP data"),
+            RlpError::TrailingBytes => write!(f, "Trailing bytes after RLP item"),
+            RlpError::UnexpectedItem => write!(f, "Unexpected RLP item"),
+            RlpError::TooDeep => write!(f, "RLP lists nested too deep"),
+        }
+    }
+}
+
+impl std::error::Error for RlpError {}
+
+/// An RLP item: either a byte string or a list of items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rlp {
+    Bytes(Vec<u8>),
+    List(Vec<Rlp>),
+}
+
+impl Rlp {
+    /// Encode the item.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Rlp::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => bytes.clone(),
+            Rlp::Bytes(bytes) => {
+                let mut result = encode_length(bytes.len(), 0x80);
+                result.extend_from_slice(bytes);
+                result
+            }
+            Rlp::List(items) => {
+                let payload = items.iter().flat_map(Rlp::encode).collect::<Vec<_>>();
+                let mut result = encode_length(payload.len(), 0xc0);
+                result.extend_from_slice(&payload);
+                result
+            }
+        }
+    }
+
+    /// Decode a single item spanning the whole data.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let (item, rest) = decode_item(data, 0)?;
+        if !rest.is_empty() {
+            return Err(RlpError::TrailingBytes.into());
+        }
+        Ok(item)
+    }
+
+    /// Return the byte string, failing on lists.
+    pub fn as_bytes(&self) -> Result<&[u8]> {
+        match self {
+            Rlp::Bytes(bytes) => Ok(bytes),
+            Rlp::List(_) => Err(RlpError::UnexpectedItem.into()),
+        }
+    }
+
+    /// Return the items of the list, failing on byte strings.
+    pub fn as_list(&self) -> Result<&[Rlp]> {
+        match self {
+            Rlp::List(items) => Ok(items),
+            Rlp::Bytes(_) => Err(RlpError::UnexpectedItem.into()),
+        }
+    }
+
+    /// Decode the byte string as a big-endian integer.
+    pub fn as_u256(&self) -> Result<U256> {
+        let bytes = self.as_bytes()?;
+        if bytes.len() > 32 {
+            return Err(RlpError::UnexpectedItem.into());
+        }
+        Ok(U256::from_big_endian(bytes))
+    }
+
+    /// Decode the byte string as a big-endian integer.
+    pub fn as_u64(&self) -> Result<u64> {
+        let bytes = self.as_bytes()?;
+        if bytes.len() > 8 {
+            return Err(RlpError::UnexpectedItem.into());
+        }
+        Ok(bytes.iter().fold(0, |acc, byte| (acc << 8) | *byte as u64))
+    }
+
+    /// Decode the byte string as an address, an empty string giving `None`.
+    pub fn as_address(&self) -> Result<Option<Address>> {
+        match self.as_bytes()? {
+            [] => Ok(None),
+            bytes => Ok(Some(Address::from_slice(bytes)?)),
+        }
+    }
+}
+
+impl From<&[u8]> for Rlp {
+    fn from(bytes: &[u8]) -> Self {
+        Rlp::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Rlp {
+    fn from(bytes: Vec<u8>) -> Self {
+        Rlp::Bytes(bytes)
+    }
+}
+
+impl From<u64> for Rlp {
+    fn from(value: u64) -> Self {
+        Rlp::from(U256::from(value))
+    }
+}
+
+impl From<U256> for Rlp {
+    fn from(value: U256) -> Self {
+        let mut bytes = [0u8; 32];
+        value.to_big_endian(&mut bytes);
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(32);
+        Rlp::Bytes(bytes[start..].to_vec())
+    }
+}
+
+impl From<Option<Address>> for Rlp {
+    fn from(address: Option<Address>) -> Self {
+        match address {
+            Some(address) => Rlp::Bytes(address.to_bytes().to_vec()),
+            None => Rlp::Bytes(Vec::new()),
+        }
+    }
+}
+
+impl From<Vec<Rlp>> for Rlp {
+    fn from(items: Vec<Rlp>) -> Self {
+        Rlp::List(items)
+    }
+}
+
+fn encode_length(len: usize, offset: u8) -> Vec<u8> {
+    if len <= 55 {
+        return vec![offset + len as u8];
+    }
+
+    let bytes = len.to_be_bytes();
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    let mut result = vec![offset + 55 + (bytes.len() - start) as u8];
+    result.extend_from_slice(&bytes[start..]);
+    result
+}
+
+/// Decode the item at the start of the data, returning it with the rest.
+fn decode_item(data: &[u8], depth: usize) -> Result<(Rlp, &[u8])> {
+    if depth > MAX_DEPTH {
+        return Err(RlpError::TooDeep.into());
+    }
+
+    let prefix = *data.first().ok_or(RlpError::Truncated)?;
+
+    let (is_list, offset, len) = match prefix {
+        0x00..=0x7f => return Ok((Rlp::Bytes(vec![prefix]), &data[1..])),
+        0x80..=0xb7 => (false, 1, (prefix - 0x80) as usize),
+        0xb8..=0xbf => {
+            let len_of_len = (prefix - 0xb7) as usize;
+            (false, 1 + len_of_len, read_length(&data[1..], len_of_len)?)
+        }
+        0xc0..=0xf7 => (true, 1, (prefix - 0xc0) as usize),
+        0xf8..=0xff => {
+            let len_of_len = (prefix - 0xf7) as usize;
+            (true, 1 + len_of_len, read_length(&data[1..], len_of_len)?)
+        }
+    };
+
+    let end = offset.checked_add(len).ok_or(RlpError::Truncated)?;
+    let payload = data.get(offset..end).ok_or(RlpError::Truncated)?;
+    let rest = &data[end..];
+
+    if !is_list {
+        return Ok((Rlp::Bytes(payload.to_vec()), rest));
+    }
+
+    let mut items = Vec::new();
+    let mut payload = payload;
+    while !payload.is_empty() {
+        let (item, tail) = decode_item(payload, depth + 1)?;
+        items.push(item);
+        payload = tail;
+    }
+
+    Ok((Rlp::List(items), rest))
+}
+
+fn read_length(data: &[u8], len_of_len: usize) -> Result<usize> {
+    let bytes = data.get(..len_of_len).ok_or(RlpError::Truncated)?;
+    if bytes.len() > std::mem::size_of::<usize>() {
+        return Err(RlpError::Truncated.into());
+    }
+    Ok(bytes
+        .iter()
+        .fold(0, |acc, byte| (acc << 8) | *byte as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(Rlp::from(b"dog".as_slice()).encode(), b"\x83dog");
+        assert_eq!(Rlp::from(0u64).encode(), [0x80]);
+        assert_eq!(Rlp::from(15u64).encode(), [0x0f]);
+        assert_eq!(Rlp::from(1024u64).encode(), [0x82, 0x04, 0x00]);
+        assert_eq!(Rlp::List(vec![]).encode(), [0xc0]);
+        assert_eq!(
+            Rlp::List(vec![
+                Rlp::from(b"cat".as_slice()),
+                Rlp::from(b"dog".as_slice())
+            ])
+            .encode(),
+            b"\xc8\x83cat\x83dog"
+        );
+
+        let lorem = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit".to_vec();
+        let encoded = Rlp::from(lorem.clone()).encode();
+        assert_eq!(&encoded[..2], &[0xb8, 0x38]);
+        assert_eq!(&encoded[2..], &lorem[..]);
+    }
+
+    #[test]
+    fn test_decode() {
+        let item = Rlp::List(vec![
+            Rlp::List(vec![]),
+            Rlp::List(vec![Rlp::List(vec![])]),
+            Rlp::List(vec![Rlp::List(vec![]), Rlp::List(vec![Rlp::List(vec![])])]),
+        ]);
+        let encoded = item.encode();
+        assert_eq!(encoded, [0xc7, 0xc0, 0xc1, 0xc0, 0xc3, 0xc0, 0xc1, 0xc0]);
+        assert_eq!(Rlp::decode(&encoded).unwrap(), item);
+
+        let item = Rlp::from(vec![0xaa; 1024]);
+        assert_eq!(Rlp::decode(&item.encode()).unwrap(), item);
+
+        assert!(Rlp::decode(&[0x83, b'd', b'o']).is_err());
+        assert!(Rlp::decode(&[0x0f, 0x0f]).is_err());
+        assert_eq!(
+            Rlp::decode(&[0x82, 0x04, 0x00]).unwrap().as_u64().unwrap(),
+            1024
+        );
+    }
+
+    #[test]
+    fn test_decode_deep() {
+        let mut item = Rlp::List(vec![]);
+        for _ in 0..MAX_DEPTH {
+            item = Rlp::List(vec![item]);
+        }
+        assert_eq!(Rlp::decode(&item.encode()).unwrap(), item);
+        let item = Rlp::List(vec![item]);
+        assert!(Rlp::decode(&item.encode()).is_err());
+
+        // lists nested far deeper than the stack allows
+        let depth = 300_000;
+        let mut data = Vec::with_capacity(depth * 4);
+        for i in 0..depth {
+            data.push(0xfa);
+            data.extend_from_slice(&((depth - i - 1) * 4).to_be_bytes()[5..]);
+        }
+        assert!(Rlp::decode(&data).is_err());
+        assert!(crate::transaction::Transaction::decode(&data).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{BlockNumber, Provider, RpcError, Transport};
+use crate::address::Address;
+use horror::Result;
+use primitive_types::U256;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// The maximum number of requests sent in a single batch. Larger batches
+/// are split, as most nodes reject batches above a few hundred requests.
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// A list of JSON-RPC requests sent together in one round trip.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Batch {
+    requests: Vec<(String, Value)>,
+}
+
+impl Batch {
+    /// Create a new empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a request to the batch and return its index in the responses.
+    pub fn push(&mut self, method: &str, params: Value) -> usize {
+        self.requests.push((method.to_string(), params));
+        self.requests.len() - 1
+    }
+
+    /// Return the number of requests in the batch.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Return true if the batch has no request.
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
+
+/// The responses to a batch, in the order of the requests. Every request
+/// succeeds or fails on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResponse(Vec<std::result::Result<Value, RpcError>>);
+
+impl BatchResponse {
+    /// Deserialize the result of the request at the given index.
+    pub fn get<R: DeserializeOwned>(&self, index: usize) -> Result<R> {
+        match self.0.get(index) {
+            Some(Ok(result)) => Ok(serde_json::from_value(result.clone())?),
+            Some(Err(err)) => Err(err.clone().into()),
+            None => Err(RpcError::InvalidResponse(format!("no response at {}", index)).into()),
+        }
+    }
+
+    /// Return the raw results.
+    pub fn results(&self) -> &[std::result::Result<Value, RpcError>] {
+        &self.0
+    }
+
+    /// Return the number of responses.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return true if there is no response.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T: Transport> Provider<T> {
+    /// Send the given batch. An error is returned only when the whole
+    /// batch fails; errors of single requests are kept in the response.
+    pub fn send_batch(&self, batch: &Batch) -> Result<BatchResponse> {
+        let mut results = Vec::with_capacity(batch.len());
+
+        for chunk in batch.requests.chunks(MAX_BATCH_SIZE) {
+            let ids = chunk.iter().map(|_| self.next_id()).collect::<Vec<_>>();
+            let request = chunk
+                .iter()
+                .zip(&ids)
+                .map(|((method, params), id)| super::request(*id, method, params.clone()))
+                .collect::<Vec<_>>();
+
+            let responses = match self.transport().send(&Value::Array(request))? {
+                Value::Array(responses) => responses,
+                // A single object is returned when the batch itself is rejected.
+                response => {
+                    return Err(super::response(response)
+                        .err()
+                        .unwrap_or_else(|| {
+                            RpcError::InvalidResponse("expected a batch response".to_string())
+                        })
+                        .into())
+                }
+            };
+
+            // Responses may come in any order, match them by id.
+            let mut responses = responses
+                .into_iter()
+                .filter_map(|response| Some((response.get("id")?.as_u64()?, response)))
+                .collect::<HashMap<_, _>>();
+
+            results.extend(ids.iter().map(|id| match responses.remove(id) {
+                Some(response) => super::response(response),
+                None => Err(RpcError::InvalidResponse(format!(
+                    "missing response {}",
+                    id
+                ))),
+            }));
+        }
+
+        Ok(BatchResponse(results))
+    }
+
+    /// Return the balances of the given addresses, using batched requests.
+    pub fn get_balances(
+        &self,
+        addresses: &[Address],
+        block: BlockNumber,
+    ) -> Result<Vec<Result<U256>>> {
+        let mut batch = Batch::new();
+        for address in addresses {
+            batch.push("eth_getBalance", json!([address, block]));
+        }
+
+        let response = self.send_batch(&batch)?;
+        Ok((0..addresses.len()).map(|i| response.get(i)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::MockTransport;
+
+    #[test]
+    fn test_batch() {
+        let transport = MockTransport::new();
+        transport.on("eth_getBalance", |params| {
+            match params[0].as_str().unwrap() {
+                "0x0000000000000000000000000000000000000000" => Err((-32000, "boom".to_string())),
+                _ => Ok(json!("0x10")),
+            }
+        });
+
+        let provider = Provider::new(transport);
+        let addresses = (0..250u8)
+            .map(|i| Address::new([i; 20]))
+            .collect::<Vec<_>>();
+        let balances = provider
+            .get_balances(&addresses, BlockNumber::Latest)
+            .unwrap();
+
+        assert_eq!(balances.len(), 250);
+        assert!(balances[0].is_err());
+        assert_eq!(*balances[1].as_ref().unwrap(), U256::from(16));
+        assert_eq!(*balances[249].as_ref().unwrap(), U256::from(16));
+        assert_eq!(provider.transport().requests().len(), 250);
+    }
+
+    #[test]
+    fn test_batch_out_of_order() {
+        struct Reversed(MockTransport);
+
+        impl Transport for Reversed {
+            fn send(&self, request: &Value) -> Result<Value> {
+                match self.0.send(request)? {
+                    Value::Array(mut responses) => {
+                        responses.reverse();
+                        responses.pop();
+                        Ok(Value::Array(responses))
+                    }
+                    response => Ok(response),
+                }
+            }
+        }
+
+        let transport = MockTransport::new();
+        transport.push("eth_chainId", "0x1");
+        transport.push("eth_blockNumber", "0x2");
+        transport.push("net_version", "3");
+
+        let provider = Provider::new(Reversed(transport));
+        let mut batch = Batch::new();
+        let chain_id = batch.push("eth_chainId", json!([]));
+        let block_number = batch.push("eth_blockNumber", json!([]));
+        let version = batch.push("net_version", json!([]));
+
+        let response = provider.send_batch(&batch).unwrap();
+        assert!(response.get::<U256>(chain_id).is_err());
+        assert_eq!(response.get::<U256>(block_number).unwrap(), U256::from(2));
+        assert_eq!(response.get::<String>(version).unwrap(), "3");
+    }
+}
@@ -0,0 +1,175 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{RpcError, Transport};
+use horror::Result;
+use serde_json::Value;
+use std::time::Duration;
+
+/// The default timeout of an HTTP request.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A transport sending requests to a node over HTTP(S).
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl HttpTransport {
+    /// Create a new transport for the given URL, with the default timeout.
+    pub fn new(url: &str) -> Self {
+        Self::with_timeout(url, DEFAULT_TIMEOUT)
+    }
+
+    /// Create a new transport for the given URL and request timeout.
+    pub fn with_timeout(url: &str, timeout: Duration) -> Self {
+        Self {
+            url: url.to_string(),
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+
+    /// Return the URL of the node.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(&self, request: &Value) -> Result<Value> {
+        match self.agent.post(&self.url).send_json(request) {
+            Ok(response) => Ok(response.into_json()?),
+            // Some nodes answer JSON-RPC errors with an HTTP error status,
+            // keep the JSON-RPC error when the body holds one.
+            Err(ureq::Error::Status(code, response)) => {
+                let body = response.into_string()?;
+                match serde_json::from_str::<Value>(&body) {
+                    Ok(value) if value.get("error").is_some() => Ok(value),
+                    _ => Err(RpcError::InvalidResponse(format!("HTTP {}: {}", code, body)).into()),
+                }
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    /// Read an HTTP request from the given stream and return its body.
+    fn read_request(reader: &mut BufReader<TcpStream>) -> Vec<u8> {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" || line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut request = vec![0u8; length];
+        reader.read_exact(&mut request).unwrap();
+        request
+    }
+
+    /// Write an HTTP response with the given status and JSON body.
+    fn write_response(stream: &mut TcpStream, status: u16, body: &Value) {
+        let body = body.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+        .unwrap();
+    }
+
+    /// Start a single-request HTTP server answering with the given body.
+    /// Return its URL and a handle joining to the received request body.
+    pub(crate) fn serve(status: u16, body: Value) -> (String, std::thread::JoinHandle<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let request = read_request(&mut reader);
+            write_response(&mut reader.into_inner(), status, &body);
+            serde_json::from_slice(&request).unwrap()
+        });
+
+        (url, handle)
+    }
+
+    /// Start an HTTP server answering every request with the given
+    /// transport, standing in for a node or a signing service. Return its
+    /// URL.
+    pub(crate) fn serve_transport<T: Transport + 'static>(transport: T) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let request = serde_json::from_slice(&read_request(&mut reader)).unwrap();
+                let response = transport.send(&request).unwrap();
+                write_response(&mut reader.into_inner(), 200, &response);
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    fn test_http() {
+        let (url, handle) = serve(200, json!({ "jsonrpc": "2.0", "id": 1, "result": "0x1" }));
+        let transport = HttpTransport::new(&url);
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_chainId", "params": [] });
+
+        let response = transport.send(&request).unwrap();
+        assert_eq!(response["result"], "0x1");
+        assert_eq!(handle.join().unwrap(), request);
+    }
+
+    #[test]
+    fn test_http_error_status() {
+        let error =
+            json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32000, "message": "boom" } });
+        let (url, handle) = serve(500, error.clone());
+        let transport = HttpTransport::new(&url);
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_chainId", "params": [] });
+
+        assert_eq!(transport.send(&request).unwrap(), error);
+        handle.join().unwrap();
+
+        let (url, handle) = serve(502, json!("bad gateway"));
+        assert!(HttpTransport::new(&url).send(&request).is_err());
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Transport;
+use horror::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A transport sending requests to a local node over its IPC Unix socket,
+/// e.g. `~/.ethereum/geth.ipc`.
+///
+/// The connection is dropped after an I/O error, such as a timeout in the
+/// middle of a response, so that the next request does not read what is
+/// left of it. It is opened again by the next request.
+#[derive(Debug)]
+pub struct IpcTransport {
+    path: PathBuf,
+    timeout: Option<Duration>,
+    stream: Mutex<Option<(UnixStream, BufReader<UnixStream>)>>,
+}
+
+impl IpcTransport {
+    /// Connect to the socket at the given path.
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open(path.as_ref(), None)
+    }
+
+    /// Connect to the socket at the given path, failing reads and writes
+    /// that take longer than the given timeout.
+    pub fn connect_with_timeout<P: AsRef<Path>>(path: P, timeout: Duration) -> Result<Self> {
+        Self::open(path.as_ref(), Some(timeout))
+    }
+
+    fn open(path: &Path, timeout: Option<Duration>) -> Result<Self> {
+        let transport = Self {
+            path: path.to_path_buf(),
+            timeout,
+            stream: Mutex::new(None),
+        };
+        *transport.stream.lock().unwrap() = Some(transport.connection()?);
+        Ok(transport)
+    }
+
+    fn connection(&self) -> Result<(UnixStream, BufReader<UnixStream>)> {
+        let stream = UnixStream::connect(&self.path)?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok((stream, reader))
+    }
+}
+
+impl Transport for IpcTransport {
+    fn send(&self, request: &Value) -> Result<Value> {
+        let mut guard = self.stream.lock().unwrap();
+        if guard.is_none() {
+            *guard = Some(self.connection()?);
+        }
+        let (writer, reader) = guard.as_mut().unwrap();
+
+        let result = serde_json::to_writer(&mut *writer, request)
+            .map_err(Into::into)
+            .and_then(|_| writer.flush())
+            .and_then(|_| {
+                // The node does not delimit its responses, read exactly one
+                // JSON value from the stream.
+                let mut deserializer = serde_json::Deserializer::from_reader(reader);
+                Value::deserialize(&mut deserializer).map_err(Into::into)
+            });
+        if result.is_err() {
+            *guard = None;
+        }
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_ipc() {
+        let path = std::env::temp_dir().join(format!("laron-wallet-{}.ipc", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let requests = serde_json::Deserializer::from_reader(stream).into_iter::<Value>();
+            for request in requests.take(2) {
+                let request = request.unwrap();
+                let response =
+                    json!({ "jsonrpc": "2.0", "id": request["id"], "result": request["method"] });
+                serde_json::to_writer(&mut writer, &response).unwrap();
+            }
+        });
+
+        let transport = IpcTransport::connect(&path).unwrap();
+        for (id, method) in [(1, "eth_chainId"), (2, "eth_blockNumber")] {
+            let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": [] });
+            let response = transport.send(&request).unwrap();
+            assert_eq!(response["id"], id);
+            assert_eq!(response["result"], method);
+        }
+
+        handle.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_ipc_timeout() {
+        let path =
+            std::env::temp_dir().join(format!("laron-wallet-timeout-{}.ipc", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let handle = std::thread::spawn(move || {
+            // the first response is only completed once the client gave up
+            // and connected again
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(br#"{"jsonrpc":"2.0","id":1,"#).unwrap();
+            let (mut second, _) = listener.accept().unwrap();
+            let _ = stream.write_all(br#""result":"late"}"#);
+
+            let request = serde_json::Deserializer::from_reader(second.try_clone().unwrap())
+                .into_iter::<Value>()
+                .next()
+                .unwrap()
+                .unwrap();
+            let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": "ok" });
+            serde_json::to_writer(&mut second, &response).unwrap();
+        });
+
+        let transport =
+            IpcTransport::connect_with_timeout(&path, Duration::from_millis(100)).unwrap();
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_chainId", "params": [] });
+        assert!(transport.send(&request).is_err());
+
+        let request = json!({ "jsonrpc": "2.0", "id": 2, "method": "eth_chainId", "params": [] });
+        let response = transport.send(&request).unwrap();
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["result"], "ok");
+
+        handle.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Transport;
+use horror::Result;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+type Handler = Box<dyn Fn(&Value) -> MockResponse + Send + Sync>;
+
+/// The result of a mocked call: either a JSON result or an error code and
+/// message.
+pub type MockResponse = std::result::Result<Value, (i64, String)>;
+
+/// An in-process transport answering requests with canned responses, used
+/// to test wallet flows without a node.
+///
+/// Responses queued with [`MockTransport::push`] are returned first, in
+/// order. Once the queue of a method is empty, the handler registered with
+/// [`MockTransport::on`] is called. Calls to other methods fail with the
+/// `-32601` "method not found" error.
+#[derive(Default)]
+pub struct MockTransport {
+    queues: Mutex<HashMap<String, VecDeque<MockResponse>>>,
+    handlers: Mutex<HashMap<String, Handler>>,
+    requests: Mutex<Vec<Value>>,
+}
+
+impl MockTransport {
+    /// Create a new mock transport with no responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a result for the next call to the given method.
+    pub fn push<T: Serialize>(&self, method: &str, result: T) {
+        let result = serde_json::to_value(result).expect("mock result must serialize");
+        self.queue(method, Ok(result));
+    }
+
+    /// Queue an error for the next call to the given method.
+    pub fn push_error(&self, method: &str, code: i64, message: &str) {
+        self.queue(method, Err((code, message.to_string())));
+    }
+
+    /// Register a handler answering every call to the given method, once
+    /// its queued responses are exhausted. The handler receives the params.
+    pub fn on<F>(&self, method: &str, handler: F)
+    where
+        F: Fn(&Value) -> MockResponse + Send + Sync + 'static,
+    {
+        self.handlers
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), Box::new(handler));
+    }
+
+    /// Return every request received so far.
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Return the requests received so far for the given method.
+    pub fn requests_for(&self, method: &str) -> Vec<Value> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request["method"] == method)
+            .collect()
+    }
+
+    fn queue(&self, method: &str, response: MockResponse) {
+        self.queues
+            .lock()
+            .unwrap()
+            .entry(method.to_string())
+            .or_default()
+            .push_back(response);
+    }
+
+    fn handle(&self, request: &Value) -> Value {
+        self.requests.lock().unwrap().push(request.clone());
+
+        let method = request["method"].as_str().unwrap_or_default();
+        let queued = self
+            .queues
+            .lock()
+            .unwrap()
+            .get_mut(method)
+            .and_then(VecDeque::pop_front);
+        let response = match queued {
+            Some(response) => response,
+            None => match self.handlers.lock().unwrap().get(method) {
+                Some(handler) => handler(&request["params"]),
+                None => Err((-32601, format!("the method {} does not exist", method))),
+            },
+        };
+
+        match response {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": code, "message": message },
+            }),
+        }
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: &Value) -> Result<Value> {
+        match request {
+            Value::Array(requests) => Ok(requests.iter().map(|r| self.handle(r)).collect()),
+            request => Ok(self.handle(request)),
+        }
+    }
+}
+
+impl std::fmt::Debug for MockTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockTransport")
+            .field("requests", &self.requests.lock().unwrap().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock() {
+        let transport = MockTransport::new();
+        transport.push("eth_blockNumber", "0x1");
+        transport.on("eth_blockNumber", |_| Ok(json!("0x2")));
+
+        let request =
+            json!({ "jsonrpc": "2.0", "id": 7, "method": "eth_blockNumber", "params": [] });
+        assert_eq!(transport.send(&request).unwrap()["result"], "0x1");
+        assert_eq!(transport.send(&request).unwrap()["result"], "0x2");
+        assert_eq!(transport.send(&request).unwrap()["id"], 7);
+
+        let request = json!({ "jsonrpc": "2.0", "id": 8, "method": "eth_unknown", "params": [] });
+        assert_eq!(transport.send(&request).unwrap()["error"]["code"], -32601);
+        assert_eq!(transport.requests_for("eth_blockNumber").len(), 3);
+    }
+}
@@ -0,0 +1,156 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # JSON-RPC Client
+//!
+//! This module implements a client for the Ethereum
+//! [JSON-RPC API](https://ethereum.org/en/developers/docs/apis/json-rpc/).
+//! A [`Provider`] exposes typed methods on top of a [`Transport`], which only
+//! has to send a JSON-RPC payload and return the response. The following
+//! transports are available:
+//! - [`HttpTransport`], behind the `http` feature flag
+//! - [`IpcTransport`], over a Unix socket
+//! - [`WsTransport`], behind the `ws` feature flag, which also supports
+//!   `eth_subscribe` subscriptions
+//! - [`MockTransport`], an in-process server to test wallet flows offline
+//!
+//! Requests can be sent together with a [`Batch`], in which every request
+//! succeeds or fails on its own.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::rpc::{MockTransport, Provider};
+//!
+//! let transport = MockTransport::new();
+//! transport.push("eth_chainId", "0x1");
+//!
+//! let provider = Provider::new(transport);
+//! assert_eq!(provider.chain_id().unwrap(), 1);
+//! ```
+
+mod batch;
+#[cfg(feature = "http")]
+pub(crate) mod http;
+#[cfg(unix)]
+mod ipc;
+mod mock;
+mod provider;
+mod types;
+#[cfg(feature = "ws")]
+mod ws;
+
+pub use batch::*;
+
+#[cfg(feature = "http")]
+pub use http::*;
+#[cfg(unix)]
+pub use ipc::*;
+pub use mock::*;
+pub use provider::*;
+pub use types::*;
+#[cfg(feature = "ws")]
+pub use ws::*;
+
+use horror::Result;
+use serde_json::Value;
+
+/// Error returned by a JSON-RPC call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcError {
+    /// The server returned a JSON-RPC error object.
+    Response {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+    /// The server returned a malformed response.
+    InvalidResponse(String),
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RpcError::Response { code, message, .. } => {
+                write!(f, "RPC error {}: {}", code, message)
+            }
+            RpcError::InvalidResponse(response) => write!(f, "Invalid response: {}", response),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// A transport sends JSON-RPC payloads to a node and returns its responses.
+pub trait Transport: Send + Sync {
+    /// Send the given JSON-RPC request and return the raw response.
+    fn send(&self, request: &Value) -> Result<Value>;
+}
+
+impl<T: Transport + ?Sized> Transport for &T {
+    fn send(&self, request: &Value) -> Result<Value> {
+        (**self).send(request)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&self, request: &Value) -> Result<Value> {
+        (**self).send(request)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn send(&self, request: &Value) -> Result<Value> {
+        (**self).send(request)
+    }
+}
+
+/// Build a JSON-RPC request object.
+pub(crate) fn request(id: u64, method: &str, params: Value) -> Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": params,
+    })
+}
+
+/// Extract the result of a JSON-RPC response object.
+pub(crate) fn response(response: Value) -> std::result::Result<Value, RpcError> {
+    if let Some(error) = response.get("error") {
+        return Err(RpcError::Response {
+            code: error
+                .get("code")
+                .and_then(Value::as_i64)
+                .unwrap_or_default(),
+            message: error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            data: error.get("data").cloned(),
+        });
+    }
+
+    match response {
+        Value::Object(mut object) if object.contains_key("result") => {
+            Ok(object.remove("result").unwrap_or_default())
+        }
+        response => Err(RpcError::InvalidResponse(response.to_string())),
+    }
+}
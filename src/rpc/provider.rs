@@ -0,0 +1,275 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{
+    BlockNumber, Bytes, CallRequest, FeeHistory, RpcError, TransactionInfo, TransactionReceipt,
+    Transport,
+};
+use crate::address::Address;
+use horror::Result;
+use primitive_types::{H256, U256};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A JSON-RPC client with typed methods for the `eth` namespace.
+#[derive(Debug)]
+pub struct Provider<T> {
+    transport: T,
+    next_id: AtomicU64,
+}
+
+impl<T: Transport> Provider<T> {
+    /// Create a new provider over the given transport.
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Return the underlying transport.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Return a new request id.
+    pub(crate) fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Call the given method and deserialize its result.
+    pub fn request<R: DeserializeOwned>(&self, method: &str, params: Value) -> Result<R> {
+        let result = super::response(self.send_request(method, params)?)?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Send the given method with a new id, and return the raw response
+    /// once checked to answer this request.
+    pub(crate) fn send_request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id();
+        let response = self.transport.send(&super::request(id, method, params))?;
+        match response.get("id") {
+            Some(response_id) if *response_id == id => Ok(response),
+            // Requests the node could not read are answered with a null id.
+            Some(Value::Null) if response.get("error").is_some() => Ok(response),
+            _ => Err(RpcError::InvalidResponse(response.to_string()).into()),
+        }
+    }
+
+    /// Return the chain id used for signing replay-protected transactions.
+    pub fn chain_id(&self) -> Result<u64> {
+        to_u64(self.request("eth_chainId", json!([]))?)
+    }
+
+    /// Return the number of the latest block.
+    pub fn block_number(&self) -> Result<u64> {
+        to_u64(self.request("eth_blockNumber", json!([]))?)
+    }
+
+    /// Return the balance of the given address, in wei.
+    pub fn get_balance(&self, address: &Address, block: BlockNumber) -> Result<U256> {
+        self.request("eth_getBalance", json!([address, block]))
+    }
+
+    /// Return the number of transactions sent from the given address.
+    pub fn get_transaction_count(&self, address: &Address, block: BlockNumber) -> Result<u64> {
+        to_u64(self.request("eth_getTransactionCount", json!([address, block]))?)
+    }
+
+    /// Return an estimate of the gas needed by the given call.
+    pub fn estimate_gas(&self, call: &CallRequest) -> Result<U256> {
+        self.request("eth_estimateGas", json!([call]))
+    }
+
+    /// Submit a signed transaction and return its hash.
+    pub fn send_raw_transaction(&self, raw: &[u8]) -> Result<H256> {
+        self.request("eth_sendRawTransaction", json!([Bytes::from(raw)]))
+    }
+
+    /// Return the transaction with the given hash, if the node knows it.
+    pub fn get_transaction(&self, hash: &H256) -> Result<Option<TransactionInfo>> {
+        self.request("eth_getTransactionByHash", json!([hash]))
+    }
+
+    /// Return the receipt of the transaction with the given hash, if it
+    /// was mined.
+    pub fn get_transaction_receipt(&self, hash: &H256) -> Result<Option<TransactionReceipt>> {
+        self.request("eth_getTransactionReceipt", json!([hash]))
+    }
+
+    /// Execute the given call without creating a transaction and return
+    /// its output.
+    pub fn call(&self, call: &CallRequest, block: BlockNumber) -> Result<Vec<u8>> {
+        let output: Bytes = self.request("eth_call", json!([call, block]))?;
+        Ok(output.0)
+    }
+
+    /// Return the base fees and the priority fees at the given percentiles
+    /// of the last `block_count` blocks, up to `newest_block`.
+    pub fn fee_history(
+        &self,
+        block_count: u64,
+        newest_block: BlockNumber,
+        reward_percentiles: &[f64],
+    ) -> Result<FeeHistory> {
+        self.request(
+            "eth_feeHistory",
+            json!([
+                format!("0x{:x}", block_count),
+                newest_block,
+                reward_percentiles
+            ]),
+        )
+    }
+}
+
+fn to_u64(value: U256) -> Result<u64> {
+    if value > U256::from(u64::MAX) {
+        return Err(RpcError::InvalidResponse(value.to_string()).into());
+    }
+    Ok(value.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::MockTransport;
+
+    #[test]
+    fn test_provider() {
+        let transport = MockTransport::new();
+        transport.push("eth_chainId", "0x5");
+        transport.push("eth_getBalance", "0xde0b6b3a7640000");
+        transport.push("eth_getTransactionCount", "0x2a");
+        transport.push("eth_estimateGas", "0x5208");
+        transport.push(
+            "eth_call",
+            "0x0000000000000000000000000000000000000000000000000000000000000001",
+        );
+        transport.push(
+            "eth_sendRawTransaction",
+            "0x88df016429689c079f3b2f6ad39fa052532c56795b733da78a91ebe6a713944b",
+        );
+
+        let provider = Provider::new(transport);
+        let address = Address::new([0x11; 20]);
+
+        assert_eq!(provider.chain_id().unwrap(), 5);
+        assert_eq!(
+            provider.get_balance(&address, BlockNumber::Latest).unwrap(),
+            U256::exp10(18)
+        );
+        assert_eq!(
+            provider
+                .get_transaction_count(&address, BlockNumber::Pending)
+                .unwrap(),
+            42
+        );
+        assert_eq!(
+            provider.estimate_gas(&CallRequest::default()).unwrap(),
+            U256::from(21000)
+        );
+        assert_eq!(
+            provider
+                .call(&CallRequest::default(), BlockNumber::Number(1))
+                .unwrap()[31],
+            1
+        );
+        assert_eq!(
+            provider.send_raw_transaction(&[0xf8]).unwrap(),
+            "0x88df016429689c079f3b2f6ad39fa052532c56795b733da78a91ebe6a713944b"
+                .parse()
+                .unwrap()
+        );
+
+        let requests = provider.transport().requests();
+        assert_eq!(requests[1]["method"], "eth_getBalance");
+        assert_eq!(
+            requests[1]["params"],
+            json!(["0x1111111111111111111111111111111111111111", "latest"])
+        );
+        assert_eq!(requests[4]["params"][1], "0x1");
+        assert_eq!(requests[5]["params"], json!(["0xf8"]));
+    }
+
+    #[test]
+    fn test_fee_history() {
+        let transport = MockTransport::new();
+        transport.push(
+            "eth_feeHistory",
+            json!({
+                "oldestBlock": "0x10",
+                "baseFeePerGas": ["0x3b9aca00", "0x3b9aca01"],
+                "gasUsedRatio": [0.5],
+                "reward": [["0x1", "0x2"]]
+            }),
+        );
+
+        let provider = Provider::new(transport);
+        let history = provider
+            .fee_history(1, BlockNumber::Latest, &[25.0, 75.0])
+            .unwrap();
+        assert_eq!(history.oldest_block, U256::from(16));
+        assert_eq!(history.base_fee_per_gas[0], U256::from(1_000_000_000));
+        assert_eq!(history.reward[0][1], U256::from(2));
+        assert_eq!(
+            provider.transport().requests()[0]["params"],
+            json!(["0x1", "latest", [25.0, 75.0]])
+        );
+    }
+
+    #[test]
+    fn test_error() {
+        let transport = MockTransport::new();
+        transport.push_error("eth_sendRawTransaction", -32000, "nonce too low");
+
+        let provider = Provider::new(transport);
+        let err = provider.send_raw_transaction(&[0xf8]).unwrap_err();
+        assert!(err.to_string().contains("nonce too low"));
+        assert!(provider.chain_id().is_err());
+    }
+
+    #[test]
+    fn test_response_id() {
+        struct Fixed(Value);
+
+        impl Transport for Fixed {
+            fn send(&self, _: &Value) -> Result<Value> {
+                Ok(self.0.clone())
+            }
+        }
+
+        // a response left over from another request
+        let provider = Provider::new(Fixed(json!({ "jsonrpc": "2.0", "id": 2, "result": "0x1" })));
+        assert!(provider.chain_id().is_err());
+        assert_eq!(provider.chain_id().unwrap(), 1);
+
+        let provider = Provider::new(Fixed(json!({ "jsonrpc": "2.0", "result": "0x1" })));
+        assert!(provider.chain_id().is_err());
+
+        let provider = Provider::new(Fixed(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32700, "message": "parse error" }
+        })));
+        let err = provider.chain_id().unwrap_err();
+        assert!(err.to_string().contains("parse error"));
+    }
+}
@@ -0,0 +1,244 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{abi::RawLog, address::Address};
+use primitive_types::{H256, U256};
+use serde::{Deserialize, Serialize};
+
+/// Arbitrary binary data, serialized as a `0x` prefixed hex string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Bytes {
+    /// Return the underlying bytes.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for Bytes {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(&self.0)))
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let s = s.strip_prefix("0x").unwrap_or(&s);
+        hex::decode(s).map(Self).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A block number or one of the block tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockNumber {
+    #[default]
+    Latest,
+    Pending,
+    Earliest,
+    Safe,
+    Finalized,
+    Number(u64),
+}
+
+impl Serialize for BlockNumber {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            BlockNumber::Latest => serializer.serialize_str("latest"),
+            BlockNumber::Pending => serializer.serialize_str("pending"),
+            BlockNumber::Earliest => serializer.serialize_str("earliest"),
+            BlockNumber::Safe => serializer.serialize_str("safe"),
+            BlockNumber::Finalized => serializer.serialize_str("finalized"),
+            BlockNumber::Number(n) => serializer.serialize_str(&format!("0x{:x}", n)),
+        }
+    }
+}
+
+impl From<u64> for BlockNumber {
+    fn from(n: u64) -> Self {
+        BlockNumber::Number(n)
+    }
+}
+
+/// The parameters of `eth_call` and `eth_estimateGas`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_price: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Bytes>,
+}
+
+/// The result of `eth_feeHistory`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeHistory {
+    pub oldest_block: U256,
+    pub base_fee_per_gas: Vec<U256>,
+    pub gas_used_ratio: Vec<f64>,
+    #[serde(default)]
+    pub reward: Vec<Vec<U256>>,
+}
+
+/// A block header, as notified by the `newHeads` subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Header {
+    pub hash: Option<H256>,
+    pub parent_hash: H256,
+    pub number: Option<U256>,
+    pub timestamp: U256,
+    pub gas_limit: U256,
+    pub gas_used: U256,
+    #[serde(default)]
+    pub base_fee_per_gas: Option<U256>,
+}
+
+/// A log emitted by a transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: Bytes,
+    pub block_hash: Option<H256>,
+    pub block_number: Option<U256>,
+    pub transaction_hash: Option<H256>,
+    pub log_index: Option<U256>,
+    /// True when the log was removed by a chain reorganization.
+    #[serde(default)]
+    pub removed: bool,
+}
+
+impl From<&Log> for RawLog {
+    fn from(log: &Log) -> Self {
+        RawLog {
+            topics: log.topics.iter().map(|topic| topic.0).collect(),
+            data: log.data.to_vec(),
+        }
+    }
+}
+
+/// A transaction as returned by `eth_getTransactionByHash`, `None` block
+/// fields meaning that it is still pending.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionInfo {
+    pub hash: H256,
+    pub from: Address,
+    pub nonce: U256,
+    pub block_hash: Option<H256>,
+    pub block_number: Option<U256>,
+}
+
+/// The receipt of a mined transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionReceipt {
+    pub transaction_hash: H256,
+    pub block_hash: H256,
+    pub block_number: U256,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub contract_address: Option<Address>,
+    pub gas_used: U256,
+    #[serde(default)]
+    pub effective_gas_price: Option<U256>,
+    /// `1` on success and `0` on failure, absent before Byzantium.
+    #[serde(default)]
+    pub status: Option<U256>,
+    #[serde(default)]
+    pub logs: Vec<Log>,
+}
+
+impl TransactionReceipt {
+    /// Return whether the transaction succeeded.
+    pub fn is_success(&self) -> bool {
+        self.status.is_none_or(|status| !status.is_zero())
+    }
+}
+
+/// A filter selecting logs by emitting address and topics.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_block: Option<BlockNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_block: Option<BlockNumber>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub address: Vec<Address>,
+    /// The topics to match at each position, `None` matching any topic.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<Option<Vec<H256>>>,
+}
+
+impl LogFilter {
+    /// Create a new filter matching every log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match logs emitted by the given address.
+    pub fn address(mut self, address: Address) -> Self {
+        self.address.push(address);
+        self
+    }
+
+    /// Only match logs having one of the given topics at the given position.
+    pub fn topic(mut self, position: usize, topics: Vec<H256>) -> Self {
+        if self.topics.len() <= position {
+            self.topics.resize(position + 1, None);
+        }
+        self.topics[position] = Some(topics);
+        self
+    }
+}
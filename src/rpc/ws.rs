@@ -0,0 +1,407 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Header, Log, LogFilter, Provider, RpcError, Transport};
+use horror::Result;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::net::TcpStream;
+use std::sync::Mutex;
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+/// The maximum number of notifications kept for a subscription which is not
+/// read, after which the oldest ones are dropped.
+pub const MAX_QUEUED_NOTIFICATIONS: usize = 1024;
+
+/// A transport sending requests to a node over a WebSocket, which also
+/// receives the notifications of `eth_subscribe`.
+///
+/// The transport is blocking: while a [`Subscription`] waits for its next
+/// notification, other requests wait for the socket. Notifications are only
+/// kept for live subscriptions, up to [`MAX_QUEUED_NOTIFICATIONS`] each.
+#[derive(Debug)]
+pub struct WsTransport {
+    socket: Mutex<WebSocket<MaybeTlsStream<TcpStream>>>,
+    notifications: Mutex<HashMap<String, VecDeque<Value>>>,
+}
+
+impl WsTransport {
+    /// Connect to the given `ws://` or `wss://` URL.
+    pub fn connect(url: &str) -> Result<Self> {
+        let (socket, _) = tungstenite::connect(url)?;
+        Ok(Self {
+            socket: Mutex::new(socket),
+            notifications: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Block until the next notification of the given subscription.
+    fn next_notification(&self, subscription: &str) -> Result<Value> {
+        loop {
+            if let Some(result) = self.pop_notification(subscription) {
+                return Ok(result);
+            }
+
+            let mut socket = self.socket.lock().unwrap();
+            // Another subscription may have received our notification
+            // while we were waiting for the socket.
+            if let Some(result) = self.pop_notification(subscription) {
+                return Ok(result);
+            }
+            if let Some(message) = read_message(&mut socket)? {
+                self.stash(message)?;
+            }
+        }
+    }
+
+    fn pop_notification(&self, subscription: &str) -> Option<Value> {
+        self.notifications
+            .lock()
+            .unwrap()
+            .get_mut(subscription)
+            .and_then(VecDeque::pop_front)
+    }
+
+    /// Stash the given message if it is a notification, or return it.
+    /// Notifications of unknown subscriptions are dropped.
+    fn stash(&self, message: Value) -> Result<Option<Value>> {
+        if message["method"] != "eth_subscription" {
+            return Ok(Some(message));
+        }
+
+        let params = &message["params"];
+        let subscription = params["subscription"]
+            .as_str()
+            .ok_or_else(|| RpcError::InvalidResponse(message.to_string()))?;
+        if let Some(queue) = self.notifications.lock().unwrap().get_mut(subscription) {
+            if queue.len() == MAX_QUEUED_NOTIFICATIONS {
+                queue.pop_front();
+            }
+            queue.push_back(params["result"].clone());
+        }
+        Ok(None)
+    }
+
+    /// Start queueing the notifications of a new subscription. This runs
+    /// before the socket is released, so that notifications read right after
+    /// the response by another caller are not dropped.
+    fn register(&self, request: &Value, response: &Value) {
+        if request["method"] != "eth_subscribe" {
+            return;
+        }
+        if let Some(id) = response["result"].as_str() {
+            self.notifications
+                .lock()
+                .unwrap()
+                .entry(id.to_string())
+                .or_default();
+        }
+    }
+}
+
+impl Transport for WsTransport {
+    fn send(&self, request: &Value) -> Result<Value> {
+        let mut socket = self.socket.lock().unwrap();
+        socket.send(Message::Text(request.to_string()))?;
+
+        loop {
+            let message = match read_message(&mut socket)? {
+                Some(message) => message,
+                None => continue,
+            };
+            let response = match self.stash(message)? {
+                Some(response) => response,
+                None => continue,
+            };
+
+            let matches = match request {
+                Value::Array(_) => response.is_array(),
+                request => response.get("id") == request.get("id"),
+            };
+            if matches {
+                self.register(request, &response);
+                return Ok(response);
+            }
+        }
+    }
+}
+
+fn read_message(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Result<Option<Value>> {
+    match socket.read()? {
+        Message::Text(text) => Ok(Some(serde_json::from_str(&text)?)),
+        Message::Binary(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        _ => Ok(None),
+    }
+}
+
+/// A stream of notifications of an `eth_subscribe` subscription.
+///
+/// Iterating blocks until the next notification. The iterator ends after
+/// the first error, e.g. when the connection is closed. Dropping the
+/// subscription cancels it on the node, ignoring errors, and discards the
+/// notifications received afterwards.
+#[derive(Debug)]
+pub struct Subscription<'a, T> {
+    provider: &'a Provider<WsTransport>,
+    id: String,
+    done: bool,
+    active: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: DeserializeOwned> Subscription<'a, T> {
+    /// Return the subscription id.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Cancel the subscription.
+    pub fn unsubscribe(mut self) -> Result<bool> {
+        self.active = false;
+        self.provider.request("eth_unsubscribe", json!([self.id]))
+    }
+}
+
+impl<'a, T> Drop for Subscription<'a, T> {
+    fn drop(&mut self) {
+        let transport = self.provider.transport();
+        transport.notifications.lock().unwrap().remove(&self.id);
+        if self.active {
+            let _ = self
+                .provider
+                .request::<bool>("eth_unsubscribe", json!([self.id]));
+        }
+    }
+}
+
+impl<'a, T: DeserializeOwned> Iterator for Subscription<'a, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self
+            .provider
+            .transport()
+            .next_notification(&self.id)
+            .and_then(|value| Ok(serde_json::from_value(value)?));
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+impl Provider<WsTransport> {
+    /// Subscribe to the given `eth_subscribe` params.
+    pub fn subscribe<T: DeserializeOwned>(&self, params: Value) -> Result<Subscription<'_, T>> {
+        let id: String = self.request("eth_subscribe", params)?;
+        Ok(Subscription {
+            provider: self,
+            id,
+            done: false,
+            active: true,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Subscribe to the headers of new blocks.
+    pub fn subscribe_new_heads(&self) -> Result<Subscription<'_, Header>> {
+        self.subscribe(json!(["newHeads"]))
+    }
+
+    /// Subscribe to the logs matching the given filter.
+    pub fn subscribe_logs(&self, filter: &LogFilter) -> Result<Subscription<'_, Log>> {
+        self.subscribe(json!(["logs", filter]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+    use crate::rpc::Batch;
+    use primitive_types::U256;
+    use std::net::TcpListener;
+
+    /// A local stand-in for a node WebSocket endpoint. It answers
+    /// `eth_subscribe`, pushes a notification in front of every other
+    /// response, and answers batches. It returns the cancelled subscriptions
+    /// once the client disconnects.
+    fn serve() -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            let mut block = 0u64;
+            let mut unsubscribed = Vec::new();
+            let mut notify = |socket: &mut WebSocket<TcpStream>, subscription: &str| {
+                block += 1;
+                let result = match subscription {
+                    "0xheads" => json!({
+                        "parentHash": format!("0x{:064x}", block - 1),
+                        "hash": format!("0x{:064x}", block),
+                        "number": format!("0x{:x}", block),
+                        "timestamp": "0x0",
+                        "gasLimit": "0x1c9c380",
+                        "gasUsed": "0x0",
+                        "baseFeePerGas": "0x7"
+                    }),
+                    _ => json!({
+                        "address": "0x1111111111111111111111111111111111111111",
+                        "topics": [format!("0x{:064x}", block)],
+                        "data": "0x",
+                        "blockNumber": format!("0x{:x}", block),
+                        "removed": false
+                    }),
+                };
+                let message = json!({
+                    "jsonrpc": "2.0",
+                    "method": "eth_subscription",
+                    "params": { "subscription": subscription, "result": result }
+                });
+                socket.send(Message::Text(message.to_string())).unwrap();
+            };
+
+            loop {
+                let request: Value = match socket.read() {
+                    Ok(Message::Text(text)) => serde_json::from_str(&text).unwrap(),
+                    Ok(_) => continue,
+                    Err(_) => return unsubscribed,
+                };
+
+                let mut answer = |request: &Value| {
+                    let result = match request["method"].as_str().unwrap() {
+                        "eth_subscribe" if request["params"][0] == "newHeads" => json!("0xheads"),
+                        "eth_subscribe" => json!("0xlogs"),
+                        "eth_unsubscribe" => {
+                            let id = request["params"][0].as_str().unwrap().to_string();
+                            let known = !unsubscribed.contains(&id);
+                            unsubscribed.push(id);
+                            json!(known)
+                        }
+                        _ => json!("0x1"),
+                    };
+                    json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+                };
+
+                let response = match &request {
+                    Value::Array(requests) => requests.iter().map(&mut answer).collect(),
+                    request => answer(request),
+                };
+                if request["method"] == "eth_chainId" {
+                    notify(&mut socket, "0xheads");
+                }
+                if request["method"] == "net_version" {
+                    for _ in 0..MAX_QUEUED_NOTIFICATIONS + 2 {
+                        notify(&mut socket, "0xheads");
+                    }
+                    notify(&mut socket, "0xunknown");
+                }
+                socket.send(Message::Text(response.to_string())).unwrap();
+                if request["method"] == "eth_subscribe" {
+                    let subscription = response["result"].as_str().unwrap().to_string();
+                    notify(&mut socket, &subscription);
+                    notify(&mut socket, &subscription);
+                }
+            }
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let (url, handle) = serve();
+        let provider = Provider::new(WsTransport::connect(&url).unwrap());
+
+        let mut heads = provider.subscribe_new_heads().unwrap();
+        assert_eq!(heads.id(), "0xheads");
+        let head = heads.next().unwrap().unwrap();
+        assert_eq!(head.number, Some(U256::from(1)));
+        assert_eq!(head.base_fee_per_gas, Some(U256::from(7)));
+
+        // The notification received in front of this response is kept for
+        // the subscription.
+        assert_eq!(provider.chain_id().unwrap(), 1);
+        assert_eq!(heads.next().unwrap().unwrap().number, Some(U256::from(2)));
+        assert_eq!(heads.next().unwrap().unwrap().number, Some(U256::from(3)));
+
+        let filter = LogFilter::new().address(Address::new([0x11; 20]));
+        let logs = provider.subscribe_logs(&filter).unwrap();
+        let log = logs.take(2).last().unwrap().unwrap();
+        assert_eq!(log.block_number, Some(U256::from(5)));
+        let notifications = || provider.transport().notifications.lock().unwrap().clone();
+        assert!(!notifications().contains_key("0xlogs"));
+
+        let mut batch = Batch::new();
+        batch.push("eth_blockNumber", json!([]));
+        batch.push("net_version", json!([]));
+        let response = provider.send_batch(&batch).unwrap();
+        assert_eq!(response.get::<U256>(1).unwrap(), U256::from(1));
+
+        // Only the latest notifications are kept, and those of unknown
+        // subscriptions are dropped.
+        assert_eq!(
+            provider
+                .request::<String>("net_version", json!([]))
+                .unwrap(),
+            "0x1"
+        );
+        assert_eq!(notifications().len(), 1);
+        assert_eq!(notifications()["0xheads"].len(), MAX_QUEUED_NOTIFICATIONS);
+        assert_eq!(heads.next().unwrap().unwrap().number, Some(U256::from(8)));
+
+        assert!(heads.unsubscribe().unwrap());
+        assert!(notifications().is_empty());
+
+        // The logs subscription was cancelled when dropped.
+        drop(provider);
+        assert_eq!(handle.join().unwrap(), ["0xlogs", "0xheads"]);
+    }
+
+    #[test]
+    fn test_subscribe_queue() {
+        let (url, handle) = serve();
+        let transport = WsTransport::connect(&url).unwrap();
+
+        // The queue exists as soon as the node answers, before another
+        // caller can read the first notifications.
+        let request = crate::rpc::request(1, "eth_subscribe", json!(["newHeads"]));
+        transport.send(&request).unwrap();
+        assert!(transport
+            .notifications
+            .lock()
+            .unwrap()
+            .contains_key("0xheads"));
+        transport
+            .send(&crate::rpc::request(2, "eth_chainId", json!([])))
+            .unwrap();
+        // Both notifications sent after the answer, and the one in front of
+        // the next response, are kept.
+        assert_eq!(transport.notifications.lock().unwrap()["0xheads"].len(), 3);
+
+        drop(transport);
+        assert!(handle.join().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Recoverable secp256k1 signatures as used by Ethereum.
+//!
+//! A signature is stored as its `r` and `s` components together with the
+//! recovery id (the y-parity of the ephemeral point). Signatures can be
+//! parsed from the 65 bytes `r || s || v` form, with `v` either in the
+//! `0/1` or `27/28` convention, or from the 64 bytes compact form described
+//! in [EIP-2098](https://eips.ethereum.org/EIPS/eip-2098).
+
+use crate::address::Address;
+use horror::{Error, Result};
+use k256::ecdsa::{RecoveryId, SigningKey, VerifyingKey};
+use laron_crypto::PrivateKey;
+
+/// Error returned when parsing or recovering a signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The signature is neither 64 nor 65 bytes long.
+    InvalidLength(usize),
+    /// The recovery id is not one of 0, 1, 27 or 28.
+    InvalidRecoveryId(u64),
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SignatureError::InvalidLength(len) => write!(f, "Invalid signature length: {}", len),
+            SignatureError::InvalidRecoveryId(v) => write!(f, "Invalid recovery id: {}", v),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// A recoverable ECDSA signature over secp256k1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Signature {
+    r: [u8; 32],
+    s: [u8; 32],
+    recovery_id: u8,
+}
+
+impl Signature {
+    /// Create a new signature from its components.
+    /// The recovery id may be given in the `0/1` or `27/28` convention.
+    pub fn new(r: [u8; 32], s: [u8; 32], v: u64) -> Result<Self> {
+        let recovery_id = match v {
+            0 | 1 => v as u8,
+            27 | 28 => (v - 27) as u8,
+            _ => return Err(SignatureError::InvalidRecoveryId(v).into()),
+        };
+
+        Ok(Self { r, s, recovery_id })
+    }
+
+    /// Parse a signature from either the 65 bytes `r || s || v` form or the
+    /// 64 bytes EIP-2098 compact form.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.len() {
+            65 => Self::new(
+                bytes[..32].try_into()?,
+                bytes[32..64].try_into()?,
+                bytes[64].into(),
+            ),
+            64 => {
+                let mut s: [u8; 32] = bytes[32..].try_into()?;
+                let recovery_id = s[0] >> 7;
+                s[0] &= 0x7f;
+                Self::new(bytes[..32].try_into()?, s, recovery_id.into())
+            }
+            len => Err(SignatureError::InvalidLength(len).into()),
+        }
+    }
+
+    /// Sign the given 32 bytes hash with the given private key.
+    /// The nonce is derived deterministically as described in RFC6979.
+    pub fn sign_hash(key: &PrivateKey, hash: &[u8; 32]) -> Result<Self> {
+        let (signature, recovery_id) = signing_key(key)?.sign_prehash_recoverable(hash)?;
+        let (r, s) = signature.split_bytes();
+
+        Ok(Self {
+            r: r.into(),
+            s: s.into(),
+            recovery_id: recovery_id.to_byte(),
+        })
+    }
+
+    /// Recover the public key that produced this signature over the given hash.
+    pub fn recover_public_key(&self, hash: &[u8; 32]) -> Result<k256::PublicKey> {
+        let signature = k256::ecdsa::Signature::from_scalars(self.r, self.s)?;
+        let recovery_id = RecoveryId::from_byte(self.recovery_id)
+            .ok_or(SignatureError::InvalidRecoveryId(self.recovery_id.into()))?;
+        let key = VerifyingKey::recover_from_prehash(hash, &signature, recovery_id)?;
+        Ok(key.into())
+    }
+
+    /// Recover the address that produced this signature over the given hash.
+    pub fn recover(&self, hash: &[u8; 32]) -> Result<Address> {
+        Ok(Address::from_point(&self.recover_public_key(hash)?))
+    }
+
+    /// Return the `r` component of the signature.
+    pub fn r(&self) -> &[u8; 32] {
+        &self.r
+    }
+
+    /// Return the `s` component of the signature.
+    pub fn s(&self) -> &[u8; 32] {
+        &self.s
+    }
+
+    /// Return the recovery id, either 0 or 1.
+    pub fn recovery_id(&self) -> u8 {
+        self.recovery_id
+    }
+
+    /// Return the recovery id in the `27/28` convention.
+    pub fn v(&self) -> u8 {
+        self.recovery_id + 27
+    }
+
+    /// Return the 65 bytes `r || s || v` form of the signature, with `v`
+    /// in the `27/28` convention.
+    pub fn to_bytes(&self) -> [u8; 65] {
+        let mut bytes = [0u8; 65];
+        bytes[..32].copy_from_slice(&self.r);
+        bytes[32..64].copy_from_slice(&self.s);
+        bytes[64] = self.v();
+        bytes
+    }
+
+    /// Return the 64 bytes EIP-2098 compact form of the signature.
+    pub fn to_compact(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.r);
+        bytes[32..].copy_from_slice(&self.s);
+        bytes[32] |= self.recovery_id << 7;
+        bytes
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{}", hex::encode(self.to_bytes()))
+    }
+}
+
+impl std::str::FromStr for Signature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        Self::from_bytes(&hex::decode(s)?)
+    }
+}
+
+pub(crate) fn signing_key(key: &PrivateKey) -> Result<SigningKey> {
+    Ok(SigningKey::from_slice(&key.to_bytes()[..])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact() {
+        // https://eips.ethereum.org/EIPS/eip-2098#test-cases
+        let signature: Signature = "0x68a020a209d3d56c46f38cc50a33f704f4a9a10a59377f8dd762ac66910e9b907e865ad05c4035ab5792787d4a0297a43617ae897930a6fe4d822b8faea520641b".parse().unwrap();
+        assert_eq!(
+            hex::encode(signature.to_compact()),
+            "68a020a209d3d56c46f38cc50a33f704f4a9a10a59377f8dd762ac66910e9b907e865ad05c4035ab5792787d4a0297a43617ae897930a6fe4d822b8faea52064"
+        );
+
+        let signature: Signature = "0x9328da16089fcba9bececa81663203989f2df5fe1faa6291a45381c81bd17f76139c6d6b623b42da56557e5e734a43dc83345ddfadec52cbe24d0cc64f5507931c".parse().unwrap();
+        let compact = signature.to_compact();
+        assert_eq!(
+            hex::encode(compact),
+            "9328da16089fcba9bececa81663203989f2df5fe1faa6291a45381c81bd17f76939c6d6b623b42da56557e5e734a43dc83345ddfadec52cbe24d0cc64f550793"
+        );
+        assert_eq!(Signature::from_bytes(&compact).unwrap(), signature);
+    }
+}
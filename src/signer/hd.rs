@@ -0,0 +1,109 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Signer;
+use crate::{
+    address::Address,
+    bips::{bip32::ExtendedKey, bip39::Mnemonic, DerivationPath},
+    signature::Signature,
+};
+use horror::Result;
+
+/// A signer holding a key derived from a BIP32 root.
+#[derive(Debug, Clone)]
+pub struct HdAccount {
+    key: ExtendedKey,
+    path: DerivationPath,
+    address: Address,
+}
+
+impl HdAccount {
+    /// Derive the account at the given path from the given root key.
+    pub fn new(root: &ExtendedKey, path: &DerivationPath) -> Result<Self> {
+        let key = root.derive_path(path)?;
+        let address = Address::from_public_key(key.public_key())?;
+        Ok(Self {
+            key,
+            path: path.clone(),
+            address,
+        })
+    }
+
+    /// Derive the account at the given path from the given mnemonic.
+    pub fn from_mnemonic(
+        mnemonic: &Mnemonic,
+        passphrase: &str,
+        path: &DerivationPath,
+    ) -> Result<Self> {
+        let root = ExtendedKey::new_master(&mnemonic.to_seed(passphrase))?;
+        Self::new(&root, path)
+    }
+
+    /// Return the derivation path of the account.
+    pub fn path(&self) -> &DerivationPath {
+        &self.path
+    }
+
+    /// Return the extended key of the account.
+    pub fn extended_key(&self) -> &ExtendedKey {
+        &self.key
+    }
+}
+
+impl Signer for HdAccount {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<Signature> {
+        Signature::sign_hash(self.key.private_key(), hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bips::wordlists::Language, signer::Wallet};
+
+    #[test]
+    fn test_hd_account() {
+        let mnemonic = Mnemonic::from_phrase(
+            "test test test test test test test test test test test junk",
+            Language::English,
+        )
+        .unwrap();
+        let path = DerivationPath::parse("m/44'/60'/0'/0/0").unwrap();
+        let account = HdAccount::from_mnemonic(&mnemonic, "", &path).unwrap();
+        assert_eq!(
+            account.address().to_string(),
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+        );
+        assert_eq!(account.path(), &path);
+
+        let wallet = Wallet::new(account.extended_key().private_key().clone()).unwrap();
+        let signers: Vec<Box<dyn Signer>> = vec![Box::new(account), Box::new(wallet)];
+        let hash = [0xab; 32];
+        let signatures = signers
+            .iter()
+            .map(|signer| signer.sign_hash(&hash).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(signatures[0], signatures[1]);
+        assert_eq!(signatures[0].recover(&hash).unwrap(), signers[0].address());
+    }
+}
@@ -0,0 +1,153 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Signers
+//!
+//! The [`Signer`] trait abstracts over where a key lives. A [`Wallet`] holds
+//! a raw private key, an [`HdAccount`] holds a key derived from a BIP32 root,
+//! and remote backends forward the requests to an external signing service.
+//! Code written against `Signer` works with any of them.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::bips::bip39::{Mnemonic, MnemonicType};
+//! use laron_wallet::bips::wordlists::Language;
+//! use laron_wallet::bips::DerivationPath;
+//! use laron_wallet::message;
+//! use laron_wallet::signer::{HdAccount, Signer};
+//!
+//! let mnemonic = Mnemonic::new(MnemonicType::Words12, Language::English);
+//! let path = DerivationPath::parse("m/44'/60'/0'/0/0").unwrap();
+//! let account = HdAccount::from_mnemonic(&mnemonic, "", &path).unwrap();
+//!
+//! let signature = account.sign_message(b"Hello World").unwrap();
+//! assert!(message::verify_message("Hello World", &signature, &account.address()).unwrap());
+//! ```
+
+mod hd;
+mod remote;
+mod wallet;
+
+pub use hd::HdAccount;
+pub use remote::*;
+pub use wallet::Wallet;
+
+use crate::{
+    address::Address,
+    eip712::TypedData,
+    message::hash_message,
+    signature::Signature,
+    transaction::{SignedTransaction, Transaction},
+};
+use horror::Result;
+
+/// A backend able to sign on behalf of an address.
+///
+/// Only [`address`](Signer::address) and [`sign_hash`](Signer::sign_hash) are
+/// required; the other methods hash their input and sign the result. Backends
+/// that do not expose raw hash signing override them instead.
+pub trait Signer: Send + Sync {
+    /// Return the address of the signer.
+    fn address(&self) -> Address;
+
+    /// Sign the given 32-byte hash.
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<Signature>;
+
+    /// Sign the given transaction.
+    fn sign_transaction(&self, transaction: &Transaction) -> Result<SignedTransaction> {
+        let signature = self.sign_hash(&transaction.sighash())?;
+        SignedTransaction::new(transaction.clone(), signature)
+    }
+
+    /// Sign the given message with the EIP-191 prefix.
+    fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        self.sign_hash(&hash_message(message))
+    }
+
+    /// Sign the given EIP-712 typed data.
+    fn sign_typed_data(&self, data: &TypedData) -> Result<Signature> {
+        self.sign_hash(&data.sign_hash()?)
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for &S {
+    fn address(&self) -> Address {
+        (**self).address()
+    }
+
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<Signature> {
+        (**self).sign_hash(hash)
+    }
+
+    fn sign_transaction(&self, transaction: &Transaction) -> Result<SignedTransaction> {
+        (**self).sign_transaction(transaction)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        (**self).sign_message(message)
+    }
+
+    fn sign_typed_data(&self, data: &TypedData) -> Result<Signature> {
+        (**self).sign_typed_data(data)
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for Box<S> {
+    fn address(&self) -> Address {
+        (**self).address()
+    }
+
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<Signature> {
+        (**self).sign_hash(hash)
+    }
+
+    fn sign_transaction(&self, transaction: &Transaction) -> Result<SignedTransaction> {
+        (**self).sign_transaction(transaction)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        (**self).sign_message(message)
+    }
+
+    fn sign_typed_data(&self, data: &TypedData) -> Result<Signature> {
+        (**self).sign_typed_data(data)
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for std::sync::Arc<S> {
+    fn address(&self) -> Address {
+        (**self).address()
+    }
+
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<Signature> {
+        (**self).sign_hash(hash)
+    }
+
+    fn sign_transaction(&self, transaction: &Transaction) -> Result<SignedTransaction> {
+        (**self).sign_transaction(transaction)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        (**self).sign_message(message)
+    }
+
+    fn sign_typed_data(&self, data: &TypedData) -> Result<Signature> {
+        (**self).sign_typed_data(data)
+    }
+}
@@ -0,0 +1,325 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Signer;
+use crate::{
+    address::Address,
+    eip712::TypedData,
+    message::hash_message,
+    rpc::{self, Bytes, Provider, RpcError, Transport},
+    signature::Signature,
+    transaction::{SignedTransaction, Transaction},
+    U256,
+};
+use horror::Result;
+use serde_json::{json, Value};
+
+/// Error returned by a remote signer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteSignerError {
+    /// The signing service does not support the operation.
+    Unsupported(String),
+    /// The signing service refused to sign.
+    Rejected(String),
+    /// The returned signature does not belong to the signer.
+    InvalidSignature,
+    /// The returned transaction differs from the one to sign.
+    TransactionMismatch,
+}
+
+impl std::fmt::Display for RemoteSignerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RemoteSignerError::Unsupported(operation) => {
+                write!(f, "Unsupported by the remote signer: {}", operation)
+            }
+            RemoteSignerError::Rejected(reason) => {
+                write!(f, "Rejected by the remote signer: {}", reason)
+            }
+            RemoteSignerError::InvalidSignature => {
+                write!(f, "Remote signature does not match the signer address")
+            }
+            RemoteSignerError::TransactionMismatch => {
+                write!(f, "Remote signed transaction does not match the request")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RemoteSignerError {}
+
+/// A signer forwarding requests to a Web3Signer or Clef compatible
+/// JSON-RPC service.
+///
+/// Transactions are signed with `eth_signTransaction`, messages with
+/// `account_signData` and typed data with `account_signTypedData`. Every
+/// result is checked against the signer address before being returned.
+/// The service does not sign raw hashes, so [`Signer::sign_hash`] fails
+/// with [`RemoteSignerError::Unsupported`].
+///
+/// Timeouts are those of the transport, see [`HttpTransport::with_timeout`](rpc::HttpTransport::with_timeout).
+pub struct RemoteSigner<T> {
+    provider: Provider<T>,
+    address: Address,
+}
+
+impl<T: Transport> RemoteSigner<T> {
+    /// Create a signer for the given address, sending requests over the
+    /// given transport.
+    pub fn new(transport: T, address: Address) -> Self {
+        Self {
+            provider: Provider::new(transport),
+            address,
+        }
+    }
+
+    /// Return the provider used to reach the signing service.
+    pub fn provider(&self) -> &Provider<T> {
+        &self.provider
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let response = self.provider.send_request(method, params)?;
+        match rpc::response(response) {
+            Ok(result) => Ok(result),
+            Err(RpcError::Response { code: -32601, .. }) => {
+                Err(RemoteSignerError::Unsupported(method.to_string()).into())
+            }
+            Err(RpcError::Response { message, .. }) => {
+                Err(RemoteSignerError::Rejected(message).into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn check(&self, signature: Signature, hash: &[u8; 32]) -> Result<Signature> {
+        if signature.recover(hash)? != self.address {
+            return Err(RemoteSignerError::InvalidSignature.into());
+        }
+        Ok(signature)
+    }
+
+    fn transaction_request(&self, transaction: &Transaction) -> Value {
+        let mut request = json!({
+            "from": self.address,
+            "gas": transaction.gas_limit(),
+            "value": transaction.value(),
+            "data": Bytes(transaction.data().to_vec()),
+            "nonce": U256::from(transaction.nonce()),
+        });
+        if let Some(to) = transaction.to() {
+            request["to"] = json!(to);
+        }
+        if let Some(chain_id) = transaction.chain_id() {
+            request["chainId"] = json!(U256::from(chain_id));
+        }
+
+        match transaction {
+            Transaction::Legacy(tx) => {
+                request["type"] = json!("0x0");
+                request["gasPrice"] = json!(tx.gas_price);
+            }
+            Transaction::Eip1559(tx) => {
+                request["type"] = json!("0x2");
+                request["maxFeePerGas"] = json!(tx.max_fee_per_gas);
+                request["maxPriorityFeePerGas"] = json!(tx.max_priority_fee_per_gas);
+                request["accessList"] = json!(tx.access_list);
+            }
+        }
+
+        request
+    }
+}
+
+#[cfg(feature = "http")]
+impl RemoteSigner<rpc::HttpTransport> {
+    /// Create a signer for the given address, sending requests to the
+    /// given URL with the default timeout.
+    pub fn connect(url: &str, address: Address) -> Self {
+        Self::new(rpc::HttpTransport::new(url), address)
+    }
+
+    /// Create a signer for the given address, sending requests to the
+    /// given URL with the given timeout.
+    pub fn connect_with_timeout(url: &str, address: Address, timeout: std::time::Duration) -> Self {
+        Self::new(rpc::HttpTransport::with_timeout(url, timeout), address)
+    }
+}
+
+impl<T: Transport> Signer for RemoteSigner<T> {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sign_hash(&self, _hash: &[u8; 32]) -> Result<Signature> {
+        Err(RemoteSignerError::Unsupported("sign_hash".to_string()).into())
+    }
+
+    fn sign_transaction(&self, transaction: &Transaction) -> Result<SignedTransaction> {
+        let result = self.call(
+            "eth_signTransaction",
+            json!([self.transaction_request(transaction)]),
+        )?;
+        // Web3Signer returns the raw transaction, Clef wraps it in an object.
+        let raw = match result.get("raw") {
+            Some(raw) => raw.clone(),
+            None => result,
+        };
+        let raw: Bytes = serde_json::from_value(raw)
+            .map_err(|_| RpcError::InvalidResponse("expected a raw transaction".to_string()))?;
+
+        let signed = SignedTransaction::decode(&raw.0)?;
+        if signed.transaction() != transaction {
+            return Err(RemoteSignerError::TransactionMismatch.into());
+        }
+        self.check(*signed.signature(), &transaction.sighash())?;
+        Ok(signed)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        let result = self.call(
+            "account_signData",
+            json!(["text/plain", self.address, Bytes(message.to_vec())]),
+        )?;
+        let signature = parse_signature(result)?;
+        self.check(signature, &hash_message(message))
+    }
+
+    fn sign_typed_data(&self, data: &TypedData) -> Result<Signature> {
+        let result = self.call("account_signTypedData", json!([self.address, data]))?;
+        let signature = parse_signature(result)?;
+        self.check(signature, &data.sign_hash()?)
+    }
+}
+
+fn parse_signature(result: Value) -> Result<Signature> {
+    match result.as_str() {
+        Some(signature) => signature.parse(),
+        None => Err(RpcError::InvalidResponse("expected a signature".to_string()).into()),
+    }
+}
+
+#[cfg(all(test, feature = "http"))]
+mod tests {
+    use super::*;
+    use crate::{
+        rpc::{http::tests::serve_transport, MockTransport},
+        signer::Wallet,
+        transaction::Eip1559Transaction,
+    };
+    use std::{net::TcpListener, time::Duration};
+
+    fn transaction() -> Transaction {
+        Transaction::Eip1559(Eip1559Transaction {
+            chain_id: 1,
+            nonce: 7,
+            max_priority_fee_per_gas: U256::from(1_000_000_000u64),
+            max_fee_per_gas: U256::from(20_000_000_000u64),
+            gas_limit: U256::from(21000),
+            to: Some(Address::new([0x35; 20])),
+            value: U256::exp10(18),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_remote_signer() {
+        let wallet = Wallet::from_bytes(&[0x46; 32]).unwrap();
+        let tx = transaction();
+        let raw = wallet.sign_transaction(&tx).unwrap().raw();
+
+        let service = MockTransport::new();
+        let signer = wallet.clone();
+        service.on("eth_signTransaction", move |_| {
+            Ok(json!(Bytes(raw.clone())))
+        });
+        service.on("account_signData", move |params| {
+            let data: Bytes = serde_json::from_value(params[2].clone()).unwrap();
+            Ok(json!(signer.sign_message(&data.0).unwrap().to_string()))
+        });
+        let url = serve_transport(service);
+
+        let remote = RemoteSigner::connect(&url, wallet.address());
+        let signed = remote.sign_transaction(&tx).unwrap();
+        assert_eq!(signed, wallet.sign_transaction(&tx).unwrap());
+
+        let signature = remote.sign_message(b"Hello World").unwrap();
+        assert_eq!(signature, wallet.sign_message(b"Hello World").unwrap());
+
+        let err = remote.sign_hash(&[0; 32]).unwrap_err();
+        assert!(err.to_string().contains("Unsupported"));
+    }
+
+    #[test]
+    fn test_remote_signer_errors() {
+        let wallet = Wallet::from_bytes(&[0x46; 32]).unwrap();
+        let other = Wallet::from_bytes(&[0x47; 32]).unwrap();
+
+        let service = MockTransport::new();
+        service.push_error("eth_signTransaction", -32000, "Request denied");
+        service.push(
+            "account_signData",
+            other.sign_message(b"Hi").unwrap().to_string(),
+        );
+        let url = serve_transport(service);
+
+        let remote = RemoteSigner::connect(&url, wallet.address());
+        let err = remote.sign_transaction(&transaction()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Rejected by the remote signer: Request denied"
+        );
+
+        let err = remote.sign_message(b"Hi").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            RemoteSignerError::InvalidSignature.to_string()
+        );
+
+        // the mock answers unknown methods with "method not found"
+        let data: TypedData = r#"{
+            "types": {"Mail": [{"name": "contents", "type": "string"}]},
+            "primaryType": "Mail",
+            "domain": {"name": "Test"},
+            "message": {"contents": "Hello"}
+        }"#
+        .parse()
+        .unwrap();
+        let err = remote.sign_typed_data(&data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported by the remote signer: account_signTypedData"
+        );
+    }
+
+    #[test]
+    fn test_remote_signer_timeout() {
+        // accept connections but never answer
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let remote = RemoteSigner::connect_with_timeout(
+            &url,
+            Address::default(),
+            Duration::from_millis(100),
+        );
+        assert!(remote.sign_message(b"Hi").is_err());
+        drop(listener);
+    }
+}
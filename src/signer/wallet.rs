@@ -0,0 +1,121 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Signer;
+use crate::{address::Address, bitcoin::Wif, signature::Signature};
+use horror::Result;
+use laron_crypto::PrivateKey;
+
+/// A signer holding a raw private key.
+#[derive(Debug, Clone)]
+pub struct Wallet {
+    key: PrivateKey,
+    address: Address,
+}
+
+impl Wallet {
+    /// Create a wallet from the given private key.
+    pub fn new(key: PrivateKey) -> Result<Self> {
+        let address = Address::from_public_key(&key.public_key())?;
+        Ok(Self { key, address })
+    }
+
+    /// Create a wallet from the given 32-byte secret.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::new(PrivateKey::from_bytes(bytes)?)
+    }
+
+    /// Create a wallet from a private key in Wallet Import Format.
+    pub fn from_wif(wif: &str) -> Result<Self> {
+        Self::new(Wif::from_wif(wif)?.private_key().clone())
+    }
+
+    /// Return the private key of the wallet.
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.key
+    }
+}
+
+impl Signer for Wallet {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<Signature> {
+        Signature::sign_hash(&self.key, hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eip712::TypedData,
+        message,
+        transaction::{LegacyTransaction, Transaction},
+        U256,
+    };
+
+    #[test]
+    fn test_wallet() {
+        let wallet = Wallet::from_bytes(&[0x46; 32]).unwrap();
+        assert_eq!(
+            wallet.address().to_string(),
+            "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"
+        );
+        let wif = Wif::new(wallet.private_key().clone(), Default::default(), false);
+        assert_eq!(
+            Wallet::from_wif(&wif.to_wif()).unwrap().address(),
+            wallet.address()
+        );
+
+        let tx = Transaction::Legacy(LegacyTransaction {
+            nonce: 9,
+            gas_price: U256::from(20_000_000_000u64),
+            gas_limit: U256::from(21000),
+            to: Some(
+                "0x3535353535353535353535353535353535353535"
+                    .parse()
+                    .unwrap(),
+            ),
+            value: U256::exp10(18),
+            data: vec![],
+            chain_id: Some(1),
+        });
+        let signed = wallet.sign_transaction(&tx).unwrap();
+        assert_eq!(signed.sender().unwrap(), wallet.address());
+
+        let signature = wallet.sign_message(b"Hello World").unwrap();
+        assert!(message::verify_message("Hello World", &signature, &wallet.address()).unwrap());
+
+        let data: TypedData = r#"{
+            "types": {"Mail": [{"name": "contents", "type": "string"}]},
+            "primaryType": "Mail",
+            "domain": {"name": "Test", "chainId": 1},
+            "message": {"contents": "Hello"}
+        }"#
+        .parse()
+        .unwrap();
+        let signature = wallet.sign_typed_data(&data).unwrap();
+        assert_eq!(
+            signature.recover(&data.sign_hash().unwrap()).unwrap(),
+            wallet.address()
+        );
+    }
+}
@@ -0,0 +1,537 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Ethereum Transactions
+//!
+//! This module implements the legacy transaction format, replay-protected
+//! as described in [EIP-155](https://eips.ethereum.org/EIPS/eip-155), and the
+//! fee market transaction format of [EIP-1559](https://eips.ethereum.org/EIPS/eip-1559).
+//! A transaction is signed by a [`Signer`](crate::signer::Signer) over its
+//! signing hash, and the resulting [`SignedTransaction`] can be broadcast
+//! with `eth_sendRawTransaction`.
+
+use crate::{address::Address, hash::keccak256, rlp::Rlp, signature::Signature};
+use horror::Result;
+use primitive_types::{H256, U256};
+use serde::{Deserialize, Serialize};
+
+/// Type byte of EIP-1559 transactions.
+pub const EIP1559_TX_TYPE: u8 = 0x02;
+
+/// Error returned when decoding a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionError {
+    /// The transaction type is not supported.
+    UnsupportedType(u8),
+    /// The transaction does not have the expected fields.
+    InvalidFields,
+    /// The chain id is too large for the EIP-155 `v` of a legacy signature.
+    ChainIdTooLarge(u64),
+}
+
+impl std::fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TransactionError::UnsupportedType(ty) => {
+                write!(f, "Unsupported transaction type: {}", ty)
+            }
+            TransactionError::InvalidFields => write!(f, "Invalid transaction fields"),
+            TransactionError::ChainIdTooLarge(chain_id) => {
+                write!(
+                    f,
+                    "Chain id too large for a legacy transaction: {}",
+                    chain_id
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
+/// An entry of an EIP-2930 access list.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<H256>,
+}
+
+/// A legacy transaction, paying a fixed gas price.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LegacyTransaction {
+    pub nonce: u64,
+    pub gas_price: U256,
+    pub gas_limit: U256,
+    /// The recipient, `None` for a contract creation.
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    /// The chain id for EIP-155 replay protection.
+    pub chain_id: Option<u64>,
+}
+
+/// An EIP-1559 transaction, paying a base fee and a priority fee.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub gas_limit: U256,
+    /// The recipient, `None` for a contract creation.
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+}
+
+/// An unsigned transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transaction {
+    Legacy(LegacyTransaction),
+    Eip1559(Eip1559Transaction),
+}
+
+impl Transaction {
+    /// Return the unsigned encoding of the transaction.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Transaction::Legacy(tx) => {
+                let mut fields = tx.fields();
+                if let Some(chain_id) = tx.chain_id {
+                    fields.extend([Rlp::from(chain_id), Rlp::from(0u64), Rlp::from(0u64)]);
+                }
+                Rlp::List(fields).encode()
+            }
+            Transaction::Eip1559(tx) => {
+                let mut data = vec![EIP1559_TX_TYPE];
+                data.extend_from_slice(&Rlp::List(tx.fields()).encode());
+                data
+            }
+        }
+    }
+
+    /// Return the hash to be signed.
+    pub fn sighash(&self) -> [u8; 32] {
+        keccak256(self.encode())
+    }
+
+    /// Return the encoding of the transaction with the given signature.
+    pub fn encode_signed(&self, signature: &Signature) -> Result<Vec<u8>> {
+        let r = Rlp::from(U256::from_big_endian(signature.r()));
+        let s = Rlp::from(U256::from_big_endian(signature.s()));
+        let recovery_id = signature.recovery_id() as u64;
+
+        match self {
+            Transaction::Legacy(tx) => {
+                let v = match tx.chain_id {
+                    Some(chain_id) => chain_id
+                        .checked_mul(2)
+                        .and_then(|v| v.checked_add(35 + recovery_id))
+                        .ok_or(TransactionError::ChainIdTooLarge(chain_id))?,
+                    None => recovery_id + 27,
+                };
+                let mut fields = tx.fields();
+                fields.extend([Rlp::from(v), r, s]);
+                Ok(Rlp::List(fields).encode())
+            }
+            Transaction::Eip1559(tx) => {
+                let mut fields = tx.fields();
+                fields.extend([Rlp::from(recovery_id), r, s]);
+                let mut data = vec![EIP1559_TX_TYPE];
+                data.extend_from_slice(&Rlp::List(fields).encode());
+                Ok(data)
+            }
+        }
+    }
+
+    /// Decode a raw transaction, returning its signature if it is signed.
+    pub fn decode(raw: &[u8]) -> Result<(Self, Option<Signature>)> {
+        match raw.first() {
+            Some(&EIP1559_TX_TYPE) => Eip1559Transaction::decode(&raw[1..]),
+            Some(ty) if *ty < 0xc0 => Err(TransactionError::UnsupportedType(*ty).into()),
+            _ => LegacyTransaction::decode(raw),
+        }
+    }
+
+    /// Return the nonce.
+    pub fn nonce(&self) -> u64 {
+        match self {
+            Transaction::Legacy(tx) => tx.nonce,
+            Transaction::Eip1559(tx) => tx.nonce,
+        }
+    }
+
+    /// Set the nonce.
+    pub fn set_nonce(&mut self, nonce: u64) {
+        match self {
+            Transaction::Legacy(tx) => tx.nonce = nonce,
+            Transaction::Eip1559(tx) => tx.nonce = nonce,
+        }
+    }
+
+    /// Return the chain id, if the transaction is replay-protected.
+    pub fn chain_id(&self) -> Option<u64> {
+        match self {
+            Transaction::Legacy(tx) => tx.chain_id,
+            Transaction::Eip1559(tx) => Some(tx.chain_id),
+        }
+    }
+
+    /// Return the gas limit.
+    pub fn gas_limit(&self) -> U256 {
+        match self {
+            Transaction::Legacy(tx) => tx.gas_limit,
+            Transaction::Eip1559(tx) => tx.gas_limit,
+        }
+    }
+
+    /// Return the recipient.
+    pub fn to(&self) -> Option<Address> {
+        match self {
+            Transaction::Legacy(tx) => tx.to,
+            Transaction::Eip1559(tx) => tx.to,
+        }
+    }
+
+    /// Return the value sent, in wei.
+    pub fn value(&self) -> U256 {
+        match self {
+            Transaction::Legacy(tx) => tx.value,
+            Transaction::Eip1559(tx) => tx.value,
+        }
+    }
+
+    /// Return the call data.
+    pub fn data(&self) -> &[u8] {
+        match self {
+            Transaction::Legacy(tx) => &tx.data,
+            Transaction::Eip1559(tx) => &tx.data,
+        }
+    }
+}
+
+impl From<LegacyTransaction> for Transaction {
+    fn from(tx: LegacyTransaction) -> Self {
+        Transaction::Legacy(tx)
+    }
+}
+
+impl From<Eip1559Transaction> for Transaction {
+    fn from(tx: Eip1559Transaction) -> Self {
+        Transaction::Eip1559(tx)
+    }
+}
+
+impl LegacyTransaction {
+    fn fields(&self) -> Vec<Rlp> {
+        vec![
+            Rlp::from(self.nonce),
+            Rlp::from(self.gas_price),
+            Rlp::from(self.gas_limit),
+            Rlp::from(self.to),
+            Rlp::from(self.value),
+            Rlp::from(self.data.clone()),
+        ]
+    }
+
+    fn decode(raw: &[u8]) -> Result<(Transaction, Option<Signature>)> {
+        let rlp = Rlp::decode(raw)?;
+        let fields = rlp.as_list()?;
+        if fields.len() != 6 && fields.len() != 9 {
+            return Err(TransactionError::InvalidFields.into());
+        }
+
+        let mut tx = LegacyTransaction {
+            nonce: fields[0].as_u64()?,
+            gas_price: fields[1].as_u256()?,
+            gas_limit: fields[2].as_u256()?,
+            to: fields[3].as_address()?,
+            value: fields[4].as_u256()?,
+            data: fields[5].as_bytes()?.to_vec(),
+            chain_id: None,
+        };
+
+        if fields.len() == 6 {
+            return Ok((tx.into(), None));
+        }
+
+        let v = fields[6].as_u64()?;
+        let (r, s) = (fields[7].as_u256()?, fields[8].as_u256()?);
+        if r.is_zero() && s.is_zero() {
+            // Unsigned EIP-155 encoding: [.., chain_id, 0, 0]
+            tx.chain_id = Some(v);
+            return Ok((tx.into(), None));
+        }
+
+        let recovery_id = match v {
+            27 | 28 => v - 27,
+            v if v >= 35 => {
+                tx.chain_id = Some((v - 35) / 2);
+                (v - 35) % 2
+            }
+            _ => return Err(TransactionError::InvalidFields.into()),
+        };
+
+        let signature = Signature::new(to_bytes32(r), to_bytes32(s), recovery_id)?;
+        Ok((tx.into(), Some(signature)))
+    }
+}
+
+impl Eip1559Transaction {
+    fn fields(&self) -> Vec<Rlp> {
+        let access_list = self
+            .access_list
+            .iter()
+            .map(|item| {
+                Rlp::List(vec![
+                    Rlp::from(Some(item.address)),
+                    Rlp::List(
+                        item.storage_keys
+                            .iter()
+                            .map(|key| Rlp::from(key.as_bytes()))
+                            .collect(),
+                    ),
+                ])
+            })
+            .collect();
+
+        vec![
+            Rlp::from(self.chain_id),
+            Rlp::from(self.nonce),
+            Rlp::from(self.max_priority_fee_per_gas),
+            Rlp::from(self.max_fee_per_gas),
+            Rlp::from(self.gas_limit),
+            Rlp::from(self.to),
+            Rlp::from(self.value),
+            Rlp::from(self.data.clone()),
+            Rlp::List(access_list),
+        ]
+    }
+
+    fn decode(raw: &[u8]) -> Result<(Transaction, Option<Signature>)> {
+        let rlp = Rlp::decode(raw)?;
+        let fields = rlp.as_list()?;
+        if fields.len() != 9 && fields.len() != 12 {
+            return Err(TransactionError::InvalidFields.into());
+        }
+
+        let access_list = fields[8]
+            .as_list()?
+            .iter()
+            .map(|item| {
+                let item = item.as_list()?;
+                if item.len() != 2 {
+                    return Err(TransactionError::InvalidFields.into());
+                }
+                Ok(AccessListItem {
+                    address: item[0]
+                        .as_address()?
+                        .ok_or(TransactionError::InvalidFields)?,
+                    storage_keys: item[1]
+                        .as_list()?
+                        .iter()
+                        .map(|key| {
+                            let key = key.as_bytes()?;
+                            if key.len() != 32 {
+                                return Err(TransactionError::InvalidFields.into());
+                            }
+                            Ok(H256::from_slice(key))
+                        })
+                        .collect::<Result<_>>()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let tx = Eip1559Transaction {
+            chain_id: fields[0].as_u64()?,
+            nonce: fields[1].as_u64()?,
+            max_priority_fee_per_gas: fields[2].as_u256()?,
+            max_fee_per_gas: fields[3].as_u256()?,
+            gas_limit: fields[4].as_u256()?,
+            to: fields[5].as_address()?,
+            value: fields[6].as_u256()?,
+            data: fields[7].as_bytes()?.to_vec(),
+            access_list,
+        };
+
+        if fields.len() == 9 {
+            return Ok((tx.into(), None));
+        }
+
+        let signature = Signature::new(
+            to_bytes32(fields[10].as_u256()?),
+            to_bytes32(fields[11].as_u256()?),
+            fields[9].as_u64()?,
+        )?;
+        Ok((tx.into(), Some(signature)))
+    }
+}
+
+/// A transaction together with its signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction {
+    transaction: Transaction,
+    signature: Signature,
+    raw: Vec<u8>,
+}
+
+impl SignedTransaction {
+    /// Create a new signed transaction, failing if the signature cannot be
+    /// encoded with its chain id.
+    pub fn new(transaction: Transaction, signature: Signature) -> Result<Self> {
+        let raw = transaction.encode_signed(&signature)?;
+        Ok(Self {
+            transaction,
+            signature,
+            raw,
+        })
+    }
+
+    /// Decode a raw signed transaction.
+    pub fn decode(raw: &[u8]) -> Result<Self> {
+        match Transaction::decode(raw)? {
+            (transaction, Some(signature)) => Self::new(transaction, signature),
+            (_, None) => Err(TransactionError::InvalidFields.into()),
+        }
+    }
+
+    /// Return the transaction.
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    /// Return the signature.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Return the raw transaction, to be sent with `eth_sendRawTransaction`.
+    pub fn raw(&self) -> Vec<u8> {
+        self.raw.clone()
+    }
+
+    /// Return the transaction hash.
+    pub fn hash(&self) -> H256 {
+        H256(keccak256(&self.raw))
+    }
+
+    /// Recover the address of the sender.
+    pub fn sender(&self) -> Result<Address> {
+        self.signature.recover(&self.transaction.sighash())
+    }
+}
+
+fn to_bytes32(value: U256) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use laron_crypto::PrivateKey;
+
+    #[test]
+    fn test_eip155() {
+        // https://eips.ethereum.org/EIPS/eip-155#example
+        let tx = Transaction::Legacy(LegacyTransaction {
+            nonce: 9,
+            gas_price: U256::from(20_000_000_000u64),
+            gas_limit: U256::from(21000),
+            to: Some(
+                "0x3535353535353535353535353535353535353535"
+                    .parse()
+                    .unwrap(),
+            ),
+            value: U256::exp10(18),
+            data: vec![],
+            chain_id: Some(1),
+        });
+        assert_eq!(
+            hex::encode(tx.sighash()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+
+        let key = PrivateKey::from_bytes(&[0x46; 32]).unwrap();
+        let signature = Signature::sign_hash(&key, &tx.sighash()).unwrap();
+        let signed = SignedTransaction::new(tx.clone(), signature).unwrap();
+        assert_eq!(
+            hex::encode(signed.raw()),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+
+        let decoded = SignedTransaction::decode(&signed.raw()).unwrap();
+        assert_eq!(decoded, signed);
+        assert_eq!(
+            decoded.sender().unwrap(),
+            Address::from_public_key(&key.public_key()).unwrap()
+        );
+
+        // v = chain_id * 2 + 35 + recovery_id must fit in 64 bits
+        let with_chain_id = |chain_id| match &tx {
+            Transaction::Legacy(legacy) => Transaction::Legacy(LegacyTransaction {
+                chain_id: Some(chain_id),
+                ..legacy.clone()
+            }),
+            tx => tx.clone(),
+        };
+        let signed = SignedTransaction::new(with_chain_id(u64::MAX / 2 - 18), signature).unwrap();
+        assert_eq!(SignedTransaction::decode(&signed.raw()).unwrap(), signed);
+        assert!(SignedTransaction::new(with_chain_id(u64::MAX / 2 - 16), signature).is_err());
+        assert!(with_chain_id(u64::MAX).encode_signed(&signature).is_err());
+    }
+
+    #[test]
+    fn test_eip1559() {
+        let tx = Transaction::Eip1559(Eip1559Transaction {
+            chain_id: 5,
+            nonce: 3,
+            max_priority_fee_per_gas: U256::from(1_500_000_000u64),
+            max_fee_per_gas: U256::from(30_000_000_000u64),
+            gas_limit: U256::from(60000),
+            to: Some(Address::new([0x11; 20])),
+            value: U256::from(1),
+            data: vec![0xa9, 0x05, 0x9c, 0xbb],
+            access_list: vec![AccessListItem {
+                address: Address::new([0x22; 20]),
+                storage_keys: vec![H256::repeat_byte(0x33)],
+            }],
+        });
+
+        let (decoded, signature) = Transaction::decode(&tx.encode()).unwrap();
+        assert_eq!(decoded, tx);
+        assert!(signature.is_none());
+
+        let key = PrivateKey::from_bytes(&[0x46; 32]).unwrap();
+        let signature = Signature::sign_hash(&key, &tx.sighash()).unwrap();
+        let signed = SignedTransaction::new(tx, signature).unwrap();
+        let raw = signed.raw();
+        assert_eq!(raw[0], EIP1559_TX_TYPE);
+
+        let decoded = SignedTransaction::decode(&raw).unwrap();
+        assert_eq!(decoded, signed);
+        assert_eq!(
+            decoded.sender().unwrap(),
+            Address::from_public_key(&key.public_key()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,187 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{cbor::Cbor, hdkey::TAG_HDKEY, CoinInfo, CryptoHdKey, UrError, UrRegistry};
+use crate::{
+    bips::bip32::ExtendedKey,
+    bitcoin::{AddressType, Network},
+};
+use horror::Result;
+
+const TAG_SH: u64 = 400;
+const TAG_PKH: u64 = 403;
+const TAG_WPKH: u64 = 404;
+const TAG_TR: u64 = 409;
+
+/// Return the script expression tags of the output descriptors of the
+/// given address type, outermost first.
+fn script_tags(address_type: AddressType) -> &'static [u64] {
+    match address_type {
+        AddressType::P2pkh => &[TAG_PKH],
+        AddressType::P2shP2wpkh => &[TAG_SH, TAG_WPKH],
+        AddressType::P2wpkh => &[TAG_WPKH],
+        AddressType::P2tr => &[TAG_TR],
+    }
+}
+
+/// An output descriptor of a [`CryptoAccount`], for the addresses of a
+/// single key type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDescriptor {
+    pub address_type: AddressType,
+    pub key: CryptoHdKey,
+}
+
+/// The Bitcoin accounts derived from a seed, `crypto-account`, which
+/// watch-only wallets import from an air-gapped one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CryptoAccount {
+    pub master_fingerprint: [u8; 4],
+    pub descriptors: Vec<AccountDescriptor>,
+}
+
+impl CryptoAccount {
+    /// Return the given account of every address type for the given master
+    /// key.
+    pub fn from_extended_key(root: &ExtendedKey, network: Network, account: u32) -> Result<Self> {
+        let descriptors = AddressType::ALL
+            .into_iter()
+            .map(|address_type| {
+                let path = address_type.account_path(network, account);
+                let mut key = CryptoHdKey::from_extended_key(root, &path)?;
+                key.use_info = Some(CoinInfo::bitcoin(network));
+                Ok(AccountDescriptor { address_type, key })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            master_fingerprint: root.fingerprint(),
+            descriptors,
+        })
+    }
+
+    /// Return the descriptor of the given address type.
+    pub fn descriptor(&self, address_type: AddressType) -> Option<&AccountDescriptor> {
+        self.descriptors
+            .iter()
+            .find(|descriptor| descriptor.address_type == address_type)
+    }
+}
+
+impl UrRegistry for CryptoAccount {
+    const UR_TYPE: &'static str = "crypto-account";
+
+    fn to_cbor(&self) -> Vec<u8> {
+        let descriptors = self
+            .descriptors
+            .iter()
+            .map(|descriptor| {
+                let key = Cbor::tag(TAG_HDKEY, descriptor.key.to_item());
+                script_tags(descriptor.address_type)
+                    .iter()
+                    .rev()
+                    .fold(key, |item, tag| Cbor::tag(*tag, item))
+            })
+            .collect();
+        Cbor::Map(vec![
+            (
+                Cbor::Unsigned(1),
+                Cbor::Unsigned(u32::from_be_bytes(self.master_fingerprint).into()),
+            ),
+            (Cbor::Unsigned(2), Cbor::Array(descriptors)),
+        ])
+        .encode()
+    }
+
+    fn from_cbor(cbor: &[u8]) -> Result<Self> {
+        let cbor = Cbor::decode(cbor)?;
+        let (mut master_fingerprint, mut descriptors) = (None, Vec::new());
+        for (key, value) in cbor.as_map()? {
+            match key {
+                1 => master_fingerprint = Some(value.as_u32()?.to_be_bytes()),
+                2 => {
+                    for mut item in value.as_array()? {
+                        let mut tags = Vec::new();
+                        while let Cbor::Tag(tag, inner) = item {
+                            tags.push(*tag);
+                            item = inner;
+                        }
+                        // the innermost tag is the key's
+                        if tags.pop() != Some(TAG_HDKEY) {
+                            return Err(UrError::UnsupportedScript.into());
+                        }
+                        let address_type = AddressType::ALL
+                            .into_iter()
+                            .find(|ty| script_tags(*ty) == tags)
+                            .ok_or(UrError::UnsupportedScript)?;
+                        descriptors.push(AccountDescriptor {
+                            address_type,
+                            key: CryptoHdKey::from_item(item)?,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            master_fingerprint: master_fingerprint.ok_or(UrError::InvalidCbor)?,
+            descriptors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bips::{bip39::Mnemonic, wordlists::Language};
+    use crate::bitcoin::KeyVersion;
+
+    #[test]
+    fn test_account() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mnemonic = Mnemonic::from_phrase(phrase, Language::English).unwrap();
+        let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+
+        let account = CryptoAccount::from_extended_key(&root, Network::Mainnet, 0).unwrap();
+        let ur = account.to_ur();
+        assert_eq!(CryptoAccount::from_ur(&ur).unwrap(), account);
+        assert_eq!(hex::encode(account.master_fingerprint), "73c5da0a");
+
+        let key = &account.descriptor(AddressType::P2wpkh).unwrap().key;
+        assert_eq!(
+            key.to_extended_public_key().unwrap().encode(KeyVersion::Zpub, Network::Mainnet),
+            "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs"
+        );
+
+        let mut nested = account.clone();
+        nested
+            .descriptors
+            .retain(|d| d.address_type == AddressType::P2shP2wpkh);
+        let decoded = CryptoAccount::from_cbor(&nested.to_cbor()).unwrap();
+        assert_eq!(decoded.descriptors[0].address_type, AddressType::P2shP2wpkh);
+
+        let bare = Cbor::Map(vec![
+            (Cbor::Unsigned(1), Cbor::Unsigned(1)),
+            (
+                Cbor::Unsigned(2),
+                Cbor::Array(vec![Cbor::tag(TAG_HDKEY, key.to_item())]),
+            ),
+        ]);
+        assert!(CryptoAccount::from_cbor(&bare.encode()).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::UrError;
+use horror::Result;
+
+/// The 256 bytewords, identified in their minimal form by their first and
+/// last letters.
+const WORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// Return the CRC-32 checksum of the given data, as used by the fountain
+/// codes and the bytewords.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+    !crc
+}
+
+/// Encode the given data as minimal bytewords, the first and last letters
+/// of each word, followed by the words of its CRC-32.
+pub fn encode(data: &[u8]) -> String {
+    let checksum = crc32(data).to_be_bytes();
+    data.iter()
+        .chain(checksum.iter())
+        .flat_map(|byte| {
+            let word = WORDS[*byte as usize].as_bytes();
+            [word[0] as char, word[3] as char]
+        })
+        .collect()
+}
+
+/// Decode the given minimal bytewords, checking their CRC-32.
+pub fn decode(encoded: &str) -> Result<Vec<u8>> {
+    let encoded = encoded.to_ascii_lowercase();
+    if !encoded.len().is_multiple_of(2) || encoded.len() < 10 || !encoded.is_ascii() {
+        return Err(UrError::InvalidBytewords.into());
+    }
+
+    let mut data = encoded
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            WORDS
+                .iter()
+                .position(|word| word.as_bytes()[0] == pair[0] && word.as_bytes()[3] == pair[1])
+                .map(|index| index as u8)
+                .ok_or(UrError::InvalidBytewords)
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let checksum = data.split_off(data.len() - 4);
+    if crc32(&data).to_be_bytes()[..] != checksum[..] {
+        return Err(UrError::InvalidChecksum.into());
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytewords() {
+        let data = [0, 1, 2, 128, 255];
+        assert_eq!(encode(&data), "aeadaolazmjendeoti");
+        assert_eq!(decode("aeadaolazmjendeoti").unwrap(), data);
+        assert_eq!(decode("AEADAOLAZMJENDEOTI").unwrap(), data);
+        assert!(decode("aeadaolazmjendeotu").is_err());
+        assert!(decode("aeadaolazmjendeoxx").is_err());
+        assert_eq!(crc32(b"Hello, world!"), 0xebe6_c6e6);
+    }
+}
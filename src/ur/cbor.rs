@@ -0,0 +1,268 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The subset of CBOR used by the UR registry types: unsigned integers,
+//! byte and text strings, arrays, maps, tags and booleans, all of definite
+//! length.
+
+use super::UrError;
+use horror::Result;
+
+/// The maximum nesting of decoded items.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Cbor {
+    Unsigned(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Bool(bool),
+}
+
+impl Cbor {
+    pub(crate) fn tag(tag: u64, item: Cbor) -> Self {
+        Cbor::Tag(tag, Box::new(item))
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_to(&mut out);
+        out
+    }
+
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Cbor::Unsigned(n) => write_head(out, 0, *n),
+            Cbor::Bytes(bytes) => {
+                write_head(out, 2, bytes.len() as u64);
+                out.extend_from_slice(bytes);
+            }
+            Cbor::Text(text) => {
+                write_head(out, 3, text.len() as u64);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Cbor::Array(items) => {
+                write_head(out, 4, items.len() as u64);
+                items.iter().for_each(|item| item.encode_to(out));
+            }
+            Cbor::Map(entries) => {
+                write_head(out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    key.encode_to(out);
+                    value.encode_to(out);
+                }
+            }
+            Cbor::Tag(tag, item) => {
+                write_head(out, 6, *tag);
+                item.encode_to(out);
+            }
+            Cbor::Bool(value) => out.push(if *value { 0xf5 } else { 0xf4 }),
+        }
+    }
+
+    /// Decode a single item spanning the whole of the given data.
+    pub(crate) fn decode(data: &[u8]) -> Result<Self> {
+        let mut position = 0;
+        let item = decode_item(data, &mut position, 0)?;
+        if position != data.len() {
+            return Err(UrError::InvalidCbor.into());
+        }
+        Ok(item)
+    }
+
+    pub(crate) fn as_u64(&self) -> Result<u64> {
+        match self {
+            Cbor::Unsigned(n) => Ok(*n),
+            _ => Err(UrError::InvalidCbor.into()),
+        }
+    }
+
+    pub(crate) fn as_u32(&self) -> Result<u32> {
+        Ok(u32::try_from(self.as_u64()?).map_err(|_| UrError::InvalidCbor)?)
+    }
+
+    pub(crate) fn as_bytes(&self) -> Result<&[u8]> {
+        match self {
+            Cbor::Bytes(bytes) => Ok(bytes),
+            _ => Err(UrError::InvalidCbor.into()),
+        }
+    }
+
+    pub(crate) fn as_text(&self) -> Result<&str> {
+        match self {
+            Cbor::Text(text) => Ok(text),
+            _ => Err(UrError::InvalidCbor.into()),
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Result<&[Cbor]> {
+        match self {
+            Cbor::Array(items) => Ok(items),
+            _ => Err(UrError::InvalidCbor.into()),
+        }
+    }
+
+    pub(crate) fn as_bool(&self) -> Result<bool> {
+        match self {
+            Cbor::Bool(value) => Ok(*value),
+            _ => Err(UrError::InvalidCbor.into()),
+        }
+    }
+
+    /// Return the entries of a map keyed by unsigned integers.
+    pub(crate) fn as_map(&self) -> Result<Vec<(u64, &Cbor)>> {
+        match self {
+            Cbor::Map(entries) => entries
+                .iter()
+                .map(|(key, value)| Ok((key.as_u64()?, value)))
+                .collect(),
+            _ => Err(UrError::InvalidCbor.into()),
+        }
+    }
+
+    /// Return the item under the given tag, accepting it untagged as well.
+    pub(crate) fn untag(&self, tag: u64) -> Result<&Cbor> {
+        match self {
+            Cbor::Tag(t, item) if *t == tag => Ok(item),
+            Cbor::Tag(..) => Err(UrError::InvalidCbor.into()),
+            item => Ok(item),
+        }
+    }
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    match n {
+        0..=23 => out.push(major | n as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, n as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+fn take<'a>(data: &'a [u8], position: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let end = position
+        .checked_add(len)
+        .filter(|end| *end <= data.len())
+        .ok_or(UrError::InvalidCbor)?;
+    let bytes = &data[*position..end];
+    *position = end;
+    Ok(bytes)
+}
+
+fn decode_item(data: &[u8], position: &mut usize, depth: usize) -> Result<Cbor> {
+    if depth > MAX_DEPTH {
+        return Err(UrError::InvalidCbor.into());
+    }
+
+    let initial = take(data, position, 1)?[0];
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    if major == 7 {
+        return match info {
+            20 => Ok(Cbor::Bool(false)),
+            21 => Ok(Cbor::Bool(true)),
+            _ => Err(UrError::InvalidCbor.into()),
+        };
+    }
+
+    let n = match info {
+        0..=23 => u64::from(info),
+        24..=27 => take(data, position, 1 << (info - 24))?
+            .iter()
+            .fold(0, |n, byte| n << 8 | u64::from(*byte)),
+        _ => return Err(UrError::InvalidCbor.into()),
+    };
+    let len = |n: u64| usize::try_from(n).map_err(|_| UrError::InvalidCbor);
+
+    match major {
+        0 => Ok(Cbor::Unsigned(n)),
+        2 => Ok(Cbor::Bytes(take(data, position, len(n)?)?.to_vec())),
+        3 => Ok(Cbor::Text(
+            String::from_utf8(take(data, position, len(n)?)?.to_vec())
+                .map_err(|_| UrError::InvalidCbor)?,
+        )),
+        4 => {
+            // every item takes at least one byte
+            if len(n)? > data.len() - *position {
+                return Err(UrError::InvalidCbor.into());
+            }
+            (0..n)
+                .map(|_| decode_item(data, position, depth + 1))
+                .collect::<Result<_>>()
+                .map(Cbor::Array)
+        }
+        5 => {
+            if len(n)? > (data.len() - *position) / 2 {
+                return Err(UrError::InvalidCbor.into());
+            }
+            (0..n)
+                .map(|_| {
+                    let key = decode_item(data, position, depth + 1)?;
+                    Ok((key, decode_item(data, position, depth + 1)?))
+                })
+                .collect::<Result<_>>()
+                .map(Cbor::Map)
+        }
+        6 => Ok(Cbor::tag(n, decode_item(data, position, depth + 1)?)),
+        _ => Err(UrError::InvalidCbor.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbor() {
+        let item = Cbor::Map(vec![
+            (Cbor::Unsigned(1), Cbor::Bool(true)),
+            (Cbor::Unsigned(2), Cbor::Bytes(vec![0xab; 3])),
+            (
+                Cbor::Unsigned(3),
+                Cbor::tag(
+                    304,
+                    Cbor::Array(vec![Cbor::Unsigned(500), Cbor::Text("a".into())]),
+                ),
+            ),
+        ]);
+        let encoded = item.encode();
+        assert_eq!(
+            hex::encode(&encoded),
+            "a301f50243ababab03d90130821901f46161"
+        );
+        assert_eq!(Cbor::decode(&encoded).unwrap(), item);
+
+        assert!(Cbor::decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(Cbor::decode(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+        assert!(Cbor::decode(&[0x81; 64]).is_err());
+    }
+}
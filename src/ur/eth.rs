@@ -0,0 +1,483 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{cbor::Cbor, Keypath, UrError, UrRegistry};
+use crate::{
+    address::Address,
+    bips::{bip32::ExtendedKey, DerivationPath},
+    eip712::TypedData,
+    message::hash_message,
+    signature::Signature,
+    transaction::Transaction,
+};
+use horror::Result;
+use rand::{CryptoRng, RngCore};
+
+const TAG_UUID: u64 = 37;
+
+/// The kind of data of an [`EthSignRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EthDataType {
+    /// A legacy transaction, RLP encoded.
+    Transaction = 1,
+    /// EIP-712 typed data, as JSON.
+    TypedData = 2,
+    /// A message, signed with the EIP-191 prefix.
+    PersonalMessage = 3,
+    /// An EIP-2718 typed transaction.
+    TypedTransaction = 4,
+}
+
+impl TryFrom<u64> for EthDataType {
+    type Error = UrError;
+
+    fn try_from(value: u64) -> std::result::Result<Self, UrError> {
+        match value {
+            1 => Ok(EthDataType::Transaction),
+            2 => Ok(EthDataType::TypedData),
+            3 => Ok(EthDataType::PersonalMessage),
+            4 => Ok(EthDataType::TypedTransaction),
+            _ => Err(UrError::InvalidDataType(value)),
+        }
+    }
+}
+
+/// A request to sign with an air-gapped wallet, `eth-sign-request`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthSignRequest {
+    /// A random UUID, echoed by the signature. It is not secret, and may be
+    /// replaced to make requests reproducible.
+    pub request_id: [u8; 16],
+    pub sign_data: Vec<u8>,
+    pub data_type: EthDataType,
+    pub chain_id: Option<u64>,
+    /// The path of the signing key from the master key of the wallet.
+    pub derivation_path: Keypath,
+    pub address: Option<Address>,
+    /// The name of the requesting wallet.
+    pub origin: Option<String>,
+}
+
+impl EthSignRequest {
+    fn new<R: RngCore + CryptoRng>(
+        sign_data: Vec<u8>,
+        data_type: EthDataType,
+        path: &DerivationPath,
+        master_fingerprint: [u8; 4],
+        rng: &mut R,
+    ) -> Self {
+        let mut request_id = [0u8; 16];
+        rng.fill_bytes(&mut request_id);
+        // random UUID, version 4
+        request_id[6] = (request_id[6] & 0x0f) | 0x40;
+        request_id[8] = (request_id[8] & 0x3f) | 0x80;
+        Self {
+            request_id,
+            sign_data,
+            data_type,
+            chain_id: None,
+            derivation_path: Keypath::new(path, Some(master_fingerprint)),
+            address: None,
+            origin: None,
+        }
+    }
+
+    /// Request to sign the given transaction with the key at the given
+    /// path from the master key with the given fingerprint.
+    pub fn transaction(
+        transaction: &Transaction,
+        path: &DerivationPath,
+        master_fingerprint: [u8; 4],
+    ) -> Self {
+        let rng = &mut rand::thread_rng();
+        Self::transaction_with_rng(transaction, path, master_fingerprint, rng)
+    }
+
+    /// Request to sign the given transaction, with a request id drawn from
+    /// the given RNG.
+    pub fn transaction_with_rng<R: RngCore + CryptoRng>(
+        transaction: &Transaction,
+        path: &DerivationPath,
+        master_fingerprint: [u8; 4],
+        rng: &mut R,
+    ) -> Self {
+        let data_type = match transaction {
+            Transaction::Legacy(_) => EthDataType::Transaction,
+            Transaction::Eip1559(_) => EthDataType::TypedTransaction,
+        };
+        let sign_data = transaction.encode();
+        let mut request = Self::new(sign_data, data_type, path, master_fingerprint, rng);
+        request.chain_id = transaction.chain_id();
+        request
+    }
+
+    /// Request to sign the given message with the EIP-191 prefix.
+    pub fn message(message: &[u8], path: &DerivationPath, master_fingerprint: [u8; 4]) -> Self {
+        let rng = &mut rand::thread_rng();
+        Self::message_with_rng(message, path, master_fingerprint, rng)
+    }
+
+    /// Request to sign the given message, with a request id drawn from the
+    /// given RNG.
+    pub fn message_with_rng<R: RngCore + CryptoRng>(
+        message: &[u8],
+        path: &DerivationPath,
+        master_fingerprint: [u8; 4],
+        rng: &mut R,
+    ) -> Self {
+        let data_type = EthDataType::PersonalMessage;
+        Self::new(message.to_vec(), data_type, path, master_fingerprint, rng)
+    }
+
+    /// Request to sign the given typed data.
+    pub fn typed_data(
+        data: &TypedData,
+        path: &DerivationPath,
+        master_fingerprint: [u8; 4],
+    ) -> Result<Self> {
+        let rng = &mut rand::thread_rng();
+        Self::typed_data_with_rng(data, path, master_fingerprint, rng)
+    }
+
+    /// Request to sign the given typed data, with a request id drawn from
+    /// the given RNG.
+    pub fn typed_data_with_rng<R: RngCore + CryptoRng>(
+        data: &TypedData,
+        path: &DerivationPath,
+        master_fingerprint: [u8; 4],
+        rng: &mut R,
+    ) -> Result<Self> {
+        let data_type = EthDataType::TypedData;
+        let sign_data = serde_json::to_vec(data)?;
+        Ok(Self::new(
+            sign_data,
+            data_type,
+            path,
+            master_fingerprint,
+            rng,
+        ))
+    }
+
+    /// Return the hash to sign.
+    pub fn sign_hash(&self) -> Result<[u8; 32]> {
+        match self.data_type {
+            EthDataType::Transaction | EthDataType::TypedTransaction => {
+                let (transaction, signature) = Transaction::decode(&self.sign_data)?;
+                let legacy = matches!(transaction, Transaction::Legacy(_));
+                if signature.is_some() || legacy != (self.data_type == EthDataType::Transaction) {
+                    return Err(UrError::InvalidDataType(self.data_type as u64).into());
+                }
+                if self.chain_id.is_some() && self.chain_id != transaction.chain_id() {
+                    return Err(UrError::ChainIdMismatch.into());
+                }
+                Ok(transaction.sighash())
+            }
+            EthDataType::TypedData => {
+                TypedData::from_json(std::str::from_utf8(&self.sign_data)?)?.sign_hash()
+            }
+            EthDataType::PersonalMessage => Ok(hash_message(&self.sign_data)),
+        }
+    }
+
+    /// Sign the request with the key it asks for, derived from the given
+    /// master key.
+    pub fn sign(&self, root: &ExtendedKey) -> Result<EthSignature> {
+        if let Some(fingerprint) = self.derivation_path.source_fingerprint {
+            if fingerprint != root.fingerprint() {
+                return Err(UrError::FingerprintMismatch.into());
+            }
+        }
+        let path = self.derivation_path.path().ok_or(UrError::InvalidCbor)?;
+        let key = root.derive_path(&path)?;
+        if let Some(address) = self.address {
+            if address != Address::from_public_key(key.public_key())? {
+                return Err(UrError::AddressMismatch.into());
+            }
+        }
+
+        let signature = Signature::sign_hash(key.private_key(), &self.sign_hash()?)?;
+        Ok(EthSignature {
+            request_id: Some(self.request_id),
+            signature: signature.to_bytes().to_vec(),
+            origin: None,
+        })
+    }
+}
+
+impl UrRegistry for EthSignRequest {
+    const UR_TYPE: &'static str = "eth-sign-request";
+
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut map = vec![
+            (
+                Cbor::Unsigned(1),
+                Cbor::tag(TAG_UUID, Cbor::Bytes(self.request_id.to_vec())),
+            ),
+            (Cbor::Unsigned(2), Cbor::Bytes(self.sign_data.clone())),
+            (Cbor::Unsigned(3), Cbor::Unsigned(self.data_type as u64)),
+        ];
+        if let Some(chain_id) = self.chain_id {
+            map.push((Cbor::Unsigned(4), Cbor::Unsigned(chain_id)));
+        }
+        map.push((Cbor::Unsigned(5), self.derivation_path.to_item()));
+        if let Some(address) = self.address {
+            map.push((Cbor::Unsigned(6), Cbor::Bytes(address.to_bytes().to_vec())));
+        }
+        if let Some(origin) = &self.origin {
+            map.push((Cbor::Unsigned(7), Cbor::Text(origin.clone())));
+        }
+        Cbor::Map(map).encode()
+    }
+
+    fn from_cbor(cbor: &[u8]) -> Result<Self> {
+        let cbor = Cbor::decode(cbor)?;
+        let (mut request_id, mut sign_data, mut data_type, mut derivation_path) =
+            (None, None, None, None);
+        let (mut chain_id, mut address, mut origin) = (None, None, None);
+        for (key, value) in cbor.as_map()? {
+            match key {
+                1 => request_id = Some(value.untag(TAG_UUID)?.as_bytes()?.try_into()?),
+                2 => sign_data = Some(value.as_bytes()?.to_vec()),
+                3 => data_type = Some(EthDataType::try_from(value.as_u64()?)?),
+                4 => chain_id = Some(value.as_u64()?),
+                5 => derivation_path = Some(Keypath::from_item(value)?),
+                6 => address = Some(Address::from_slice(value.as_bytes()?)?),
+                7 => origin = Some(value.as_text()?.to_string()),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            request_id: request_id.ok_or(UrError::InvalidCbor)?,
+            sign_data: sign_data.ok_or(UrError::InvalidCbor)?,
+            data_type: data_type.ok_or(UrError::InvalidCbor)?,
+            chain_id,
+            derivation_path: derivation_path.ok_or(UrError::InvalidCbor)?,
+            address,
+            origin,
+        })
+    }
+}
+
+/// The signature of an [`EthSignRequest`], `eth-signature`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthSignature {
+    pub request_id: Option<[u8; 16]>,
+    /// The `r || s || v` signature, where `v` is a big-endian integer of
+    /// one byte or more.
+    pub signature: Vec<u8>,
+    /// The name of the signing wallet.
+    pub origin: Option<String>,
+}
+
+impl EthSignature {
+    /// Return the signature, accepting `v` in the EIP-155 convention of
+    /// legacy transactions as well.
+    pub fn signature(&self) -> Result<Signature> {
+        if !(65..=72).contains(&self.signature.len()) {
+            return Err(UrError::InvalidCbor.into());
+        }
+        let (rs, v) = self.signature.split_at(64);
+        let v = v.iter().fold(0, |v, byte| (v << 8) | u64::from(*byte));
+        let v = match v {
+            35.. => (v - 35) % 2,
+            v => v,
+        };
+        Signature::new(rs[..32].try_into()?, rs[32..].try_into()?, v)
+    }
+}
+
+impl UrRegistry for EthSignature {
+    const UR_TYPE: &'static str = "eth-signature";
+
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut map = Vec::new();
+        if let Some(request_id) = self.request_id {
+            map.push((
+                Cbor::Unsigned(1),
+                Cbor::tag(TAG_UUID, Cbor::Bytes(request_id.to_vec())),
+            ));
+        }
+        map.push((Cbor::Unsigned(2), Cbor::Bytes(self.signature.clone())));
+        if let Some(origin) = &self.origin {
+            map.push((Cbor::Unsigned(3), Cbor::Text(origin.clone())));
+        }
+        Cbor::Map(map).encode()
+    }
+
+    fn from_cbor(cbor: &[u8]) -> Result<Self> {
+        let cbor = Cbor::decode(cbor)?;
+        let (mut request_id, mut signature, mut origin) = (None, None, None);
+        for (key, value) in cbor.as_map()? {
+            match key {
+                1 => request_id = Some(value.untag(TAG_UUID)?.as_bytes()?.try_into()?),
+                2 => signature = Some(value.as_bytes()?.to_vec()),
+                3 => origin = Some(value.as_text()?.to_string()),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            request_id,
+            signature: signature.ok_or(UrError::InvalidCbor)?,
+            origin,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bips::{bip39::Mnemonic, wordlists::Language},
+        transaction::{LegacyTransaction, SignedTransaction},
+        U256,
+    };
+
+    #[test]
+    fn test_sign_request() {
+        let phrase = "test test test test test test test test test test test junk";
+        let mnemonic = Mnemonic::from_phrase(phrase, Language::English).unwrap();
+        let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+        let path = DerivationPath::parse("m/44'/60'/0'/0/0").unwrap();
+
+        let tx = Transaction::Legacy(LegacyTransaction {
+            nonce: 9,
+            gas_price: U256::from(20_000_000_000u64),
+            gas_limit: U256::from(21000),
+            to: Some(
+                "0x3535353535353535353535353535353535353535"
+                    .parse()
+                    .unwrap(),
+            ),
+            value: U256::exp10(18),
+            data: vec![],
+            chain_id: Some(1),
+        });
+        let mut request = EthSignRequest::transaction(&tx, &path, root.fingerprint());
+        request.address = Some(
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+                .parse()
+                .unwrap(),
+        );
+        request.origin = Some("laron".to_string());
+        assert_eq!(request.request_id[6] >> 4, 4);
+
+        let request = EthSignRequest::from_ur(&request.to_ur()).unwrap();
+        assert_eq!(request.chain_id, Some(1));
+        let signature = EthSignature::from_ur(&request.sign(&root).unwrap().to_ur()).unwrap();
+        assert_eq!(signature.request_id, Some(request.request_id));
+
+        let signed = SignedTransaction::new(tx.clone(), signature.signature().unwrap()).unwrap();
+        assert_eq!(signed.sender().unwrap(), request.address.unwrap());
+
+        let other = ExtendedKey::new_master(&mnemonic.to_seed("other")).unwrap();
+        assert!(request.sign(&other).is_err());
+        let mut request = request;
+        request.address = Some(Address::new([0x11; 20]));
+        let err = request.sign(&root).unwrap_err();
+        assert_eq!(err.to_string(), "Address mismatch");
+        request.address = None;
+
+        // A pre-EIP-155 transaction is not signed for a request on chain 1.
+        let legacy = Transaction::Legacy(LegacyTransaction {
+            chain_id: None,
+            ..Default::default()
+        });
+        request.sign_data = legacy.encode();
+        let err = request.sign(&root).unwrap_err();
+        assert_eq!(err.to_string(), "Chain id mismatch");
+        request.chain_id = None;
+        assert!(request.sign(&root).is_ok());
+        request.chain_id = Some(5);
+        request.sign_data = tx.encode();
+        assert!(request.sign(&root).is_err());
+
+        request.data_type = EthDataType::TypedTransaction;
+        assert!(request.sign_hash().is_err());
+    }
+
+    #[test]
+    fn test_sign_message() {
+        let phrase = "test test test test test test test test test test test junk";
+        let mnemonic = Mnemonic::from_phrase(phrase, Language::English).unwrap();
+        let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+        let path = DerivationPath::parse("m/44'/60'/0'/0/1").unwrap();
+
+        let request = EthSignRequest::message(b"Hello World", &path, root.fingerprint());
+        let signature = request.sign(&root).unwrap().signature().unwrap();
+        assert_eq!(
+            crate::message::recover_message("Hello World", &signature)
+                .unwrap()
+                .to_string(),
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79C8"
+        );
+    }
+
+    #[test]
+    fn test_eip155_signature() {
+        let phrase = "test test test test test test test test test test test junk";
+        let mnemonic = Mnemonic::from_phrase(phrase, Language::English).unwrap();
+        let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+        let path = DerivationPath::parse("m/44'/60'/0'/0/0").unwrap();
+        let key = root.derive_path(&path).unwrap();
+        let tx = Transaction::Legacy(LegacyTransaction {
+            nonce: 0,
+            gas_price: U256::from(30_000_000_000u64),
+            gas_limit: U256::from(21000),
+            to: Some(Address::from_public_key(key.public_key()).unwrap()),
+            value: U256::zero(),
+            data: vec![],
+            chain_id: Some(137),
+        });
+        let signature = Signature::sign_hash(key.private_key(), &tx.sighash()).unwrap();
+
+        // v = 137 * 2 + 35 + recovery id, two bytes
+        let mut bytes = signature.to_bytes()[..64].to_vec();
+        let v = 137 * 2 + 35 + u16::from(signature.recovery_id());
+        bytes.extend_from_slice(&v.to_be_bytes());
+        let signature = EthSignature {
+            request_id: None,
+            signature: bytes,
+            origin: None,
+        };
+        let signature = EthSignature::from_ur(&signature.to_ur()).unwrap();
+        assert_eq!(signature.signature.len(), 66);
+
+        let signed = SignedTransaction::new(tx.clone(), signature.signature().unwrap()).unwrap();
+        assert_eq!(signed.sender().unwrap(), tx.to().unwrap());
+
+        let mut short = signature.clone();
+        short.signature.truncate(64);
+        assert!(short.signature().is_err());
+    }
+
+    #[test]
+    fn test_request_id_with_rng() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let path = DerivationPath::parse("m/44'/60'/0'/0/0").unwrap();
+        let request = |seed| {
+            let rng = &mut StdRng::seed_from_u64(seed);
+            EthSignRequest::message_with_rng(b"Hello World", &path, [0; 4], rng)
+        };
+        assert_eq!(request(7), request(7));
+        assert_ne!(request(7).request_id, request(8).request_id);
+        assert_eq!(request(7).request_id[6] >> 4, 4);
+        assert_eq!(request(7).request_id[8] >> 6, 2);
+    }
+}
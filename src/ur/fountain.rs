@@ -0,0 +1,377 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Fountain codes splitting a message into an endless sequence of parts,
+//! any large enough subset of which rebuilds the message. The first parts
+//! are the fragments of the message in order, and the next ones XOR random
+//! subsets of them, chosen with Xoshiro256** seeded by the part number.
+
+use super::{bytewords::crc32, cbor::Cbor, UrError};
+use crate::hash::sha256;
+use horror::Result;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The maximum length of a message, so that a malicious first part cannot
+/// make the decoder allocate without bound.
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+/// The maximum number of fragments of a message, as choosing the fragments
+/// of a mixed part takes a time quadratic in their number. Longer messages
+/// are split into longer fragments.
+const MAX_FRAGMENT_COUNT: usize = 1024;
+
+/// The Xoshiro256** generator, seeded by the SHA-256 of its seed.
+pub(crate) struct Xoshiro256 {
+    s: [u64; 4],
+}
+
+impl Xoshiro256 {
+    pub(crate) fn new(seed: &[u8]) -> Self {
+        let hash = sha256(seed);
+        let mut s = [0u64; 4];
+        for (i, word) in s.iter_mut().enumerate() {
+            *word = u64::from_be_bytes(hash[i * 8..i * 8 + 8].try_into().unwrap());
+        }
+        Self { s }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next_u64() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    /// Return an integer in the inclusive range `low..=high`.
+    fn next_int(&mut self, low: u64, high: u64) -> u64 {
+        (self.next_double() * (high - low + 1) as f64) as u64 + low
+    }
+
+    #[cfg(test)]
+    pub(crate) fn next_bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_int(0, 255) as u8).collect()
+    }
+
+    fn shuffled(&mut self, mut items: Vec<usize>) -> Vec<usize> {
+        let mut result = Vec::with_capacity(items.len());
+        while !items.is_empty() {
+            let index = self.next_int(0, items.len() as u64 - 1) as usize;
+            result.push(items.remove(index));
+        }
+        result
+    }
+
+    /// Choose the number of fragments mixed in a part, with a probability
+    /// inversely proportional to it, using Vose's alias method.
+    fn choose_degree(&mut self, count: usize) -> usize {
+        let sum: f64 = (1..=count).map(|degree| 1.0 / degree as f64).sum();
+        let mut probabilities = (1..=count)
+            .map(|degree| 1.0 / degree as f64 * count as f64 / sum)
+            .collect::<Vec<_>>();
+        let mut aliases = vec![0; count];
+        let mut accepted = vec![0.0; count];
+        let (mut small, mut large): (Vec<_>, Vec<_>) =
+            (0..count).rev().partition(|i| probabilities[*i] < 1.0);
+
+        while let (Some(&less), Some(&more)) = (small.last(), large.last()) {
+            small.pop();
+            large.pop();
+            accepted[less] = probabilities[less];
+            aliases[less] = more;
+            probabilities[more] += probabilities[less] - 1.0;
+            if probabilities[more] < 1.0 {
+                small.push(more);
+            } else {
+                large.push(more);
+            }
+        }
+        for i in large.into_iter().chain(small) {
+            accepted[i] = 1.0;
+        }
+
+        let (r1, r2) = (self.next_double(), self.next_double());
+        let i = (count as f64 * r1) as usize;
+        1 + if r2 < accepted[i] { i } else { aliases[i] }
+    }
+}
+
+/// Return the indexes of the fragments mixed in the given part.
+fn choose_fragments(sequence: u32, count: usize, checksum: u32) -> BTreeSet<usize> {
+    if sequence as usize <= count {
+        return BTreeSet::from([sequence as usize - 1]);
+    }
+    let seed = [sequence.to_be_bytes(), checksum.to_be_bytes()].concat();
+    let mut rng = Xoshiro256::new(&seed);
+    let degree = rng.choose_degree(count);
+    rng.shuffled((0..count).collect())
+        .into_iter()
+        .take(degree)
+        .collect()
+}
+
+fn xor_into(target: &mut [u8], source: &[u8]) {
+    target.iter_mut().zip(source).for_each(|(a, b)| *a ^= b);
+}
+
+/// A part of a message, as carried by a multi-part UR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Part {
+    pub(crate) sequence: u32,
+    pub(crate) count: usize,
+    message_length: usize,
+    checksum: u32,
+    data: Vec<u8>,
+}
+
+impl Part {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        Cbor::Array(vec![
+            Cbor::Unsigned(self.sequence.into()),
+            Cbor::Unsigned(self.count as u64),
+            Cbor::Unsigned(self.message_length as u64),
+            Cbor::Unsigned(self.checksum.into()),
+            Cbor::Bytes(self.data.clone()),
+        ])
+        .encode()
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Result<Self> {
+        let cbor = Cbor::decode(data)?;
+        let items = cbor.as_array()?;
+        if items.len() != 5 {
+            return Err(UrError::InvalidPart.into());
+        }
+        let part = Self {
+            sequence: items[0].as_u32()?,
+            count: items[1].as_u32()? as usize,
+            message_length: items[2].as_u32()? as usize,
+            checksum: items[3].as_u32()?,
+            data: items[4].as_bytes()?.to_vec(),
+        };
+        if part.sequence == 0
+            || part.count == 0
+            || part.data.is_empty()
+            || part.message_length > MAX_MESSAGE_LENGTH
+            || part.count > MAX_FRAGMENT_COUNT
+            || part.count != part.message_length.div_ceil(part.data.len()).max(1)
+        {
+            return Err(UrError::InvalidPart.into());
+        }
+        Ok(part)
+    }
+}
+
+/// Split a message into an endless sequence of parts.
+#[derive(Debug, Clone)]
+pub(crate) struct FountainEncoder {
+    fragments: Vec<Vec<u8>>,
+    message_length: usize,
+    checksum: u32,
+    sequence: u32,
+}
+
+impl FountainEncoder {
+    pub(crate) fn new(message: &[u8], max_fragment_length: usize) -> Self {
+        let max_fragment_length = max_fragment_length
+            .max(message.len().div_ceil(MAX_FRAGMENT_COUNT))
+            .max(1);
+        let count = message.len().div_ceil(max_fragment_length).max(1);
+        let fragment_length = message.len().div_ceil(count).max(1);
+
+        let mut padded = message.to_vec();
+        padded.resize(count * fragment_length, 0);
+        Self {
+            fragments: padded.chunks(fragment_length).map(<[u8]>::to_vec).collect(),
+            message_length: message.len(),
+            checksum: crc32(message),
+            sequence: 0,
+        }
+    }
+
+    pub(crate) fn fragment_count(&self) -> usize {
+        self.fragments.len()
+    }
+
+    pub(crate) fn next_part(&mut self) -> Part {
+        self.sequence = self.sequence.wrapping_add(1).max(1);
+        let indexes = choose_fragments(self.sequence, self.fragment_count(), self.checksum);
+        let mut data = vec![0; self.fragments[0].len()];
+        for index in indexes {
+            xor_into(&mut data, &self.fragments[index]);
+        }
+        Part {
+            sequence: self.sequence,
+            count: self.fragment_count(),
+            message_length: self.message_length,
+            checksum: self.checksum,
+            data,
+        }
+    }
+}
+
+/// Rebuild a message from its parts, received in any order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FountainDecoder {
+    /// The count, message length, checksum and fragment length of the
+    /// parts, set by the first one.
+    expected: Option<(usize, usize, u32, usize)>,
+    received: BTreeSet<u32>,
+    simple: BTreeMap<usize, Vec<u8>>,
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+    message: Option<Vec<u8>>,
+}
+
+impl FountainDecoder {
+    pub(crate) fn is_complete(&self) -> bool {
+        self.message.is_some()
+    }
+
+    pub(crate) fn message(&self) -> Option<&[u8]> {
+        self.message.as_deref()
+    }
+
+    /// Return the estimated fraction of the message received.
+    pub(crate) fn progress(&self) -> f64 {
+        match (self.expected, &self.message) {
+            (_, Some(_)) => 1.0,
+            (Some((count, ..)), None) => (self.simple.len() as f64 / count as f64).min(0.99),
+            (None, None) => 0.0,
+        }
+    }
+
+    pub(crate) fn receive(&mut self, part: Part) -> Result<()> {
+        let shape = (
+            part.count,
+            part.message_length,
+            part.checksum,
+            part.data.len(),
+        );
+        match self.expected {
+            Some(expected) if expected != shape => return Err(UrError::InvalidPart.into()),
+            _ => self.expected = Some(shape),
+        }
+        if self.is_complete() || !self.received.insert(part.sequence) {
+            return Ok(());
+        }
+
+        let indexes = choose_fragments(part.sequence, part.count, part.checksum);
+        let mut queue = vec![(indexes, part.data)];
+        while let Some((mut indexes, mut data)) = queue.pop() {
+            // remove the fragments already known from the part
+            for (index, fragment) in &self.simple {
+                if indexes.remove(index) {
+                    xor_into(&mut data, fragment);
+                }
+            }
+            match indexes.len() {
+                0 => continue,
+                1 => {
+                    let index = *indexes.iter().next().unwrap();
+                    // the new fragment may reduce the mixed parts
+                    let (reducible, mixed) = std::mem::take(&mut self.mixed)
+                        .into_iter()
+                        .partition(|(mixed, _)| mixed.contains(&index));
+                    self.mixed = mixed;
+                    queue.extend(reducible);
+                    self.simple.insert(index, data);
+                }
+                _ => {
+                    if !self.mixed.iter().any(|(mixed, _)| *mixed == indexes) {
+                        self.mixed.push((indexes, data));
+                    }
+                }
+            }
+        }
+
+        if self.simple.len() == part.count {
+            let mut message = self.simple.values().flatten().copied().collect::<Vec<_>>();
+            message.truncate(part.message_length);
+            if crc32(&message) != part.checksum {
+                return Err(UrError::InvalidChecksum.into());
+            }
+            self.message = Some(message);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xoshiro() {
+        let mut rng = Xoshiro256::new(b"Wolf");
+        assert_eq!(
+            hex::encode(rng.next_bytes(16)),
+            "916ec65cf77cadf55cd7f9cda1a10300"
+        );
+    }
+
+    #[test]
+    fn test_fountain() {
+        let message = Xoshiro256::new(b"Wolf").next_bytes(1024);
+        let mut encoder = FountainEncoder::new(&message, 100);
+        assert_eq!(encoder.fragment_count(), 11);
+
+        // every other part is lost, so the message needs mixed parts
+        let mut decoder = FountainDecoder::default();
+        let mut sequence = 0;
+        while !decoder.is_complete() {
+            let part = encoder.next_part();
+            sequence = part.sequence;
+            if sequence % 2 == 0 {
+                let part = Part::decode(&part.encode()).unwrap();
+                decoder.receive(part).unwrap();
+            }
+            assert!(sequence < 1000);
+        }
+        assert_eq!(decoder.message().unwrap(), &message[..]);
+        assert!(sequence > 11);
+    }
+
+    #[test]
+    fn test_fragment_count() {
+        // a single part announcing millions of one-byte fragments
+        let part = Part {
+            sequence: 1,
+            count: MAX_MESSAGE_LENGTH,
+            message_length: MAX_MESSAGE_LENGTH,
+            checksum: 0,
+            data: vec![0],
+        };
+        assert!(Part::decode(&part.encode()).is_err());
+
+        let message = Xoshiro256::new(b"Wolf").next_bytes(4 * MAX_FRAGMENT_COUNT);
+        let mut encoder = FountainEncoder::new(&message, 1);
+        assert_eq!(encoder.fragment_count(), MAX_FRAGMENT_COUNT);
+        let part = encoder.next_part();
+        assert_eq!(part.data.len(), 4);
+        assert!(Part::decode(&part.encode()).is_ok());
+    }
+}
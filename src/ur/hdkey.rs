@@ -0,0 +1,417 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{cbor::Cbor, UrError, UrRegistry};
+use crate::{
+    bips::{bip32::ExtendedKey, ChildNumber, DerivationPath},
+    bitcoin::{ExtendedPublicKey, Network},
+};
+use horror::Result;
+
+pub(crate) const TAG_HDKEY: u64 = 303;
+const TAG_KEYPATH: u64 = 304;
+const TAG_COIN_INFO: u64 = 305;
+
+/// A component of a [`Keypath`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathComponent {
+    /// A child index.
+    Index(ChildNumber),
+    /// Any child, `*`, as in the children of an account.
+    Wildcard { hardened: bool },
+}
+
+/// A derivation path, `crypto-keypath`, with the fingerprint of the key it
+/// starts from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keypath {
+    pub components: Vec<PathComponent>,
+    pub source_fingerprint: Option<[u8; 4]>,
+    pub depth: Option<u8>,
+}
+
+impl Keypath {
+    /// Create the keypath of the given path from the key with the given
+    /// fingerprint.
+    pub fn new(path: &DerivationPath, source_fingerprint: Option<[u8; 4]>) -> Self {
+        Self {
+            components: path.iter().copied().map(PathComponent::Index).collect(),
+            source_fingerprint,
+            depth: None,
+        }
+    }
+
+    /// Return the derivation path, unless it has wildcards.
+    pub fn path(&self) -> Option<DerivationPath> {
+        self.components
+            .iter()
+            .map(|component| match component {
+                PathComponent::Index(child) => Some(*child),
+                PathComponent::Wildcard { .. } => None,
+            })
+            .collect()
+    }
+
+    pub(crate) fn to_item(&self) -> Cbor {
+        let components = self
+            .components
+            .iter()
+            .flat_map(|component| match component {
+                PathComponent::Index(child) => [
+                    Cbor::Unsigned(child.index().into()),
+                    Cbor::Bool(child.is_hardened()),
+                ],
+                PathComponent::Wildcard { hardened } => {
+                    [Cbor::Array(Vec::new()), Cbor::Bool(*hardened)]
+                }
+            })
+            .collect();
+
+        let mut map = vec![(Cbor::Unsigned(1), Cbor::Array(components))];
+        if let Some(fingerprint) = self.source_fingerprint {
+            map.push((
+                Cbor::Unsigned(2),
+                Cbor::Unsigned(u32::from_be_bytes(fingerprint).into()),
+            ));
+        }
+        if let Some(depth) = self.depth {
+            map.push((Cbor::Unsigned(3), Cbor::Unsigned(depth.into())));
+        }
+        Cbor::tag(TAG_KEYPATH, Cbor::Map(map))
+    }
+
+    pub(crate) fn from_item(item: &Cbor) -> Result<Self> {
+        let mut keypath = Self {
+            components: Vec::new(),
+            source_fingerprint: None,
+            depth: None,
+        };
+        for (key, value) in item.untag(TAG_KEYPATH)?.as_map()? {
+            match key {
+                1 => {
+                    let components = value.as_array()?;
+                    if !components.len().is_multiple_of(2) {
+                        return Err(UrError::InvalidCbor.into());
+                    }
+                    for pair in components.chunks(2) {
+                        let hardened = pair[1].as_bool()?;
+                        keypath.components.push(match &pair[0] {
+                            Cbor::Array(items) if items.is_empty() => {
+                                PathComponent::Wildcard { hardened }
+                            }
+                            index => {
+                                let index = index.as_u32()?;
+                                if index >= 1 << 31 {
+                                    return Err(UrError::InvalidCbor.into());
+                                }
+                                PathComponent::Index(match hardened {
+                                    true => ChildNumber::hardened(index),
+                                    false => ChildNumber::normal(index),
+                                })
+                            }
+                        });
+                    }
+                }
+                2 => keypath.source_fingerprint = Some(value.as_u32()?.to_be_bytes()),
+                3 => {
+                    let depth = u8::try_from(value.as_u64()?).map_err(|_| UrError::InvalidCbor)?;
+                    keypath.depth = Some(depth);
+                }
+                _ => {}
+            }
+        }
+        Ok(keypath)
+    }
+}
+
+impl std::fmt::Display for Keypath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "m")?;
+        for component in &self.components {
+            let (index, hardened) = match component {
+                PathComponent::Index(child) => (child.index().to_string(), child.is_hardened()),
+                PathComponent::Wildcard { hardened } => ("*".to_string(), *hardened),
+            };
+            write!(f, "/{}{}", index, if hardened { "'" } else { "" })?;
+        }
+        Ok(())
+    }
+}
+
+/// The coin and network of a key, `crypto-coininfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoinInfo {
+    /// The SLIP-44 coin type.
+    pub coin_type: u32,
+    /// The network, `0` for mainnet and `1` for testnet.
+    pub network: u32,
+}
+
+impl CoinInfo {
+    /// Bitcoin on the given network.
+    pub fn bitcoin(network: Network) -> Self {
+        Self {
+            coin_type: 0,
+            network: (network != Network::Mainnet).into(),
+        }
+    }
+
+    /// Ethereum mainnet.
+    pub fn ethereum() -> Self {
+        Self {
+            coin_type: 60,
+            network: 0,
+        }
+    }
+
+    fn to_item(self) -> Cbor {
+        Cbor::tag(
+            TAG_COIN_INFO,
+            Cbor::Map(vec![
+                (Cbor::Unsigned(1), Cbor::Unsigned(self.coin_type.into())),
+                (Cbor::Unsigned(2), Cbor::Unsigned(self.network.into())),
+            ]),
+        )
+    }
+
+    fn from_item(item: &Cbor) -> Result<Self> {
+        let mut info = Self {
+            coin_type: 0,
+            network: 0,
+        };
+        for (key, value) in item.untag(TAG_COIN_INFO)?.as_map()? {
+            match key {
+                1 => info.coin_type = value.as_u32()?,
+                2 => info.network = value.as_u32()?,
+                _ => {}
+            }
+        }
+        Ok(info)
+    }
+}
+
+/// An extended public key, `crypto-hdkey`, with the path it was derived at
+/// from its master key and the paths of the children to derive from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CryptoHdKey {
+    /// The compressed public key.
+    pub key_data: [u8; 33],
+    pub chain_code: Option<[u8; 32]>,
+    pub use_info: Option<CoinInfo>,
+    pub origin: Option<Keypath>,
+    pub children: Option<Keypath>,
+    pub parent_fingerprint: Option<[u8; 4]>,
+    pub name: Option<String>,
+    pub note: Option<String>,
+}
+
+impl CryptoHdKey {
+    /// Create the key of the given extended public key, derived from its
+    /// master key at the given origin.
+    pub fn from_extended_public_key(key: &ExtendedPublicKey, origin: Keypath) -> Self {
+        Self {
+            key_data: key.to_bytes(),
+            chain_code: Some(*key.chain_code()),
+            use_info: None,
+            origin: Some(origin),
+            children: None,
+            parent_fingerprint: Some(key.parent_fingerprint()),
+            name: None,
+            note: None,
+        }
+    }
+
+    /// Derive the key at the given path from the given master key.
+    pub fn from_extended_key(root: &ExtendedKey, path: &DerivationPath) -> Result<Self> {
+        let key = ExtendedPublicKey::from_extended_key(&root.derive_path(path)?)?;
+        let mut origin = Keypath::new(path, Some(root.fingerprint()));
+        origin.depth = Some(key.depth());
+        Ok(Self::from_extended_public_key(&key, origin))
+    }
+
+    /// Return the extended public key, to derive the addresses of the
+    /// account it is for.
+    pub fn to_extended_public_key(&self) -> Result<ExtendedPublicKey> {
+        let chain_code = self.chain_code.ok_or(UrError::UnsupportedKey)?;
+        let (depth, child_number) = match &self.origin {
+            Some(origin) => {
+                let depth = match origin.depth {
+                    Some(depth) => depth,
+                    None => u8::try_from(origin.components.len())
+                        .map_err(|_| UrError::UnsupportedKey)?,
+                };
+                let child_number = match origin.components.last() {
+                    Some(PathComponent::Index(child)) => *child,
+                    Some(PathComponent::Wildcard { .. }) => {
+                        return Err(UrError::UnsupportedKey.into())
+                    }
+                    None => ChildNumber::from(0),
+                };
+                (depth, child_number)
+            }
+            None => (0, ChildNumber::from(0)),
+        };
+        ExtendedPublicKey::new(
+            &self.key_data,
+            chain_code,
+            self.parent_fingerprint.unwrap_or_default(),
+            child_number,
+            depth,
+        )
+    }
+
+    pub(crate) fn to_item(&self) -> Cbor {
+        let mut map = vec![(Cbor::Unsigned(3), Cbor::Bytes(self.key_data.to_vec()))];
+        if let Some(chain_code) = self.chain_code {
+            map.push((Cbor::Unsigned(4), Cbor::Bytes(chain_code.to_vec())));
+        }
+        if let Some(use_info) = self.use_info {
+            map.push((Cbor::Unsigned(5), use_info.to_item()));
+        }
+        if let Some(origin) = &self.origin {
+            map.push((Cbor::Unsigned(6), origin.to_item()));
+        }
+        if let Some(children) = &self.children {
+            map.push((Cbor::Unsigned(7), children.to_item()));
+        }
+        if let Some(fingerprint) = self.parent_fingerprint {
+            map.push((
+                Cbor::Unsigned(8),
+                Cbor::Unsigned(u32::from_be_bytes(fingerprint).into()),
+            ));
+        }
+        if let Some(name) = &self.name {
+            map.push((Cbor::Unsigned(9), Cbor::Text(name.clone())));
+        }
+        if let Some(note) = &self.note {
+            map.push((Cbor::Unsigned(10), Cbor::Text(note.clone())));
+        }
+        Cbor::Map(map)
+    }
+
+    pub(crate) fn from_item(item: &Cbor) -> Result<Self> {
+        let mut key = Self {
+            key_data: [0; 33],
+            chain_code: None,
+            use_info: None,
+            origin: None,
+            children: None,
+            parent_fingerprint: None,
+            name: None,
+            note: None,
+        };
+        let mut key_data = None;
+        for (index, value) in item.untag(TAG_HDKEY)?.as_map()? {
+            match index {
+                // master and private keys are not shared with other devices
+                1 | 2 if value.as_bool()? => return Err(UrError::UnsupportedKey.into()),
+                3 => key_data = Some(value.as_bytes()?.try_into()?),
+                4 => key.chain_code = Some(value.as_bytes()?.try_into()?),
+                5 => key.use_info = Some(CoinInfo::from_item(value)?),
+                6 => key.origin = Some(Keypath::from_item(value)?),
+                7 => key.children = Some(Keypath::from_item(value)?),
+                8 => key.parent_fingerprint = Some(value.as_u32()?.to_be_bytes()),
+                9 => key.name = Some(value.as_text()?.to_string()),
+                10 => key.note = Some(value.as_text()?.to_string()),
+                _ => {}
+            }
+        }
+        key.key_data = key_data.ok_or(UrError::InvalidCbor)?;
+        Ok(key)
+    }
+}
+
+impl UrRegistry for CryptoHdKey {
+    const UR_TYPE: &'static str = "crypto-hdkey";
+
+    fn to_cbor(&self) -> Vec<u8> {
+        self.to_item().encode()
+    }
+
+    fn from_cbor(cbor: &[u8]) -> Result<Self> {
+        Self::from_item(&Cbor::decode(cbor)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bips::{bip39::Mnemonic, wordlists::Language};
+    use crate::bitcoin::KeyVersion;
+
+    #[test]
+    fn test_hdkey() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mnemonic = Mnemonic::from_phrase(phrase, Language::English).unwrap();
+        let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+
+        let path = DerivationPath::parse("m/44'/60'/0'").unwrap();
+        let mut key = CryptoHdKey::from_extended_key(&root, &path).unwrap();
+        key.use_info = Some(CoinInfo::ethereum());
+        key.children = Some(Keypath {
+            components: vec![
+                PathComponent::Index(ChildNumber::normal(0)),
+                PathComponent::Wildcard { hardened: false },
+            ],
+            source_fingerprint: None,
+            depth: None,
+        });
+        key.name = Some("laron".to_string());
+
+        let ur = key.to_ur().to_string();
+        assert!(ur.starts_with("ur:crypto-hdkey/"));
+        let decoded = CryptoHdKey::from_ur(&ur.parse().unwrap()).unwrap();
+        assert_eq!(decoded, key);
+        assert_eq!(decoded.origin.as_ref().unwrap().to_string(), "m/44'/60'/0'");
+        assert_eq!(decoded.children.as_ref().unwrap().to_string(), "m/0/*");
+        assert_eq!(decoded.origin.as_ref().unwrap().path().unwrap(), path);
+
+        let xpub = ExtendedPublicKey::from_extended_key(&root.derive_path(&path).unwrap()).unwrap();
+        assert_eq!(
+            decoded
+                .to_extended_public_key()
+                .unwrap()
+                .encode(KeyVersion::Xpub, Network::Mainnet),
+            xpub.encode(KeyVersion::Xpub, Network::Mainnet)
+        );
+    }
+
+    #[test]
+    fn test_bcr_2020_007() {
+        // the master key of the BIP32 test vector 1, which is not exported
+        let master = "ur:crypto-hdkey/otadykaxhdclaevswfdmjpfswpwkahcywspsmndwmusoskprbbehetchsnpfcybbmwrhchspfxjeecaahdcxltfszmlyrtdlgmhfcnzcctvwcmkbpsftgonbgauefsehgrqzdmvodizmweemtlaybakiylat";
+        assert!(CryptoHdKey::from_ur(&master.parse().unwrap()).is_err());
+
+        let ur = "ur:crypto-hdkey/onaxhdclaojlvoechgferkdpqdiabdrflawshlhdmdcemtfnlrctghchbdolvwsednvdztbgolaahdcxtottgostdkhfdahdlykkecbbweskrymwflvdylgerkloswtbrpfdbsticmwylklpahtaadehoyaoadamtaaddyoyadlecsdwykadykadykaewkadwkaycywlcscewfihbdaehn";
+        let key = CryptoHdKey::from_ur(&ur.parse().unwrap()).unwrap();
+        assert_eq!(
+            hex::encode(key.key_data),
+            "026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6"
+        );
+        assert_eq!(key.use_info, Some(CoinInfo::bitcoin(Network::Testnet)));
+        assert_eq!(key.origin.as_ref().unwrap().to_string(), "m/44'/1'/1'/0/1");
+        assert_eq!(key.parent_fingerprint, Some([0xe9, 0x18, 0x1c, 0xf3]));
+        assert_eq!(
+            key.to_extended_public_key()
+                .unwrap()
+                .encode(KeyVersion::Xpub, Network::Testnet),
+            "tpubDHW3GtnVrTatx38EcygoSf9UhUd9Dx1rht7FAL8unrMo8r2NWhJuYNqDFS7cZFVbDaxJkV94MLZAr86XFPsAPYcoHWJ7sWYsrmHDw5sKQ2K"
+        );
+    }
+}
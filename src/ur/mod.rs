@@ -0,0 +1,427 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Uniform Resources
+//!
+//! This module implements the Blockchain Commons
+//! [Uniform Resources](https://github.com/BlockchainCommons/Research/blob/master/papers/bcr-2020-005-ur.md)
+//! used by air-gapped wallets, such as Keystone and AirGap, to exchange keys,
+//! sign requests and signatures as QR codes. A [`Ur`] carries CBOR encoded
+//! as bytewords, in a single QR code or, with a [`UrEncoder`], as a sequence
+//! of fountain coded parts which a [`UrDecoder`] rebuilds from any large
+//! enough subset, in any order.
+//!
+//! The following registry types are available:
+//! - [`CryptoHdKey`], an extended public key with its origin
+//! - [`CryptoAccount`], the Bitcoin accounts of a seed
+//! - [`EthSignRequest`], a transaction, message or typed data to sign
+//! - [`EthSignature`], the signature sent back
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::bips::bip32::ExtendedKey;
+//! use laron_wallet::bips::bip39::Mnemonic;
+//! use laron_wallet::bips::wordlists::Language;
+//! use laron_wallet::bips::DerivationPath;
+//! use laron_wallet::ur::{EthSignRequest, EthSignature, UrDecoder, UrRegistry};
+//!
+//! let phrase = "test test test test test test test test test test test junk";
+//! let mnemonic = Mnemonic::from_phrase(phrase, Language::English).unwrap();
+//! let root = ExtendedKey::new_master(&mnemonic.to_seed("")).unwrap();
+//!
+//! // the online wallet shows the request as a sequence of QR codes
+//! let path = DerivationPath::parse("m/44'/60'/0'/0/0").unwrap();
+//! let request = EthSignRequest::message(b"Hello World", &path, root.fingerprint());
+//! let mut encoder = request.to_ur().encoder(50);
+//!
+//! // the offline wallet scans them until it has the whole request
+//! let mut decoder = UrDecoder::new();
+//! while !decoder.is_complete() {
+//!     decoder.receive(&encoder.next_part()).unwrap();
+//! }
+//! let request = EthSignRequest::from_ur(decoder.result().unwrap()).unwrap();
+//!
+//! // and shows the signature back
+//! let signature = request.sign(&root).unwrap().to_ur().to_string();
+//! let signature = EthSignature::from_ur(&signature.parse().unwrap()).unwrap();
+//! assert_eq!(signature.request_id, Some(request.request_id));
+//! ```
+
+mod account;
+pub mod bytewords;
+mod cbor;
+mod eth;
+mod fountain;
+mod hdkey;
+
+pub use account::*;
+pub use eth::*;
+pub use hdkey::*;
+
+use fountain::{FountainDecoder, FountainEncoder, Part};
+use horror::Result;
+
+/// Error returned when decoding a UR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrError {
+    /// The string is not a UR.
+    InvalidUr,
+    /// The type contains characters other than `a-z`, `0-9` and `-`.
+    InvalidType(String),
+    /// The bytewords are malformed.
+    InvalidBytewords,
+    /// The checksum of the bytewords or of the message does not match.
+    InvalidChecksum,
+    /// The CBOR is malformed, or not of the expected registry type.
+    InvalidCbor,
+    /// The part does not belong to the same message as the previous ones.
+    InvalidPart,
+    /// The UR is one part of a longer message, to be read by a [`UrDecoder`].
+    IncompleteMessage,
+    /// The UR is not of the expected type.
+    UnexpectedType(String),
+    /// The output descriptor is not of a single key address type.
+    UnsupportedScript,
+    /// The extended key is private, or misses its chain code.
+    UnsupportedKey,
+    /// The request is for a key of another seed.
+    FingerprintMismatch,
+    /// The request is for another address than the derived key.
+    AddressMismatch,
+    /// The transaction is for another chain than the request.
+    ChainIdMismatch,
+    /// The sign request has an unknown data type.
+    InvalidDataType(u64),
+}
+
+impl std::fmt::Display for UrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UrError::InvalidUr => write!(f, "Invalid UR"),
+            UrError::InvalidType(ty) => write!(f, "Invalid UR type: {}", ty),
+            UrError::InvalidBytewords => write!(f, "Invalid bytewords"),
+            UrError::InvalidChecksum => write!(f, "Invalid checksum"),
+            UrError::InvalidCbor => write!(f, "Invalid CBOR"),
+            UrError::InvalidPart => write!(f, "Invalid UR part"),
+            UrError::IncompleteMessage => write!(f, "UR is one part of a multi-part message"),
+            UrError::UnexpectedType(ty) => write!(f, "Unexpected UR type: {}", ty),
+            UrError::UnsupportedScript => write!(f, "Unsupported output descriptor"),
+            UrError::UnsupportedKey => write!(f, "Unsupported extended key"),
+            UrError::FingerprintMismatch => write!(f, "Master fingerprint mismatch"),
+            UrError::AddressMismatch => write!(f, "Address mismatch"),
+            UrError::ChainIdMismatch => write!(f, "Chain id mismatch"),
+            UrError::InvalidDataType(ty) => write!(f, "Invalid sign data type: {}", ty),
+        }
+    }
+}
+
+impl std::error::Error for UrError {}
+
+/// A Uniform Resource, some CBOR tagged with its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ur {
+    ur_type: String,
+    cbor: Vec<u8>,
+}
+
+impl Ur {
+    /// Create a UR of the given type carrying the given CBOR.
+    pub fn new(ur_type: &str, cbor: Vec<u8>) -> Result<Self> {
+        check_type(ur_type)?;
+        Ok(Self {
+            ur_type: ur_type.to_string(),
+            cbor,
+        })
+    }
+
+    /// Return the type of the UR.
+    pub fn ur_type(&self) -> &str {
+        &self.ur_type
+    }
+
+    /// Return the CBOR carried by the UR.
+    pub fn cbor(&self) -> &[u8] {
+        &self.cbor
+    }
+
+    /// Encode the UR as a single part, `ur:<type>/<bytewords>`.
+    pub fn encode(&self) -> String {
+        format!("ur:{}/{}", self.ur_type, bytewords::encode(&self.cbor))
+    }
+
+    /// Decode a single part UR, or a multi-part one holding the whole
+    /// message in its first part.
+    pub fn decode(encoded: &str) -> Result<Self> {
+        let mut decoder = UrDecoder::new();
+        decoder.receive(encoded)?;
+        decoder
+            .result
+            .ok_or_else(|| UrError::IncompleteMessage.into())
+    }
+
+    /// Return an encoder splitting the UR into parts carrying at most the
+    /// given number of bytes each.
+    pub fn encoder(&self, max_fragment_length: usize) -> UrEncoder {
+        UrEncoder::new(self, max_fragment_length)
+    }
+}
+
+fn check_type(ur_type: &str) -> Result<()> {
+    if ur_type.is_empty()
+        || !ur_type
+            .bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-')
+    {
+        return Err(UrError::InvalidType(ur_type.to_string()).into());
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for Ur {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.encode())
+    }
+}
+
+impl std::str::FromStr for Ur {
+    type Err = horror::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::decode(s)
+    }
+}
+
+/// Split a UR into an endless sequence of parts, `ur:<type>/<n>-<count>/..`,
+/// to be shown as an animated QR code.
+#[derive(Debug, Clone)]
+pub struct UrEncoder {
+    ur: Ur,
+    fountain: FountainEncoder,
+}
+
+impl UrEncoder {
+    /// Create an encoder splitting the given UR into parts carrying at
+    /// most the given number of bytes each.
+    pub fn new(ur: &Ur, max_fragment_length: usize) -> Self {
+        Self {
+            ur: ur.clone(),
+            fountain: FountainEncoder::new(&ur.cbor, max_fragment_length),
+        }
+    }
+
+    /// Return the number of fragments of the message, the minimum number
+    /// of parts needed to decode it.
+    pub fn fragment_count(&self) -> usize {
+        self.fountain.fragment_count()
+    }
+
+    /// Return whether the UR fits in a single part.
+    pub fn is_single_part(&self) -> bool {
+        self.fragment_count() == 1
+    }
+
+    /// Return the next part. After the fragments of the message, every
+    /// part mixes several of them, so that a receiver missing some parts
+    /// only has to keep scanning.
+    pub fn next_part(&mut self) -> String {
+        if self.is_single_part() {
+            return self.ur.encode();
+        }
+        let part = self.fountain.next_part();
+        format!(
+            "ur:{}/{}-{}/{}",
+            self.ur.ur_type,
+            part.sequence,
+            part.count,
+            bytewords::encode(&part.encode())
+        )
+    }
+}
+
+/// Rebuild a UR from its parts.
+#[derive(Debug, Clone, Default)]
+pub struct UrDecoder {
+    ur_type: Option<String>,
+    fountain: FountainDecoder,
+    result: Option<Ur>,
+}
+
+impl UrDecoder {
+    /// Create a new decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive a part, single or multi-part, ignoring it once the UR is
+    /// complete. The parts of another UR are rejected.
+    pub fn receive(&mut self, part: &str) -> Result<()> {
+        if self.is_complete() {
+            return Ok(());
+        }
+
+        let part = part.to_ascii_lowercase();
+        let components = part
+            .strip_prefix("ur:")
+            .ok_or(UrError::InvalidUr)?
+            .split('/')
+            .collect::<Vec<_>>();
+        let ur_type = components[0];
+        if let Some(expected) = &self.ur_type {
+            if expected != ur_type {
+                return Err(UrError::UnexpectedType(ur_type.to_string()).into());
+            }
+        }
+
+        match components[1..] {
+            [payload] => {
+                self.result = Some(Ur::new(ur_type, bytewords::decode(payload)?)?);
+            }
+            [sequence, payload] => {
+                let (n, count) = sequence.split_once('-').ok_or(UrError::InvalidPart)?;
+                let part = Part::decode(&bytewords::decode(payload)?)?;
+                if n.parse().ok() != Some(part.sequence) || count.parse().ok() != Some(part.count) {
+                    return Err(UrError::InvalidPart.into());
+                }
+                check_type(ur_type)?;
+                self.fountain.receive(part)?;
+                self.ur_type = Some(ur_type.to_string());
+                if let Some(message) = self.fountain.message() {
+                    self.result = Some(Ur::new(ur_type, message.to_vec())?);
+                }
+            }
+            _ => return Err(UrError::InvalidUr.into()),
+        }
+        Ok(())
+    }
+
+    /// Return whether the UR is complete.
+    pub fn is_complete(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Return the estimated fraction of the UR received, between 0 and 1.
+    pub fn progress(&self) -> f64 {
+        match self.result {
+            Some(_) => 1.0,
+            None => self.fountain.progress(),
+        }
+    }
+
+    /// Return the UR, once complete.
+    pub fn result(&self) -> Option<&Ur> {
+        self.result.as_ref()
+    }
+}
+
+/// A type of the UR registry, carried as CBOR in a UR of its type.
+pub trait UrRegistry: Sized {
+    /// The type of the URs carrying it.
+    const UR_TYPE: &'static str;
+
+    /// Encode it as CBOR.
+    fn to_cbor(&self) -> Vec<u8>;
+
+    /// Decode it from CBOR.
+    fn from_cbor(cbor: &[u8]) -> Result<Self>;
+
+    /// Return the UR carrying it.
+    fn to_ur(&self) -> Ur {
+        Ur {
+            ur_type: Self::UR_TYPE.to_string(),
+            cbor: self.to_cbor(),
+        }
+    }
+
+    /// Decode it from the given UR, which must be of its type.
+    fn from_ur(ur: &Ur) -> Result<Self> {
+        if ur.ur_type != Self::UR_TYPE {
+            return Err(UrError::UnexpectedType(ur.ur_type.clone()).into());
+        }
+        Self::from_cbor(&ur.cbor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fountain::Xoshiro256;
+
+    fn message_ur(len: usize) -> Ur {
+        let message = Xoshiro256::new(b"Wolf").next_bytes(len);
+        Ur::new("bytes", cbor::Cbor::Bytes(message).encode()).unwrap()
+    }
+
+    #[test]
+    fn test_single_part() {
+        let ur = message_ur(50);
+        assert_eq!(
+            ur.to_string(),
+            "ur:bytes/hdeymejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtgwdpfnsboxgwlbaawzuefywkdplrsrjynbvygabwjldapfcsdwkbrkch"
+        );
+        assert_eq!(ur.to_string().parse::<Ur>().unwrap(), ur);
+        assert_eq!(ur.to_string().to_uppercase().parse::<Ur>().unwrap(), ur);
+        assert!("ur:bytes".parse::<Ur>().is_err());
+        assert!("ur:Bytes!/hdey".parse::<Ur>().is_err());
+    }
+
+    #[test]
+    fn test_multi_part() {
+        let mut encoder = message_ur(256).encoder(30);
+        let parts = (0..19).map(|_| encoder.next_part()).collect::<Vec<_>>();
+        assert_eq!(
+            parts[0],
+            "ur:bytes/1-9/lpadascfadaxcywenbpljkhdcahkadaemejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtdkgslpgh"
+        );
+        assert_eq!(
+            parts[1],
+            "ur:bytes/2-9/lpaoascfadaxcywenbpljkhdcagwdpfnsboxgwlbaawzuefywkdplrsrjynbvygabwjldapfcsgmghhkhstlrdcxaefz"
+        );
+        // a part mixing several fragments
+        assert_eq!(
+            parts[18],
+            "ur:bytes/19-9/lpbwascfadaxcywenbpljkhdcadekicpaajootjzpsdrbalpeywllbdsnbinaerkurspbncxgslgftvtsrjtksplcpeo"
+        );
+
+        let ur = message_ur(32767);
+        let mut encoder = ur.encoder(1000);
+        assert_eq!(encoder.fragment_count(), 33);
+        assert!(encoder.next_part().starts_with("ur:bytes/1-33/"));
+        assert!(Ur::decode(&encoder.next_part()).is_err());
+
+        let mut decoder = UrDecoder::new();
+        for n in 0.. {
+            let part = encoder.next_part();
+            // lose a third of the parts
+            if n % 3 != 0 {
+                decoder.receive(&part).unwrap();
+            }
+            if decoder.is_complete() {
+                break;
+            }
+            assert!(decoder.progress() <= 1.0);
+        }
+        assert_eq!(decoder.result().unwrap(), &ur);
+
+        let mut decoder = UrDecoder::new();
+        decoder.receive(&ur.encoder(1000).next_part()).unwrap();
+        let other = message_ur(5000).encoder(1000).next_part();
+        assert!(decoder.receive(&other).is_err());
+        assert!(decoder.receive(&other.replace("bytes", "other")).is_err());
+    }
+}
@@ -0,0 +1,722 @@
+// This file is part of the laron-wallet.
+//
+// Copyright (C) 2022 Ade M Ramdani
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! # Vault
+//!
+//! A [`Vault`] persists a [`Keyring`] to disk, encrypted with a password.
+//! The file starts with a versioned header holding the parameters of the
+//! key derivation, followed by the encrypted keyring:
+//!
+//! | Field        | Size     | Description                                  |
+//! |--------------|----------|----------------------------------------------|
+//! | magic        | 4        | `LRWV`                                       |
+//! | version      | 1        | `1`                                          |
+//! | KDF          | 1 + 12   | `1` for scrypt or `2` for Argon2id, then its parameters |
+//! | salt         | 32       | random salt of the KDF                       |
+//! | cipher       | 1        | `1` for XChaCha20-Poly1305                   |
+//! | nonce        | 24       | random nonce of the cipher                   |
+//! | ciphertext   | variable | the encrypted keyring and its 16-byte tag    |
+//!
+//! The header is authenticated along with the ciphertext, so a vault which
+//! was tampered with fails to decrypt like a wrong password does. Once
+//! decrypted, every root and account is rebuilt and checked against the
+//! fingerprint and address recorded when it was saved.
+//!
+//! ## Example
+//! ```rust
+//! use laron_wallet::bips::bip39::{Mnemonic, MnemonicType};
+//! use laron_wallet::bips::wordlists::Language;
+//! use laron_wallet::bips::DerivationScheme;
+//! use laron_wallet::keyring::Keyring;
+//! use laron_wallet::vault::{Kdf, Vault};
+//!
+//! let mut keyring = Keyring::new();
+//! let mnemonic = Mnemonic::new(MnemonicType::Words12, Language::English);
+//! let root = keyring.add_mnemonic("Main", &mnemonic, "").unwrap();
+//! keyring.derive_account(root, &DerivationScheme::MetaMask.path(0), "Savings").unwrap();
+//!
+//! // weak parameters to keep the example fast, use the default in production
+//! let vault = Vault::new(keyring).kdf(Kdf::Scrypt { log_n: 8, r: 8, p: 1 });
+//! let data = vault.encrypt("correct horse battery staple").unwrap();
+//!
+//! let vault = Vault::decrypt(&data, "correct horse battery staple").unwrap();
+//! assert_eq!(vault.keyring().accounts()[0].label(), "Savings");
+//! assert!(Vault::decrypt(&data, "wrong password").is_err());
+//! ```
+
+use crate::{
+    address::Address,
+    bips::{bip39::Mnemonic, DerivationPath},
+    keyring::{AccountSigner, Keyring},
+};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use horror::Result;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8; 4] = b"LRWV";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const CIPHER_XCHACHA20_POLY1305: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 13 + SALT_LEN + 1 + NONCE_LEN;
+
+/// The largest memory cost accepted when loading a vault, 4 GiB, so that a
+/// crafted header cannot exhaust the memory.
+const MAX_MEMORY_KIB: u64 = 4 * 1024 * 1024;
+
+/// The largest memory cost times the number of passes over it, 16 GiB, so
+/// that a crafted header cannot keep the KDF running for hours before the
+/// password is checked.
+const MAX_WORK_KIB: u64 = 16 * 1024 * 1024;
+
+/// The largest Argon2id `iterations`.
+const MAX_ITERATIONS: u32 = 64;
+
+/// The largest Argon2id `parallelism` and scrypt `p`.
+const MAX_PARALLELISM: u32 = 64;
+
+/// The largest scrypt block size `r`.
+const MAX_BLOCK_SIZE: u32 = 32;
+
+/// Error returned by the vault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VaultError {
+    /// The data is not a vault, or is truncated.
+    InvalidFormat,
+    /// The vault was written by a newer version of the format.
+    UnsupportedVersion(u8),
+    /// The KDF or cipher is unknown, or its parameters are out of range.
+    InvalidParameters,
+    /// The password is wrong, or the vault was tampered with.
+    Decryption,
+    /// The decrypted keyring does not match what was saved.
+    IntegrityCheck(String),
+}
+
+impl std::fmt::Display for VaultError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VaultError::InvalidFormat => write!(f, "Invalid vault format"),
+            VaultError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported vault version: {}", version)
+            }
+            VaultError::InvalidParameters => write!(f, "Invalid vault parameters"),
+            VaultError::Decryption => write!(f, "Wrong password or corrupted vault"),
+            VaultError::IntegrityCheck(reason) => write!(f, "Integrity check failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for VaultError {}
+
+/// The memory-hard function deriving the encryption key from the password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    /// scrypt, with a cost of `2^log_n`.
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    /// Argon2id, with a memory cost in KiB.
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+impl Default for Kdf {
+    /// Argon2id with 64 MiB of memory and 3 iterations.
+    fn default() -> Self {
+        Kdf::Argon2id {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+impl Kdf {
+    fn derive_key(&self, password: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+        let mut key = Zeroizing::new([0u8; 32]);
+        match *self {
+            Kdf::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(log_n, r, p, 32)
+                    .map_err(|_| VaultError::InvalidParameters)?;
+                scrypt::scrypt(password.as_bytes(), salt, &params, &mut key[..])
+                    .map_err(|_| VaultError::InvalidParameters)?;
+            }
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                let params = argon2::Params::new(memory_kib, iterations, parallelism, Some(32))
+                    .map_err(|_| VaultError::InvalidParameters)?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(password.as_bytes(), salt, &mut key[..])
+                    .map_err(|_| VaultError::InvalidParameters)?;
+            }
+        }
+        Ok(key)
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            Kdf::Scrypt { log_n, r, p } => {
+                out.push(1);
+                out.push(log_n);
+                out.extend_from_slice(&r.to_le_bytes());
+                out.extend_from_slice(&p.to_le_bytes());
+                out.extend_from_slice(&[0; 3]);
+            }
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                out.push(2);
+                out.extend_from_slice(&memory_kib.to_le_bytes());
+                out.extend_from_slice(&iterations.to_le_bytes());
+                out.extend_from_slice(&parallelism.to_le_bytes());
+            }
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let word = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let kdf = match data[0] {
+            1 => Kdf::Scrypt {
+                log_n: data[1],
+                r: word(2),
+                p: word(6),
+            },
+            2 => Kdf::Argon2id {
+                memory_kib: word(1),
+                iterations: word(5),
+                parallelism: word(9),
+            },
+            _ => return Err(VaultError::InvalidParameters.into()),
+        };
+        kdf.check()?;
+        Ok(kdf)
+    }
+
+    /// Check that the parameters are within the limits accepted when
+    /// loading a vault.
+    fn check(&self) -> Result<()> {
+        let (work_kib, in_range) = match *self {
+            Kdf::Scrypt { log_n, r, p } => {
+                let blocks = 1u64.checked_shl(log_n as u32).unwrap_or(u64::MAX);
+                let work_kib = blocks
+                    .saturating_mul(r as u64)
+                    .saturating_mul(128)
+                    .saturating_mul(p as u64)
+                    / 1024;
+                (work_kib, r <= MAX_BLOCK_SIZE && p <= MAX_PARALLELISM)
+            }
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => (
+                (memory_kib as u64).saturating_mul(iterations as u64),
+                iterations <= MAX_ITERATIONS && parallelism <= MAX_PARALLELISM,
+            ),
+        };
+        if !in_range || self.memory_kib() > MAX_MEMORY_KIB || work_kib > MAX_WORK_KIB {
+            return Err(VaultError::InvalidParameters.into());
+        }
+        Ok(())
+    }
+
+    /// Return the memory used to derive the key, in KiB.
+    pub fn memory_kib(&self) -> u64 {
+        match *self {
+            Kdf::Scrypt { log_n, r, p } => {
+                let blocks = 1u64.checked_shl(log_n as u32).unwrap_or(u64::MAX);
+                blocks
+                    .saturating_add(p as u64)
+                    .saturating_mul(r as u64)
+                    .saturating_mul(128)
+                    / 1024
+            }
+            Kdf::Argon2id { memory_kib, .. } => memory_kib as u64,
+        }
+    }
+}
+
+/// A keyring encrypted with a password.
+#[derive(Debug, Clone, Default)]
+pub struct Vault {
+    keyring: Keyring,
+    kdf: Kdf,
+}
+
+impl Vault {
+    /// Create a vault holding the given keyring, encrypted with the default
+    /// [`Kdf`].
+    pub fn new(keyring: Keyring) -> Self {
+        Self {
+            keyring,
+            kdf: Kdf::default(),
+        }
+    }
+
+    /// Set the KDF used the next time the vault is encrypted. A decrypted
+    /// vault keeps the KDF it was encrypted with.
+    pub fn kdf(mut self, kdf: Kdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Return the keyring of the vault.
+    pub fn keyring(&self) -> &Keyring {
+        &self.keyring
+    }
+
+    /// Return the keyring of the vault, to add or remove accounts.
+    pub fn keyring_mut(&mut self) -> &mut Keyring {
+        &mut self.keyring
+    }
+
+    /// Consume the vault and return its keyring.
+    pub fn into_keyring(self) -> Keyring {
+        self.keyring
+    }
+
+    /// Encrypt the vault with the given password, with a fresh salt and
+    /// nonce.
+    pub fn encrypt(&self, password: &str) -> Result<Vec<u8>> {
+        self.encrypt_with_rng(password, &mut rand::thread_rng())
+    }
+
+    /// Encrypt the vault with the given password, with a salt and nonce
+    /// drawn from the given RNG.
+    pub fn encrypt_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        password: &str,
+        rng: &mut R,
+    ) -> Result<Vec<u8>> {
+        // a vault which cannot be loaded back must not be written
+        self.kdf.check()?;
+
+        let mut salt = [0u8; SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION);
+        self.kdf.encode(&mut header);
+        header.extend_from_slice(&salt);
+        header.push(CIPHER_XCHACHA20_POLY1305);
+        header.extend_from_slice(&nonce);
+
+        let plaintext = Zeroizing::new(serde_json::to_vec(&Contents::new(&self.keyring))?);
+        let key = self.kdf.derive_key(password, &salt)?;
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| VaultError::Decryption)?;
+
+        header.extend_from_slice(&ciphertext);
+        Ok(header)
+    }
+
+    /// Decrypt a vault with the given password, and check that the keyring
+    /// rebuilt from it matches what was saved.
+    pub fn decrypt(data: &[u8], password: &str) -> Result<Self> {
+        if data.len() < HEADER_LEN || &data[..4] != MAGIC {
+            return Err(VaultError::InvalidFormat.into());
+        }
+        if data[4] != VERSION {
+            return Err(VaultError::UnsupportedVersion(data[4]).into());
+        }
+        let (header, ciphertext) = data.split_at(HEADER_LEN);
+        let kdf = Kdf::decode(&header[5..18])?;
+        let salt = &header[18..18 + SALT_LEN];
+        if header[18 + SALT_LEN] != CIPHER_XCHACHA20_POLY1305 {
+            return Err(VaultError::InvalidParameters.into());
+        }
+        let nonce = &header[HEADER_LEN - NONCE_LEN..];
+
+        let key = kdf.derive_key(password, salt)?;
+        let plaintext = XChaCha20Poly1305::new(key.as_ref().into())
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| VaultError::Decryption)?;
+
+        let contents: Contents = serde_json::from_slice(&plaintext)
+            .map_err(|e| VaultError::IntegrityCheck(e.to_string()))?;
+        Ok(Self {
+            keyring: contents.into_keyring()?,
+            kdf,
+        })
+    }
+
+    /// Encrypt the vault and write it to the given file, replacing it
+    /// atomically. On Unix, the file is only readable by its owner.
+    pub fn save<P: AsRef<Path>>(&self, path: P, password: &str) -> Result<()> {
+        self.save_with_rng(path, password, &mut rand::thread_rng())
+    }
+
+    /// Encrypt the vault with a salt and nonce drawn from the given RNG, and
+    /// write it to the given file like [`save`](Vault::save).
+    pub fn save_with_rng<P: AsRef<Path>, R: RngCore + CryptoRng>(
+        &self,
+        path: P,
+        password: &str,
+        rng: &mut R,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let data = self.encrypt_with_rng(password, rng)?;
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options.open(&tmp)?;
+        std::io::Write::write_all(&mut file, &data)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Read and decrypt the vault stored in the given file.
+    pub fn load<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        Self::decrypt(&std::fs::read(path)?, password)
+    }
+
+    /// Re-encrypt the vault stored in the given file with a new password.
+    /// The file is left untouched if the old password is wrong.
+    pub fn change_password<P: AsRef<Path>>(
+        path: P,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        Self::load(path, old_password)?.save(path, new_password)
+    }
+}
+
+/// The encrypted contents of a vault.
+#[derive(Serialize, Deserialize)]
+struct Contents {
+    roots: Vec<RootRecord>,
+    accounts: Vec<AccountRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RootRecord {
+    label: String,
+    language: String,
+    entropy: String,
+    passphrase: String,
+    fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum AccountRecord {
+    Hd {
+        label: String,
+        root: String,
+        path: String,
+        address: Address,
+    },
+    Imported {
+        label: String,
+        key: String,
+        address: Address,
+    },
+}
+
+impl Contents {
+    fn new(keyring: &Keyring) -> Self {
+        let roots = keyring
+            .roots()
+            .iter()
+            .map(|root| RootRecord {
+                label: root.label().to_string(),
+                language: root.mnemonic().language().name().to_string(),
+                entropy: hex::encode(root.mnemonic().entropy()),
+                passphrase: root.passphrase().to_string(),
+                fingerprint: hex::encode(root.fingerprint()),
+            })
+            .collect();
+
+        let accounts = keyring
+            .accounts()
+            .iter()
+            .map(|account| match account.signer() {
+                AccountSigner::Hd { root, account: hd } => AccountRecord::Hd {
+                    label: account.label().to_string(),
+                    root: hex::encode(root),
+                    path: path_string(hd.path()),
+                    address: account.address(),
+                },
+                AccountSigner::Imported(wallet) => AccountRecord::Imported {
+                    label: account.label().to_string(),
+                    key: hex::encode(&wallet.private_key().to_bytes()[..]),
+                    address: account.address(),
+                },
+            })
+            .collect();
+
+        Self { roots, accounts }
+    }
+
+    fn into_keyring(self) -> Result<Keyring> {
+        let mismatch = |what: &str| VaultError::IntegrityCheck(format!("{} mismatch", what));
+
+        let mut keyring = Keyring::new();
+        for root in &self.roots {
+            let entropy = Zeroizing::new(hex::decode(&root.entropy)?);
+            let mnemonic = Mnemonic::from_entropy(&entropy, root.language.parse()?)?;
+            let fingerprint = keyring.add_mnemonic(&root.label, &mnemonic, &root.passphrase)?;
+            if hex::encode(fingerprint) != root.fingerprint {
+                return Err(mismatch("root fingerprint").into());
+            }
+        }
+
+        for account in &self.accounts {
+            let (address, expected) = match account {
+                AccountRecord::Hd {
+                    label,
+                    root,
+                    path,
+                    address,
+                } => {
+                    let root = hex::decode(root)?
+                        .try_into()
+                        .map_err(|_| mismatch("root fingerprint"))?;
+                    let path = DerivationPath::parse(path)?;
+                    (keyring.derive_account(root, &path, label)?, address)
+                }
+                AccountRecord::Imported {
+                    label,
+                    key,
+                    address,
+                } => (keyring.import_hex(label, key)?, address),
+            };
+            if address != *expected {
+                return Err(mismatch("account address").into());
+            }
+        }
+        Ok(keyring)
+    }
+}
+
+/// Return the path in a form accepted by [`DerivationPath::parse`], which
+/// the master key itself does not have.
+fn path_string(path: &DerivationPath) -> String {
+    match path.iter().len() {
+        0 => "m".to_string(),
+        _ => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bips::{wordlists::Language, DerivationScheme};
+
+    fn keyring() -> Keyring {
+        let mut keyring = Keyring::new();
+        let mnemonic = Mnemonic::from_phrase(
+            "test test test test test test test test test test test junk",
+            Language::English,
+        )
+        .unwrap();
+        let root = keyring.add_mnemonic("Main", &mnemonic, "").unwrap();
+        keyring
+            .derive_account(root, &DerivationScheme::MetaMask.path(0), "First")
+            .unwrap();
+
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            Language::English,
+        )
+        .unwrap();
+        let root = keyring.add_mnemonic("Hidden", &mnemonic, "TREZOR").unwrap();
+        keyring
+            .derive_account(root, &DerivationScheme::LedgerLive.path(2), "Ledger")
+            .unwrap();
+        keyring
+            .import_hex("Imported", &hex::encode([0x46; 32]))
+            .unwrap();
+        keyring
+    }
+
+    #[test]
+    fn test_vault() {
+        let keyring = keyring();
+        for kdf in [
+            Kdf::Scrypt {
+                log_n: 4,
+                r: 8,
+                p: 1,
+            },
+            Kdf::Argon2id {
+                memory_kib: 64,
+                iterations: 1,
+                parallelism: 1,
+            },
+        ] {
+            let data = Vault::new(keyring.clone())
+                .kdf(kdf)
+                .encrypt("password")
+                .unwrap();
+            let vault = Vault::decrypt(&data, "password").unwrap();
+            assert_eq!(vault.encrypt("password").unwrap()[..18], data[..18]);
+            assert_eq!(vault.keyring().addresses(), keyring.addresses());
+            assert_eq!(vault.keyring().roots()[1].passphrase(), "TREZOR");
+            assert_eq!(
+                vault.keyring().accounts()[1].signer().path(),
+                Some(&DerivationScheme::LedgerLive.path(2))
+            );
+
+            assert!(Vault::decrypt(&data, "wrong").is_err());
+            for index in [4, 10, 30, data.len() - 1] {
+                let mut tampered = data.clone();
+                tampered[index] ^= 1;
+                assert!(Vault::decrypt(&tampered, "password").is_err());
+            }
+        }
+        assert!(Vault::decrypt(b"LRWV", "password").is_err());
+    }
+
+    #[test]
+    fn test_kdf_limits() {
+        let decode = |kdf: Kdf| {
+            let mut data = Vec::new();
+            kdf.encode(&mut data);
+            Kdf::decode(&data)
+        };
+        for kdf in [
+            Kdf::default(),
+            Kdf::Scrypt {
+                log_n: 20,
+                r: 8,
+                p: 1,
+            },
+        ] {
+            assert_eq!(decode(kdf).unwrap(), kdf);
+        }
+        for kdf in [
+            Kdf::Argon2id {
+                memory_kib: 64,
+                iterations: u32::MAX,
+                parallelism: 1,
+            },
+            Kdf::Argon2id {
+                memory_kib: 64,
+                iterations: 1,
+                parallelism: u32::MAX,
+            },
+            Kdf::Argon2id {
+                memory_kib: 1024 * 1024,
+                iterations: 64,
+                parallelism: 1,
+            },
+            Kdf::Scrypt {
+                log_n: 4,
+                r: u32::MAX,
+                p: 1,
+            },
+            Kdf::Scrypt {
+                log_n: 4,
+                r: 8,
+                p: u32::MAX,
+            },
+            Kdf::Scrypt {
+                log_n: 22,
+                r: 8,
+                p: 64,
+            },
+        ] {
+            assert!(decode(kdf).is_err());
+            assert!(Vault::new(keyring()).kdf(kdf).encrypt("password").is_err());
+        }
+    }
+
+    #[test]
+    fn test_encrypt_with_rng() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let vault = Vault::new(keyring()).kdf(Kdf::Scrypt {
+            log_n: 4,
+            r: 8,
+            p: 1,
+        });
+        let data = vault
+            .encrypt_with_rng("password", &mut StdRng::seed_from_u64(7))
+            .unwrap();
+        assert_eq!(
+            data,
+            vault
+                .encrypt_with_rng("password", &mut StdRng::seed_from_u64(7))
+                .unwrap()
+        );
+        assert_ne!(data, vault.encrypt("password").unwrap());
+        assert_eq!(
+            Vault::decrypt(&data, "password")
+                .unwrap()
+                .keyring()
+                .addresses(),
+            vault.keyring().addresses()
+        );
+    }
+
+    #[test]
+    fn test_change_password() {
+        let path = std::env::temp_dir().join(format!("laron-vault-{}", std::process::id()));
+        let vault = Vault::new(keyring()).kdf(Kdf::Scrypt {
+            log_n: 4,
+            r: 8,
+            p: 1,
+        });
+        vault.save(&path, "old").unwrap();
+
+        assert!(Vault::change_password(&path, "wrong", "new").is_err());
+        Vault::change_password(&path, "old", "new").unwrap();
+        assert!(Vault::load(&path, "old").is_err());
+        let loaded = Vault::load(&path, "new").unwrap();
+        assert_eq!(loaded.keyring().addresses(), vault.keyring().addresses());
+        std::fs::remove_file(&path).unwrap();
+    }
+}